@@ -127,7 +127,8 @@ async fn main() -> Result<(), Box<dyn Error>> {
     // Note: since the compiler doesn't know for sure that the handle will outlive
     // the created threads, we have to move cloned versions of the handle to each
     // thread we create. This is fine to do, since Handle uses an internal Arc to
-    // allow for clones to use the same underlying data.
+    // allow for clones to use the same underlying data. (Futures that run within a
+    // single task, for example with tokio::join!, can borrow &handle instead.)
     //
     // It would be nice if the rust compiler had an "async clone" keyword that could be
     // used in place of "async move", as that would save a few lines and the extra closure.
@@ -23,6 +23,19 @@ use syn::{
 /// The single `nosql` attribute can be used to rename a field using the `column` key, and/or to specify
 /// its NoSQL Database field type using the `type` key (for example, from a Rust `i32` to a NoSQL `long`).
 ///
+/// By default, an `Option` field that is `None` is written as SQL `NULL`, which replaces any
+/// existing value when the whole row is put. Adding `skip_if_none` (as in
+/// `#[nosql(skip_if_none)]` or `#[nosql(column=name, skip_if_none)]`) leaves `None` fields out
+/// of the row instead, for sparse documents. When reading, a missing field gives `None`.
+///
+/// Fields may themselves be structs that derive `NoSQLRow`, or collections of them
+/// (`Vec<Inner>`, `HashMap<String, Inner>`, `BTreeMap<String, Inner>`, `Option<Inner>`).
+/// These are written as NoSQL maps and arrays of maps, and map to `RECORD`, `MAP`, `ARRAY` and
+/// `JSON` columns, so nested domain models can be stored in document-style tables. When reading,
+/// a missing or NULL array or map field gives an empty collection, and integer fields accept any
+/// numeric value that fits, since numbers in JSON fields are returned in the smallest type that
+/// holds them.
+///
 /// See the documentation of [`PutRequest::put()`](../struct.PutRequest.html#method.put) for
 /// example usage of this macro to put and get native structs to and from a NoSQL Database table.
 #[proc_macro_derive(NoSQLRow, attributes(nosql))]
@@ -51,6 +64,8 @@ fn impl_to_from_map_value(input: DeriveInput) -> TokenStream {
     struct FieldNameType {
         fname: String,
         alias: Option<String>,
+        // omit the field from the row when it is None
+        skip_if_none: bool,
         // TODO: ftype: String,
     }
 
@@ -61,6 +76,7 @@ fn impl_to_from_map_value(input: DeriveInput) -> TokenStream {
         // if "column" attribute given, use that
 
         let mut alias: Option<String> = None;
+        let mut skip_if_none: bool = false;
         for a in field.attrs {
             let mut good: bool = false;
             if let Meta::List(l) = a.meta {
@@ -82,7 +98,11 @@ fn impl_to_from_map_value(input: DeriveInput) -> TokenStream {
                         TokenTree::Ident(i) => {
                             if is_column {
                                 alias = Some(i.to_string());
-                                break;
+                                is_column = false;
+                                continue;
+                            }
+                            if i.to_string() == "skip_if_none" {
+                                skip_if_none = true;
                             }
                             if i.to_string() == "column" {
                                 is_column = true;
@@ -94,9 +114,6 @@ fn impl_to_from_map_value(input: DeriveInput) -> TokenStream {
                         _ => (),
                     }
                 }
-                if alias.is_some() {
-                    break;
-                }
             }
         }
 
@@ -110,12 +127,19 @@ fn impl_to_from_map_value(input: DeriveInput) -> TokenStream {
         // if "type" attribute given, use that
         // otherwise, infer from rust type
         let _ftype = if let Type::Path(p) = field.ty {
+            if skip_if_none && p.path.segments.last().map_or(true, |s| s.ident != "Option") {
+                panic!("skip_if_none can only be used on Option fields ({})", fname);
+            }
             //ftypes.push(get_path_segment(&p, ""));
             get_path_segment(&p, "")
         } else {
             panic!("Field type in NoSQLRow does not have Path element");
         };
-        fntypes.push(FieldNameType { fname, alias });
+        fntypes.push(FieldNameType {
+            fname,
+            alias,
+            skip_if_none,
+        });
         //fntypes.push(FieldNameType{fname, alias, ftype});
     }
 
@@ -130,9 +154,19 @@ fn impl_to_from_map_value(input: DeriveInput) -> TokenStream {
             Some(s) => fnameq = s,
             None => fnameq = f.fname,
         }
-        tbody.extend(quote! {
-            m.put(#fnameq, &self.#fname);
-        });
+        if f.skip_if_none {
+            tbody.extend(quote! {
+                if let Some(v) = &self.#fname {
+                    m.put_field_value(#fnameq,
+                        oracle_nosql_rust_sdk::NoSQLColumnToFieldValue::try_to_field_value(v)?);
+                }
+            });
+        } else {
+            tbody.extend(quote! {
+                m.put_field_value(#fnameq,
+                    oracle_nosql_rust_sdk::NoSQLColumnToFieldValue::try_to_field_value(&self.#fname)?);
+            });
+        }
         fbody.extend(quote! {
             self.#fname = self.#fname.from_map(#fnameq, value)?;
         });
@@ -154,12 +188,15 @@ fn impl_to_from_map_value(input: DeriveInput) -> TokenStream {
 
         impl NoSQLColumnToFieldValue for #name {
             fn to_field_value(&self) -> FieldValue {
-                let m = self.to_map_value();
-                if let Ok(mv) = m {
-                    return FieldValue::Map(mv);
+                // to_field_value() cannot return errors: writes use try_to_field_value()
+                match self.to_map_value() {
+                    Ok(mv) => FieldValue::Map(mv),
+                    Err(_) => FieldValue::Null,
                 }
-                // TODO: How to expose this error??
-                FieldValue::Null
+            }
+
+            fn try_to_field_value(&self) -> Result<FieldValue, oracle_nosql_rust_sdk::NoSQLError> {
+                Ok(FieldValue::Map(self.to_map_value()?))
             }
         }
 
@@ -145,7 +145,7 @@ impl FuncSumIter {
             self.data.count += 1;
             match self.data.sum_type {
                 FieldType::Long => {
-                    self.data.long_sum += i32::from_field(&val)? as i64;
+                    self.add_long(i32::from_field(&val)? as i64);
                 }
                 FieldType::Double => {
                     self.data.double_sum += i32::from_field(&val)? as f64;
@@ -162,7 +162,7 @@ impl FuncSumIter {
             self.data.count += 1;
             match self.data.sum_type {
                 FieldType::Long => {
-                    self.data.long_sum += i64::from_field(&val)?;
+                    self.add_long(i64::from_field(&val)?);
                 }
                 FieldType::Double => {
                     self.data.double_sum += i64::from_field(&val)? as f64;
@@ -221,6 +221,18 @@ impl FuncSumIter {
         Ok(())
     }
 
+    // Add to the long sum. If the sum does not fit in a long, switch to a Number
+    // sum instead of wrapping around, as the server does.
+    fn add_long(&mut self, val: i64) {
+        match self.data.long_sum.checked_add(val) {
+            Some(sum) => self.data.long_sum = sum,
+            None => {
+                self.data.number_sum = BigDecimal::from(self.data.long_sum) + val;
+                self.data.sum_type = FieldType::Number;
+            }
+        }
+    }
+
     pub fn get_result(&self, req: &mut QueryRequest) -> FieldValue {
         req.get_result(self.result_reg)
     }
@@ -433,3 +445,40 @@ impl FuncMinMaxIter {
         Ok(Some(self.data.min_max.clone_internal()))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sum_overflow() -> Result<(), NoSQLError> {
+        let req = QueryRequest::new("select 1");
+        let mut sum = FuncSumIter::default();
+        sum.data.null_input_only = false;
+        sum.sum_new_value(FieldValue::Long(i64::MAX - 1))?;
+        sum.sum_new_value(FieldValue::Integer(1))?;
+        assert!(matches!(
+            sum.get_aggr_value(&req, false)?,
+            Some(FieldValue::Long(i64::MAX))
+        ));
+
+        // the sum does not wrap around
+        sum.sum_new_value(FieldValue::Integer(1))?;
+        sum.sum_new_value(FieldValue::Long(i64::MAX))?;
+        let expected = BigDecimal::from(i64::MAX) * 2 + 1;
+        assert!(matches!(
+            sum.get_aggr_value(&req, true)?,
+            Some(FieldValue::Number(n)) if n == expected
+        ));
+
+        sum.data.null_input_only = false;
+        sum.sum_new_value(FieldValue::Long(i64::MIN))?;
+        sum.sum_new_value(FieldValue::Integer(-1))?;
+        let expected = BigDecimal::from(i64::MIN) - 1;
+        assert!(matches!(
+            sum.get_aggr_value(&req, true)?,
+            Some(FieldValue::Number(n)) if n == expected
+        ));
+        Ok(())
+    }
+}
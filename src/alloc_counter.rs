@@ -0,0 +1,44 @@
+//
+// Copyright (c) 2024, 2025 Oracle and/or its affiliates. All rights reserved.
+//
+// Licensed under the Universal Permissive License v 1.0 as shown at
+//  https://oss.oracle.com/licenses/upl/
+//
+
+// A global allocator for tests that counts the allocations made by each
+// thread, so tests can measure how much a code path allocates. Counts are
+// per thread, so tests running in parallel do not affect each other.
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+    static ALLOCATED_BYTES: Cell<u64> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // try_with(), since this may be called while the thread is exiting
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+        let _ = ALLOCATED_BYTES.try_with(|n| n.set(n.get() + layout.size() as u64));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+// Get the number of allocations, and the total bytes allocated, by the
+// current thread so far.
+pub(crate) fn allocations() -> (u64, u64) {
+    (
+        ALLOCATIONS.with(|n| n.get()),
+        ALLOCATED_BYTES.with(|n| n.get()),
+    )
+}
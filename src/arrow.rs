@@ -0,0 +1,293 @@
+//
+// Copyright (c) 2024, 2025 Oracle and/or its affiliates. All rights reserved.
+//
+// Licensed under the Universal Permissive License v 1.0 as shown at
+//  https://oss.oracle.com/licenses/upl/
+//
+use crate::error::ia_err;
+use crate::error::NoSQLError;
+use crate::prepared_statement::QueryColumn;
+use crate::query_request::QueryResult;
+use crate::types::{FieldValue, MapValue};
+use arrow_array::{
+    ArrayRef, BinaryArray, BooleanArray, Decimal128Array, Float64Array, Int32Array, Int64Array,
+    NullArray, RecordBatch, RecordBatchOptions, StringArray, TimestampMicrosecondArray,
+    TimestampMillisecondArray, TimestampNanosecondArray, TimestampSecondArray,
+};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use bigdecimal::{BigDecimal, ToPrimitive};
+use std::collections::BTreeSet;
+use std::result::Result;
+use std::sync::Arc;
+
+// Arrow Decimal128 values have at most 38 digits.
+const DECIMAL_PRECISION: u8 = 38;
+
+// The Arrow type used for a result column.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColumnKind {
+    Int32,
+    Int64,
+    Float64,
+    Decimal,
+    Boolean,
+    Utf8,
+    // maps, arrays and values of unknown type, as JSON text
+    Json,
+    Binary,
+    Timestamp(TimeUnit),
+    Null,
+}
+
+impl ColumnKind {
+    fn from_column(col: &QueryColumn) -> ColumnKind {
+        match col.type_name() {
+            "INTEGER" => ColumnKind::Int32,
+            "LONG" => ColumnKind::Int64,
+            "FLOAT" | "DOUBLE" => ColumnKind::Float64,
+            "NUMBER" => ColumnKind::Decimal,
+            "BOOLEAN" => ColumnKind::Boolean,
+            "STRING" | "ENUM" => ColumnKind::Utf8,
+            "BINARY" | "FIXED_BINARY" => ColumnKind::Binary,
+            "TIMESTAMP" => ColumnKind::Timestamp(match col.timestamp_precision() {
+                Some(0) => TimeUnit::Second,
+                Some(1..=3) => TimeUnit::Millisecond,
+                Some(7..=9) => TimeUnit::Nanosecond,
+                _ => TimeUnit::Microsecond,
+            }),
+            _ => ColumnKind::Json,
+        }
+    }
+
+    fn from_value(v: &FieldValue) -> ColumnKind {
+        match v {
+            FieldValue::Integer(_) => ColumnKind::Int32,
+            FieldValue::Long(_) => ColumnKind::Int64,
+            FieldValue::Double(_) => ColumnKind::Float64,
+            FieldValue::Number(_) => ColumnKind::Decimal,
+            FieldValue::Boolean(_) => ColumnKind::Boolean,
+            FieldValue::String(_) => ColumnKind::Utf8,
+            FieldValue::Binary(_) => ColumnKind::Binary,
+            FieldValue::Timestamp(_) => ColumnKind::Timestamp(TimeUnit::Microsecond),
+            FieldValue::Array(_) | FieldValue::Map(_) => ColumnKind::Json,
+            _ => ColumnKind::Null,
+        }
+    }
+}
+
+fn is_null(v: &FieldValue) -> bool {
+    matches!(
+        v,
+        FieldValue::Null | FieldValue::JsonNull | FieldValue::Empty | FieldValue::Uninitialized
+    )
+}
+
+fn string_value(v: &FieldValue) -> Option<&str> {
+    match v {
+        FieldValue::String(s) => Some(s),
+        _ => None,
+    }
+}
+
+fn binary_value(v: &FieldValue) -> Option<&[u8]> {
+    match v {
+        FieldValue::Binary(b) => Some(b),
+        _ => None,
+    }
+}
+
+fn to_decimal(v: &FieldValue) -> Option<BigDecimal> {
+    match v {
+        FieldValue::Number(n) => Some(n.clone()),
+        FieldValue::Integer(i) => Some(BigDecimal::from(*i)),
+        FieldValue::Long(l) => Some(BigDecimal::from(*l)),
+        _ => None,
+    }
+}
+
+impl QueryResult {
+    /// Convert the query result rows to an Apache Arrow [`RecordBatch`].
+    ///
+    /// This is only available with the `arrow` feature. The Arrow schema is derived from the
+    /// query result schema ([`PreparedStatement::columns()`](crate::PreparedStatement::columns())),
+    /// so the query should be run with
+    /// [`QueryRequest::include_query_schema()`](crate::QueryRequest::include_query_schema()).
+    /// Without it, columns are in field name order and their types are taken from the first
+    /// non-null value of each column.
+    ///
+    /// Types are mapped as follows: `INTEGER` to `Int32`, `LONG` to `Int64`, `FLOAT` and
+    /// `DOUBLE` to `Float64`, `NUMBER` to `Decimal128` (with the largest scale of the column's
+    /// values), `BOOLEAN` to `Boolean`, `STRING` and `ENUM` to `Utf8`, `BINARY` to `Binary`
+    /// and `TIMESTAMP` to a UTC `Timestamp` with a unit that keeps the column precision
+    /// (microseconds if it is not known). `JSON`, `MAP`, `ARRAY`, `RECORD` and `ANY` columns
+    /// are converted to `Utf8` JSON text. All fields are nullable; missing fields are null.
+    ///
+    /// Returns an `IllegalArgument` error if a value does not fit its column type, such as a
+    /// `NUMBER` with more than 38 digits.
+    ///
+    /// ```no_run
+    /// # use oracle_nosql_rust_sdk::{Handle, QueryRequest};
+    /// # #[tokio::main]
+    /// # pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let handle = Handle::builder().build().await?;
+    /// let batch = QueryRequest::new("select id, name, created from users")
+    ///     .include_query_schema(true)
+    ///     .execute(&handle)
+    ///     .await?
+    ///     .to_record_batch()?;
+    /// println!("{} rows of {}", batch.num_rows(), batch.schema());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn to_record_batch(&self) -> Result<RecordBatch, NoSQLError> {
+        let mut columns: Vec<(String, ColumnKind)> = self
+            .prepared_statement
+            .columns()
+            .iter()
+            .map(|c| (c.name().to_string(), ColumnKind::from_column(c)))
+            .collect();
+        if columns.is_empty() {
+            let names: BTreeSet<&String> = self.rows.iter().flat_map(|r| r.keys()).collect();
+            for name in names {
+                let kind = self
+                    .rows
+                    .iter()
+                    .filter_map(|r| r.get_field_value(name))
+                    .find(|v| !is_null(v))
+                    .map_or(ColumnKind::Null, ColumnKind::from_value);
+                columns.push((name.clone(), kind));
+            }
+        }
+
+        let mut fields: Vec<Field> = Vec::with_capacity(columns.len());
+        let mut arrays: Vec<ArrayRef> = Vec::with_capacity(columns.len());
+        for (name, kind) in &columns {
+            let array = column_array(&self.rows, name, *kind)?;
+            fields.push(Field::new(name, array.data_type().clone(), true));
+            arrays.push(array);
+        }
+        let options = RecordBatchOptions::new().with_row_count(Some(self.rows.len()));
+        match RecordBatch::try_new_with_options(Arc::new(Schema::new(fields)), arrays, &options) {
+            Ok(b) => Ok(b),
+            Err(e) => ia_err!("error creating record batch: {}", e),
+        }
+    }
+}
+
+// Build the Arrow array for one column of the result rows.
+fn column_array(rows: &[MapValue], name: &str, kind: ColumnKind) -> Result<ArrayRef, NoSQLError> {
+    let values: Vec<Option<&FieldValue>> = rows
+        .iter()
+        .map(|r| r.get_field_value(name).filter(|v| !is_null(v)))
+        .collect();
+    let mismatch = |v: &FieldValue| -> Result<ArrayRef, NoSQLError> {
+        ia_err!(
+            "cannot convert value {:?} of column '{}' to {:?}",
+            v,
+            name,
+            kind
+        )
+    };
+
+    macro_rules! convert {
+        ($array:ty, $conv:expr) => {{
+            let mut out = Vec::with_capacity(values.len());
+            for v in &values {
+                match v {
+                    None => out.push(None),
+                    Some(v) => match $conv(*v) {
+                        Some(x) => out.push(Some(x)),
+                        None => return mismatch(v),
+                    },
+                }
+            }
+            <$array>::from(out)
+        }};
+    }
+
+    let array: ArrayRef = match kind {
+        ColumnKind::Int32 => Arc::new(convert!(Int32Array, |v: &FieldValue| match v {
+            FieldValue::Integer(i) => Some(*i),
+            _ => None,
+        })),
+        ColumnKind::Int64 => Arc::new(convert!(Int64Array, |v: &FieldValue| match v {
+            FieldValue::Integer(i) => Some(*i as i64),
+            FieldValue::Long(l) => Some(*l),
+            _ => None,
+        })),
+        ColumnKind::Float64 => Arc::new(convert!(Float64Array, |v: &FieldValue| match v {
+            FieldValue::Double(d) => Some(*d),
+            FieldValue::Integer(i) => Some(*i as f64),
+            FieldValue::Long(l) => Some(*l as f64),
+            FieldValue::Number(n) => n.to_f64(),
+            _ => None,
+        })),
+        ColumnKind::Boolean => Arc::new(convert!(BooleanArray, |v: &FieldValue| match v {
+            FieldValue::Boolean(b) => Some(*b),
+            _ => None,
+        })),
+        ColumnKind::Utf8 => Arc::new(convert!(StringArray, string_value)),
+        ColumnKind::Json => Arc::new(convert!(StringArray, |v: &FieldValue| Some(
+            v.to_json_value().to_string()
+        ))),
+        ColumnKind::Binary => Arc::new(convert!(BinaryArray, binary_value)),
+        ColumnKind::Timestamp(unit) => {
+            let ts = |v: &FieldValue| match v {
+                FieldValue::Timestamp(t) => match unit {
+                    TimeUnit::Second => Some(t.timestamp()),
+                    TimeUnit::Millisecond => Some(t.timestamp_millis()),
+                    TimeUnit::Microsecond => Some(t.timestamp_micros()),
+                    TimeUnit::Nanosecond => t.timestamp_nanos_opt(),
+                },
+                _ => None,
+            };
+            match unit {
+                TimeUnit::Second => {
+                    Arc::new(convert!(TimestampSecondArray, ts).with_timezone_utc())
+                }
+                TimeUnit::Millisecond => {
+                    Arc::new(convert!(TimestampMillisecondArray, ts).with_timezone_utc())
+                }
+                TimeUnit::Microsecond => {
+                    Arc::new(convert!(TimestampMicrosecondArray, ts).with_timezone_utc())
+                }
+                TimeUnit::Nanosecond => {
+                    Arc::new(convert!(TimestampNanosecondArray, ts).with_timezone_utc())
+                }
+            }
+        }
+        ColumnKind::Decimal => {
+            // use the largest scale of all values, so none are rounded
+            let mut scale: i64 = 0;
+            for v in values.iter().flatten() {
+                match to_decimal(v) {
+                    Some(d) => scale = scale.max(d.fractional_digit_count()),
+                    None => return mismatch(v),
+                }
+            }
+            if scale > DECIMAL_PRECISION as i64 {
+                return ia_err!(
+                    "values of column '{}' have more than {} fractional digits",
+                    name,
+                    DECIMAL_PRECISION
+                );
+            }
+            let limit = 10i128.pow(DECIMAL_PRECISION as u32);
+            let array = convert!(Decimal128Array, |v: &FieldValue| {
+                let (digits, _) = to_decimal(v)?.with_scale(scale).into_bigint_and_exponent();
+                digits.to_i128().filter(|d| d.abs() < limit)
+            });
+            match array.with_precision_and_scale(DECIMAL_PRECISION, scale as i8) {
+                Ok(a) => Arc::new(a),
+                Err(e) => return ia_err!("invalid decimal column '{}': {}", name, e),
+            }
+        }
+        ColumnKind::Null => {
+            if let Some(v) = values.iter().flatten().next() {
+                return mismatch(v);
+            }
+            Arc::new(NullArray::new(values.len()))
+        }
+    };
+    Ok(array)
+}
@@ -0,0 +1,487 @@
+//
+// Copyright (c) 2024, 2025 Oracle and/or its affiliates. All rights reserved.
+//
+// Licensed under the Universal Permissive License v 1.0 as shown at
+//  https://oss.oracle.com/licenses/upl/
+//
+use crate::deadline::Deadline;
+use crate::error::ia_err;
+use crate::error::{NoSQLError, NoSQLErrorCode};
+use crate::handle::Handle;
+use crate::put_request::PutRequest;
+use crate::table_request::GetTableRequest;
+use crate::types::{Capacity, CapacityMode, Durability, FieldValue, MapValue};
+use crate::write_multiple_request::WriteMultipleRequest;
+use std::collections::HashMap;
+use std::result::Result;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::Receiver;
+use tokio::task::{Id, JoinError, JoinSet};
+use tracing::trace;
+
+// Largest number of operations allowed in one WriteMultipleRequest.
+const MAX_BATCH_SIZE: usize = 50;
+
+const DEFAULT_CONCURRENCY: usize = 4;
+const DEFAULT_MAX_RETRIES: u32 = 3;
+
+// Largest number of distinct shard keys with rows waiting for a full batch.
+// Past this, the largest partial batch is sent. This also bounds the rows held
+// to MAX_PENDING_KEYS * batch_size.
+const MAX_PENDING_KEYS: usize = 64;
+
+// Largest number of errors kept in a BulkLoadResult.
+const MAX_ERRORS: usize = 10;
+
+/// Concurrent loading of many rows into a table, with [`WriteMultipleRequest`] batches.
+///
+/// A `BulkLoader` puts all rows it is given into a table. Rows are grouped into batches
+/// of up to [`batch_size()`](BulkLoader::batch_size()) rows with the same shard key, as
+/// required by `WriteMultipleRequest`, and up to [`concurrency()`](BulkLoader::concurrency())
+/// batches are written at the same time.
+///
+/// ```no_run
+/// # use oracle_nosql_rust_sdk::{BulkLoader, Handle};
+/// # use oracle_nosql_rust_sdk::types::*;
+/// # #[tokio::main]
+/// # pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let handle = Handle::builder().build().await?;
+/// let rows = (0..100_000).map(|i| {
+///     MapValue::new()
+///         .i32("shard", i % 100)
+///         .i32("id", i)
+///         .str("name", "x")
+/// });
+/// let res = BulkLoader::new(&handle, "users")
+///     .concurrency(8)
+///     .load(rows)
+///     .await?;
+/// println!("wrote {} rows, {} failed", res.rows_written(), res.rows_failed());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Rows can also be produced by other tasks, and given to the loader through a channel
+/// with [`load_channel()`](BulkLoader::load_channel()).
+///
+/// A batch that fails with a retryable error, such as a throttling error or a timeout, is
+/// retried up to [`max_retries()`](BulkLoader::max_retries()) times, with backoff (and the
+/// [`backoff_jitter()`](crate::HandleBuilder::backoff_jitter()) of the handle). After that,
+/// its rows are counted as failed and loading continues with the other batches. Rows that are
+/// missing a shard key field are also counted as failed.
+///
+/// Rows with different shard keys cannot be written in the same batch. Only a limited number
+/// of partial batches are held waiting for more rows, so when most rows have a distinct shard
+/// key (for example, when the shard key is the whole primary key), batches are small. In that
+/// case, loading is limited by the number of requests, and a higher concurrency helps.
+///
+/// For tables with provisioned capacity, the loader limits its writes to the write units of
+/// the table, so that concurrent batches do not cause throttling. Batches that are still being
+/// written are counted with an estimate of at least one write unit per row. This can be
+/// disabled with [`respect_table_limits()`](BulkLoader::respect_table_limits()).
+#[derive(Debug)]
+pub struct BulkLoader {
+    handle: Handle,
+    table_name: String,
+    concurrency: usize,
+    batch_size: usize,
+    max_retries: u32,
+    respect_table_limits: bool,
+    timeout: Option<Duration>,
+    compartment_id: String,
+    durability: Option<Durability>,
+}
+
+/// Struct representing the result of a [`BulkLoader`] execution.
+#[derive(Debug, Default)]
+pub struct BulkLoadResult {
+    pub(crate) rows_written: u64,
+    pub(crate) rows_failed: u64,
+    pub(crate) batches: u64,
+    pub(crate) retries: u64,
+    pub(crate) consumed: Capacity,
+    pub(crate) errors: Vec<NoSQLError>,
+}
+
+// The rows given to BulkLoader.
+enum Rows<I: Iterator<Item = MapValue>> {
+    Iter(I),
+    Channel(Receiver<MapValue>),
+}
+
+impl<I: Iterator<Item = MapValue>> Rows<I> {
+    async fn next(&mut self) -> Option<MapValue> {
+        match self {
+            Rows::Iter(i) => i.next(),
+            Rows::Channel(rx) => rx.recv().await,
+        }
+    }
+}
+
+// The outcome of writing one batch.
+#[derive(Default)]
+pub(crate) struct BatchResult {
+    written: u64,
+    failed: u64,
+    retries: u64,
+    consumed: Capacity,
+    error: Option<NoSQLError>,
+}
+
+impl BulkLoader {
+    /// Create a new loader for the given table.
+    pub fn new(handle: &Handle, table_name: &str) -> BulkLoader {
+        BulkLoader {
+            handle: handle.clone(),
+            table_name: table_name.to_string(),
+            concurrency: DEFAULT_CONCURRENCY,
+            batch_size: MAX_BATCH_SIZE,
+            max_retries: DEFAULT_MAX_RETRIES,
+            respect_table_limits: true,
+            timeout: None,
+            compartment_id: String::new(),
+            durability: None,
+        }
+    }
+
+    /// Specify the largest number of batches written at the same time.
+    ///
+    /// The default is 4. Values less than 1 are treated as 1.
+    pub fn concurrency(mut self, n: usize) -> Self {
+        self.concurrency = n.max(1);
+        self
+    }
+
+    /// Specify the largest number of rows in each batch.
+    ///
+    /// The default, and the largest allowed, is 50. Values are limited to the range 1 to 50.
+    pub fn batch_size(mut self, size: usize) -> Self {
+        self.batch_size = size.clamp(1, MAX_BATCH_SIZE);
+        self
+    }
+
+    /// Specify the number of times a batch is retried after a retryable error.
+    ///
+    /// The default is 3. This is in addition to the internal retries of each request.
+    pub fn max_retries(mut self, n: u32) -> Self {
+        self.max_retries = n;
+        self
+    }
+
+    /// Specify whether writes are limited to the provisioned write units of the table.
+    ///
+    /// The default is `true`. This has no effect for on-demand tables, or when the table
+    /// limits are not available (such as with on-premises servers).
+    pub fn respect_table_limits(mut self, respect: bool) -> Self {
+        self.respect_table_limits = respect;
+        self
+    }
+
+    /// Specify the timeout value for each batch.
+    ///
+    /// If not set, the default timeout value configured for the [`Handle`](crate::HandleBuilder::timeout()) is used.
+    pub fn timeout(mut self, t: &Duration) -> Self {
+        self.timeout = Some(*t);
+        self
+    }
+
+    /// Cloud Service only: set the name or id of a compartment to be used for the load.
+    ///
+    /// See [`PutRequest::compartment_id()`].
+    pub fn compartment_id(mut self, compartment_id: &str) -> Self {
+        self.compartment_id = compartment_id.to_string();
+        self
+    }
+
+    /// On-premises only: set the desired durability for the writes.
+    ///
+    /// See [`WriteMultipleRequest::durability()`].
+    pub fn durability(mut self, d: Durability) -> Self {
+        self.durability = Some(d);
+        self
+    }
+
+    /// Put all of the given rows into the table, returning a [`BulkLoadResult`].
+    ///
+    /// An error is returned only if the table cannot be read; failed batches are reported in
+    /// the result.
+    pub async fn load(
+        &self,
+        rows: impl IntoIterator<Item = MapValue>,
+    ) -> Result<BulkLoadResult, NoSQLError> {
+        self.load_rows(Rows::Iter(rows.into_iter())).await
+    }
+
+    /// Put all rows received from the given channel into the table, until all of its senders
+    /// are dropped, returning a [`BulkLoadResult`].
+    ///
+    /// See [`load()`](BulkLoader::load()).
+    pub async fn load_channel(
+        &self,
+        rows: Receiver<MapValue>,
+    ) -> Result<BulkLoadResult, NoSQLError> {
+        self.load_rows(Rows::<std::iter::Empty<MapValue>>::Channel(rows))
+            .await
+    }
+
+    async fn load_rows<I: Iterator<Item = MapValue>>(
+        &self,
+        mut rows: Rows<I>,
+    ) -> Result<BulkLoadResult, NoSQLError> {
+        let table = GetTableRequest::new(&self.table_name)
+            .compartment_id(&self.compartment_id)
+            .execute(&self.handle)
+            .await?;
+        let shard_fields = table.shard_key_fields();
+        if shard_fields.is_empty() {
+            return ia_err!("could not get the shard key of table '{}'", self.table_name);
+        }
+        // write units per second allowed, if limited
+        let write_units = match table.limits() {
+            Some(l)
+                if self.respect_table_limits
+                    && l.mode == CapacityMode::Provisioned
+                    && l.write_units > 0 =>
+            {
+                Some(l.write_units as f64)
+            }
+            _ => None,
+        };
+
+        let start = Instant::now();
+        let mut res = BulkLoadResult::default();
+        let mut tasks: JoinSet<BatchResult> = JoinSet::new();
+        let mut pending: HashMap<Vec<FieldValue>, Vec<MapValue>> = HashMap::new();
+        // rows of the batches sent but not completed yet, in total and by task
+        let mut in_flight: u64 = 0;
+        let mut batch_rows: HashMap<Id, u64> = HashMap::new();
+        let mut done = false;
+        while !done || !pending.is_empty() {
+            let batch = if done {
+                // send what is left
+                let key = pending
+                    .keys()
+                    .next()
+                    .map(|k| copy_key(k))
+                    .unwrap_or_default();
+                pending.remove(&key).unwrap_or_default()
+            } else {
+                let Some(row) = rows.next().await else {
+                    done = true;
+                    continue;
+                };
+                let key = match shard_key(&row, &shard_fields) {
+                    Ok(k) => k,
+                    Err(e) => {
+                        res.rows_failed += 1;
+                        res.add_error(e);
+                        continue;
+                    }
+                };
+                let batch = pending.entry(copy_key(&key)).or_default();
+                batch.push(row);
+                if batch.len() >= self.batch_size {
+                    pending.remove(&key).unwrap_or_default()
+                } else if pending.len() > MAX_PENDING_KEYS {
+                    // send the largest partial batch to bound memory
+                    let key = pending
+                        .iter()
+                        .max_by_key(|(_, b)| b.len())
+                        .map(|(k, _)| copy_key(k))
+                        .unwrap_or_default();
+                    pending.remove(&key).unwrap_or_default()
+                } else {
+                    continue;
+                }
+            };
+
+            if tasks.len() >= self.concurrency {
+                if let Some(r) = tasks.join_next_with_id().await {
+                    in_flight -= res.add_batch(r, &mut batch_rows);
+                }
+            }
+            let num_rows = batch.len() as u64;
+            in_flight += num_rows;
+            if let Some(units) = write_units {
+                let delay = res.write_limit_delay(in_flight, units, start.elapsed());
+                if !delay.is_zero() {
+                    trace!("bulk load waiting {:?} for table write limit", delay);
+                    tokio::time::sleep(delay).await;
+                }
+            }
+            let task = tasks.spawn(Deadline::inherit(
+                Deadline::current(),
+                self.write_batch(batch),
+            ));
+            batch_rows.insert(task.id(), num_rows);
+        }
+        while let Some(r) = tasks.join_next_with_id().await {
+            res.add_batch(r, &mut batch_rows);
+        }
+        Ok(res)
+    }
+
+    // Get a future that writes one batch of rows, with retries.
+    fn write_batch(
+        &self,
+        batch: Vec<MapValue>,
+    ) -> impl std::future::Future<Output = BatchResult> + Send + 'static {
+        let handle = self.handle.clone();
+        let table_name = self.table_name.clone();
+        let compartment_id = self.compartment_id.clone();
+        let timeout = self.timeout;
+        let durability = self.durability;
+        let max_retries = self.max_retries;
+        async move {
+            let mut res = BatchResult::default();
+            let mut last_delay = Duration::ZERO;
+            loop {
+                let mut wm = WriteMultipleRequest::new(&table_name).compartment_id(&compartment_id);
+                if let Some(t) = &timeout {
+                    wm = wm.timeout(t);
+                }
+                if let Some(d) = durability {
+                    wm = wm.durability(d);
+                }
+                for row in &batch {
+                    wm = wm.add(Box::new(PutRequest::new("").value(row.deep_clone())));
+                }
+                match wm.execute(&handle).await {
+                    Ok(r) => {
+                        let written = r.results().iter().filter(|s| s.success()).count() as u64;
+                        res.written = written;
+                        res.failed = batch.len() as u64 - written;
+                        if let Some(c) = r.consumed() {
+                            res.consumed.add(c);
+                        }
+                        return res;
+                    }
+                    Err(e) if e.is_retryable() && res.retries < max_retries as u64 => {
+                        let delay = handle.retry_backoff(res.retries as u32, last_delay);
+                        last_delay = delay;
+                        trace!("bulk load batch failed, retrying in {:?}: {}", delay, e);
+                        res.retries += 1;
+                        tokio::time::sleep(delay).await;
+                    }
+                    Err(e) => {
+                        res.failed = batch.len() as u64;
+                        res.error = Some(e);
+                        return res;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Get the shard key values of a row.
+fn shard_key(row: &MapValue, shard_fields: &[String]) -> Result<Vec<FieldValue>, NoSQLError> {
+    let mut key = Vec::with_capacity(shard_fields.len());
+    for f in shard_fields {
+        match row.get_field_value(f) {
+            Some(v) => key.push(v.clone_internal()),
+            None => {
+                return ia_err!("row is missing shard key field '{}'", f);
+            }
+        }
+    }
+    Ok(key)
+}
+
+fn copy_key(key: &[FieldValue]) -> Vec<FieldValue> {
+    key.iter().map(FieldValue::clone_internal).collect()
+}
+
+impl BulkLoadResult {
+    // Get the time to wait before sending more rows, to keep the average write
+    // rate since `elapsed` ago within `units` per second. The batches not
+    // completed yet, `in_flight` rows including the one about to be sent, are
+    // counted at the average units per row so far, and at least one unit per row.
+    pub(crate) fn write_limit_delay(
+        &self,
+        in_flight: u64,
+        units: f64,
+        elapsed: Duration,
+    ) -> Duration {
+        let done_rows = self.rows_written + self.rows_failed;
+        let per_row = if done_rows > 0 {
+            self.consumed.write_units() as f64 / done_rows as f64
+        } else {
+            0.0
+        };
+        let used = self.consumed.write_units() as f64 + in_flight as f64 * per_row.max(1.0);
+        Duration::from_secs_f64(used / units).saturating_sub(elapsed)
+    }
+
+    // Add the result of a batch task, returning its number of rows. The rows of
+    // each task still running are in `batch_rows`. If the task panicked, all of
+    // its rows are counted as failed.
+    pub(crate) fn add_batch(
+        &mut self,
+        joined: Result<(Id, BatchResult), JoinError>,
+        batch_rows: &mut HashMap<Id, u64>,
+    ) -> u64 {
+        let r = match joined {
+            Ok((id, r)) => {
+                batch_rows.remove(&id);
+                r
+            }
+            Err(e) => {
+                let rows = batch_rows.remove(&e.id()).unwrap_or(0);
+                self.batches += 1;
+                self.rows_failed += rows;
+                self.add_error(NoSQLError::new(
+                    NoSQLErrorCode::UnknownError,
+                    &format!("bulk load batch task failed: {}", e),
+                ));
+                return rows;
+            }
+        };
+        self.batches += 1;
+        self.rows_written += r.written;
+        self.rows_failed += r.failed;
+        self.retries += r.retries;
+        self.consumed.add(&r.consumed);
+        if let Some(e) = r.error {
+            self.add_error(e);
+        }
+        r.written + r.failed
+    }
+
+    fn add_error(&mut self, e: NoSQLError) {
+        if self.errors.len() < MAX_ERRORS {
+            self.errors.push(e);
+        }
+    }
+
+    /// Get the number of rows written.
+    pub fn rows_written(&self) -> u64 {
+        self.rows_written
+    }
+
+    /// Get the number of rows that could not be written.
+    pub fn rows_failed(&self) -> u64 {
+        self.rows_failed
+    }
+
+    /// Get the number of batches written or attempted.
+    pub fn batches(&self) -> u64 {
+        self.batches
+    }
+
+    /// Get the total number of batch retries.
+    pub fn retries(&self) -> u64 {
+        self.retries
+    }
+
+    /// Get the total capacity (read/write units) consumed by the load. This is only valid
+    /// in the NoSQL Cloud Service.
+    pub fn consumed(&self) -> &Capacity {
+        &self.consumed
+    }
+
+    /// Get the errors of failed batches and rows, up to the first 10.
+    pub fn errors(&self) -> &Vec<NoSQLError> {
+        &self.errors
+    }
+}
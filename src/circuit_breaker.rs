@@ -0,0 +1,160 @@
+//
+// Copyright (c) 2024, 2025 Oracle and/or its affiliates. All rights reserved.
+//
+// Licensed under the Universal Permissive License v 1.0 as shown at
+//  https://oss.oracle.com/licenses/upl/
+//
+use crate::error::{NoSQLError, NoSQLErrorCode};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+// A handle-level circuit breaker, configured with HandleBuilder::circuit_breaker().
+//
+// The breaker is closed (requests are sent normally) until failure_threshold
+// consecutive requests fail with an error that indicates the service is down or
+// unreachable. It then opens: all new requests fail immediately with a
+// CircuitOpen error for the cooldown period. After the cooldown, a single probe
+// request is let through at a time (half-open). If the probe succeeds the
+// breaker closes again; if it fails the breaker stays open for another cooldown.
+#[derive(Debug)]
+pub(crate) struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    // set while the breaker is open
+    opened_at: Option<Instant>,
+    // set while a probe is in flight. A probe that never reports back (for
+    // example because its future was dropped) is replaced after a cooldown.
+    probe_started: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub(crate) fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        CircuitBreaker {
+            failure_threshold,
+            cooldown,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    // Check whether a request may be sent. Returns true if the request is a
+    // probe, which must be reported back with record().
+    pub(crate) fn admit(&self) -> Result<bool, NoSQLError> {
+        let mut state = self.state.lock().unwrap();
+        let opened_at = match state.opened_at {
+            Some(t) => t,
+            None => {
+                return Ok(false);
+            }
+        };
+        let elapsed = opened_at.elapsed();
+        if elapsed < self.cooldown {
+            return Err(NoSQLError::circuit_open(self.cooldown - elapsed));
+        }
+        if let Some(p) = state.probe_started {
+            if p.elapsed() < self.cooldown {
+                return Err(NoSQLError::circuit_open(self.cooldown - p.elapsed()));
+            }
+        }
+        debug!("circuit breaker: sending probe request");
+        state.probe_started = Some(Instant::now());
+        Ok(true)
+    }
+
+    // Record the result of a request that was admitted.
+    pub(crate) fn record(&self, err: Option<&NoSQLError>, probe: bool) {
+        let mut state = self.state.lock().unwrap();
+        let failed = match err {
+            Some(e) => Self::is_outage_error(e.code),
+            None => false,
+        };
+        if !failed {
+            if state.opened_at.is_some() && !probe {
+                // a request admitted before the breaker opened: this says
+                // nothing about the current state of the service
+                return;
+            }
+            if probe {
+                debug!("circuit breaker: probe succeeded, closing");
+            }
+            *state = BreakerState::default();
+            return;
+        }
+        state.consecutive_failures += 1;
+        if probe {
+            debug!("circuit breaker: probe failed, reopening");
+            state.probe_started = None;
+            state.opened_at = Some(Instant::now());
+        } else if state.opened_at.is_none() && state.consecutive_failures >= self.failure_threshold
+        {
+            debug!(
+                "circuit breaker: opening after {} consecutive failures",
+                state.consecutive_failures
+            );
+            state.opened_at = Some(Instant::now());
+        }
+    }
+
+    // Errors that indicate the service is unavailable, as opposed to errors
+    // caused by a specific request.
+    fn is_outage_error(code: NoSQLErrorCode) -> bool {
+        code == NoSQLErrorCode::ServerError
+            || code == NoSQLErrorCode::ServiceUnavailable
+            || code == NoSQLErrorCode::RequestTimeout
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn outage() -> NoSQLError {
+        NoSQLError::new(NoSQLErrorCode::ServiceUnavailable, "down")
+    }
+
+    #[test]
+    fn test_circuit_breaker_states() {
+        let cooldown = Duration::from_millis(50);
+        let cb = CircuitBreaker::new(3, cooldown);
+
+        // request errors and successes do not open the breaker
+        cb.record(Some(&outage()), false);
+        cb.record(Some(&outage()), false);
+        cb.record(None, false);
+        cb.record(Some(&outage()), false);
+        cb.record(
+            Some(&NoSQLError::new(NoSQLErrorCode::TableNotFound, "")),
+            false,
+        );
+        cb.record(Some(&outage()), false);
+        cb.record(Some(&outage()), false);
+        assert!(!cb.admit().unwrap());
+
+        // third consecutive outage error opens it
+        cb.record(Some(&outage()), false);
+        let err = cb.admit().unwrap_err();
+        assert_eq!(err.code, NoSQLErrorCode::CircuitOpen);
+
+        // after the cooldown, one probe at a time is allowed
+        std::thread::sleep(cooldown);
+        assert!(cb.admit().unwrap());
+        assert!(cb.admit().is_err());
+
+        // a failed probe reopens it for another cooldown
+        cb.record(Some(&outage()), true);
+        assert!(cb.admit().is_err());
+        std::thread::sleep(cooldown);
+        assert!(cb.admit().unwrap());
+
+        // a successful probe closes it
+        cb.record(None, true);
+        assert!(!cb.admit().unwrap());
+        assert!(!cb.admit().unwrap());
+    }
+}
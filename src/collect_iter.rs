@@ -23,7 +23,7 @@ use tracing::trace;
 #[derive(Debug, Default, Clone)]
 pub(crate) struct CollectIter {
     is_distinct: bool,
-    input_iter: Box<PlanIter>,
+    pub(crate) input_iter: Box<PlanIter>,
 
     data: CollectIterData,
 }
@@ -0,0 +1,250 @@
+//
+// Copyright (c) 2024, 2025 Oracle and/or its affiliates. All rights reserved.
+//
+// Licensed under the Universal Permissive License v 1.0 as shown at
+//  https://oss.oracle.com/licenses/upl/
+//
+use crate::error::ia_err;
+use crate::error::NoSQLError;
+use crate::handle::Handle;
+use crate::query_request::QueryRequest;
+use crate::types::{Capacity, Durability, FieldValue, MapValue, NoSQLColumnToFieldValue};
+use std::result::Result;
+use std::time::Duration;
+
+/// A predicate on the field values of an existing row, used for conditional writes.
+///
+/// A `Condition` is a conjunction of comparisons: a conditional write is only applied if
+/// the existing row matches all of them. It can be given to
+/// [`DeleteRequest::if_condition()`](crate::DeleteRequest::if_condition()),
+/// [`PutRequest::if_condition()`](crate::PutRequest::if_condition()) and
+/// [`UpdateRequest::if_condition()`](crate::UpdateRequest::if_condition()):
+/// ```no_run
+/// # use oracle_nosql_rust_sdk::{Condition, DeleteRequest, Handle};
+/// # use oracle_nosql_rust_sdk::types::MapValue;
+/// # #[tokio::main]
+/// # pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let handle = Handle::builder().build().await?;
+/// let res = DeleteRequest::new("users", MapValue::new().i32("id", 10))
+///     .if_condition(Condition::new().eq("status", "inactive").lt("logins", 5))
+///     .execute(&handle)
+///     .await?;
+/// if !res.success() {
+///     println!("user 10 does not exist, or is still active");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// This gives value-based optimistic concurrency, without the need to track row versions
+/// (see [`DeleteRequest::if_version()`](crate::DeleteRequest::if_version())).
+///
+/// Field names may be top-level column names or dot-separated paths into JSON, Record or
+/// Map columns. Values are always passed to the server as bind variables. If a value cannot
+/// be converted to a [`FieldValue`], the error is returned when the request is executed.
+#[derive(Default, Debug)]
+pub struct Condition {
+    pub(crate) terms: Vec<ConditionTerm>,
+    // the first error converting a comparison value, returned when the
+    // condition is added to a statement
+    pub(crate) err: Option<NoSQLError>,
+}
+
+#[derive(Debug)]
+pub(crate) struct ConditionTerm {
+    field: String,
+    op: &'static str,
+    value: Option<FieldValue>,
+}
+
+impl ConditionTerm {
+    pub(crate) fn clone_internal(&self) -> ConditionTerm {
+        ConditionTerm {
+            field: self.field.clone(),
+            op: self.op,
+            value: self.value.as_ref().map(|v| v.clone_internal()),
+        }
+    }
+}
+
+impl Condition {
+    /// Create a new, empty `Condition`.
+    pub fn new() -> Condition {
+        Condition::default()
+    }
+
+    fn term(mut self, field: &str, op: &'static str, value: Option<FieldValue>) -> Self {
+        self.terms.push(ConditionTerm {
+            field: field.to_string(),
+            op,
+            value,
+        });
+        self
+    }
+
+    fn compare(
+        mut self,
+        field: &str,
+        op: &'static str,
+        value: impl NoSQLColumnToFieldValue,
+    ) -> Self {
+        match value.try_to_field_value() {
+            Ok(v) => self.term(field, op, Some(v)),
+            Err(e) => {
+                self.err.get_or_insert(e);
+                self
+            }
+        }
+    }
+
+    pub(crate) fn clone_internal(&self) -> Condition {
+        Condition {
+            terms: self.terms.iter().map(|t| t.clone_internal()).collect(),
+            err: self.err.clone(),
+        }
+    }
+
+    /// Require that `field` is equal to `value`.
+    pub fn eq(self, field: &str, value: impl NoSQLColumnToFieldValue) -> Self {
+        self.compare(field, "=", value)
+    }
+    /// Require that `field` is not equal to `value`.
+    pub fn ne(self, field: &str, value: impl NoSQLColumnToFieldValue) -> Self {
+        self.compare(field, "!=", value)
+    }
+    /// Require that `field` is less than `value`.
+    pub fn lt(self, field: &str, value: impl NoSQLColumnToFieldValue) -> Self {
+        self.compare(field, "<", value)
+    }
+    /// Require that `field` is less than or equal to `value`.
+    pub fn le(self, field: &str, value: impl NoSQLColumnToFieldValue) -> Self {
+        self.compare(field, "<=", value)
+    }
+    /// Require that `field` is greater than `value`.
+    pub fn gt(self, field: &str, value: impl NoSQLColumnToFieldValue) -> Self {
+        self.compare(field, ">", value)
+    }
+    /// Require that `field` is greater than or equal to `value`.
+    pub fn ge(self, field: &str, value: impl NoSQLColumnToFieldValue) -> Self {
+        self.compare(field, ">=", value)
+    }
+    /// Require that `field` is NULL.
+    pub fn is_null(self, field: &str) -> Self {
+        self.term(field, "IS NULL", None)
+    }
+    /// Require that `field` is not NULL.
+    pub fn is_not_null(self, field: &str) -> Self {
+        self.term(field, "IS NOT NULL", None)
+    }
+
+    /// Returns true if no comparisons have been added.
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty() && self.err.is_none()
+    }
+
+    // Append the SQL for this condition to the given WHERE clauses, using
+    // positional bind variables for all values. Returns the first error
+    // converting a comparison value, if any.
+    pub(crate) fn add_clauses(&self, clauses: &mut Vec<String>) -> Result<(), NoSQLError> {
+        if let Some(e) = &self.err {
+            return Err(e.clone());
+        }
+        for t in &self.terms {
+            validate_path("condition field", &t.field, ".")?;
+            if t.value.is_some() {
+                clauses.push(format!("t.{} {} ?", t.field, t.op));
+            } else {
+                clauses.push(format!("t.{} {}", t.field, t.op));
+            }
+        }
+        Ok(())
+    }
+
+    // The bind variable values, in the order of add_clauses()
+    pub(crate) fn values(&self) -> impl Iterator<Item = &FieldValue> {
+        self.terms.iter().filter_map(|t| t.value.as_ref())
+    }
+}
+
+// Get the WHERE clauses matching a primary key, using positional bind variables.
+pub(crate) fn key_clauses(key: &MapValue) -> Result<Vec<String>, NoSQLError> {
+    if key.len() == 0 {
+        return ia_err!("key must not be empty");
+    }
+    let mut clauses: Vec<String> = Vec::new();
+    for (k, _) in key.iter() {
+        validate_path("key field", k, "")?;
+        clauses.push(format!("t.{} = ?", k));
+    }
+    Ok(clauses)
+}
+
+// Get the number of rows affected from the result row of an UPDATE or DELETE
+// statement, which may be returned as either an integer or a long.
+pub(crate) fn rows_affected(row: &MapValue, name: &str) -> i64 {
+    match row.get_field_value(name) {
+        Some(FieldValue::Integer(i)) => *i as i64,
+        Some(FieldValue::Long(l)) => *l,
+        _ => 0,
+    }
+}
+
+// Prepare a generated statement, or get it from the handle's prepare cache,
+// and bind the given positional variable values. Returns the query, ready to
+// execute, and the capacity consumed by preparing it.
+pub(crate) async fn prepare_statement(
+    h: &Handle,
+    stmt: &str,
+    values: &[&FieldValue],
+    timeout: &Option<Duration>,
+    compartment_id: &str,
+    durability: Option<Durability>,
+) -> Result<(QueryRequest, Capacity), NoSQLError> {
+    let timeout = h.get_timeout(timeout);
+    let mut qreq = QueryRequest::new(stmt)
+        .timeout(&timeout)
+        .compartment_id(compartment_id);
+    if let Some(d) = durability {
+        qreq = qreq.durability(d);
+    }
+    let consumed = qreq.prepare(h).await?;
+    for (i, v) in values.iter().enumerate() {
+        qreq.set_variable_by_id(i as i32 + 1, *v)?;
+    }
+    Ok((qreq, consumed))
+}
+
+// Prepare and execute a generated statement with the given positional bind
+// variable values. Returns the resulting rows and the capacity consumed by
+// both round trips.
+pub(crate) async fn execute_statement(
+    h: &Handle,
+    stmt: &str,
+    values: &[&FieldValue],
+    timeout: &Option<Duration>,
+    compartment_id: &str,
+    durability: Option<Durability>,
+) -> Result<(Vec<MapValue>, Capacity), NoSQLError> {
+    let (mut qreq, mut consumed) =
+        prepare_statement(h, stmt, values, timeout, compartment_id, durability).await?;
+    let mut qres = qreq.execute(h).await?;
+    consumed.add(&qres.consumed());
+    Ok((qres.take_rows(), consumed))
+}
+
+// Names are used directly in generated SQL, so only allow identifiers,
+// separated by any of the given characters.
+pub(crate) fn validate_path(what: &str, name: &str, separators: &str) -> Result<(), NoSQLError> {
+    let valid = name.split(|c| separators.contains(c)).all(|part| {
+        let mut chars = part.chars();
+        match chars.next() {
+            Some(c) if c.is_ascii_alphabetic() => {}
+            _ => return false,
+        }
+        chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    });
+    if !valid {
+        return ia_err!("invalid {} '{}'", what, name);
+    }
+    Ok(())
+}
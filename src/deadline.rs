@@ -0,0 +1,123 @@
+//
+// Copyright (c) 2024, 2025 Oracle and/or its affiliates. All rights reserved.
+//
+// Licensed under the Universal Permissive License v 1.0 as shown at
+//  https://oss.oracle.com/licenses/upl/
+//
+use crate::error::{NoSQLError, NoSQLErrorCode};
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+tokio::task_local! {
+    static DEADLINE: Deadline;
+}
+
+/// An absolute deadline shared by a sequence of operations.
+///
+/// Each request has its own timeout (see [`HandleBuilder::timeout()`](crate::HandleBuilder::timeout())),
+/// so a sequence of requests, such as preparing and then executing a query, can take up to
+/// the sum of their timeouts. When a deadline is in effect, every request started within
+/// [`scope()`](Deadline::scope()) uses a timeout of at most the time remaining until the
+/// deadline, including requests made internally by the SDK, such as the batches of a query.
+/// Requests started after the deadline fail with a
+/// [`RequestTimeout`](crate::NoSQLErrorCode::RequestTimeout) error, without being sent.
+///
+/// ```no_run
+/// # use oracle_nosql_rust_sdk::{Deadline, Handle, NoSQLError, QueryRequest};
+/// # use std::time::Duration;
+/// # #[tokio::main]
+/// # pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let handle = Handle::builder().build().await?;
+/// let deadline = Deadline::after(Duration::from_millis(800));
+/// let rows = deadline
+///     .scope(async {
+///         let mut prep = QueryRequest::new("select * from users where id = $id").prepare_only();
+///         let ps = prep.execute(&handle).await?.prepared_statement();
+///         let mut query = QueryRequest::new_prepared(&ps);
+///         query.set_variable("$id", &10)?;
+///         Ok::<_, NoSQLError>(query.execute(&handle).await?.take_rows())
+///     })
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Scopes can be nested; the earliest deadline applies. The deadline does not apply to tasks
+/// spawned within the scope, unless they also call `scope()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deadline {
+    at: Instant,
+}
+
+impl Deadline {
+    /// Create a deadline at the given instant.
+    pub fn at(at: Instant) -> Deadline {
+        Deadline { at }
+    }
+
+    /// Create a deadline the given time from now.
+    pub fn after(d: Duration) -> Deadline {
+        Deadline {
+            at: Instant::now() + d,
+        }
+    }
+
+    /// Get the instant of the deadline.
+    pub fn instant(&self) -> Instant {
+        self.at
+    }
+
+    /// Get the time remaining until the deadline, or zero if it has passed.
+    pub fn remaining(&self) -> Duration {
+        self.at.saturating_duration_since(Instant::now())
+    }
+
+    /// Return `true` if the deadline has passed.
+    pub fn is_expired(&self) -> bool {
+        Instant::now() >= self.at
+    }
+
+    /// Get the deadline in effect for the current task, if any.
+    pub fn current() -> Option<Deadline> {
+        DEADLINE.try_with(|d| *d).ok()
+    }
+
+    /// Run the given future with this deadline in effect for all requests it makes.
+    ///
+    /// If a deadline is already in effect, the earlier of the two is used.
+    pub async fn scope<F: Future>(self, f: F) -> F::Output {
+        let deadline = match Deadline::current() {
+            Some(outer) if outer.at < self.at => outer,
+            _ => self,
+        };
+        DEADLINE.scope(deadline, f).await
+    }
+
+    // Run the given future with the deadline of the current task, if any. This is
+    // used for tasks spawned internally, which do not inherit it.
+    pub(crate) async fn inherit<F: Future>(current: Option<Deadline>, f: F) -> F::Output {
+        match current {
+            Some(d) => DEADLINE.scope(d, f).await,
+            None => f.await,
+        }
+    }
+
+    // Limit a request timeout to the time remaining until the current deadline.
+    pub(crate) fn limit(timeout: Duration) -> Duration {
+        match Deadline::current() {
+            Some(d) => std::cmp::min(timeout, d.remaining()),
+            None => timeout,
+        }
+    }
+
+    // Return a RequestTimeout error if the current deadline has passed.
+    pub(crate) fn check() -> Result<(), NoSQLError> {
+        match Deadline::current() {
+            Some(d) if d.is_expired() => Err(NoSQLError::new(
+                NoSQLErrorCode::RequestTimeout,
+                "deadline expired before the request was sent",
+            )),
+            _ => Ok(()),
+        }
+    }
+}
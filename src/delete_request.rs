@@ -4,12 +4,15 @@
 // Licensed under the Universal Permissive License v 1.0 as shown at
 //  https://oss.oracle.com/licenses/upl/
 //
+use crate::condition::{execute_statement, key_clauses, rows_affected, validate_path, Condition};
+use crate::error::ia_err;
 use crate::error::NoSQLError;
 use crate::handle::Handle;
 use crate::handle::SendOptions;
 use crate::nson::*;
 use crate::reader::Reader;
-use crate::types::{Capacity, MapValue, OpCode};
+use crate::request_options::RequestOptions;
+use crate::types::{Capacity, Durability, FieldValue, MapValue, OpCode};
 use crate::writer::Writer;
 use crate::Version;
 use std::result::Result;
@@ -22,6 +25,8 @@ use std::time::Duration;
 /// - Delete any existing row. This is the default.
 /// - Succeed only if the row exists and its Version matches a specific Version. Use
 ///   [`if_version()`](DeleteRequest::if_version()) for this case.
+/// - Succeed only if the row exists and its field values match a [`Condition`]. Use
+///   [`if_condition()`](DeleteRequest::if_condition()) for this case.
 ///
 /// Information about the existing row can be returned from a delete operation using
 /// [`return_row(true)`](DeleteRequest::return_row()). Requesting this information incurs
@@ -34,8 +39,9 @@ pub struct DeleteRequest {
     pub(crate) compartment_id: String,
     pub(crate) abort_on_fail: bool,
     pub(crate) return_row: bool,
-    // TODO: durability
+    pub(crate) durability: Option<Durability>,
     match_version: Version,
+    condition: Option<Condition>,
 }
 
 /// Struct representing the result of a [`DeleteRequest`] execution.
@@ -53,6 +59,12 @@ pub struct DeleteResult {
 
 impl DeleteResult {
     /// Get the result of the operation: `true` if the row was deleted from the table.
+    ///
+    /// This is false if the row did not exist, or for conditional deletes
+    /// ([`DeleteRequest::if_version()`], [`DeleteRequest::if_condition()`]) if the condition
+    /// was not met and the row was left in place. Use [`existing_version()`](DeleteResult::existing_version())
+    /// with [`DeleteRequest::return_row()`] to find the current version of a row whose
+    /// version did not match.
     pub fn success(&self) -> bool {
         self.success
     }
@@ -105,10 +117,12 @@ impl DeleteRequest {
     /// `table_name` and `key` are required and must be non-empty.
     ///
     /// `key` must contain all fields required to construct the primary key for the table.
-    pub fn new(table_name: &str, key: MapValue) -> DeleteRequest {
+    /// As with [`GetRequest::key()`](crate::GetRequest::key()), it may be given as a
+    /// `(column, value)` pair or a tuple of pairs.
+    pub fn new(table_name: &str, key: impl Into<MapValue>) -> DeleteRequest {
         DeleteRequest {
             table_name: table_name.to_string(),
-            key: key,
+            key: key.into(),
             ..Default::default()
         }
     }
@@ -137,12 +151,46 @@ impl DeleteRequest {
         self
     }
 
+    /// On-premises only: specify the [`Durability`] to use for the operation.
+    ///
+    /// This is ignored if the request is part of a [`WriteMultipleRequest`](crate::WriteMultipleRequest),
+    /// which uses its own durability setting.
+    ///
+    /// If not set, the server's default durability is used.
+    pub fn durability(mut self, d: Durability) -> Self {
+        self.durability = Some(d);
+        self
+    }
+
+    /// Apply [`RequestOptions`] to this request, using their timeout, compartment and durability.
+    pub fn options(mut self, options: &RequestOptions) -> Self {
+        options.apply_timeout(&mut self.timeout);
+        options.apply_compartment_id(&mut self.compartment_id);
+        options.apply_durability(&mut self.durability);
+        self
+    }
+
     /// Succeed only if the record already exists its version matches the given version.
     pub fn if_version(mut self, version: &Version) -> DeleteRequest {
         self.match_version = version.clone();
         self
     }
 
+    /// Succeed only if the record exists and its current field values match the given [`Condition`].
+    ///
+    /// The key protocol operations only support version-based conditions, so a conditional
+    /// delete is executed as a generated SQL `DELETE` statement. This takes two round trips to
+    /// the server, and cannot be used in a [`WriteMultipleRequest`](crate::WriteMultipleRequest).
+    /// If [`return_row(true)`](DeleteRequest::return_row()) is given, the deleted row is
+    /// returned in [`DeleteResult::existing_value()`]; the existing version and modification
+    /// time are not available.
+    ///
+    /// This cannot be combined with [`if_version()`](DeleteRequest::if_version()).
+    pub fn if_condition(mut self, condition: Condition) -> DeleteRequest {
+        self.condition = Some(condition);
+        self
+    }
+
     /// Return information about the existing row. Requesting this information incurs
     /// additional cost and may affect operation latency.
     pub fn return_row(mut self, val: bool) -> DeleteRequest {
@@ -156,8 +204,11 @@ impl DeleteRequest {
     }
 
     pub async fn execute(&self, h: &Handle) -> Result<DeleteResult, NoSQLError> {
-        let mut w: Writer = Writer::new();
-        w.write_i16(h.inner.serial_version);
+        h.check_key_size(&self.table_name, &self.key)?;
+        if let Some(condition) = &self.condition {
+            return self.execute_conditional(condition, h).await;
+        }
+        let mut w = h.request_writer();
         let timeout = h.get_timeout(&self.timeout);
         self.serialize_internal(&mut w, false, false, &timeout);
         let mut opts = SendOptions {
@@ -171,6 +222,69 @@ impl DeleteRequest {
         Ok(resp)
     }
 
+    // Get the SQL statement used for a delete with a field condition.
+    #[cfg(test)]
+    pub(crate) fn conditional_statement(
+        &self,
+        condition: &Condition,
+    ) -> Result<String, NoSQLError> {
+        self.conditional_statement_for_table(condition, &self.table_name)
+    }
+
+    fn conditional_statement_for_table(
+        &self,
+        condition: &Condition,
+        table_name: &str,
+    ) -> Result<String, NoSQLError> {
+        if !self.match_version.is_empty() {
+            return ia_err!("DeleteRequest: cannot use both if_version() and if_condition()");
+        }
+        validate_path("table name", table_name, ":.")?;
+        let mut clauses = key_clauses(&self.key)?;
+        condition.add_clauses(&mut clauses)?;
+        let mut stmt = format!(
+            "DELETE FROM {} t WHERE {}",
+            table_name,
+            clauses.join(" AND ")
+        );
+        if self.return_row {
+            stmt.push_str(" RETURNING *");
+        }
+        Ok(stmt)
+    }
+
+    async fn execute_conditional(
+        &self,
+        condition: &Condition,
+        h: &Handle,
+    ) -> Result<DeleteResult, NoSQLError> {
+        let stmt =
+            self.conditional_statement_for_table(condition, &h.table_name(&self.table_name))?;
+        let mut values: Vec<&FieldValue> = self.key.iter().map(|(_, v)| v).collect();
+        values.extend(condition.values());
+        let (rows, consumed) = execute_statement(
+            h,
+            &stmt,
+            &values,
+            &self.timeout,
+            &self.compartment_id,
+            self.durability,
+        )
+        .await?;
+        let mut res = DeleteResult {
+            consumed: Some(consumed),
+            ..Default::default()
+        };
+        let mut rows = rows.into_iter();
+        if self.return_row {
+            res.existing_value = rows.next();
+            res.success = res.existing_value.is_some();
+        } else if let Some(r) = rows.next() {
+            res.success = rows_affected(&r, "numRowsDeleted") > 0;
+        }
+        Ok(res)
+    }
+
     // TODO: when is add_table_name ever true??
     fn serialize_internal(
         &self,
@@ -187,7 +301,7 @@ impl DeleteRequest {
 
         if is_sub_request {
             if add_table_name {
-                ns.write_string_field(TABLE_NAME, &self.table_name);
+                ns.write_table_name_field(&self.table_name);
             }
             ns.write_i32_field(OP_CODE, opcode as i32);
             if self.abort_on_fail {
@@ -198,7 +312,7 @@ impl DeleteRequest {
             ns.write_header(opcode, timeout, &self.table_name);
             ns.end_header();
             ns.start_payload();
-            // TODO: ns.write_durability(self.durability);
+            ns.write_durability(&self.durability);
         }
 
         ns.write_true_bool_field(RETURN_ROW, true);
@@ -291,6 +405,15 @@ impl NsonRequest for DeleteRequest {
 }
 
 impl NsonSubRequest for DeleteRequest {
+    fn validate(&self) -> Result<(), NoSQLError> {
+        if self.condition.is_some() {
+            return ia_err!(
+                "DeleteRequest with if_condition() cannot be used in WriteMultipleRequest"
+            );
+        }
+        Ok(())
+    }
+
     fn serialize(&self, w: &mut Writer, _timeout: &Duration) {
         // For subrequests in WriteMultiple, we need to write a complete operation map
         let mut ns = NsonSerializer::new(w);
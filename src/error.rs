@@ -5,6 +5,7 @@
 //  https://oss.oracle.com/licenses/upl/
 //
 use num_enum::TryFromPrimitive;
+use std::time::Duration;
 
 include!(concat!(env!("OUT_DIR"), "/ua.rs"));
 
@@ -16,11 +17,31 @@ pub(crate) fn user_agent() -> &'static str {
     USER_AGENT
 }
 
+// Truncate a string to at most max_bytes for use in an error message, without
+// splitting a multi-byte UTF-8 character.
+pub(crate) fn truncate_utf8(s: &str, max_bytes: usize) -> &str {
+    if s.len() <= max_bytes {
+        return s;
+    }
+    let mut end = max_bytes;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
 /// Enumeration of all possible errors returned by this library.
+///
+/// Create errors with [`NoSQLError::new()`]; the struct may gain fields in future releases.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct NoSQLError {
     pub code: NoSQLErrorCode,
     pub message: String,
+    // delay requested by a Retry-After header in an http error response
+    pub(crate) retry_after: Option<Duration>,
+    // individual problems, for errors that report more than one
+    pub(crate) problems: Vec<String>,
 }
 
 impl std::error::Error for NoSQLError {
@@ -40,20 +61,155 @@ impl NoSQLError {
         NoSQLError {
             code,
             message: msg.to_string(),
+            retry_after: None,
+            problems: Vec::new(),
+        }
+    }
+
+    /// Create the error returned when a request is rejected by an open circuit breaker.
+    ///
+    /// `retry_in` is the time remaining before the circuit breaker will allow a new request.
+    pub fn circuit_open(retry_in: Duration) -> NoSQLError {
+        NoSQLError::new(
+            NoSQLErrorCode::CircuitOpen,
+            &format!(
+                "request not sent: circuit breaker is open after repeated failures reaching the service, retry in {}ms",
+                retry_in.as_millis()
+            ),
+        )
+    }
+
+    /// Returns true if the operation that failed with this error may succeed if retried.
+    ///
+    /// See [`NoSQLErrorCode::is_retryable()`].
+    pub fn is_retryable(&self) -> bool {
+        self.code.is_retryable()
+    }
+
+    /// Returns the delay requested by the service before retrying, if any.
+    ///
+    /// This is set when the service, or a proxy, API gateway or load balancer in front of
+    /// it, rejects a request with an HTTP 429 (Too Many Requests) or 503 (Service
+    /// Unavailable) status and a `Retry-After` header, or when the service returns a retry
+    /// hint with a read or write throttling error. Such requests are already retried
+    /// internally after the requested delay, as long as it fits in the request timeout; this
+    /// can be used by applications that implement their own retries on top of that.
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.retry_after
+    }
+
+    /// Returns the individual problems found, for errors that report more than one.
+    ///
+    /// This is set by [`HandleBuilder::validate()`](crate::HandleBuilder::validate()) and
+    /// [`HandleBuilder::build()`](crate::HandleBuilder::build()), which check the whole
+    /// configuration and report every problem found at once. The message of the error
+    /// includes all of them. For other errors, this is empty.
+    pub fn problems(&self) -> &[String] {
+        &self.problems
+    }
+
+    // Create an error reporting one or more problems. With a single problem, the
+    // message is the problem itself.
+    pub(crate) fn from_problems(
+        code: NoSQLErrorCode,
+        context: &str,
+        problems: Vec<String>,
+    ) -> NoSQLError {
+        let message = match problems.len() {
+            1 => problems[0].clone(),
+            n => format!("{}: {} problems: {}", context, n, problems.join("; ")),
+        };
+        let mut err = NoSQLError::new(code, &message);
+        err.problems = problems;
+        err
+    }
+
+    /// Returns true if this error is a request timeout.
+    pub fn is_timeout(&self) -> bool {
+        self.code == NoSQLErrorCode::RequestTimeout
+    }
+
+    /// Returns true if this error reports that a table does not exist.
+    pub fn is_table_not_found(&self) -> bool {
+        self.code == NoSQLErrorCode::TableNotFound
+    }
+
+    /// Returns the location of a query error as `(line, column)`, if the server reported one.
+    ///
+    /// Query compile errors ([`NoSQLErrorCode::QueryCompileError`]), and some errors raised
+    /// while running a query, include the position in the statement where the error was
+    /// found, in the form `at (line, column)`. Lines and columns start at 1.
+    pub fn query_error_location(&self) -> Option<(u32, u32)> {
+        let mut rest = self.message.as_str();
+        while let Some(pos) = rest.find("at (") {
+            rest = &rest[pos + 4..];
+            if let Some(end) = rest.find(')') {
+                if let Some((line, col)) = rest[..end].split_once(',') {
+                    if let (Ok(l), Ok(c)) = (line.trim().parse(), col.trim().parse()) {
+                        return Some((l, c));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    // Map an IllegalArgument error returned by the server for a query that was
+    // not prepared yet to a QueryCompileError, if the server reported the
+    // location of the error in the statement.
+    pub(crate) fn into_query_compile_error(self) -> NoSQLError {
+        if self.code != NoSQLErrorCode::IllegalArgument || self.query_error_location().is_none() {
+            return self;
         }
+        NoSQLError::new(NoSQLErrorCode::QueryCompileError, &self.message)
+    }
+
+    // Create an error for a non-success HTTP status returned by the service,
+    // or by a proxy or load balancer in front of it, including any delay given
+    // in a Retry-After header of a 429 or 503 response.
+    pub(crate) fn from_http_response(
+        status: reqwest::StatusCode,
+        retry_after: Option<&str>,
+        content: &str,
+    ) -> NoSQLError {
+        let mut err = NoSQLError::from_http_status(status, content);
+        if status.as_u16() == 429 || status.as_u16() == 503 {
+            err.retry_after = retry_after.and_then(parse_retry_after);
+        }
+        err
+    }
+
+    // Create an error for a non-success HTTP status returned by the service,
+    // or by a proxy or load balancer in front of it.
+    pub(crate) fn from_http_status(status: reqwest::StatusCode, content: &str) -> NoSQLError {
+        let code = match status.as_u16() {
+            401 => NoSQLErrorCode::InvalidAuthorization,
+            403 => NoSQLErrorCode::InsufficientPermission,
+            408 | 504 => NoSQLErrorCode::RequestTimeout,
+            429 => NoSQLErrorCode::OperationLimitExceeded,
+            502 | 503 => NoSQLErrorCode::ServiceUnavailable,
+            500..=599 => NoSQLErrorCode::ServerError,
+            _ => NoSQLErrorCode::IllegalArgument,
+        };
+        NoSQLError::new(
+            code,
+            &format!(
+                "got unexpected http status: {}, response text: {} ({})",
+                status,
+                content,
+                sdk_version()
+            ),
+        )
     }
 
     pub fn from_int(icode: i32, msg: &str) -> NoSQLError {
         if let Ok(code) = NoSQLErrorCode::try_from(icode) {
-            return NoSQLError {
-                code: code,
-                message: msg.to_string(),
-            };
-        }
-        NoSQLError {
-            code: NoSQLErrorCode::UnknownError,
-            message: format!("Invalid integer error code {}", icode),
+            return NoSQLError::new(code, msg);
         }
+        NoSQLError::new(
+            NoSQLErrorCode::UnknownError,
+            &format!("Invalid integer error code {}", icode),
+        )
     }
 }
 
@@ -73,10 +229,10 @@ impl NoSQLError {
 macro_rules! ia_error {
     ($($t:tt)*) => {{
         let m = format!($($t)*);
-        NoSQLError {
-            code: crate::error::NoSQLErrorCode::IllegalArgument,
-            message: format!("{} ({})", m, crate::error::sdk_version()),
-        }
+        NoSQLError::new(
+            crate::error::NoSQLErrorCode::IllegalArgument,
+            &format!("{} ({})", m, crate::error::sdk_version()),
+        )
     }};
 }
 
@@ -85,30 +241,89 @@ pub(crate) use ia_error;
 macro_rules! ia_err {
     ($($t:tt)*) => {{
         let m = format!($($t)*);
-        Err(NoSQLError {
-            code: crate::error::NoSQLErrorCode::IllegalArgument,
-            message: format!("{} ({})", m, crate::error::sdk_version()),
-        })
+        Err(NoSQLError::new(
+            crate::error::NoSQLErrorCode::IllegalArgument,
+            &format!("{} ({})", m, crate::error::sdk_version()),
+        ))
     }};
 }
 
 pub(crate) use ia_err;
 
+// Parse the value of a Retry-After header: either a number of seconds, or an
+// HTTP date. A date in the past means no delay.
+pub(crate) fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let millis = date.timestamp_millis() - chrono::Utc::now().timestamp_millis();
+    Some(Duration::from_millis(millis.max(0) as u64))
+}
+
+// Get the message of an error followed by the messages of all its sources,
+// which often hold the actual cause (such as "connection refused").
+fn error_chain(e: &dyn std::error::Error) -> String {
+    let mut msg = e.to_string();
+    let mut source = e.source();
+    while let Some(s) = source {
+        msg.push_str(": ");
+        msg.push_str(&s.to_string());
+        source = s.source();
+    }
+    msg
+}
+
 impl From<reqwest::Error> for NoSQLError {
     fn from(e: reqwest::Error) -> Self {
-        let mut code = NoSQLErrorCode::ServerError;
-        if e.is_timeout() {
-            code = NoSQLErrorCode::RequestTimeout;
+        if let Some(status) = e.status() {
+            return NoSQLError::from_http_status(status, &error_chain(&e));
         }
-        // TODO: others?
-        NoSQLError {
-            code: code,
-            message: format!(
+        // A timeout may also be a connect timeout, so check it first. Failures
+        // to connect or to read a response are transient; errors building the
+        // request are not.
+        let code = if e.is_timeout() {
+            NoSQLErrorCode::RequestTimeout
+        } else if e.is_connect() {
+            NoSQLErrorCode::ServiceUnavailable
+        } else if e.is_builder() {
+            NoSQLErrorCode::IllegalArgument
+        } else {
+            NoSQLErrorCode::ServerError
+        };
+        NoSQLError::new(
+            code,
+            &format!(
                 "reqwest error: {} ({})",
-                e.to_string(),
+                error_chain(&e),
                 crate::error::sdk_version()
             ),
-        }
+        )
+    }
+}
+
+impl From<std::io::Error> for NoSQLError {
+    fn from(e: std::io::Error) -> Self {
+        use std::io::ErrorKind;
+        let code = match e.kind() {
+            ErrorKind::TimedOut => NoSQLErrorCode::RequestTimeout,
+            ErrorKind::ConnectionRefused
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::NotConnected
+            | ErrorKind::BrokenPipe
+            | ErrorKind::UnexpectedEof => NoSQLErrorCode::ServiceUnavailable,
+            ErrorKind::PermissionDenied => NoSQLErrorCode::InsufficientPermission,
+            ErrorKind::NotFound | ErrorKind::InvalidInput | ErrorKind::InvalidData => {
+                NoSQLErrorCode::IllegalArgument
+            }
+            _ => NoSQLErrorCode::UnknownError,
+        };
+        NoSQLError::new(
+            code,
+            &format!("io error: {} ({})", error_chain(&e), sdk_version()),
+        )
     }
 }
 
@@ -278,6 +493,10 @@ pub enum NoSQLErrorCode {
     /// protocol version (and accompanying logic) and try again.
     UnsupportedProtocol = 24,
 
+    /// UnsupportedQueryVersion error indicates the server does not support the
+    /// query version of the driver (see [`Handle::query_version()`](crate::Handle::query_version())).
+    UnsupportedQueryVersion = 27,
+
     /// ReadLimitExceeded error represents that the provisioned read throughput
     /// has been exceeded.
     ///
@@ -377,4 +596,53 @@ pub enum NoSQLErrorCode {
 
     /// InternalRetry is used internally for retry logic.
     InternalRetry = 1001,
+
+    /// CircuitOpen error represents that the request was not sent because the
+    /// handle's circuit breaker is open, after too many consecutive failures
+    /// reaching the service. See [`HandleBuilder::circuit_breaker()`](crate::HandleBuilder::circuit_breaker()).
+    CircuitOpen = 1002,
+
+    /// QueryCompileError represents that the server could not compile a query
+    /// statement, for example because of a syntax error. The error message is the
+    /// message returned by the server; use [`NoSQLError::query_error_location()`]
+    /// to get the line and column of the error.
+    QueryCompileError = 1003,
+
+    /// RegionMismatch represents that the service redirected the request to the
+    /// endpoint of a different region, typically because the handle is configured
+    /// for the wrong region. The error message names the region. See
+    /// [`HandleBuilder::follow_region_redirects()`](crate::HandleBuilder::follow_region_redirects()).
+    RegionMismatch = 1004,
+
+    /// SchemaChanged represents that the table used by a prepared query was altered
+    /// after the query was prepared, so its results may not match the expected schema.
+    /// This is only detected for queries that enable
+    /// [`QueryRequest::check_schema()`](crate::QueryRequest::check_schema()).
+    SchemaChanged = 1005,
+}
+
+impl NoSQLErrorCode {
+    /// Returns true if an operation that failed with this error code may succeed if retried,
+    /// usually after a delay.
+    ///
+    /// This includes throttling errors, timeouts, transient server and network errors, and
+    /// authentication errors that clear once security information is available. Errors caused
+    /// by the request itself (such as `IllegalArgument` or `TableNotFound`) and
+    /// authorization failures (`InvalidAuthorization`, `InsufficientPermission`) are not
+    /// retryable.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            NoSQLErrorCode::ReadLimitExceeded
+                | NoSQLErrorCode::WriteLimitExceeded
+                | NoSQLErrorCode::OperationLimitExceeded
+                | NoSQLErrorCode::RequestTimeout
+                | NoSQLErrorCode::ServerError
+                | NoSQLErrorCode::ServiceUnavailable
+                | NoSQLErrorCode::TableBusy
+                | NoSQLErrorCode::SecurityInfoUnavailable
+                | NoSQLErrorCode::RetryAuthentication
+                | NoSQLErrorCode::CircuitOpen
+        )
+    }
 }
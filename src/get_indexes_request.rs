@@ -10,6 +10,7 @@ use crate::handle::Handle;
 use crate::handle::SendOptions;
 use crate::nson::*;
 use crate::reader::Reader;
+use crate::request_options::RequestOptions;
 use crate::types::{FieldType, OpCode};
 use crate::writer::Writer;
 use std::result::Result;
@@ -78,6 +79,14 @@ impl GetIndexesRequest {
         self
     }
 
+    /// Apply [`RequestOptions`] to this request, using their timeout, compartment and namespace.
+    pub fn options(mut self, options: &RequestOptions) -> Self {
+        options.apply_timeout(&mut self.timeout);
+        options.apply_compartment_id(&mut self.compartment_id);
+        options.apply_namespace(&mut self.namespace);
+        self
+    }
+
     pub fn namespace(mut self, namespace: &str) -> GetIndexesRequest {
         self.namespace = namespace.to_string();
         self
@@ -85,8 +94,7 @@ impl GetIndexesRequest {
 
     pub async fn execute(&self, h: &Handle) -> Result<GetIndexesResult, NoSQLError> {
         // TODO: validate
-        let mut w: Writer = Writer::new();
-        w.write_i16(h.inner.serial_version);
+        let mut w = h.request_writer();
         let timeout = h.get_timeout(&self.timeout);
         self.nson_serialize(&mut w, &timeout);
         let mut opts = SendOptions {
@@ -4,15 +4,24 @@
 // Licensed under the Universal Permissive License v 1.0 as shown at
 //  https://oss.oracle.com/licenses/upl/
 //
+use crate::condition::{key_clauses, validate_path};
+use crate::error::ia_err;
 use crate::error::NoSQLError;
 use crate::error::NoSQLErrorCode::{IllegalArgument, ResourceNotFound};
 use crate::handle::Handle;
 use crate::handle::SendOptions;
 use crate::nson::*;
+use crate::query_request::QueryRequest;
 use crate::reader::Reader;
-use crate::types::{Capacity, Consistency, MapValue, NoSQLRow, OpCode};
+use crate::request_options::RequestOptions;
+use crate::types::{
+    row_to_map_value, Capacity, Consistency, FieldType, MapValue, NoSQLRow, OpCode,
+};
 use crate::writer::Writer;
 use crate::Version;
+use bytes::Bytes;
+use chrono::{DateTime, FixedOffset};
+use std::collections::HashMap;
 use std::result::Result;
 use std::time::Duration;
 
@@ -23,7 +32,9 @@ pub struct GetRequest {
     pub(crate) compartment_id: String,
     pub(crate) timeout: Option<Duration>,
     pub(crate) key: MapValue,
-    pub(crate) consistency: Consistency,
+    pub(crate) consistency: Option<Consistency>,
+    // top-level fields to return, or empty for the whole row
+    pub(crate) projection: Vec<String>,
     // TODO: limiters, retry stats, etc
 }
 
@@ -37,7 +48,12 @@ pub struct GetResult {
     pub(crate) modification_time: i64, // TODO: Time
     pub(crate) expiration_time: i64,   // TODO: Time
     pub(crate) version: Option<Version>,
-    // TODO: stats, rldelay, etc...
+    // only populated by execute_borrowed()
+    pub(crate) binary_values: HashMap<String, Bytes>,
+    // from HandleBuilder::display_timezone()
+    pub(crate) display_timezone: Option<FixedOffset>,
+    pub(crate) rate_limit_delayed_ms: u64,
+    // TODO: stats, etc...
 }
 
 impl GetResult {
@@ -72,9 +88,105 @@ impl GetResult {
         }
         None
     }
+    /// Get the time, in milliseconds, this operation was delayed backing off from throttling.
+    ///
+    /// This is only relevant for NoSQL Cloud operation on provisioned tables. If the table's
+    /// read throughput limit is exceeded, the request is retried with backoff within the
+    /// request timeout. This returns the total backoff delay, which is zero if the request
+    /// was not throttled. A nonzero value is a signal to slow down before requests start
+    /// failing with `ReadLimitExceeded` errors.
+    pub fn rate_limit_delayed_ms(&self) -> u64 {
+        self.rate_limit_delayed_ms
+    }
+    /// Get the value of a top-level binary column without copying it.
+    ///
+    /// This is only populated by [`GetRequest::execute_borrowed()`]. The returned [`Bytes`] refers
+    /// directly to the response data received from the server.
+    pub fn binary_value(&self, column: &str) -> Option<&Bytes> {
+        self.binary_values.get(column)
+    }
+    /// Get the value of a top-level timestamp column.
+    ///
+    /// If a display timezone was set with [`HandleBuilder::display_timezone()`](crate::HandleBuilder::display_timezone()),
+    /// the timestamp is converted to that timezone. Otherwise it is returned as sent by the
+    /// server (UTC). Returns `None` if there is no row, or the column is not a timestamp.
+    pub fn timestamp(&self, column: &str) -> Option<DateTime<FixedOffset>> {
+        let ts = self.row.as_ref()?.get_timestamp(column)?;
+        if let Some(tz) = &self.display_timezone {
+            return Some(ts.with_timezone(tz));
+        }
+        Some(ts)
+    }
     // TODO: stats, rldelay, etc...
 }
 
+/// The value of a binary column, returned in chunks, from
+/// [`GetRequest::binary_field_as_stream()`].
+///
+/// Iterating over a `BinaryStream` returns the value in chunks of [`Bytes`] that refer to the
+/// received data without copying it; these can be passed to most HTTP server frameworks as a
+/// streaming response body. It also implements [`tokio::io::AsyncRead`], to be used with
+/// functions such as [`tokio::io::copy()`].
+#[derive(Debug, Clone)]
+pub struct BinaryStream {
+    pub(crate) data: Bytes,
+    pub(crate) chunk_size: usize,
+}
+
+impl BinaryStream {
+    pub(crate) fn new(data: Bytes) -> BinaryStream {
+        BinaryStream {
+            data,
+            chunk_size: 64 * 1024,
+        }
+    }
+    /// Set the maximum size of the chunks returned by the iterator.
+    ///
+    /// The default is 64 KB. A size of zero returns the rest of the value as one chunk.
+    pub fn chunk_size(mut self, size: usize) -> Self {
+        self.chunk_size = size;
+        self
+    }
+    /// Get the number of bytes remaining in the stream.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+    /// Return `true` if there are no bytes remaining in the stream.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+    /// Get the bytes remaining in the stream, as a single value.
+    pub fn into_bytes(self) -> Bytes {
+        self.data
+    }
+}
+
+impl Iterator for BinaryStream {
+    type Item = Bytes;
+    fn next(&mut self) -> Option<Bytes> {
+        if self.data.is_empty() {
+            return None;
+        }
+        let n = match self.chunk_size {
+            0 => self.data.len(),
+            size => std::cmp::min(size, self.data.len()),
+        };
+        Some(self.data.split_to(n))
+    }
+}
+
+impl tokio::io::AsyncRead for BinaryStream {
+    fn poll_read(
+        mut self: std::pin::Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        let n = std::cmp::min(buf.remaining(), self.data.len());
+        buf.put_slice(&self.data.split_to(n));
+        std::task::Poll::Ready(Ok(()))
+    }
+}
+
 impl GetRequest {
     /// Create a new `GetRequest`.
     ///
@@ -110,11 +222,33 @@ impl GetRequest {
         self
     }
 
+    /// Apply [`RequestOptions`] to this request, using their timeout, consistency and compartment.
+    pub fn options(mut self, options: &RequestOptions) -> Self {
+        options.apply_timeout(&mut self.timeout);
+        options.apply_consistency(&mut self.consistency);
+        options.apply_compartment_id(&mut self.compartment_id);
+        self
+    }
+
     /// Specify the primary key to use to find the row (record) in the table, from a [`MapValue`].
     ///
     /// `key` must contain all fields required to construct the primary key for the table.
-    pub fn key(mut self, key: MapValue) -> GetRequest {
-        self.key = key;
+    /// Besides a `MapValue`, it may be given as a `(column, value)` pair or a tuple of pairs,
+    /// for compound keys:
+    /// ```no_run
+    /// # use oracle_nosql_rust_sdk::{GetRequest, Handle};
+    /// # #[tokio::main]
+    /// # pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let handle = Handle::builder().build().await?;
+    /// let res = GetRequest::new("users")
+    ///     .key((("region", "us"), ("id", 5)))
+    ///     .execute(&handle)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn key(mut self, key: impl Into<MapValue>) -> GetRequest {
+        self.key = key.into();
         self
     }
 
@@ -127,24 +261,33 @@ impl GetRequest {
     /// See the [`GetRequest::execute_into()`] documentation below for an example of how to
     /// add the `NoSQLRow` derive to a struct.
     pub fn row_key(mut self, row_key: &dyn NoSQLRow) -> Result<GetRequest, NoSQLError> {
-        match row_key.to_map_value() {
-            Ok(value) => {
-                self = self.key(value);
-                return Ok(self);
-            }
-            Err(e) => {
-                // TODO: save error as source
-                return Err(NoSQLError::new(
-                    IllegalArgument,
-                    &format!("could not convert struct to MapValue: {}", e.to_string()),
-                ));
-            }
-        }
+        self.key = row_to_map_value(row_key)?;
+        Ok(self)
     }
 
     /// Specify the desired [`Consistency`] for the operation.
     pub fn consistency(mut self, c: Consistency) -> GetRequest {
-        self.consistency = c;
+        self.consistency = Some(c);
+        self
+    }
+
+    /// Specify the top-level fields of the row to return.
+    ///
+    /// By default the whole row is returned. With a projection, the returned row only
+    /// contains the given fields, which saves network bandwidth when reading a few columns
+    /// of wide rows. Fields that do not exist in the table cause an `IllegalArgument`
+    /// error from the server.
+    ///
+    /// Get operations do not support projections, so the request is sent as a
+    /// `SELECT <fields> FROM <table> WHERE <key>` query, prepared and then executed with the
+    /// key values as bind variables. This takes two round trips, and the result does not
+    /// include the row version, modification time or expiration time.
+    ///
+    /// A projection does not reduce the read units consumed: the server reads the whole row
+    /// either way, and the query also consumes a small amount for preparing the statement.
+    /// An empty list of fields returns the whole row with a normal get.
+    pub fn project(mut self, fields: &[&str]) -> GetRequest {
+        self.projection = fields.iter().map(|f| f.to_string()).collect();
         self
     }
 
@@ -152,19 +295,149 @@ impl GetRequest {
     ///
     /// If the record exists in the table, [`GetResult::row`] will be `Some()`.
     pub async fn execute(&self, h: &Handle) -> Result<GetResult, NoSQLError> {
-        let mut w: Writer = Writer::new();
-        w.write_i16(h.inner.serial_version);
+        if !self.projection.is_empty() {
+            return self.execute_projected(h).await;
+        }
+        let (mut r, delay) = self.send(h).await?;
+        let mut resp = GetRequest::nson_deserialize(&mut r)?;
+        resp.display_timezone = h.inner.builder.display_timezone;
+        resp.rate_limit_delayed_ms = delay.as_millis() as u64;
+        Ok(resp)
+    }
+
+    /// Execute the request, without copying top-level binary column values.
+    ///
+    /// This is intended for rows with large binary columns (images, documents, etc) where the
+    /// application only needs to forward the bytes elsewhere. Binary columns at the top level
+    /// of the row are *not* included in [`GetResult::row()`]; instead they are available from
+    /// [`GetResult::binary_value()`] as [`Bytes`] that refer directly to the data received from
+    /// the server. All other columns are returned in the row as usual.
+    ///
+    /// Note that holding on to any of the returned [`Bytes`] keeps the whole response in memory.
+    ///
+    /// This cannot be used with a projection set by [`project()`](GetRequest::project()).
+    pub async fn execute_borrowed(&self, h: &Handle) -> Result<GetResult, NoSQLError> {
+        if !self.projection.is_empty() {
+            return ia_err!("GetRequest: execute_borrowed() cannot be used with project()");
+        }
+        let (mut r, delay) = self.send(h).await?;
+        let mut resp = GetRequest::nson_deserialize_internal(&mut r, true)?;
+        resp.display_timezone = h.inner.builder.display_timezone;
+        resp.rate_limit_delayed_ms = delay.as_millis() as u64;
+        Ok(resp)
+    }
+
+    /// Get the value of a top-level binary column of the row as a [`BinaryStream`].
+    ///
+    /// This is intended for serving large binary values, such as images or documents,
+    /// where the bytes are forwarded elsewhere (for example to an HTTP response) in chunks.
+    /// The row is read with [`execute_borrowed()`](GetRequest::execute_borrowed()), so the
+    /// value is not copied: each chunk of the stream refers directly to the data received
+    /// from the server. The server does not support reading part of a value, so the whole
+    /// row is received before the stream is returned, and is held in memory until the
+    /// stream is dropped. In the NoSQL Cloud Service, rows are limited to 512 KB.
+    ///
+    /// Returns `None` if the row does not exist, or the column is missing or `NULL`, and an
+    /// IllegalArgument error if the column is not binary.
+    /// ```no_run
+    /// # use oracle_nosql_rust_sdk::{GetRequest, Handle};
+    /// # use oracle_nosql_rust_sdk::types::MapValue;
+    /// # #[tokio::main]
+    /// # pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let handle = Handle::builder().build().await?;
+    /// # let mut response = tokio::io::sink();
+    /// let stream = GetRequest::new("media")
+    ///     .key(MapValue::new().i64("id", 10))
+    ///     .binary_field_as_stream(&handle, "content")
+    ///     .await?;
+    /// if let Some(mut stream) = stream {
+    ///     tokio::io::copy(&mut stream, &mut response).await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn binary_field_as_stream(
+        &self,
+        h: &Handle,
+        field: &str,
+    ) -> Result<Option<BinaryStream>, NoSQLError> {
+        let mut res = self.execute_borrowed(h).await?;
+        if let Some(data) = res.binary_values.remove(field) {
+            return Ok(Some(BinaryStream::new(data)));
+        }
+        match res.row.as_ref().and_then(|r| r.get_field_value(field)) {
+            None => Ok(None),
+            Some(v) if v.is_null() => Ok(None),
+            Some(v) => ia_err!(
+                "GetRequest: column '{}' is not a binary column: {:?}",
+                field,
+                v
+            ),
+        }
+    }
+
+    // Get the query used for a get with a projection.
+    fn projection_statement(&self, table_name: &str) -> Result<String, NoSQLError> {
+        validate_path("table name", table_name, ":.")?;
+        let mut fields: Vec<String> = Vec::with_capacity(self.projection.len());
+        for f in &self.projection {
+            validate_path("field", f, "")?;
+            fields.push(format!("t.{}", f));
+        }
+        Ok(format!(
+            "SELECT {} FROM {} t WHERE {}",
+            fields.join(", "),
+            table_name,
+            key_clauses(&self.key)?.join(" AND ")
+        ))
+    }
+
+    async fn execute_projected(&self, h: &Handle) -> Result<GetResult, NoSQLError> {
+        h.check_key_size(&self.table_name, &self.key)?;
+        let stmt = self.projection_statement(&h.table_name(&self.table_name))?;
+        let timeout = h.get_timeout(&self.timeout);
+        let mut prep = QueryRequest::new(&stmt)
+            .prepare_only()
+            .timeout(&timeout)
+            .compartment_id(&self.compartment_id);
+        let pres = prep.execute(h).await?;
+        let mut qreq = QueryRequest::new_prepared(&pres.prepared_statement())
+            .timeout(&timeout)
+            .compartment_id(&self.compartment_id);
+        if let Some(c) = self.consistency {
+            qreq = qreq.consistency(&c);
+        }
+        for (i, (_, v)) in self.key.iter().enumerate() {
+            qreq.set_variable_by_id(i as i32 + 1, v)?;
+        }
+        let mut qres = qreq.execute(h).await?;
+        let mut consumed = pres.consumed();
+        consumed.add(&qres.consumed());
+        Ok(GetResult {
+            row: qres.take_rows().into_iter().next(),
+            consumed: Some(consumed),
+            display_timezone: h.inner.builder.display_timezone,
+            rate_limit_delayed_ms: qres.rate_limit_delayed_ms(),
+            ..Default::default()
+        })
+    }
+
+    // Send the request, returning the response and the time spent backing off
+    // from throttling errors.
+    async fn send(&self, h: &Handle) -> Result<(Reader, Duration), NoSQLError> {
+        h.check_key_size(&self.table_name, &self.key)?;
+        let mut w = h.request_writer();
         let timeout = h.get_timeout(&self.timeout);
         self.nson_serialize(&mut w, &timeout);
         let mut opts = SendOptions {
             timeout: timeout,
             retryable: true,
             compartment_id: self.compartment_id.clone(),
+            retry_throttling: true,
             ..Default::default()
         };
-        let mut r = h.send_and_receive(w, &mut opts).await?;
-        let resp = GetRequest::nson_deserialize(&mut r)?;
-        Ok(resp)
+        let r = h.send_and_receive(w, &mut opts).await?;
+        Ok((r, opts.throttle_delay))
     }
 
     /// Execute the request, populating an existing Rust native struct.
@@ -282,7 +555,7 @@ impl GetRequest {
 
         // payload
         ns.start_payload();
-        ns.write_consistency(self.consistency);
+        ns.write_consistency(self.consistency.unwrap_or_default());
         ns.write_map_field(KEY, &self.key);
         ns.end_payload();
 
@@ -290,6 +563,13 @@ impl GetRequest {
     }
 
     pub(crate) fn nson_deserialize(r: &mut Reader) -> Result<GetResult, NoSQLError> {
+        GetRequest::nson_deserialize_internal(r, false)
+    }
+
+    pub(crate) fn nson_deserialize_internal(
+        r: &mut Reader,
+        shared_binary: bool,
+    ) -> Result<GetResult, NoSQLError> {
         let mut walker = MapWalker::new(r)?;
         let mut res: GetResult = Default::default();
         while walker.has_next() {
@@ -307,7 +587,7 @@ impl GetRequest {
                 }
                 ROW => {
                     //println!("   get_result: ROW");
-                    read_row(walker.r, &mut res)?;
+                    read_row(walker.r, &mut res, shared_binary)?;
                     //for (f,v) in res.row.iter() {
                     //println!("row: field={} value={:?}", f, v);
                     //}
@@ -322,7 +602,7 @@ impl GetRequest {
     }
 }
 
-fn read_row(r: &mut Reader, res: &mut GetResult) -> Result<(), NoSQLError> {
+fn read_row(r: &mut Reader, res: &mut GetResult, shared_binary: bool) -> Result<(), NoSQLError> {
     let mut walker = MapWalker::new(r)?;
     while walker.has_next() {
         walker.next()?;
@@ -342,7 +622,12 @@ fn read_row(r: &mut Reader, res: &mut GetResult) -> Result<(), NoSQLError> {
             }
             VALUE => {
                 //println!("   read_row: VALUE");
-                res.row = Some(walker.read_nson_map()?);
+                if shared_binary {
+                    MapWalker::expect_type(walker.r, FieldType::Map)?;
+                    res.row = Some(walker.r.read_map_shared_binary(&mut res.binary_values)?);
+                } else {
+                    res.row = Some(walker.read_nson_map()?);
+                }
             }
             _ => {
                 //println!("   read_row: skipping field '{}'", name);
@@ -15,6 +15,7 @@ use crate::query_request::QueryRequest;
 use crate::reader::Reader;
 use crate::types::{bd_try_from_f64, compare_atomics_total_order, FieldType, FieldValue, MapValue};
 
+use bigdecimal::BigDecimal;
 use std::cmp::Ordering;
 use std::collections::BTreeMap;
 use std::collections::BTreeSet;
@@ -25,7 +26,7 @@ use tracing::{debug, trace};
 #[add_planiter_fields]
 #[derive(Debug, Default, Clone)]
 pub(crate) struct GroupIter {
-    input_iter: Box<PlanIter>,
+    pub(crate) input_iter: Box<PlanIter>,
     num_gb_columns: usize,
     column_names: Vec<String>,
     is_distinct: bool,
@@ -173,10 +174,10 @@ impl AggrValue {
         if let AggrValueEnum::Field(sum_value) = &mut self.value {
             match sum_value {
                 FieldValue::Integer(i) => {
-                    *i += 1;
+                    *sum_value = sum_longs(*i as i64, 1);
                 }
                 FieldValue::Long(l) => {
-                    *l += 1;
+                    *sum_value = sum_longs(*l, 1);
                 }
                 FieldValue::Double(d) => {
                     *d += 1.0;
@@ -215,12 +216,12 @@ impl AggrValue {
         if let AggrValueEnum::Field(sum_value) = &mut self.value {
             match sum_value {
                 FieldValue::Integer(i) => match val {
-                    FieldValue::Integer(vi) => {
-                        *i += *vi;
-                    }
+                    FieldValue::Integer(vi) => match i.checked_add(*vi) {
+                        Some(sum) => *i = sum,
+                        None => *sum_value = FieldValue::Long(*i as i64 + *vi as i64),
+                    },
                     FieldValue::Long(vl) => {
-                        let l = *i as i64 + *vl;
-                        self.value = AggrValueEnum::Field(FieldValue::Long(l));
+                        *sum_value = sum_longs(*i as i64, *vl);
                     }
                     FieldValue::Double(vd) => {
                         let d = *i as f64 + *vd;
@@ -235,10 +236,10 @@ impl AggrValue {
                 },
                 FieldValue::Long(l) => match val {
                     FieldValue::Integer(vi) => {
-                        *l += *vi as i64;
+                        *sum_value = sum_longs(*l, *vi as i64);
                     }
                     FieldValue::Long(vl) => {
-                        *l += *vl;
+                        *sum_value = sum_longs(*l, *vl);
                     }
                     FieldValue::Double(vd) => {
                         let d = *l as f64 + *vd;
@@ -300,6 +301,15 @@ impl AggrValue {
     }
 }
 
+// Add two longs, promoting the sum to a Number if it does not fit in a long, as
+// the server does. Counts and sums must never wrap around.
+fn sum_longs(a: i64, b: i64) -> FieldValue {
+    match a.checked_add(b) {
+        Some(sum) => FieldValue::Long(sum),
+        None => FieldValue::Number(BigDecimal::from(a) + b),
+    }
+}
+
 #[derive(Debug, Default)]
 struct GroupIterData {
     state: PlanIterState,
@@ -702,3 +712,88 @@ impl GroupIter {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Run the given arrays through an array_collect aggregator and return the
+    // final value, as it would appear in a result row.
+    fn collect_arrays(func: FuncCode, arrays: Vec<Vec<i32>>) -> FieldValue {
+        let req = QueryRequest::new("select 1");
+        let mut gi = GroupIter::default();
+        let mut tuple = vec![AggrValue::new(func)];
+        for a in arrays {
+            let val = FieldValue::Array(a.into_iter().map(FieldValue::Integer).collect());
+            tuple[0].collect(&req, val, false);
+        }
+        tuple[0].collect(&req, FieldValue::Null, false);
+        let value = take(&mut tuple[0].value);
+        gi.get_aggr_value_internal(&req, &tuple, value, 0)
+    }
+
+    #[test]
+    fn test_array_collect_results() -> Result<(), NoSQLError> {
+        let mut row = MapValue::new();
+        row.put_field_value(
+            "all",
+            collect_arrays(FuncCode::ArrayCollect, vec![vec![3, 1], vec![3, 2]]),
+        );
+        row.put_field_value(
+            "distinct",
+            collect_arrays(FuncCode::ArrayCollectDistinct, vec![vec![3, 1], vec![3, 2]]),
+        );
+        row.put_field_value("none", FieldValue::Null);
+        row.put_i32("scalar", 5);
+
+        assert_eq!(row.get_vec::<i32>("all")?, vec![3, 1, 3, 2]);
+        assert_eq!(row.get_distinct_vec::<i32>("all")?, vec![3, 1, 2]);
+        let mut d = row.get_vec::<i32>("distinct")?;
+        d.sort();
+        assert_eq!(d, vec![1, 2, 3]);
+        assert_eq!(row.get_vec::<i32>("none")?, Vec::<i32>::new());
+        assert!(row.get_vec::<String>("all").is_err());
+        assert!(row.get_vec::<i32>("scalar").is_err());
+        assert!(row.get_distinct_vec::<i32>("scalar").is_err());
+        assert!(row.get_vec::<i32>("missing").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sum_and_count_overflow() -> Result<(), NoSQLError> {
+        let req = QueryRequest::new("select 1");
+        let field = |av: &AggrValue| match &av.value {
+            AggrValueEnum::Field(f) => f.clone_internal(),
+            v => panic!("unexpected aggregate value {:?}", v),
+        };
+
+        // integer sums become longs
+        let mut sum = AggrValue::new(FuncCode::FnSum);
+        sum.value = AggrValueEnum::Field(FieldValue::Integer(i32::MAX));
+        sum.add(&req, &FieldValue::Integer(1), false)?;
+        assert_eq!(field(&sum), FieldValue::Long(i32::MAX as i64 + 1));
+
+        // long sums become numbers
+        let mut sum = AggrValue::new(FuncCode::FnSum);
+        sum.add(&req, &FieldValue::Long(i64::MAX), false)?;
+        sum.add(&req, &FieldValue::Integer(2), false)?;
+        let expected = BigDecimal::from(i64::MAX) + 2;
+        assert!(matches!(field(&sum), FieldValue::Number(n) if n == expected));
+        sum.add(&req, &FieldValue::Long(-3), false)?;
+        assert!(matches!(field(&sum), FieldValue::Number(n) if n == expected - 3));
+
+        let mut sum = AggrValue::new(FuncCode::FnSum);
+        sum.add(&req, &FieldValue::Long(i64::MIN), false)?;
+        sum.add(&req, &FieldValue::Long(-1), false)?;
+        let expected = BigDecimal::from(i64::MIN) - 1;
+        assert!(matches!(field(&sum), FieldValue::Number(n) if n == expected));
+
+        // counts too
+        let mut count = AggrValue::new(FuncCode::FnCount);
+        count.value = AggrValueEnum::Field(FieldValue::Long(i64::MAX));
+        count.increment(&req, false)?;
+        let expected = BigDecimal::from(i64::MAX) + 1;
+        assert!(matches!(field(&count), FieldValue::Number(n) if n == expected));
+        Ok(())
+    }
+}
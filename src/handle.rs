@@ -7,27 +7,41 @@
 use crate::auth_common::authentication_provider::AuthenticationProvider;
 use crate::auth_common::resource_principal_auth_provider::ResourcePrincipalAuthProvider;
 use crate::auth_common::signer;
+use crate::circuit_breaker::CircuitBreaker;
+use crate::deadline::Deadline;
 use crate::handle_builder::AuthConfig;
 use crate::handle_builder::AuthType;
 use reqwest::header::{HeaderMap, HeaderValue};
 
 use crate::error::NoSQLErrorCode::InternalRetry;
-use crate::error::{ia_err, user_agent};
+use crate::error::{ia_err, truncate_utf8, user_agent};
 use crate::error::{NoSQLError, NoSQLErrorCode};
 use crate::handle_builder::AuthProvider;
 use crate::handle_builder::HandleBuilder;
 use crate::handle_builder::HandleMode;
-use crate::nson::MapWalker;
+use crate::handle_builder::LogLevel;
+use crate::handle_builder::{BACKOFF_BASE, BACKOFF_MAX};
+use crate::nson::{
+    MapWalker, CONSUMED, HEADER, OP_CODE, QUERY_V3, SERIAL_VERSION, TABLE_NAME, V4_VERSION,
+};
+use crate::observer::RequestEvent;
+use crate::prepare_cache::PrepareCache;
+use crate::query_cache::QueryCache;
+use crate::query_request::QueryRequest;
 use crate::reader::Reader;
+use crate::region::region_for_host;
+use crate::table_request::GetTableRequest;
+use crate::types::{Capacity, FieldValue, MapValue, NoSQLRow, OpCode, TableState};
 use crate::writer::Writer;
+use bytes::Bytes;
 
 use std::collections::HashMap;
 use std::result::Result;
-use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicU64, AtomicUsize};
 use std::sync::Arc;
-use std::time::Duration;
-use tracing::{debug, trace};
+use std::time::{Duration, Instant};
+use tracing::{debug, trace, warn, Level};
 use url::Url;
 
 /// **The main database handle**.
@@ -38,6 +52,51 @@ use url::Url;
 /// Note: there is no need to enclose this struct in an `Rc` or [`Arc`], as it uses an
 /// [`Arc`] internally, so calling `.clone()` on this struct will always return the
 /// same underlying handle.
+///
+/// # Sharing a handle
+///
+/// All request `execute()` methods take a `&Handle`, so a single handle can be used by
+/// any number of concurrent requests. How to share it depends on how the concurrent work
+/// is started:
+///
+/// - Futures that run concurrently within one task (with `tokio::join!`, `select!`, or a
+///   stream combinator) can all borrow the same handle. No clones are needed:
+/// ```no_run
+/// # use oracle_nosql_rust_sdk::{GetRequest, Handle};
+/// # use oracle_nosql_rust_sdk::types::MapValue;
+/// # async fn run(handle: &Handle) -> Result<(), Box<dyn std::error::Error>> {
+/// let user_req = GetRequest::new("users").key(MapValue::new().i32("id", 10));
+/// let order_req = GetRequest::new("orders").key(MapValue::new().i32("id", 500));
+/// let (user, order) = tokio::join!(user_req.execute(handle), order_req.execute(handle));
+/// # Ok(())
+/// # }
+/// ```
+/// - Tasks started with `tokio::spawn()` or a `JoinSet` must be `'static`, so they cannot
+///   borrow a handle owned by the spawning function. Move a clone of the handle into each
+///   task instead. This is the equivalent of sharing an `Arc`: a clone only increments a
+///   reference count, and all clones share the same connection pool, authentication and
+///   configuration:
+/// ```no_run
+/// # use oracle_nosql_rust_sdk::{Handle, QueryRequest};
+/// # async fn run(handle: &Handle) -> Result<(), Box<dyn std::error::Error>> {
+/// let mut tasks = tokio::task::JoinSet::new();
+/// for shard in 0..4 {
+///     let handle = handle.clone();
+///     tasks.spawn(async move {
+///         QueryRequest::new(&format!("select * from events where shard = {}", shard))
+///             .execute(&handle)
+///             .await
+///     });
+/// }
+/// while let Some(res) = tasks.join_next().await {
+///     println!("rows: {}", res??.rows().len());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Avoid creating a new handle per task or per request: each handle has its own connection
+/// pool and authentication state, which must be set up again on first use.
 #[derive(Clone, Debug)]
 pub struct Handle {
     // Use an inner Arc so cloning keeps the same contents
@@ -47,13 +106,24 @@ pub struct Handle {
 #[derive(Debug)]
 pub(crate) struct HandleRef {
     pub(crate) client: reqwest::Client,
-    pub(crate) endpoint: String,
+    // changed when following a region redirect
+    endpoint: std::sync::RwLock<String>,
     pub(crate) serial_version: i16,
     pub(crate) builder: HandleBuilder,
     // session doesn't require a tokio Mutex because it's never held across awaits
     session: std::sync::Mutex<String>,
     request_id: AtomicUsize,
+    // total time spent backing off from throttling errors, in milliseconds
+    rate_limit_delayed_ms: AtomicU64,
     timeout: Duration,
+    // default timeout for TableRequest and SystemRequest
+    ddl_timeout: Duration,
+    circuit_breaker: Option<CircuitBreaker>,
+    pub(crate) prepare_cache: Option<PrepareCache>,
+    pub(crate) query_cache: Option<QueryCache>,
+    // table versions read by schema checks, keyed by compartment and table name,
+    // with the time they were read
+    pub(crate) table_versions: std::sync::Mutex<HashMap<String, (tokio::time::Instant, String)>>,
 }
 
 impl Handle {
@@ -62,16 +132,149 @@ impl Handle {
         HandleBuilder::new()
     }
 
+    /// Get the serial version of the binary (NSON) protocol used for requests to the server.
+    ///
+    /// This is written at the start of every request. The version is not negotiated with the
+    /// server: the SDK currently supports a single serial version, so this does not depend on
+    /// the server, and a server that does not support it rejects requests with an
+    /// [`UnsupportedProtocol`](NoSQLErrorCode::UnsupportedProtocol) error, which is not retried.
+    pub fn serial_version(&self) -> i16 {
+        self.inner.serial_version
+    }
+
+    /// Get the query version sent with queries to the server.
+    ///
+    /// This determines the query features the SDK can use with the server. As with
+    /// [`serial_version()`](Handle::serial_version()), it is not negotiated: the SDK currently
+    /// supports a single query version, and a server that does not support it rejects queries
+    /// with an [`UnsupportedQueryVersion`](NoSQLErrorCode::UnsupportedQueryVersion) error.
+    pub fn query_version(&self) -> i16 {
+        QUERY_V3
+    }
+
+    /// Get the version of the request header format of the binary (NSON) protocol.
+    pub fn protocol_version(&self) -> i32 {
+        V4_VERSION
+    }
+
+    /// Get the name of a table as stored in the database, with the table prefix of this
+    /// handle added.
+    ///
+    /// Requests add the prefix to table names themselves, but the SQL text of queries and
+    /// DDL statements is sent as-is. Use this to build statements that refer to tables:
+    ///
+    /// ```no_run
+    /// # use oracle_nosql_rust_sdk::{Handle, QueryRequest};
+    /// # async fn run(handle: &Handle) -> Result<(), Box<dyn std::error::Error>> {
+    /// let stmt = format!("select * from {} where id = 10", handle.table_name("users"));
+    /// let res = QueryRequest::new(&stmt).execute(handle).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// See [`HandleBuilder::table_prefix()`]. Without a prefix, this returns the name
+    /// unchanged.
+    pub fn table_name(&self, name: &str) -> String {
+        prefixed_table_name(&self.inner.builder.table_prefix, name)
+    }
+
+    /// Get the total time, in milliseconds, that operations using this handle have been
+    /// delayed backing off from throttling errors.
+    ///
+    /// This is the sum of the `rate_limit_delayed_ms()` values of all results, such as
+    /// [`PutResult::rate_limit_delayed_ms()`](crate::PutResult::rate_limit_delayed_ms()),
+    /// including operations that eventually failed. Applications that do their own
+    /// throttling can sample it periodically: a growing value means the request rate is
+    /// above the table's provisioned throughput.
+    pub fn rate_limit_delayed_ms(&self) -> u64 {
+        self.inner.rate_limit_delayed_ms.load(Ordering::Relaxed)
+    }
+
+    /// Remove all results from the query result cache.
+    ///
+    /// Call this after writes that must be visible to the next execution of cached queries.
+    /// See [`HandleBuilder::query_cache()`]. This does nothing if the cache is not enabled.
+    pub fn invalidate_query_cache(&self) {
+        if let Some(cache) = &self.inner.query_cache {
+            cache.clear();
+        }
+    }
+
+    /// Determine whether a table exists.
+    ///
+    /// This sends a [`GetTableRequest`](crate::GetTableRequest) for the table. It returns
+    /// `false` if the table does not exist, or has been dropped, and `true` if it exists in any
+    /// other state (including while it is being created or altered). Other errors, such as
+    /// authorization or network errors, are returned as errors.
+    ///
+    /// ```no_run
+    /// # use oracle_nosql_rust_sdk::{Handle, TableRequest};
+    /// # async fn run(handle: &Handle) -> Result<(), Box<dyn std::error::Error>> {
+    /// if !handle.table_exists("users").await? {
+    ///     TableRequest::new("users")
+    ///         .statement("create table users (id integer, name string, primary key(id))")
+    ///         .execute(handle)
+    ///         .await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn table_exists(&self, table_name: &str) -> Result<bool, NoSQLError> {
+        match GetTableRequest::new(table_name).execute(self).await {
+            Ok(res) => Ok(res.state() != TableState::Dropped),
+            Err(e) if e.is_table_not_found() => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Execute a query that is expected to return at most one row, such as a lookup by a
+    /// unique field.
+    ///
+    /// Returns the first row, or `None` if the query returned no rows. If `strict` is `true`,
+    /// an `IllegalState` error is returned if the query returns more than one row.
+    ///
+    /// ```no_run
+    /// # use oracle_nosql_rust_sdk::Handle;
+    /// # async fn run(handle: &Handle) -> Result<(), Box<dyn std::error::Error>> {
+    /// match handle
+    ///     .query_one("select * from users where email = 'jane@example.com'", true)
+    ///     .await?
+    /// {
+    ///     Some(user) => println!("found user {:?}", user.get_i32("id")),
+    ///     None => println!("no such user"),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// For statements with bind variables, or other query options, use
+    /// [`QueryRequest::execute_one()`](crate::QueryRequest::execute_one()).
+    pub async fn query_one(
+        &self,
+        statement: &str,
+        strict: bool,
+    ) -> Result<Option<MapValue>, NoSQLError> {
+        QueryRequest::new(statement).execute_one(self, strict).await
+    }
+
+    /// Execute a query that is expected to return at most one row, and convert the row to a
+    /// native struct.
+    ///
+    /// This is the same as [`query_one()`](Handle::query_one()), with the row converted using
+    /// the [`macro@NoSQLRow`](crate::NoSQLRow) derive macro.
+    pub async fn query_one_as<T: NoSQLRow + Default>(
+        &self,
+        statement: &str,
+        strict: bool,
+    ) -> Result<Option<T>, NoSQLError> {
+        QueryRequest::new(statement)
+            .execute_one_as(self, strict)
+            .await
+    }
+
     // Create the new Handle based on builder configuration
     pub(crate) async fn new(b: &HandleBuilder) -> Result<Handle, NoSQLError> {
-        if b.auth_type == AuthType::None {
-            if b.from_environment {
-                return ia_err!("cannot build handle: no auth type specified. set ORACLE_NOSQL_AUTH environment.");
-            }
-            return ia_err!("cannot build handle: no auth type specified");
-            //trace!("defaulting auth config to user-based OCI auth from ~/.oci/config");
-            //builder = builder.cloud_auth_from_file("~/.oci/config")?;
-        }
+        b.validate()?;
 
         let builder = b.clone();
         // default timeout to 30 seconds
@@ -98,25 +301,38 @@ impl Handle {
                 if builder.accept_invalid_certs {
                     cb = cb.danger_accept_invalid_certs(true);
                 }
+                if let Some(nodelay) = builder.tcp_nodelay {
+                    cb = cb.tcp_nodelay(nodelay);
+                }
+                if let Some(interval) = builder.tcp_keepalive {
+                    cb = cb.tcp_keepalive(interval);
+                }
+                if let Some(version) = builder.min_tls_version {
+                    cb = cb.min_tls_version(version.to_reqwest());
+                }
+                // Redirects to another host are returned, not followed, so they
+                // can be checked for a region redirect. Redirects to the same
+                // host are followed as usual.
+                cb = cb.redirect(reqwest::redirect::Policy::custom(|attempt| {
+                    if attempt.previous().len() >= 10 {
+                        attempt.error("too many redirects")
+                    } else if attempt.url().host_str()
+                        != attempt.previous().first().and_then(|u| u.host_str())
+                    {
+                        attempt.stop()
+                    } else {
+                        attempt.follow()
+                    }
+                }));
                 cb.build()?
             }
         };
-        // create auth provider if not already created
-        match builder.auth_type {
-            AuthType::Instance => {
-                return ia_err!("InstancePrincipalAuthProvider has been removed in this simplified implementation");
-            }
-            AuthType::Resource => {
-                return ia_err!("ResourcePrincipalAuthProvider::new() has been removed - use new_from_values() instead");
-            }
-            _ => {}
-        }
-        if builder.endpoint.is_empty() {
-            if builder.from_environment {
-                return ia_err!("can't determine NoSQL endpoint: set ORACLE_NOSQL_ENDPOINT or ORACLE_NOSQL_REGION");
-            } else {
-                return ia_err!("can't determine NoSQL endpoint: call HandleBuilder::endpoint() or HandleBuilder::cloud_region()");
-            }
+        // only allowed by validate() with danger_allow_http_cloud_auth()
+        if !builder.use_https && builder.auth_type.is_cloud() {
+            warn!(
+                "cloud authentication is used with plaintext endpoint http://{}: request signatures are not encrypted",
+                builder.endpoint
+            );
         }
         // normalize endpoint to "http[s]://{endpoint}/V2/nosql/data"
         let mut ep = String::from("http");
@@ -130,15 +346,31 @@ impl Handle {
             "Creating new Handle: {:?}, {:?}, endpoint={}",
             builder.mode, builder.auth, ep
         );
+        let circuit_breaker = builder
+            .circuit_breaker
+            .map(|(threshold, cooldown)| CircuitBreaker::new(threshold, cooldown));
+        let prepare_cache = match builder.prepare_cache_size {
+            0 => None,
+            size => Some(PrepareCache::new(size)),
+        };
+        let query_cache = builder
+            .query_cache
+            .map(|(size, ttl)| QueryCache::new(size, ttl));
         Ok(Handle {
             inner: Arc::new(HandleRef {
                 client: c,
-                endpoint: ep,
-                serial_version: 4,
+                endpoint: std::sync::RwLock::new(ep),
+                serial_version: SERIAL_VERSION,
                 builder: builder,
                 timeout: timeout.clone(),
+                ddl_timeout: b.ddl_timeout.unwrap_or(timeout),
                 session: std::sync::Mutex::new("".to_string()),
                 request_id: AtomicUsize::new(1),
+                rate_limit_delayed_ms: AtomicU64::new(0),
+                circuit_breaker,
+                prepare_cache,
+                query_cache,
+                table_versions: Default::default(),
             }),
         })
     }
@@ -170,8 +402,10 @@ impl Handle {
         &self,
         data: &Vec<u8>,
         send_options: &mut SendOptions,
-    ) -> Result<Vec<u8>, NoSQLError> {
+    ) -> Result<Bytes, NoSQLError> {
         let request_id = self.inner.request_id.fetch_add(1, Ordering::Relaxed);
+        send_options.request_id = request_id;
+        let endpoint = self.endpoint();
         let mut headers = HeaderMap::new();
         headers.insert("x-nosql-request-id", HeaderValue::from(request_id));
 
@@ -195,7 +429,7 @@ impl Handle {
             }
             AuthProvider::Onprem { provider } => {
                 if let Some(p) = provider {
-                    p.add_required_headers(&self.inner.client, &mut headers)
+                    p.add_required_headers(&self.inner.client, &self.inner.builder, &mut headers)
                         .await?;
                 }
             }
@@ -223,7 +457,7 @@ impl Handle {
                 reqwest::Method::POST,
                 "",
                 headers,
-                Url::parse(&self.inner.endpoint)?,
+                Url::parse(&endpoint)?,
                 sp,
                 HashMap::new(),
                 true,
@@ -255,25 +489,48 @@ impl Handle {
         // Set User-Agent
         headers.insert("User-Agent", HeaderValue::from_str(user_agent())?);
 
+        // Add any extra headers from the builder, never replacing the headers above
+        let defaults = &self.inner.builder.default_headers;
+        for name in defaults.keys() {
+            if !headers.contains_key(name) {
+                for value in defaults.get_all(name) {
+                    headers.append(name, value.clone());
+                }
+            }
+        }
+
         let resp = self
             .inner
             .client
-            .post(&self.inner.endpoint)
+            .post(&endpoint)
             // TODO: resolve this clone... Hmmm
             .body(data.clone())
             .timeout(send_options.timeout.clone())
             .headers(headers)
             .send()
             .await?;
+        if resp.status().is_redirection() {
+            let location = resp
+                .headers()
+                .get("location")
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.to_string());
+            return Err(self.region_redirect(resp.status(), location.as_deref(), send_options));
+        }
         // check resp status for 200, err on others
         if !resp.status().is_success() {
             let status = resp.status().clone();
+            let retry_after = resp
+                .headers()
+                .get("retry-after")
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.to_string());
             let content = resp.text().await?;
-            return ia_err!(
-                "got unexpected http status: {}, response text: {}",
+            return Err(NoSQLError::from_http_response(
                 status,
-                content
-            );
+                retry_after.as_deref(),
+                &content,
+            ));
         }
 
         // read request id in return, validate
@@ -302,8 +559,68 @@ impl Handle {
             }
         }
         let result = resp.bytes().await?;
-        // TODO: some way to avoid this copy
-        Ok(result.to_vec())
+        Ok(result)
+    }
+
+    pub(crate) fn endpoint(&self) -> String {
+        self.inner.endpoint.read().unwrap().clone()
+    }
+
+    // Handle a redirect to another host. A redirect to the NoSQL endpoint of a
+    // known region is a RegionMismatch error, unless following region redirects
+    // is enabled: then the handle switches to that endpoint and the request is
+    // retried, at most once per request. Other redirects are not followed.
+    fn region_redirect(
+        &self,
+        status: reqwest::StatusCode,
+        location: Option<&str>,
+        send_options: &mut SendOptions,
+    ) -> NoSQLError {
+        let target = location
+            .and_then(|l| Url::parse(l).ok())
+            .filter(|u| u.scheme() == "https");
+        let (url, region) = match target.and_then(|u| {
+            let r = region_for_host(u.host_str()?)?;
+            Some((u, r))
+        }) {
+            Some(t) => t,
+            None => {
+                return NoSQLError::new(
+                    NoSQLErrorCode::IllegalArgument,
+                    &format!(
+                        "unexpected redirect ({}) from {} to '{}': only redirects to the NoSQL endpoint of a known region are supported",
+                        status,
+                        self.endpoint(),
+                        location.unwrap_or_default()
+                    ),
+                );
+            }
+        };
+        let host = match url.port() {
+            Some(p) => format!("{}:{}", region.nosql_endpoint(), p),
+            None => region.nosql_endpoint(),
+        };
+        if self.inner.builder.follow_region_redirects && !send_options.redirected {
+            let endpoint = format!("https://{}/V2/nosql/data", host);
+            warn!(
+                "request redirected to region {}: switching endpoint from {} to {}",
+                region.id(),
+                self.endpoint(),
+                endpoint
+            );
+            *self.inner.endpoint.write().unwrap() = endpoint;
+            send_options.redirected = true;
+            return NoSQLError::new(InternalRetry, "");
+        }
+        NoSQLError::new(
+            NoSQLErrorCode::RegionMismatch,
+            &format!(
+                "the service redirected the request to region {} ({}): the handle may be configured for the wrong region. Use HandleBuilder::cloud_region(\"{}\"), or enable HandleBuilder::follow_region_redirects()",
+                region.id(),
+                host,
+                region.id()
+            ),
+        )
     }
 
     // TODO: opCode
@@ -311,23 +628,187 @@ impl Handle {
         &self,
         w: Writer,
         send_options: &mut SendOptions,
+    ) -> Result<Reader, NoSQLError> {
+        Deadline::check()?;
+        let start = Instant::now();
+        let observer = self.inner.builder.observer.as_ref().map(|o| &o.0);
+        let mut event = observer.map(|o| {
+            let (op, table) = request_op_and_table(&w.buf).unwrap_or_default();
+            let event = RequestEvent::new(op, table);
+            o.on_request_start(&event);
+            event
+        });
+        let res = match &self.inner.circuit_breaker {
+            Some(cb) => match cb.admit() {
+                Ok(probe) => {
+                    let res = self
+                        .send_and_receive_retry(&w, send_options, event.as_mut())
+                        .await;
+                    cb.record(res.as_ref().err(), probe);
+                    res
+                }
+                Err(e) => Err(e),
+            },
+            None => {
+                self.send_and_receive_retry(&w, send_options, event.as_mut())
+                    .await
+            }
+        };
+        if let (Some(o), Some(event)) = (observer, event.as_mut()) {
+            event.elapsed = event.start.elapsed();
+            event.request_id = send_options.request_id;
+            match &res {
+                Ok(r) => o.on_response(event, response_consumed(r).as_ref()),
+                Err(e) => o.on_error(event, e),
+            }
+        }
+        if let Some(threshold) = self.inner.builder.slow_request_threshold {
+            let elapsed = start.elapsed();
+            if elapsed >= threshold {
+                self.log_slow_request(&w, &res, elapsed, send_options);
+            }
+        }
+        res
+    }
+
+    // Log a request that took longer than the slow request threshold, with the
+    // operation and table name from the request header, and the capacity
+    // consumed from the response.
+    fn log_slow_request(
+        &self,
+        w: &Writer,
+        res: &Result<Reader, NoSQLError>,
+        elapsed: Duration,
+        send_options: &SendOptions,
+    ) {
+        let (op, table) = request_op_and_table(&w.buf).unwrap_or_default();
+        let op = match op {
+            Some(op) => format!("{:?}", op),
+            None => "Unknown".to_string(),
+        };
+        let consumed = match res {
+            Ok(r) => response_consumed(r),
+            Err(_) => None,
+        }
+        .unwrap_or_default();
+        let error = match res {
+            Ok(_) => String::new(),
+            Err(e) => format!("{:?}", e.code),
+        };
+        macro_rules! slow_request_event {
+            ($level:expr) => {
+                tracing::event!(
+                    target: SLOW_REQUEST_TARGET,
+                    $level,
+                    op = %op,
+                    table = %table,
+                    elapsed_ms = elapsed.as_millis() as u64,
+                    read_units = consumed.read_units,
+                    read_kb = consumed.read_kb,
+                    write_kb = consumed.write_kb,
+                    request_id = send_options.request_id,
+                    retries = send_options.retries,
+                    error = %error,
+                    "slow request"
+                )
+            };
+        }
+        match self.inner.builder.slow_request_log_level {
+            LogLevel::Error => slow_request_event!(Level::ERROR),
+            LogLevel::Warn => slow_request_event!(Level::WARN),
+            LogLevel::Info => slow_request_event!(Level::INFO),
+            LogLevel::Debug => slow_request_event!(Level::DEBUG),
+            LogLevel::Trace => slow_request_event!(Level::TRACE),
+        }
+    }
+
+    async fn send_and_receive_retry(
+        &self,
+        w: &Writer,
+        send_options: &mut SendOptions,
+        mut event: Option<&mut RequestEvent>,
     ) -> Result<Reader, NoSQLError> {
         send_options.retries = 0;
+        // measured on the runtime clock, like the backoff delays
+        let start = tokio::time::Instant::now();
+        let mut throttle_retries: u32 = 0;
+        let mut throttle_delay = Duration::ZERO;
         loop {
-            match self.send_and_receive_once(&w, send_options).await {
+            match self.send_and_receive_once(w, send_options).await {
                 Ok(r) => return Ok(r),
                 Err(e) => {
                     if e.code == InternalRetry {
                         send_options.retries += 1;
+                        self.notify_retry(event.as_deref_mut(), &e, Duration::ZERO, send_options);
                         //tokio::time::sleep(Duration::from_millis(30)).await;
                         continue;
                     }
+                    // A Retry-After delay, or a retry hint on a throttling error, means the
+                    // request was rejected before it was processed, so it is safe to resend
+                    // any request. Its delay is honored, but is never shorter than the
+                    // usual backoff.
+                    if e.retry_after.is_some()
+                        || (send_options.retry_throttling && Self::is_throttling_error(e.code))
+                    {
+                        // Back off and resend the same request, as long as the
+                        // delay still fits in the request timeout.
+                        let delay = std::cmp::max(
+                            e.retry_after.unwrap_or_default(),
+                            self.retry_backoff(throttle_retries, throttle_delay),
+                        );
+                        if start.elapsed() + delay < send_options.timeout {
+                            trace!("request throttled ({:?}): retrying in {:?}", e.code, delay);
+                            self.notify_retry(event.as_deref_mut(), &e, delay, send_options);
+                            tokio::time::sleep(delay).await;
+                            throttle_retries += 1;
+                            throttle_delay = delay;
+                            send_options.throttle_delay += delay;
+                            self.inner
+                                .rate_limit_delayed_ms
+                                .fetch_add(delay.as_millis() as u64, Ordering::Relaxed);
+                            continue;
+                        }
+                    }
                     return Err(e);
                 }
             }
         }
     }
 
+    fn notify_retry(
+        &self,
+        event: Option<&mut RequestEvent>,
+        error: &NoSQLError,
+        delay: Duration,
+        send_options: &SendOptions,
+    ) {
+        if let (Some(o), Some(event)) = (&self.inner.builder.observer, event) {
+            event.retries += 1;
+            event.elapsed = event.start.elapsed();
+            event.request_id = send_options.request_id;
+            o.0.on_retry(event, error, delay);
+        }
+    }
+
+    fn is_throttling_error(code: NoSQLErrorCode) -> bool {
+        code == NoSQLErrorCode::ReadLimitExceeded || code == NoSQLErrorCode::WriteLimitExceeded
+    }
+
+    // Exponential backoff for throttled requests: 100ms, 200ms, 400ms, ... up to 2s.
+    pub(crate) fn throttle_backoff(retries: u32) -> Duration {
+        let delay = BACKOFF_BASE * (1u32 << std::cmp::min(retries, 5));
+        std::cmp::min(delay, BACKOFF_MAX)
+    }
+
+    // The backoff delay for a retry, with the jitter set by HandleBuilder::backoff_jitter().
+    // `previous` is the delay of the previous retry, or zero.
+    pub(crate) fn retry_backoff(&self, retries: u32, previous: Duration) -> Duration {
+        self.inner
+            .builder
+            .backoff_jitter
+            .apply(Self::throttle_backoff(retries), previous)
+    }
+
     pub(crate) async fn send_and_receive_once(
         &self,
         w: &Writer,
@@ -336,7 +817,7 @@ impl Handle {
         let bytes = self.post_data(&w.buf, send_options).await?;
 
         //println!("returned data: len={}", bytes.len());
-        let mut r = Reader::new().from_bytes(&bytes);
+        let mut r = Reader::from_shared(bytes);
         let m = MapWalker::check_reader_for_error(&mut r);
         if m.is_ok() {
             return Ok(r);
@@ -388,15 +869,130 @@ impl Handle {
         Err(err)
     }
 
+    // Check the size of a primary key against the key size limit of the cloud
+    // service (and cloudsim), before sending a request. The exact size of the
+    // key on the server depends on its internal format, so this only uses a
+    // lower bound: the UTF-8 length of string and binary fields, and one byte
+    // for other fields. On-premises stores have no default key size limit.
+    pub(crate) fn check_key_size(
+        &self,
+        table_name: &str,
+        key: &MapValue,
+    ) -> Result<(), NoSQLError> {
+        if self.inner.builder.mode == HandleMode::Onprem {
+            return Ok(());
+        }
+        let mut size: usize = 0;
+        let mut largest: Option<(&String, &str)> = None;
+        for (k, v) in key.iter() {
+            match v {
+                FieldValue::String(s) => {
+                    size += s.len();
+                    if largest.map_or(true, |(_, l)| s.len() > l.len()) {
+                        largest = Some((k, s));
+                    }
+                }
+                FieldValue::Binary(b) => size += b.len(),
+                _ => size += 1,
+            }
+        }
+        if size <= MAX_KEY_SIZE {
+            return Ok(());
+        }
+        let mut msg = format!(
+            "primary key for table '{}' is at least {} bytes, which exceeds the limit of {} bytes",
+            table_name, size, MAX_KEY_SIZE
+        );
+        if let Some((k, s)) = largest {
+            let t = truncate_utf8(s, 32);
+            msg.push_str(&format!(
+                " (largest field: {}=\"{}{}\", {} bytes)",
+                k,
+                t,
+                if t.len() < s.len() { "..." } else { "" },
+                s.len()
+            ));
+        }
+        Err(NoSQLError::new(NoSQLErrorCode::KeySizeLimitExceeded, &msg))
+    }
+
     pub(crate) fn get_timeout(&self, t: &Option<Duration>) -> Duration {
-        // if t is given, use that. If not, use handle's timeout
-        if let Some(d) = t {
-            return d.clone();
+        // if t is given, use that. If not, use handle's timeout. Either way,
+        // never wait past the current Deadline, if any.
+        Deadline::limit(t.unwrap_or(self.inner.timeout))
+    }
+
+    // Same as get_timeout(), for DDL requests.
+    pub(crate) fn get_ddl_timeout(&self, t: &Option<Duration>) -> Duration {
+        Deadline::limit(t.unwrap_or(self.inner.ddl_timeout))
+    }
+
+    // Create a writer for a new request, starting with the serial version.
+    pub(crate) fn request_writer(&self) -> Writer {
+        let mut w = Writer::new();
+        w.table_prefix = self.inner.builder.table_prefix.clone();
+        w.write_i16(self.inner.serial_version);
+        w
+    }
+
+    // Convert a table name returned by the server to the name used by the
+    // application: the inverse of table_name().
+    pub(crate) fn logical_table_name(&self, name: &str) -> String {
+        let prefix = &self.inner.builder.table_prefix;
+        if prefix.is_empty() || name.is_empty() {
+            return name.to_string();
+        }
+        let (ns, table) = match name.split_once(':') {
+            Some((ns, table)) => (format!("{}:", ns), table),
+            None => (String::new(), name),
+        };
+        match table.get(..prefix.len()) {
+            Some(p) if p.eq_ignore_ascii_case(prefix) => {
+                format!("{}{}", ns, &table[prefix.len()..])
+            }
+            _ => format!("{}{}{}", RAW_TABLE_NAME_MARKER, ns, table),
         }
-        self.inner.timeout.clone()
+    }
+
+    // Check that a table named in the SQL text of a query or DDL statement has
+    // the table prefix, if there is one. SQL text is sent unchanged, so a table
+    // without the prefix would bypass it.
+    pub(crate) fn check_table_prefix(&self, name: &str) -> Result<(), NoSQLError> {
+        if self
+            .logical_table_name(name)
+            .starts_with(RAW_TABLE_NAME_MARKER)
+        {
+            return ia_err!(
+                "table '{}' does not start with the table prefix '{}' of the handle",
+                name,
+                self.inner.builder.table_prefix
+            );
+        }
+        Ok(())
     }
 }
 
+// A table name starting with this is used without the table prefix.
+pub(crate) const RAW_TABLE_NAME_MARKER: char = '/';
+
+// Add a table prefix (see HandleBuilder::table_prefix()) to a table name,
+// which may include a namespace ("ns:table") and child tables ("parent.child").
+pub(crate) fn prefixed_table_name(prefix: &str, name: &str) -> String {
+    if let Some(raw) = name.strip_prefix(RAW_TABLE_NAME_MARKER) {
+        return raw.to_string();
+    }
+    if prefix.is_empty() || name.is_empty() {
+        return name.to_string();
+    }
+    match name.split_once(':') {
+        Some((ns, table)) => format!("{}:{}{}", ns, prefix, table),
+        None => format!("{}{}", prefix, name),
+    }
+}
+
+// The maximum size of a primary key, in bytes, in the cloud service.
+pub(crate) const MAX_KEY_SIZE: usize = 64;
+
 #[derive(Debug, Default)]
 pub(crate) struct SendOptions {
     #[allow(dead_code)]
@@ -405,4 +1001,58 @@ pub(crate) struct SendOptions {
     pub(crate) timeout: Duration,
     pub(crate) compartment_id: String,
     pub(crate) namespace: String,
+    // If true, requests that fail due to read/write throttling are retried
+    // with backoff until the request timeout expires.
+    pub(crate) retry_throttling: bool,
+    // Total time spent backing off from throttling errors.
+    pub(crate) throttle_delay: Duration,
+    // The request id sent with the last attempt of the request.
+    pub(crate) request_id: usize,
+    // True once a region redirect has been followed for the request.
+    pub(crate) redirected: bool,
+}
+
+// Target of the events logged for slow requests.
+pub(crate) const SLOW_REQUEST_TARGET: &str = "oracle_nosql_rust_sdk::slow_request";
+
+// Get the operation and table name from the header of a serialized request.
+fn request_op_and_table(buf: &[u8]) -> Result<(Option<OpCode>, String), NoSQLError> {
+    let mut op: Option<OpCode> = None;
+    let mut table = String::new();
+    let mut r = Reader::new().from_bytes(buf);
+    // skip the serial version
+    r.read_i16()?;
+    let mut walker = MapWalker::new(&mut r)?;
+    while walker.has_next() {
+        walker.next()?;
+        if walker.current_name() != HEADER {
+            walker.skip_nson_field()?;
+            continue;
+        }
+        let mut header = MapWalker::new(walker.r)?;
+        while header.has_next() {
+            header.next()?;
+            match header.current_name().as_str() {
+                OP_CODE => op = OpCode::try_from(header.read_nson_i32()? as u8).ok(),
+                TABLE_NAME => table = header.read_nson_string()?,
+                _ => header.skip_nson_field()?,
+            }
+        }
+        break;
+    }
+    Ok((op, table))
+}
+
+// Get the capacity consumed by a request from its response, if it was returned.
+fn response_consumed(r: &Reader) -> Option<Capacity> {
+    let mut r = Reader::from_shared(r.buf.clone());
+    let mut walker = MapWalker::new(&mut r).ok()?;
+    while walker.has_next() {
+        walker.next().ok()?;
+        if walker.current_name() == CONSUMED {
+            return walker.read_nson_consumed_capacity().ok();
+        }
+        walker.skip_nson_field().ok()?;
+    }
+    None
 }
@@ -8,22 +8,45 @@
 //!
 
 use base64::prelude::{Engine as _, BASE64_STANDARD};
+use chrono::FixedOffset;
+use rand::Rng;
 use std::default::Default;
 use std::env;
 use std::result::Result;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use crate::auth_common::authentication_provider::AuthenticationProvider;
-use crate::error::{ia_err, NoSQLError};
+use crate::error::{ia_err, NoSQLError, NoSQLErrorCode};
 use crate::handle::Handle;
+use crate::observer::{ObserverRef, RequestObserver};
 use reqwest::header::HeaderValue;
 use reqwest::Client;
 use reqwest::{header::HeaderMap, Certificate};
 use serde_derive::Deserialize;
+use tracing::{debug, trace};
+use url::Url;
 
 use crate::region::{file_to_string, string_to_region, Region};
 
+// Headers set by the SDK, which cannot be given to default_headers(). Header
+// names are always lowercase in a HeaderMap.
+const RESERVED_HEADERS: &[&str] = &[
+    "authorization",
+    "cookie",
+    "date",
+    "host",
+    "content-type",
+    "content-length",
+    "user-agent",
+    "x-content-sha256",
+    "opc-obo-token",
+    "x-nosql-request-id",
+    "x-nosql-compartment-id",
+    "x-nosql-default-ns",
+];
+
 /// Builder used to set all the parameters to create a [`NoSQL Handle`](crate::Handle).
 ///
 /// See [Configuring the SDK](index.html#configuring-the-sdk) for a detailed description of creating configurations for
@@ -33,6 +56,7 @@ use crate::region::{file_to_string, string_to_region, Region};
 pub struct HandleBuilder {
     pub(crate) endpoint: String,
     pub(crate) timeout: Option<Duration>,
+    pub(crate) ddl_timeout: Option<Duration>,
     pub(crate) region: Option<Region>,
     // TODO
     //pub(crate) allow_imds: bool,
@@ -41,6 +65,22 @@ pub struct HandleBuilder {
     pub(crate) add_cert: Option<Certificate>,
     pub(crate) client: Option<Client>,
     pub(crate) accept_invalid_certs: bool,
+    pub(crate) allow_http_cloud_auth: bool,
+    pub(crate) follow_region_redirects: bool,
+    pub(crate) tcp_nodelay: Option<bool>,
+    pub(crate) tcp_keepalive: Option<Duration>,
+    pub(crate) default_headers: HeaderMap,
+    pub(crate) min_tls_version: Option<TlsVersion>,
+    pub(crate) display_timezone: Option<FixedOffset>,
+    pub(crate) circuit_breaker: Option<(u32, Duration)>,
+    pub(crate) slow_request_threshold: Option<Duration>,
+    pub(crate) backoff_jitter: BackoffJitter,
+    pub(crate) slow_request_log_level: LogLevel,
+    pub(crate) observer: Option<ObserverRef>,
+    pub(crate) prepare_cache_size: usize,
+    pub(crate) schema_check_interval: Option<Duration>,
+    pub(crate) query_cache: Option<(usize, Duration)>,
+    pub(crate) table_prefix: String,
     pub(crate) auth_type: AuthType,
     // auth uses a tokio Mutex because we occasionally hold a lock across awaits
     pub(crate) auth: Arc<tokio::sync::Mutex<AuthConfig>>,
@@ -96,8 +136,19 @@ pub(crate) enum AuthType {
     None,
 }
 
+impl AuthType {
+    // True for the auth types that sign requests with OCI credentials.
+    pub(crate) fn is_cloud(&self) -> bool {
+        matches!(
+            self,
+            AuthType::File | AuthType::Instance | AuthType::Resource | AuthType::External
+        )
+    }
+}
+
 /// The Oracle NoSQL Database mode to use.
-#[derive(Default, Debug, Clone, PartialEq)]
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum HandleMode {
     /// Connect to the Oracle NoSQL Cloud Service.
     #[default]
@@ -108,6 +159,128 @@ pub enum HandleMode {
     Onprem,
 }
 
+/// A TLS protocol version, for [`HandleBuilder::min_tls_version()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlsVersion {
+    /// TLS 1.2
+    Tls12,
+    /// TLS 1.3
+    Tls13,
+}
+
+/// The level of the events logged for slow requests, for
+/// [`HandleBuilder::slow_request_log_level()`].
+///
+/// These are the levels of [`tracing`](https://docs.rs/tracing) events.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogLevel {
+    /// The `ERROR` level.
+    Error,
+    /// The `WARN` level. This is the default.
+    #[default]
+    Warn,
+    /// The `INFO` level.
+    Info,
+    /// The `DEBUG` level.
+    Debug,
+    /// The `TRACE` level.
+    Trace,
+}
+
+/// Random jitter applied to retry backoff delays, for [`HandleBuilder::backoff_jitter()`].
+///
+/// Throttled requests are retried after an exponential backoff delay: 100ms, 200ms, 400ms,
+/// and so on, up to 2 seconds. Without jitter, many clients throttled at the same time
+/// retry at the same times, and can overload the service again. Jitter spreads the retries
+/// of different clients over time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BackoffJitter {
+    /// Use the backoff delay as is. This is the default.
+    #[default]
+    None,
+    /// Use a random delay between zero and the backoff delay.
+    Full,
+    /// Use half of the backoff delay, plus a random delay up to the other half.
+    Equal,
+    /// Use a random delay between the initial backoff delay (100ms) and three times the
+    /// previous delay, up to the maximum backoff delay. Successive delays are not tied to
+    /// the number of retries.
+    Decorrelated,
+}
+
+// Default for HandleBuilder::schema_check_interval().
+pub(crate) const SCHEMA_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+// Initial and maximum backoff delays for retries.
+pub(crate) const BACKOFF_BASE: Duration = Duration::from_millis(100);
+pub(crate) const BACKOFF_MAX: Duration = Duration::from_secs(2);
+
+impl BackoffJitter {
+    // Get the delay to use for the given exponential backoff delay. `previous` is
+    // the delay used for the previous retry, or zero.
+    pub(crate) fn apply(&self, backoff: Duration, previous: Duration) -> Duration {
+        let mut rng = rand::thread_rng();
+        match self {
+            BackoffJitter::None => backoff,
+            BackoffJitter::Full => backoff.mul_f64(rng.gen::<f64>()),
+            BackoffJitter::Equal => backoff / 2 + (backoff / 2).mul_f64(rng.gen::<f64>()),
+            BackoffJitter::Decorrelated => {
+                let high = std::cmp::max(previous * 3, BACKOFF_BASE);
+                let delay = BACKOFF_BASE + (high - BACKOFF_BASE).mul_f64(rng.gen::<f64>());
+                std::cmp::min(delay, BACKOFF_MAX)
+            }
+        }
+    }
+}
+
+/// Handle configuration settings, for [`HandleBuilder::from_config()`].
+///
+/// This can be deserialized with serde from an application's own configuration file, such as
+/// a TOML or YAML file, or created in code. All fields are optional, and unknown fields are
+/// rejected when deserializing. The fields correspond to the settings read by
+/// [`HandleBuilder::from_environment()`]:
+/// ```toml
+/// endpoint = "https://nosql.mycompany.com:8080"
+/// mode = "onprem"
+/// auth = "onprem"
+/// auth_file = "/etc/myapp/nosql_credentials"
+/// ca_cert = "/etc/myapp/nosql_ca.pem"
+/// timeout_ms = 10000
+/// ```
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NoSQLConfig {
+    /// The endpoint to connect to. See [`HandleBuilder::endpoint()`].
+    pub endpoint: Option<String>,
+    /// The cloud region identifier. See [`HandleBuilder::cloud_region()`].
+    pub region: Option<String>,
+    /// The mode: `cloud`, `cloudsim` or `onprem`. See [`HandleBuilder::mode()`].
+    pub mode: Option<HandleMode>,
+    /// The auth mechanism. One of: `user`, `instance`, `resource`, `onprem`, `cloudsim`, as
+    /// for the `ORACLE_NOSQL_AUTH` environment variable.
+    pub auth: Option<String>,
+    /// For `user` auth, the path to the OCI config file. For `onprem` auth, the path to the
+    /// user/password file (see [`HandleBuilder::onprem_auth_from_file()`]).
+    pub auth_file: Option<String>,
+    /// The path to a certificate file in `pem` format. See [`HandleBuilder::add_cert_from_pemfile()`].
+    pub ca_cert: Option<String>,
+    /// Do not check certificates. See [`HandleBuilder::danger_accept_invalid_certs()`].
+    pub accept_invalid_certs: bool,
+    /// The timeout for operations, in milliseconds. See [`HandleBuilder::timeout()`].
+    pub timeout_ms: Option<u64>,
+    /// The timeout for DDL operations, in milliseconds. See [`HandleBuilder::ddl_timeout()`].
+    pub ddl_timeout_ms: Option<u64>,
+}
+
+impl TlsVersion {
+    pub(crate) fn to_reqwest(self) -> reqwest::tls::Version {
+        match self {
+            TlsVersion::Tls12 => reqwest::tls::Version::TLS_1_2,
+            TlsVersion::Tls13 => reqwest::tls::Version::TLS_1_3,
+        }
+    }
+}
+
 impl HandleBuilder {
     /// Create a new HandleBuilder struct.
     ///
@@ -125,9 +298,101 @@ impl HandleBuilder {
     /// a reference to an existing [`reqwest::Client`], it will clone and
     /// use that. Otherwise, it will create a new [`reqwest::Client`] for its
     /// own internal use. See [`reqwest_client()`](HandleBuilder::reqwest_client()).
+    ///
+    /// The configuration is checked with [`validate()`](HandleBuilder::validate()) first.
     pub async fn build(self) -> Result<Handle, NoSQLError> {
         Handle::new(&self).await
     }
+    /// Check that the configuration is complete and consistent, without connecting.
+    ///
+    /// This checks that an authentication method and an endpoint (or region) are set, that
+    /// the authentication method matches the [`HandleMode`] and is not used with a region it
+    /// does not apply to, that cloud authentication is not used with a plaintext `http://`
+    /// endpoint (see [`danger_allow_http_cloud_auth()`](HandleBuilder::danger_allow_http_cloud_auth())),
+    /// and that the timeout is not zero. No network requests are made and no files are read:
+    /// region identifiers, certificate files and credential files are already checked by the
+    /// methods that set them.
+    ///
+    /// All problems found are returned at once, in a single `IllegalArgument` error; use
+    /// [`NoSQLError::problems()`] to get them individually.
+    /// [`build()`](HandleBuilder::build()) calls this before creating the handle.
+    ///```no_run
+    /// # use oracle_nosql_rust_sdk::Handle;
+    /// # fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// if let Err(e) = Handle::builder().from_environment()?.validate() {
+    ///     for problem in e.problems() {
+    ///         eprintln!("invalid NoSQL configuration: {}", problem);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    ///```
+    pub fn validate(&self) -> Result<(), NoSQLError> {
+        let mut problems: Vec<String> = Vec::new();
+        match self.auth_type {
+            AuthType::None => {
+                if self.from_environment {
+                    problems.push("cannot build handle: no auth type specified. set ORACLE_NOSQL_AUTH environment.".to_string());
+                } else {
+                    problems.push("cannot build handle: no auth type specified".to_string());
+                }
+            }
+            AuthType::Instance => {
+                problems.push("InstancePrincipalAuthProvider has been removed in this simplified implementation".to_string());
+            }
+            AuthType::Resource => {
+                problems.push("ResourcePrincipalAuthProvider::new() has been removed - use new_from_values() instead".to_string());
+            }
+            _ => {}
+        }
+        let auth_mode = match self.auth_type {
+            AuthType::Onprem => Some(HandleMode::Onprem),
+            AuthType::Cloudsim => Some(HandleMode::Cloudsim),
+            AuthType::None => None,
+            _ => Some(HandleMode::Cloud),
+        };
+        if let Some(m) = auth_mode {
+            if m != self.mode {
+                problems.push(format!(
+                    "mode {:?} cannot be used with {:?} auth",
+                    self.mode, self.auth_type
+                ));
+            }
+        }
+        if let Some(r) = &self.region {
+            if self.mode != HandleMode::Cloud
+                || matches!(self.auth_type, AuthType::Onprem | AuthType::Cloudsim)
+            {
+                problems.push(format!(
+                    "cloud region {} cannot be used with mode {:?} and {:?} auth",
+                    r.id(),
+                    self.mode,
+                    self.auth_type
+                ));
+            }
+        }
+        if self.endpoint.is_empty() {
+            if self.from_environment {
+                problems.push("can't determine NoSQL endpoint: set ORACLE_NOSQL_ENDPOINT or ORACLE_NOSQL_REGION".to_string());
+            } else {
+                problems.push("can't determine NoSQL endpoint: call HandleBuilder::endpoint() or HandleBuilder::cloud_region()".to_string());
+            }
+        } else if !self.use_https && self.auth_type.is_cloud() && !self.allow_http_cloud_auth {
+            // cloud request signatures must not be sent in plaintext
+            problems.push(format!("cannot build handle: cloud authentication requires an https endpoint, not http://{}. For a local Cloudsim, use HandleBuilder::mode(HandleMode::Cloudsim)", self.endpoint));
+        }
+        if self.timeout.is_some_and(|t| t.is_zero()) {
+            problems.push("timeout must be greater than zero".to_string());
+        }
+        if problems.is_empty() {
+            return Ok(());
+        }
+        Err(NoSQLError::from_problems(
+            NoSQLErrorCode::IllegalArgument,
+            "invalid handle configuration",
+            problems,
+        ))
+    }
     /// Gather configuration settings from the current envrionment.
     ///
     /// This method will scan the process [`standard environment`](std::env::Vars) to collect and
@@ -187,6 +452,7 @@ impl HandleBuilder {
                         // TODO: error (need file)?
                         // Need a way to discern between insecure onprem and cloudsim
                     }
+                    self.mode = HandleMode::Onprem;
                     self.auth_type = AuthType::Onprem;
                 }
                 "resource" => self = self.cloud_auth_from_resource()?,
@@ -209,6 +475,93 @@ impl HandleBuilder {
         }
         Ok(self)
     }
+    /// Apply all settings from a [`NoSQLConfig`].
+    ///
+    /// This is the same as calling the builder method for each field that is set, and is
+    /// typically used with a `NoSQLConfig` deserialized from an application's own configuration
+    /// file. As with [`from_environment()`](HandleBuilder::from_environment()), settings can be
+    /// overridden by calling other methods afterwards:
+    ///```no_run
+    /// # use oracle_nosql_rust_sdk::{Handle, NoSQLConfig};
+    /// # async fn run(config_text: &str) -> Result<(), Box<dyn std::error::Error>> {
+    /// let config: NoSQLConfig = serde_json::from_str(config_text)?;
+    /// let handle = Handle::builder().from_config(config)?.build().await?;
+    /// # Ok(())
+    /// # }
+    ///```
+    ///
+    /// Returns an `IllegalArgument` error if a value is invalid, or if settings conflict, such
+    /// as a `mode` that does not match the `auth` mechanism, a `region` with a mode other than
+    /// `cloud`, or an `auth_file` with an auth mechanism that does not use one.
+    pub fn from_config(mut self, config: NoSQLConfig) -> Result<Self, NoSQLError> {
+        let auth = config.auth.as_ref().map(|a| a.to_lowercase());
+        let auth_mode = match auth.as_deref() {
+            None => None,
+            Some("user") | Some("instance") | Some("resource") => Some(HandleMode::Cloud),
+            Some("onprem") => Some(HandleMode::Onprem),
+            Some("cloudsim") => Some(HandleMode::Cloudsim),
+            Some(a) => {
+                return ia_err!("invalid auth '{}' in config: expected one of user, instance, resource, onprem, cloudsim", a);
+            }
+        };
+        if let (Some(m), Some(am)) = (&config.mode, &auth_mode) {
+            if m != am {
+                return ia_err!(
+                    "conflicting config: mode {:?} cannot be used with '{}' auth",
+                    m,
+                    auth.unwrap_or_default()
+                );
+            }
+        }
+        let mode = config.mode.clone().or(auth_mode);
+        if config.region.is_some()
+            && matches!(mode, Some(HandleMode::Onprem | HandleMode::Cloudsim))
+        {
+            return ia_err!(
+                "conflicting config: region cannot be used with mode {:?}",
+                mode.unwrap()
+            );
+        }
+        if config.auth_file.is_some() && !matches!(auth.as_deref(), Some("user") | Some("onprem")) {
+            return ia_err!(
+                "conflicting config: auth_file can only be used with 'user' or 'onprem' auth"
+            );
+        }
+
+        if let Some(cert) = &config.ca_cert {
+            self = self.add_cert_from_pemfile(cert)?;
+        }
+        if config.accept_invalid_certs {
+            self = self.danger_accept_invalid_certs(true)?;
+        }
+        if let Some(endpoint) = &config.endpoint {
+            self = self.endpoint(endpoint)?;
+        }
+        if let Some(region) = &config.region {
+            self = self.cloud_region(region)?;
+        }
+        match (auth.as_deref(), &config.auth_file) {
+            (Some("user"), Some(f)) => self = self.cloud_auth_from_file(f)?,
+            (Some("user"), None) => self = self.cloud_auth_from_file("~/.oci/config")?,
+            (Some("instance"), _) => self = self.cloud_auth_from_instance()?,
+            (Some("resource"), _) => self = self.cloud_auth_from_resource()?,
+            (Some("onprem"), Some(f)) => self = self.onprem_auth_from_file(f)?,
+            _ => {}
+        }
+        if let Some(m) = mode {
+            self = self.mode(m)?;
+        }
+        if let Some(ms) = config.timeout_ms {
+            if ms == 0 {
+                return ia_err!("timeout_ms must be greater than zero");
+            }
+            self = self.timeout(Duration::from_millis(ms))?;
+        }
+        if let Some(ms) = config.ddl_timeout_ms {
+            self = self.ddl_timeout(Duration::from_millis(ms))?;
+        }
+        Ok(self)
+    }
     /// Set a specific endpoint connection to use.
     ///
     /// This is typically used when specifying a local cloudsim instance, or an
@@ -227,21 +580,62 @@ impl HandleBuilder {
     ///     // Cloud service
     ///     https://nosql.us-ashburn-1.oci.oraclecloud.com
     /// ```
+    ///
+    /// The endpoint must be of the form `[http[s]://]host[:port]`. A single trailing `/` is
+    /// allowed, but paths, query strings, credentials and other schemes are rejected with an
+    /// `IllegalArgument` error.
     pub fn endpoint(mut self, endpoint: &str) -> Result<Self, NoSQLError> {
         // normalize to just domain[:port]
+        let host_port;
         if endpoint.starts_with("https://") {
             self.use_https = true;
             let (_, b) = endpoint.split_at(8);
-            self.endpoint = b.to_string();
+            host_port = b;
         } else if endpoint.starts_with("http://") {
             self.use_https = false;
             let (_, b) = endpoint.split_at(7);
-            self.endpoint = b.to_string();
+            host_port = b;
         } else {
-            self.endpoint = endpoint.to_string();
+            host_port = endpoint;
         }
+        let host_port = host_port.strip_suffix('/').unwrap_or(host_port);
+        Self::validate_endpoint(endpoint, host_port)?;
+        self.endpoint = host_port.to_string();
         Ok(self)
     }
+
+    // Verify that host_port is a well-formed host[:port], with nothing else.
+    fn validate_endpoint(endpoint: &str, host_port: &str) -> Result<(), NoSQLError> {
+        if host_port.is_empty() {
+            return ia_err!("invalid endpoint '{}': missing host", endpoint);
+        }
+        if host_port.contains("://") {
+            return ia_err!(
+                "invalid endpoint '{}': only http:// and https:// schemes are supported",
+                endpoint
+            );
+        }
+        if let Some(c) = host_port
+            .chars()
+            .find(|c| c.is_whitespace() || *c == '/' || *c == '?' || *c == '#' || *c == '@')
+        {
+            return ia_err!(
+                "invalid endpoint '{}': unexpected '{}', expected host[:port]",
+                endpoint,
+                c
+            );
+        }
+        let url = match Url::parse(&format!("http://{}", host_port)) {
+            Ok(u) => u,
+            Err(e) => {
+                return ia_err!("invalid endpoint '{}': {}", endpoint, e);
+            }
+        };
+        if url.host_str().is_none() {
+            return ia_err!("invalid endpoint '{}': missing host", endpoint);
+        }
+        Ok(())
+    }
     /// Set the mode for the handle.
     ///
     /// Use [`HandleMode::Cloudsim`] to specify connection to a local cloudsim instance.
@@ -330,6 +724,24 @@ impl HandleBuilder {
         self.mode = HandleMode::Cloud;
         Ok(self)
     }
+    /// Follow redirects to the endpoint of another region.
+    ///
+    /// If the service redirects a request to the NoSQL endpoint of a different region, for
+    /// example because the table is in another region than the one the handle is configured
+    /// for, the request fails by default with a [`RegionMismatch`](crate::NoSQLErrorCode::RegionMismatch)
+    /// error that names the region. With this set to `true`, the handle instead switches to the
+    /// new region's endpoint, logs a warning, and retries the request there. All later requests
+    /// from the handle also use the new endpoint.
+    ///
+    /// Only redirects to the `https` NoSQL endpoint of a known region (see
+    /// [`Region::all_regions()`](crate::Region::all_regions())) are followed, so signed requests
+    /// are never sent to arbitrary hosts; other redirects to different hosts fail with an
+    /// `IllegalArgument` error. If a client is given with [`reqwest_client()`](HandleBuilder::reqwest_client()),
+    /// it must be built with [`reqwest::redirect::Policy::none()`] for redirects to be detected.
+    pub fn follow_region_redirects(mut self, follow: bool) -> Result<Self, NoSQLError> {
+        self.follow_region_redirects = follow;
+        Ok(self)
+    }
     /// Specify a region identifier for the NoSQL Cloud Service.
     ///
     /// This method is only required if using cloud user file-based authentication and the
@@ -356,13 +768,14 @@ impl HandleBuilder {
     /// Specify credentials for use with a secure On-premises NoSQL Server.
     ///
     /// When using a secure server, a username and password are required. Use this method
-    /// to specify the values.
+    /// to specify the values. The login token obtained from the server is renewed
+    /// automatically in the background before it expires.
     ///
     /// Calling this method will also internally set the `HandleMode` to `Onprem`.
     pub fn onprem_auth(mut self, username: &str, passwd: &str) -> Result<Self, NoSQLError> {
         if !username.is_empty() {
             let ap = AuthProvider::Onprem {
-                provider: Some(OnpremAuthProvider::new(&self, username, passwd)),
+                provider: Some(OnpremAuthProvider::new(username, passwd)),
             };
             self.auth = Arc::new(tokio::sync::Mutex::new(AuthConfig { provider: ap }));
         } else {
@@ -443,6 +856,20 @@ impl HandleBuilder {
         self.accept_invalid_certs = accept_invalid_certs;
         Ok(self)
     }
+    /// Allow cloud authentication with a plaintext `http://` endpoint.
+    ///
+    /// Cloud requests are signed with OCI credentials, so by default
+    /// [`build()`](HandleBuilder::build()) returns an error if cloud authentication is combined
+    /// with an `http://` endpoint. A local Cloudsim instance uses `http://`, and should be
+    /// configured with [`mode(HandleMode::Cloudsim)`](HandleBuilder::mode()) instead.
+    ///
+    /// **Warning:** This is only intended for unusual test setups, such as a local proxy in
+    /// front of the service. Its use is insecure: the signed request headers are sent
+    /// unencrypted, and a warning is logged when the handle is built.
+    pub fn danger_allow_http_cloud_auth(mut self, allow: bool) -> Result<Self, NoSQLError> {
+        self.allow_http_cloud_auth = allow;
+        Ok(self)
+    }
     /// Specify a [`reqwest::Client`] to use for all http/s connections.
     ///
     /// By default, the [`NoSQL Handle`](crate::Handle) creates an internal [`reqwest::Client`] to use for
@@ -453,6 +880,374 @@ impl HandleBuilder {
         self.client = Some(client.clone());
         Ok(self)
     }
+    /// Specify whether to set `TCP_NODELAY` on connections to the NoSQL service.
+    ///
+    /// Setting this to `true` disables Nagle's algorithm, which sends small packets
+    /// immediately instead of batching them. This generally lowers latency for small
+    /// single-row operations (gets, puts, deletes) at the cost of possibly sending more
+    /// packets on the network. The internal [`reqwest::Client`] enables `TCP_NODELAY` by default.
+    ///
+    /// This setting is ignored if a client is given with [`reqwest_client()`](HandleBuilder::reqwest_client()).
+    pub fn tcp_nodelay(mut self, nodelay: bool) -> Result<Self, NoSQLError> {
+        self.tcp_nodelay = Some(nodelay);
+        Ok(self)
+    }
+    /// Specify the interval for TCP keep-alive probes on connections to the NoSQL service.
+    ///
+    /// Keep-alive probes keep idle pooled connections open through firewalls and load
+    /// balancers that drop inactive connections, avoiding the cost of a new connection (and
+    /// TLS handshake) on the next request. Shorter intervals detect dead connections sooner
+    /// but generate more network traffic. By default, TCP keep-alive is not enabled.
+    ///
+    /// This setting is ignored if a client is given with [`reqwest_client()`](HandleBuilder::reqwest_client()).
+    pub fn tcp_keepalive(mut self, interval: Duration) -> Result<Self, NoSQLError> {
+        if interval.is_zero() {
+            return ia_err!("tcp_keepalive interval must be greater than zero");
+        }
+        self.tcp_keepalive = Some(interval);
+        Ok(self)
+    }
+    /// Specify extra HTTP headers to send with every request.
+    ///
+    /// This is for deployments where the NoSQL service is behind infrastructure that expects
+    /// custom headers, such as an API gateway key. The headers are added to each request in
+    /// addition to the headers managed by the SDK.
+    ///
+    /// The following headers are managed by the SDK, and are reserved: `Authorization`,
+    /// `Cookie`, `Date`, `Host`, `Content-Type`, `Content-Length`, `User-Agent`,
+    /// `x-content-sha256`, `opc-obo-token`, `x-nosql-request-id`, `x-nosql-compartment-id`
+    /// and `x-nosql-default-ns`. Giving any of them returns an `IllegalArgument` error. Note
+    /// that extra headers are not included in the request signature used for cloud
+    /// authentication.
+    ///
+    /// ```no_run
+    /// # use oracle_nosql_rust_sdk::Handle;
+    /// # use reqwest::header::{HeaderMap, HeaderValue};
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut headers = HeaderMap::new();
+    /// headers.insert("x-api-key", HeaderValue::from_static("my-gateway-key"));
+    /// let handle = Handle::builder()
+    ///     .from_environment()?
+    ///     .default_headers(headers)?
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn default_headers(mut self, headers: HeaderMap) -> Result<Self, NoSQLError> {
+        for name in headers.keys() {
+            if RESERVED_HEADERS.contains(&name.as_str()) {
+                return ia_err!(
+                    "default_headers: header '{}' is managed by the SDK and cannot be set",
+                    name
+                );
+            }
+        }
+        self.default_headers = headers;
+        Ok(self)
+    }
+    /// Specify the minimum TLS version allowed for https connections to the NoSQL service.
+    ///
+    /// This can be used to meet compliance requirements, for example by setting
+    /// [`TlsVersion::Tls13`] to only allow TLS 1.3 connections. Connections to a server that
+    /// does not support the minimum version fail with an error. By default, the secure defaults
+    /// of the TLS library are used, which allow TLS 1.2 and TLS 1.3.
+    ///
+    /// ```no_run
+    /// # use oracle_nosql_rust_sdk::{Handle, TlsVersion};
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let handle = Handle::builder()
+    ///     .from_environment()?
+    ///     .min_tls_version(TlsVersion::Tls13)?
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Cipher suites cannot be configured with this builder. If a specific cipher policy is
+    /// required, configure a [`reqwest::Client`] and pass it with
+    /// [`reqwest_client()`](HandleBuilder::reqwest_client()).
+    ///
+    /// This setting is ignored if a client is given with [`reqwest_client()`](HandleBuilder::reqwest_client()).
+    pub fn min_tls_version(mut self, version: TlsVersion) -> Result<Self, NoSQLError> {
+        self.min_tls_version = Some(version);
+        Ok(self)
+    }
+    /// Specify a timezone for returning timestamp values to the application.
+    ///
+    /// Timestamps are stored in the database in UTC, and are returned by the server in UTC.
+    /// If a display timezone is set, the `timestamp()` accessors of [`GetResult`](crate::GetResult::timestamp())
+    /// and [`QueryResult`](crate::QueryResult::timestamp()) return values shifted to this timezone.
+    /// This is a read-side convenience only: it does not change the values in the returned rows,
+    /// and it does not affect how timestamps are written or stored.
+    ///
+    /// ```no_run
+    /// # use oracle_nosql_rust_sdk::Handle;
+    /// # use chrono::FixedOffset;
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let handle = Handle::builder()
+    ///     .from_environment()?
+    ///     .display_timezone(FixedOffset::east_opt(9 * 3600).unwrap())?
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn display_timezone(mut self, tz: FixedOffset) -> Result<Self, NoSQLError> {
+        self.display_timezone = Some(tz);
+        Ok(self)
+    }
+    /// Enable a circuit breaker to fail fast while the NoSQL service is unavailable.
+    ///
+    /// Normally every request is sent to the service, and is retried internally until its
+    /// timeout expires. During an outage, an application with many concurrent requests (see
+    /// the `multi_threaded` example) can then flood the service with retries just as it
+    /// comes back up.
+    ///
+    /// With a circuit breaker, after `failure_threshold` consecutive requests fail because
+    /// the service could not be reached or was unavailable, the [`Handle`](crate::Handle)
+    /// stops sending requests for `cooldown`: all requests fail immediately with a
+    /// [`CircuitOpen`](crate::NoSQLErrorCode::CircuitOpen) error (see [`NoSQLError::circuit_open()`]).
+    /// After the cooldown, a single probe request at a time is sent. If it succeeds, normal
+    /// operation resumes; otherwise, requests are rejected for another cooldown.
+    ///
+    /// Errors caused by a specific request (such as a missing table or an invalid argument)
+    /// do not count as failures. By default, no circuit breaker is used.
+    ///
+    /// ```no_run
+    /// # use oracle_nosql_rust_sdk::Handle;
+    /// # use std::time::Duration;
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let handle = Handle::builder()
+    ///     .from_environment()?
+    ///     .circuit_breaker(5, Duration::from_secs(10))?
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn circuit_breaker(
+        mut self,
+        failure_threshold: u32,
+        cooldown: Duration,
+    ) -> Result<Self, NoSQLError> {
+        if failure_threshold == 0 {
+            return ia_err!("circuit_breaker failure_threshold must be greater than zero");
+        }
+        if cooldown.is_zero() {
+            return ia_err!("circuit_breaker cooldown must be greater than zero");
+        }
+        self.circuit_breaker = Some((failure_threshold, cooldown));
+        Ok(self)
+    }
+    /// Log requests that take longer than the given threshold.
+    ///
+    /// Every request sent to the server that takes at least `threshold` to complete,
+    /// including its internal retries, is logged as a [`tracing`](https://docs.rs/tracing) event with the
+    /// `oracle_nosql_rust_sdk::slow_request` target, at the `WARN` level by default (see
+    /// [`slow_request_log_level()`](HandleBuilder::slow_request_log_level())). The event has
+    /// the following fields:
+    /// - `op`: the operation, such as `Get`, `Put` or `Query`
+    /// - `table`: the table name, empty if the operation is not for a single table (queries)
+    /// - `elapsed_ms`: the elapsed time in milliseconds
+    /// - `read_units`, `read_kb`, `write_kb`: the capacity consumed, if returned by the server
+    /// - `request_id`: the `x-nosql-request-id` header of the last attempt
+    /// - `retries`: the number of internal retries
+    /// - `error`: the error code, if the request failed
+    ///
+    /// Each batch of a query is a separate request, and is logged on its own. Use the target
+    /// to send these events to a separate log, or to filter them, for example with
+    /// `RUST_LOG=oracle_nosql_rust_sdk::slow_request=warn` and `tracing_subscriber::EnvFilter`.
+    ///
+    /// By default, slow requests are not logged. Returns an `IllegalArgument` error if
+    /// `threshold` is zero.
+    ///
+    /// ```no_run
+    /// # use oracle_nosql_rust_sdk::Handle;
+    /// # use std::time::Duration;
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let handle = Handle::builder()
+    ///     .from_environment()?
+    ///     .slow_request_threshold(Duration::from_millis(500))?
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn slow_request_threshold(mut self, threshold: Duration) -> Result<Self, NoSQLError> {
+        if threshold.is_zero() {
+            return ia_err!("slow_request_threshold must be greater than zero");
+        }
+        self.slow_request_threshold = Some(threshold);
+        Ok(self)
+    }
+    /// Specify the level of the events logged for slow requests.
+    ///
+    /// See [`slow_request_threshold()`](HandleBuilder::slow_request_threshold()). The
+    /// default is [`LogLevel::Warn`].
+    pub fn slow_request_log_level(mut self, level: LogLevel) -> Result<Self, NoSQLError> {
+        self.slow_request_log_level = level;
+        Ok(self)
+    }
+    /// Set a [`RequestObserver`] to be notified of request lifecycle events.
+    ///
+    /// The observer is called when each request starts, is retried internally, and succeeds
+    /// or fails, with its operation, table name, timing and consumed capacity. This can be
+    /// used to record metrics with any backend. See [`RequestObserver`] for the order of
+    /// the callbacks, and for what they may do. By default, no observer is used.
+    pub fn observer(mut self, observer: Box<dyn RequestObserver>) -> Result<Self, NoSQLError> {
+        self.observer = Some(ObserverRef(Arc::from(observer)));
+        Ok(self)
+    }
+    /// Specify the random jitter applied to the backoff delays of retried requests.
+    ///
+    /// Requests that are throttled by the service are retried internally, with exponential
+    /// backoff, until they succeed or their timeout expires. Applications running many
+    /// identical clients (or many tasks sharing a [`Handle`](crate::Handle)) should use jitter,
+    /// so that requests throttled together do not all retry at the same time. See
+    /// [`BackoffJitter`] for the strategies. A `Retry-After` delay given by the service is
+    /// always honored. The default is [`BackoffJitter::None`].
+    ///
+    /// ```no_run
+    /// # use oracle_nosql_rust_sdk::{BackoffJitter, Handle};
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let handle = Handle::builder()
+    ///     .from_environment()?
+    ///     .backoff_jitter(BackoffJitter::Full)?
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn backoff_jitter(mut self, jitter: BackoffJitter) -> Result<Self, NoSQLError> {
+        self.backoff_jitter = jitter;
+        Ok(self)
+    }
+    /// Enable a cache of prepared query statements, keyed by SQL text.
+    ///
+    /// Queries created with [`QueryRequest::new()`](crate::QueryRequest::new()) are normally
+    /// compiled by the server every time they are executed. With a prepare cache, the
+    /// [`Handle`](crate::Handle) keeps the prepared form of up to `size` statements (the least
+    /// recently used statement is evicted when the cache is full), and reuses it when the
+    /// same SQL text is executed again in the same compartment. This gives most of the benefit
+    /// of [`QueryRequest::new_prepared()`](crate::QueryRequest::new_prepared()) without any
+    /// code changes.
+    ///
+    /// If a query using a cached statement fails with a `TableNotFound`, `IndexNotFound` or
+    /// `IllegalArgument` error before returning any rows, for example because the table was
+    /// altered or dropped and recreated, the statement is removed from the cache and the
+    /// query is prepared and executed again. This retry is done by
+    /// [`QueryRequest::execute()`](crate::QueryRequest::execute()), but not by
+    /// `execute_batch()`.
+    ///
+    /// A size of zero, the default, disables the cache.
+    pub fn prepare_cache_size(mut self, size: usize) -> Result<Self, NoSQLError> {
+        self.prepare_cache_size = size;
+        Ok(self)
+    }
+    /// Specify how long the table version read by a schema check is reused.
+    ///
+    /// Queries using [`QueryRequest::check_schema()`](crate::QueryRequest::check_schema())
+    /// read the version of their table to check that it was not altered. The version read is
+    /// reused by all such queries of the [`Handle`](crate::Handle) on the same table for
+    /// `interval`, so most executions do not need an extra round trip, but a table altered
+    /// within `interval` of the last check may be used for up to `interval` before the change
+    /// is detected. The default is 10 seconds.
+    ///
+    /// Returns an `IllegalArgument` error if `interval` is zero.
+    pub fn schema_check_interval(mut self, interval: Duration) -> Result<Self, NoSQLError> {
+        if interval.is_zero() {
+            return ia_err!("schema_check_interval must be greater than zero");
+        }
+        self.schema_check_interval = Some(interval);
+        Ok(self)
+    }
+    /// Enable a cache of query results, holding up to `size` results for `ttl` each.
+    ///
+    /// When enabled, [`QueryRequest::execute()`](crate::QueryRequest::execute()) returns a
+    /// copy of a cached result, without contacting the server, if the same read-only query was
+    /// executed within the last `ttl`. Queries are the same if they have the same compartment,
+    /// SQL text (ignoring differences in whitespace), bind variable values and
+    /// [string collation](crate::QueryRequest::string_collation()), and are run the same way
+    /// (`execute()`, or `execute_one()` with the same `strict` setting). Only queries that the
+    /// server reports as `SELECT` statements are cached, never inserts, updates or deletes.
+    /// Queries that request a plan or schema, target a shard, or use
+    /// [`Consistency::Absolute`](crate::types::Consistency::Absolute) are not cached, nor are
+    /// queries run with `execute_batch()`, `for_each()` or `execute_channel()`. Absolute reads
+    /// ask for the latest data, which a cached result may not be.
+    /// When the cache is full, the least recently used result is evicted. See [`QueryResult::from_cache()`](crate::QueryResult::from_cache()).
+    ///
+    /// This is meant for read-heavy applications, such as dashboards, that run the same
+    /// queries repeatedly. The tradeoff is staleness: a cached result does not reflect writes
+    /// made after it was cached, by this or any other client, until it expires. Choose a
+    /// `ttl` that the application can tolerate, and call
+    /// [`Handle::invalidate_query_cache()`] to discard all results after writes that must be
+    /// visible immediately. Every cached result is held in memory, so `size` should also take
+    /// the size of results into account.
+    ///
+    /// ```no_run
+    /// # use oracle_nosql_rust_sdk::Handle;
+    /// # use std::time::Duration;
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let handle = Handle::builder()
+    ///     .from_environment()?
+    ///     .query_cache(100, Duration::from_secs(5))?
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// A `size` of zero disables the cache, which is the default. Returns an `IllegalArgument`
+    /// error if `ttl` is zero.
+    pub fn query_cache(mut self, size: usize, ttl: Duration) -> Result<Self, NoSQLError> {
+        if ttl.is_zero() {
+            return ia_err!("query_cache ttl must be greater than zero");
+        }
+        self.query_cache = match size {
+            0 => None,
+            _ => Some((size, ttl)),
+        };
+        Ok(self)
+    }
+    /// Specify a prefix to add to all table names used in requests.
+    ///
+    /// This can be used to keep the tables of different tenants or environments apart,
+    /// while application code uses the same logical table names: with a prefix of `acme_`,
+    /// `GetRequest::new("users")` reads from the table `acme_users`. The prefix is added to
+    /// the table names of all key/value and table requests ([`GetRequest`](crate::GetRequest),
+    /// [`PutRequest`](crate::PutRequest), [`GetTableRequest`](crate::GetTableRequest), etc.),
+    /// and to the statements that the SDK generates, such as for
+    /// [`UpdateRequest`](crate::UpdateRequest). For namespaced tables (`ns:table`) the prefix is
+    /// added to the table name, and for child tables (`parent.child`) to the top-level table.
+    /// Table names returned in a [`TableResult`](crate::TableResult) have the prefix removed.
+    ///
+    /// To use a table without the prefix, such as a table shared by all tenants, start its
+    /// name with `/`: `GetRequest::new("/settings")` reads from the table `settings`.
+    ///
+    /// The SQL text of queries and DDL statements is not changed, so it must use the prefixed
+    /// table names: use [`Handle::table_name()`](crate::Handle::table_name()) to get them when
+    /// building statements. Statements on a table without the prefix are rejected with an
+    /// `IllegalArgument` error. A query is prepared before it is executed, and the table
+    /// name returned by the server is checked: this costs one extra round trip per query, or
+    /// per statement with [`prepare_cache_size()`](HandleBuilder::prepare_cache_size()). The
+    /// table of a `CREATE`, `ALTER` or `DROP` statement for a table or index is checked
+    /// before the statement is sent. To use a shared table in SQL, use a handle without a
+    /// prefix.
+    /// [`ListTablesRequest`](crate::ListTablesRequest) returns the full table names.
+    ///
+    /// The prefix must start with a letter, and contain only letters, digits and underscores.
+    pub fn table_prefix(mut self, prefix: &str) -> Result<Self, NoSQLError> {
+        let mut chars = prefix.chars();
+        let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            return ia_err!("invalid table prefix '{}'", prefix);
+        }
+        self.table_prefix = prefix.to_string();
+        Ok(self)
+    }
     /// Specify the timeout used for operations.
     ///
     /// Currently this is used for both connection and request timeouts.
@@ -464,6 +1259,21 @@ impl HandleBuilder {
         self.timeout = Some(timeout);
         Ok(self)
     }
+    /// Specify the default request timeout for DDL operations.
+    ///
+    /// This is used by [`TableRequest`](crate::TableRequest) (creating, altering and dropping
+    /// tables and indexes) and [`SystemRequest`](crate::SystemRequest), which can take much
+    /// longer than data operations, instead of the [`timeout()`](HandleBuilder::timeout()) used
+    /// for all other requests. A timeout set on an individual request still takes precedence.
+    ///
+    /// If not set, DDL operations use the same default as data operations.
+    pub fn ddl_timeout(mut self, timeout: Duration) -> Result<Self, NoSQLError> {
+        if timeout.is_zero() {
+            return ia_err!("ddl_timeout must be greater than zero");
+        }
+        self.ddl_timeout = Some(timeout);
+        Ok(self)
+    }
 
     // for doc testing use only
     #[doc(hidden)]
@@ -484,7 +1294,7 @@ impl HandleBuilder {
             }
             AuthProvider::Onprem { provider } => {
                 if let Some(prov) = provider {
-                    let _ = prov.generate_token(client, true).await?;
+                    let _ = prov.generate_token(client, self, true).await?;
                 }
             }
             // TODO: maybe refresh file-based auth?
@@ -504,9 +1314,10 @@ pub(crate) struct OnpremAuthProvider {
 pub(crate) struct OnpremAuthProviderRef {
     username: String,
     password: String,
-    endpoint: String,
     // We use a tokio Mutex because we occasionally hold a lock across awaits
     token: tokio::sync::Mutex<OnpremToken>,
+    // set once the background renewal task has been started
+    refresh_started: AtomicBool,
 }
 
 #[derive(Default, Debug, Deserialize)]
@@ -514,53 +1325,86 @@ struct OnpremToken {
     token: String,
     #[serde(rename = "expireAt")]
     expire_at: i64,
+    // time at which the background task renews the token, on the runtime clock
+    #[serde(skip)]
+    refresh_at: Option<tokio::time::Instant>,
 }
 
+// Time to wait before retrying a failed background token renewal.
+const ONPREM_RENEW_RETRY: Duration = Duration::from_secs(5);
+
 impl OnpremAuthProvider {
-    pub fn new(builder: &HandleBuilder, user: &str, pass: &str) -> OnpremAuthProvider {
-        // normalize endpoint to "http[s]://{endpoint}/V2/nosql/security"
-        let mut ep = String::from("http");
-        if builder.use_https {
-            ep.push('s');
-        }
-        ep.push_str("://");
-        ep.push_str(&builder.endpoint);
-        ep.push_str("/V2/nosql/security");
+    pub fn new(user: &str, pass: &str) -> OnpremAuthProvider {
         OnpremAuthProvider {
             inner: Arc::new(OnpremAuthProviderRef {
                 username: user.to_string(),
                 password: pass.to_string(),
-                endpoint: ep,
                 token: tokio::sync::Mutex::new(OnpremToken::default()),
+                refresh_started: AtomicBool::new(false),
             }),
         }
         // TODO: should new() attempt to connect to the service? Or wait until
         // first time needing headers?
     }
+    // The endpoint of the security service is derived from the final handle
+    // configuration, so it does not depend on the order in which onprem_auth()
+    // and endpoint() were called. Tokens are requested with the same client as
+    // data requests, so they use the same certificates and TLS settings.
     pub async fn add_required_headers(
         &self,
         client: &Client,
+        builder: &HandleBuilder,
         headers: &mut HeaderMap,
     ) -> Result<(), NoSQLError> {
-        let mut bearer = self.generate_token(client, false).await?;
+        let mut bearer = self.generate_token(client, builder, false).await?;
         bearer.insert_str(0, "Bearer ");
         headers.insert("Authorization", HeaderValue::from_str(&bearer)?);
         Ok(())
     }
-    async fn generate_token(&self, client: &Client, force: bool) -> Result<String, NoSQLError> {
+    // normalize endpoint to "http[s]://{endpoint}/V2/nosql/security"
+    fn security_endpoint(builder: &HandleBuilder) -> String {
+        let mut ep = String::from("http");
+        if builder.use_https {
+            ep.push('s');
+        }
+        ep.push_str("://");
+        ep.push_str(&builder.endpoint);
+        ep.push_str("/V2/nosql/security");
+        ep
+    }
+    async fn generate_token(
+        &self,
+        client: &Client,
+        builder: &HandleBuilder,
+        force: bool,
+    ) -> Result<String, NoSQLError> {
         let mut tguard = self.inner.token.lock().await;
         if !force && !tguard.token.is_empty() && (tguard.expire_at - 10000) > Self::now() {
             return Ok(tguard.token.clone());
         }
+        let endpoint = Self::security_endpoint(builder);
+        Self::request_token(&self.inner, client, &endpoint, &mut tguard).await?;
+        if !self.inner.refresh_started.swap(true, Ordering::SeqCst) {
+            Self::start_refresh(Arc::downgrade(&self.inner), client.clone(), endpoint);
+        }
+        Ok(tguard.token.clone())
+    }
 
+    // Log in, or renew the current token if it has not expired yet.
+    async fn request_token(
+        inner: &OnpremAuthProviderRef,
+        client: &Client,
+        endpoint: &str,
+        tguard: &mut OnpremToken,
+    ) -> Result<(), NoSQLError> {
         let mut headers = HeaderMap::new();
         headers.insert("Accept", HeaderValue::from_str("application/json")?);
-        let mut ep = self.inner.endpoint.clone();
+        let mut ep = endpoint.to_string();
         let bup = {
-            if tguard.token.is_empty() {
+            if tguard.token.is_empty() || tguard.expire_at <= Self::now() {
                 ep.push_str("/login");
                 // set Authorization: Basic base64(username:password)
-                let up = format!("{}:{}", &self.inner.username, &self.inner.password);
+                let up = format!("{}:{}", &inner.username, &inner.password);
                 format!("Basic {}", BASE64_STANDARD.encode(up))
             } else {
                 ep.push_str("/renew");
@@ -574,13 +1418,55 @@ impl OnpremAuthProvider {
         let result = resp.text().await?;
         let nt: Result<OnpremToken, serde_json::Error> = serde_json::from_str(&result);
         if let Ok(new_token) = nt {
+            let now = Self::now();
             tguard.token = new_token.token;
             tguard.expire_at = new_token.expire_at;
-            return Ok(tguard.token.clone());
+            // renew at 80% of the token lifetime
+            let lifetime = (new_token.expire_at - now).max(0) as u64;
+            tguard.refresh_at =
+                Some(tokio::time::Instant::now() + Duration::from_millis(lifetime * 4 / 5));
+            return Ok(());
         }
         ia_err!("error from onprem login service: {}", result)
     }
 
+    // Renew the token in the background ahead of its expiry, so requests do not
+    // race with it. The task holds only a weak reference to the provider, and
+    // stops once all handles using it have been dropped. If a renewal fails, the
+    // synchronous refresh in generate_token() is still used near expiry.
+    fn start_refresh(inner: Weak<OnpremAuthProviderRef>, client: Client, endpoint: String) {
+        tokio::spawn(async move {
+            loop {
+                let refresh_at = match inner.upgrade() {
+                    Some(p) => p.token.lock().await.refresh_at,
+                    None => return,
+                };
+                if let Some(at) = refresh_at {
+                    tokio::time::sleep_until(at).await;
+                }
+                let p = match inner.upgrade() {
+                    Some(p) => p,
+                    None => return,
+                };
+                let mut tguard = p.token.lock().await;
+                if tguard
+                    .refresh_at
+                    .is_some_and(|at| at > tokio::time::Instant::now())
+                {
+                    // renewed by a request in the meantime
+                    continue;
+                }
+                match Self::request_token(&p, &client, &endpoint, &mut tguard).await {
+                    Ok(()) => trace!("renewed onprem auth token"),
+                    Err(e) => {
+                        debug!("error renewing onprem auth token: {}", e.message);
+                        tguard.refresh_at = Some(tokio::time::Instant::now() + ONPREM_RENEW_RETRY);
+                    }
+                }
+            }
+        });
+    }
+
     fn now() -> i64 {
         let umillis = SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -590,3 +1476,256 @@ impl OnpremAuthProvider {
         imillis
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::NoSQLErrorCode;
+
+    #[test]
+    fn test_backoff_jitter() {
+        let backoff = Duration::from_millis(400);
+        let samples = |j: BackoffJitter, previous: Duration| -> Vec<Duration> {
+            (0..200).map(|_| j.apply(backoff, previous)).collect()
+        };
+        let spread =
+            |v: &Vec<Duration>| -> Duration { *v.iter().max().unwrap() - *v.iter().min().unwrap() };
+        assert!(samples(BackoffJitter::None, Duration::ZERO)
+            .iter()
+            .all(|d| *d == backoff));
+
+        // retries of clients throttled together are spread over the allowed range
+        let full = samples(BackoffJitter::Full, Duration::ZERO);
+        assert!(full.iter().all(|d| *d <= backoff));
+        assert!(spread(&full) > backoff / 2);
+
+        let equal = samples(BackoffJitter::Equal, Duration::ZERO);
+        assert!(equal.iter().all(|d| *d >= backoff / 2 && *d <= backoff));
+        assert!(spread(&equal) > backoff / 4);
+
+        let previous = Duration::from_millis(300);
+        let decorrelated = samples(BackoffJitter::Decorrelated, previous);
+        assert!(decorrelated
+            .iter()
+            .all(|d| *d >= BACKOFF_BASE && *d <= previous * 3));
+        assert!(spread(&decorrelated) > previous);
+        // never more than the maximum backoff
+        assert!(samples(BackoffJitter::Decorrelated, BACKOFF_MAX)
+            .iter()
+            .all(|d| *d >= BACKOFF_BASE && *d <= BACKOFF_MAX));
+        // the first retry
+        assert!(samples(BackoffJitter::Decorrelated, Duration::ZERO)
+            .iter()
+            .all(|d| *d == BACKOFF_BASE));
+    }
+
+    #[test]
+    fn test_from_config() -> Result<(), Box<dyn std::error::Error>> {
+        let config: NoSQLConfig = serde_json::from_str(
+            r#"{"endpoint": "http://localhost:8080", "mode": "cloudsim", "auth": "cloudsim",
+                "timeout_ms": 5000, "ddl_timeout_ms": 60000}"#,
+        )?;
+        let b = HandleBuilder::new().from_config(config)?;
+        assert_eq!(b.endpoint, "localhost:8080");
+        assert!(!b.use_https);
+        assert_eq!(b.mode, HandleMode::Cloudsim);
+        assert_eq!(b.auth_type, AuthType::Cloudsim);
+        assert_eq!(b.timeout, Some(Duration::from_secs(5)));
+        assert_eq!(b.ddl_timeout, Some(Duration::from_secs(60)));
+
+        // the mode is implied by the auth mechanism
+        let config = NoSQLConfig {
+            endpoint: Some("https://nosql.mycompany.com:8080".to_string()),
+            auth: Some("onprem".to_string()),
+            accept_invalid_certs: true,
+            ..Default::default()
+        };
+        let b = HandleBuilder::new().from_config(config)?;
+        assert_eq!(b.mode, HandleMode::Onprem);
+        assert_eq!(b.auth_type, AuthType::Onprem);
+        assert!(b.use_https);
+        assert!(b.accept_invalid_certs);
+
+        for bad in [
+            r#"{"mode": "onprem", "auth": "instance"}"#,
+            r#"{"auth": "cloudsim", "region": "us-ashburn-1"}"#,
+            r#"{"auth": "instance", "auth_file": "/tmp/creds"}"#,
+            r#"{"auth": "password"}"#,
+            r#"{"endpoint": "localhost:8080/path"}"#,
+            r#"{"timeout_ms": 0}"#,
+        ] {
+            let config: NoSQLConfig = serde_json::from_str(bad)?;
+            let err = HandleBuilder::new().from_config(config).err();
+            assert_eq!(
+                err.map(|e| e.code),
+                Some(NoSQLErrorCode::IllegalArgument),
+                "{}",
+                bad
+            );
+        }
+        // unknown fields and modes are rejected when deserializing
+        assert!(serde_json::from_str::<NoSQLConfig>(r#"{"endpiont": "localhost"}"#).is_err());
+        assert!(serde_json::from_str::<NoSQLConfig>(r#"{"mode": "local"}"#).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate() -> Result<(), Box<dyn std::error::Error>> {
+        HandleBuilder::new()
+            .mode(HandleMode::Cloudsim)?
+            .endpoint("http://localhost:8080")?
+            .validate()?;
+        HandleBuilder::new()
+            .onprem_auth("user", "pass")?
+            .endpoint("https://nosql.mycompany.com:8080")?
+            .validate()?;
+
+        // all problems are reported at once
+        let err = HandleBuilder::new()
+            .timeout(Duration::ZERO)?
+            .validate()
+            .unwrap_err();
+        assert_eq!(err.code, NoSQLErrorCode::IllegalArgument);
+        assert_eq!(err.problems().len(), 3, "{}", err);
+        assert!(err.problems()[0].contains("no auth type specified"));
+        assert!(err.problems()[1].contains("can't determine NoSQL endpoint"));
+        assert!(err.problems()[2].contains("timeout"));
+        assert!(err.message.contains("3 problems"));
+
+        // a single problem is the message itself
+        let err = HandleBuilder::new()
+            .cloud_auth_from_instance()?
+            .endpoint("https://nosql.us-ashburn-1.oci.oraclecloud.com")?
+            .validate()
+            .unwrap_err();
+        assert_eq!(err.problems().len(), 1);
+        assert_eq!(err.message, err.problems()[0]);
+
+        // region and auth that do not match the mode
+        let err = HandleBuilder::new()
+            .cloud_region("us-ashburn-1")?
+            .mode(HandleMode::Onprem)?
+            .validate()
+            .unwrap_err();
+        assert_eq!(err.problems().len(), 1, "{}", err);
+        assert!(err.problems()[0].contains("us-ashburn-1"));
+        let err = HandleBuilder::new()
+            .onprem_auth("user", "pass")?
+            .cloud_region("us-ashburn-1")?
+            .validate()
+            .unwrap_err();
+        assert_eq!(err.problems().len(), 2, "{}", err);
+
+        // cloud auth over http
+        let mut b = HandleBuilder::new().endpoint("http://localhost:8080")?;
+        b.auth_type = AuthType::External;
+        let err = b.validate().unwrap_err();
+        assert!(err.problems()[0].contains("requires an https endpoint"));
+        b.danger_allow_http_cloud_auth(true)?.validate()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_endpoint_validation() {
+        let b = HandleBuilder::new()
+            .endpoint("https://nosql.mycompany.com:8080/")
+            .unwrap();
+        assert_eq!(b.endpoint, "nosql.mycompany.com:8080");
+        assert!(b.use_https);
+        let b = HandleBuilder::new()
+            .endpoint("http://localhost:8080")
+            .unwrap();
+        assert_eq!(b.endpoint, "localhost:8080");
+        assert!(!b.use_https);
+        let b = HandleBuilder::new().endpoint("127.0.0.1").unwrap();
+        assert_eq!(b.endpoint, "127.0.0.1");
+
+        for bad in [
+            "",
+            "http://",
+            "htpp://localhost:8080",
+            "localhost:8080/V2/nosql/data",
+            "https://host:port",
+            "https://host:99999",
+            "http://user@localhost",
+            "local host",
+            "localhost?x=1",
+        ] {
+            let err = HandleBuilder::new().endpoint(bad).err();
+            assert!(err.is_some(), "expected error for endpoint '{}'", bad);
+            assert_eq!(err.unwrap().code, NoSQLErrorCode::IllegalArgument);
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    struct TestProvider;
+
+    impl AuthenticationProvider for TestProvider {
+        fn tenancy_id(&self) -> &str {
+            "ocid1.tenancy.oc1..test"
+        }
+        fn user_id(&self) -> &str {
+            "ocid1.user.oc1..test"
+        }
+        fn fingerprint(&self) -> &str {
+            "00:11"
+        }
+        fn private_key(&self) -> Result<rsa::RsaPrivateKey, Box<dyn std::error::Error>> {
+            Err("no key".into())
+        }
+        fn region_id(&self) -> &str {
+            "us-ashburn-1"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cloud_auth_requires_https() {
+        let cloud = || {
+            HandleBuilder::new()
+                .cloud_auth(Box::new(TestProvider))
+                .unwrap()
+        };
+
+        let err = cloud()
+            .endpoint("http://localhost:8080")
+            .unwrap()
+            .build()
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, NoSQLErrorCode::IllegalArgument);
+        assert!(err.message.contains("https"), "{}", err.message);
+
+        // cloud_auth() after endpoint() switches to https
+        assert!(HandleBuilder::new()
+            .endpoint("http://localhost:8080")
+            .unwrap()
+            .cloud_auth(Box::new(TestProvider))
+            .unwrap()
+            .build()
+            .await
+            .is_ok());
+        assert!(cloud()
+            .endpoint("https://localhost:8080")
+            .unwrap()
+            .build()
+            .await
+            .is_ok());
+        assert!(cloud()
+            .endpoint("http://localhost:8080")
+            .unwrap()
+            .danger_allow_http_cloud_auth(true)
+            .unwrap()
+            .build()
+            .await
+            .is_ok());
+        // cloudsim does not sign requests
+        assert!(HandleBuilder::new()
+            .mode(HandleMode::Cloudsim)
+            .unwrap()
+            .endpoint("http://localhost:8080")
+            .unwrap()
+            .build()
+            .await
+            .is_ok());
+    }
+}
@@ -149,7 +149,7 @@
 //!
 //! #### Using the Config to Connect an Application to the Cloud Service
 //!
-//! The first step in any Oracle NoSQL Database Cloud Service `rust` application is to create a [`Handle`] used to send requests to the service, based on the configuration given in a [`HandleBuilder`] struct. Instances of the Handle are safe for concurrent use by multiple goroutines and intended to be shared in a multi-threaded / async application.
+//! The first step in any Oracle NoSQL Database Cloud Service `rust` application is to create a [`Handle`] used to send requests to the service, based on the configuration given in a [`HandleBuilder`] struct. Instances of the Handle are safe for concurrent use by multiple tasks and threads, and intended to be shared in a multi-threaded / async application. See [Sharing a handle](Handle#sharing-a-handle) for the recommended patterns.
 //!
 //! The following code example shows how to connect to the cloud service, using a user config file:
 //!
@@ -467,14 +467,24 @@
 //! details.
 //!
 
+// allow the NoSQLRow derive macro, which refers to this crate by name, in unit tests
+#[cfg(test)]
+extern crate self as oracle_nosql_rust_sdk;
+
 pub(crate) mod handle_builder;
-pub use crate::handle_builder::{HandleBuilder, HandleMode};
+pub use crate::handle_builder::{
+    BackoffJitter, HandleBuilder, HandleMode, LogLevel, NoSQLConfig, TlsVersion,
+};
 
 pub(crate) mod handle;
 pub use crate::handle::Handle;
 
 pub(crate) mod aggr_iter;
+#[cfg(test)]
+pub(crate) mod alloc_counter;
 pub(crate) mod arith_op_iter;
+#[cfg(feature = "arrow")]
+pub(crate) mod arrow;
 pub mod auth_common;
 
 // Export authentication components for external use
@@ -483,8 +493,17 @@ pub use auth_common::{
     resource_principal_auth_provider::ResourcePrincipalAuthProvider,
 };
 
+pub(crate) mod bulk_loader;
+pub use crate::bulk_loader::{BulkLoadResult, BulkLoader};
+
+pub(crate) mod circuit_breaker;
 pub(crate) mod collect_iter;
+pub(crate) mod condition;
+pub use crate::condition::Condition;
 pub(crate) mod const_iter;
+pub(crate) mod deadline;
+pub use crate::deadline::Deadline;
+
 pub(crate) mod delete_request;
 pub use crate::delete_request::{DeleteRequest, DeleteResult};
 
@@ -497,7 +516,7 @@ pub(crate) mod get_indexes_request;
 pub use crate::get_indexes_request::{GetIndexesRequest, GetIndexesResult, IndexInfo};
 
 pub(crate) mod get_request;
-pub use crate::get_request::{GetRequest, GetResult};
+pub use crate::get_request::{BinaryStream, GetRequest, GetResult};
 
 pub(crate) mod group_iter;
 pub(crate) mod list_tables_request;
@@ -505,14 +524,27 @@ pub use crate::list_tables_request::{ListTablesRequest, ListTablesResult};
 
 #[cfg(test)]
 pub(crate) mod mapvalue_tests;
+#[cfg(test)]
+pub(crate) mod mock_server;
 pub(crate) mod multi_delete_request;
 pub use crate::multi_delete_request::{FieldRange, MultiDeleteRequest, MultiDeleteResult};
 
+pub(crate) mod multi_get_request;
+pub use crate::multi_get_request::{MultiGetRequest, MultiGetResult};
+
+pub(crate) mod ndjson;
 pub(crate) mod nson;
+#[cfg(test)]
+pub(crate) mod nson_tests;
+pub(crate) mod observer;
+pub use crate::observer::{RequestEvent, RequestObserver};
+
 pub(crate) mod packed_integer;
 pub(crate) mod plan_iter;
+pub(crate) mod prepare_cache;
 pub(crate) mod prepared_statement;
-pub use crate::prepared_statement::PreparedStatement;
+pub use crate::prepared_statement::{PreparedStatement, QueryColumn, QueryDescription};
+pub use crate::receive_iter::DistributionKind;
 
 pub(crate) mod put_request;
 pub use crate::put_request::{PutRequest, PutResult};
@@ -523,12 +555,23 @@ pub(crate) mod qtf;
 pub(crate) mod qtf_custom_setup;
 #[cfg(test)]
 pub(crate) mod qtf_tests;
+pub(crate) mod query_cache;
 pub(crate) mod query_request;
 pub use crate::query_request::{QueryRequest, QueryResult};
 
+pub(crate) mod query_macro;
+#[doc(hidden)]
+pub use crate::query_macro::{__check_query_template, __execute_query_template};
+
+pub(crate) mod raw_request;
+pub use crate::raw_request::RawRequest;
+
 pub(crate) mod reader;
 pub(crate) mod receive_iter;
 pub(crate) mod region;
+pub use crate::region::Region;
+pub(crate) mod request_options;
+pub use crate::request_options::RequestOptions;
 
 #[cfg(test)]
 pub(crate) mod request_tests;
@@ -538,15 +581,21 @@ pub(crate) mod sfw_iter;
 pub(crate) mod size_iter;
 pub(crate) mod sort_iter;
 pub(crate) mod system_request;
-pub use crate::system_request::{SystemRequest, SystemResult};
+pub use crate::system_request::{SystemRequest, SystemResult, SystemStatusRequest};
 
 pub(crate) mod table_request;
-pub use crate::table_request::{GetTableRequest, TableRequest, TableResult};
+pub use crate::table_request::{GetTableRequest, ReplicaInfo, TableRequest, TableResult};
+
+pub(crate) mod table_scanner;
+pub use crate::table_scanner::TableScanner;
 
 pub(crate) mod table_usage_request;
 pub use crate::table_usage_request::{TableUsage, TableUsageRequest, TableUsageResult};
 
 pub mod types;
+pub(crate) mod update_request;
+pub use crate::update_request::{UpdateRequest, UpdateResult};
+
 /// Type representing a specific version of a table row in the NoSQL Database.
 pub type Version = Vec<u8>;
 pub use crate::types::NoSQLColumnToFieldValue;
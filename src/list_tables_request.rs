@@ -9,6 +9,7 @@ use crate::handle::Handle;
 use crate::handle::SendOptions;
 use crate::nson::*;
 use crate::reader::Reader;
+use crate::request_options::RequestOptions;
 use crate::types::OpCode;
 use crate::writer::Writer;
 use std::result::Result;
@@ -63,6 +64,14 @@ impl ListTablesRequest {
         self
     }
 
+    /// Apply [`RequestOptions`] to this request, using their timeout, compartment and namespace.
+    pub fn options(mut self, options: &RequestOptions) -> Self {
+        options.apply_timeout(&mut self.timeout);
+        options.apply_compartment_id(&mut self.compartment_id);
+        options.apply_namespace(&mut self.namespace);
+        self
+    }
+
     pub fn namespace(mut self, namespace: &str) -> ListTablesRequest {
         self.namespace = namespace.to_string();
         self
@@ -79,8 +88,7 @@ impl ListTablesRequest {
     }
 
     pub async fn execute(&self, h: &Handle) -> Result<ListTablesResult, NoSQLError> {
-        let mut w: Writer = Writer::new();
-        w.write_i16(h.inner.serial_version);
+        let mut w = h.request_writer();
         let timeout = h.get_timeout(&self.timeout);
         self.nson_serialize(&mut w, &timeout);
         let mut opts = SendOptions {
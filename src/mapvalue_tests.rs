@@ -9,6 +9,7 @@ use crate::types;
 use crate::types::FieldValue;
 use crate::types::NoSQLColumnToFieldValue;
 use crate::writer::Writer;
+use bigdecimal::Num;
 use std::error::Error;
 use std::result::Result;
 
@@ -113,3 +114,603 @@ fn test_basic_mapvalue() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+#[test]
+fn test_mapvalue_json() -> Result<(), Box<dyn Error>> {
+    let ts = chrono::DateTime::parse_from_rfc3339("2024-01-02T03:04:05.678Z")?;
+    let mut m = types::MapValue::new()
+        .i32("id", 5)
+        .str("name", "jane")
+        .timestamp("ts", &ts);
+    m.put_binary("data", vec![0x01, 0xab, 0xff]);
+    m.put_field_value(
+        "num",
+        FieldValue::Number(bigdecimal::BigDecimal::from_str_radix(
+            "12345678901234567890.123",
+            10,
+        )?),
+    );
+    m.put_field_value("nothing", FieldValue::JsonNull);
+    m.put_array("arr", vec![FieldValue::Long(1), FieldValue::Boolean(true)]);
+
+    assert_eq!(
+        m.to_json_string(),
+        r#"{"arr":[1,true],"data":"Aav/","id":5,"name":"jane","nothing":null,"num":12345678901234567890.123,"ts":"2024-01-02T03:04:05.678+00:00"}"#
+    );
+
+    let opts = types::JsonOptions::new()
+        .timestamp_format(types::JsonTimestampFormat::EpochMillis)
+        .number_format(types::JsonNumberFormat::String)
+        .binary_format(types::JsonBinaryFormat::Hex);
+    let v = m.to_json_value_with(&opts);
+    assert_eq!(v["ts"], serde_json::json!(1704164645678i64));
+    assert_eq!(v["num"], serde_json::json!("12345678901234567890.123"));
+    assert_eq!(v["data"], serde_json::json!("01abff"));
+
+    let pretty = m.to_json_string_pretty();
+    assert!(pretty.contains("\n  \"id\": 5"));
+    // pretty and compact output are the same JSON
+    let a: serde_json::Value = serde_json::from_str(&pretty)?;
+    assert_eq!(a, m.to_json_value());
+    Ok(())
+}
+
+#[test]
+fn test_mapvalue_index() -> Result<(), Box<dyn Error>> {
+    let row = types::MapValue::new()
+        .column("id", 10)
+        .column("tags", vec!["a".to_string(), "b".to_string()])
+        .column("info", types::MapValue::new().column("zip", 12345));
+    assert_eq!(row["id"], FieldValue::Integer(10));
+    assert_eq!(row["tags"][0], FieldValue::String("a".to_string()));
+    assert_eq!(row["info"]["zip"], FieldValue::Integer(12345));
+    assert_eq!(row["id"], *row.get_field_value("id").unwrap());
+
+    let missing = std::panic::catch_unwind(|| row["missing"].clone_internal());
+    assert!(missing.is_err());
+    let out_of_bounds = std::panic::catch_unwind(|| row["tags"][2].clone_internal());
+    assert!(out_of_bounds.is_err());
+    let not_array = std::panic::catch_unwind(|| row["id"][0].clone_internal());
+    assert!(not_array.is_err());
+    let not_map = std::panic::catch_unwind(|| row["id"]["x"].clone_internal());
+    assert!(not_map.is_err());
+    Ok(())
+}
+
+#[test]
+fn test_mapvalue_keys_values() -> Result<(), Box<dyn Error>> {
+    let row = types::MapValue::new()
+        .column("name", "jane")
+        .column("id", 10)
+        .column("age", 30);
+    assert!(row.contains_key("id"));
+    assert!(!row.contains_key("ID"));
+    let keys: Vec<&String> = row.keys().collect();
+    assert_eq!(keys, vec!["age", "id", "name"]);
+    let values: Vec<&FieldValue> = row.values().collect();
+    assert_eq!(values[0], &FieldValue::Integer(30));
+    assert_eq!(values[2], &FieldValue::String("jane".to_string()));
+    Ok(())
+}
+
+#[test]
+fn test_deep_clone() -> Result<(), Box<dyn Error>> {
+    let row = types::MapValue::new().column("id", 10).column(
+        "info",
+        types::MapValue::new().column("tags", vec!["a".to_string(), "b".to_string()]),
+    );
+    let mut copy = row.deep_clone();
+    assert_eq!(copy.to_json_string(), row.to_json_string());
+    copy.put("id", 11);
+    assert_eq!(row.get_i32("id"), Some(10));
+    let tags = row["info"]["tags"].deep_clone();
+    assert_eq!(&tags, &row["info"]["tags"]);
+    Ok(())
+}
+
+#[test]
+fn test_nested_struct_round_trip() -> Result<(), Box<dyn Error>> {
+    use crate::types::*;
+    use std::collections::HashMap;
+
+    #[derive(Default, Debug, PartialEq, NoSQLRow)]
+    struct Address {
+        city: String,
+        zip: i64,
+        tags: Vec<String>,
+    }
+    #[derive(Default, Debug, PartialEq, NoSQLRow)]
+    struct Person {
+        id: i64,
+        home: Address,
+        #[nosql(column=previous)]
+        history: Vec<Address>,
+        by_kind: HashMap<String, Address>,
+        work: Option<Address>,
+        score: f64,
+    }
+
+    let addr = |city: &str, zip: i64| Address {
+        city: city.to_string(),
+        zip,
+        tags: vec!["a".to_string(), "b".to_string()],
+    };
+    let p = Person {
+        id: 10,
+        home: addr("Springfield", 12345),
+        history: vec![addr("Shelbyville", 1), addr("Capital City", 2)],
+        by_kind: HashMap::from([("summer".to_string(), addr("Ogdenville", 3))]),
+        work: None,
+        score: 4.5,
+    };
+    let mv = p.to_map_value()?;
+    let prev = mv.get_field_value("previous").unwrap();
+    assert_eq!(
+        prev[1]["city"],
+        FieldValue::String("Capital City".to_string())
+    );
+    assert!(mv.get_field_value("work").unwrap().is_null());
+
+    // round trip through the wire format
+    let mut w = Writer::new();
+    w.write_field_value(&FieldValue::Map(mv));
+    let mut r = Reader::new().from_bytes(&w.buf);
+    let mv = r.read_field_value()?.get_map_value()?;
+    let mut p2 = Person::default();
+    p2.from_map_value(&mv)?;
+    assert_eq!(p, p2);
+
+    // as read back from a JSON document: small numbers are returned as integers,
+    // and missing arrays and maps are empty
+    let json = MapValue::new()
+        .i32("id", 11)
+        .column(
+            "home",
+            MapValue::new().str("city", "Springfield").i32("zip", 5),
+        )
+        .i32("score", 3);
+    let mut p3 = Person::default();
+    p3.from_map_value(&json)?;
+    assert_eq!(p3.id, 11);
+    assert_eq!(p3.home.zip, 5);
+    assert!(p3.home.tags.is_empty());
+    assert!(p3.history.is_empty());
+    assert!(p3.by_kind.is_empty());
+    assert_eq!(p3.score, 3.0);
+    Ok(())
+}
+
+#[test]
+fn test_nested_struct_conversion_error() -> Result<(), Box<dyn Error>> {
+    use crate::error::NoSQLErrorCode::IllegalArgument;
+    use crate::types::*;
+    use crate::{Condition, NoSQLError, PutRequest, UpdateRequest};
+
+    // a column type that cannot hold negative values
+    #[derive(Default, Debug)]
+    struct Positive(i32);
+    impl NoSQLColumnToFieldValue for Positive {
+        fn to_field_value(&self) -> FieldValue {
+            FieldValue::Integer(self.0)
+        }
+        fn try_to_field_value(&self) -> Result<FieldValue, NoSQLError> {
+            if self.0 < 0 {
+                return Err(NoSQLError::new(IllegalArgument, "negative value"));
+            }
+            Ok(self.to_field_value())
+        }
+    }
+    impl NoSQLColumnFromFieldValue for Positive {
+        fn from_field(fv: &FieldValue) -> Result<Self, NoSQLError> {
+            Ok(Positive(i32::from_field(fv)?))
+        }
+    }
+    #[derive(Default, Debug, NoSQLRow)]
+    struct Item {
+        qty: Positive,
+    }
+    #[derive(Default, Debug, NoSQLRow)]
+    struct Order {
+        id: i64,
+        items: Vec<Item>,
+        gift: Option<Item>,
+    }
+
+    let order = Order {
+        id: 1,
+        items: vec![Item { qty: Positive(2) }],
+        gift: Some(Item { qty: Positive(1) }),
+    };
+    let mv = order.to_map_value()?;
+    assert_eq!(mv["items"][0]["qty"], FieldValue::Integer(2));
+
+    // errors in nested structs are returned instead of being written as NULL
+    let bad = Order {
+        id: 2,
+        items: vec![Item { qty: Positive(2) }, Item { qty: Positive(-1) }],
+        gift: None,
+    };
+    let err = bad.to_map_value().unwrap_err();
+    assert!(err.message.contains("negative value"));
+    assert!(PutRequest::new("orders").put(bad).is_err());
+    let bad = Order {
+        id: 3,
+        items: Vec::new(),
+        gift: Some(Item { qty: Positive(-5) }),
+    };
+    assert!(bad.to_map_value().is_err());
+    assert!(bad.try_to_field_value().is_err());
+
+    // as are errors in update values and condition values
+    let key = || MapValue::new().i64("id", 1);
+    let err = UpdateRequest::new("orders", key())
+        .set("qty", Positive(-1))
+        .statement()
+        .unwrap_err();
+    assert!(err.message.contains("negative value"));
+    let err = UpdateRequest::new("orders", key())
+        .set("qty", 1)
+        .if_condition(Condition::new().gt("qty", Positive(-1)))
+        .statement()
+        .unwrap_err();
+    assert!(err.message.contains("negative value"));
+    assert!(!Condition::new().eq("qty", Positive(-1)).is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_skip_if_none() -> Result<(), Box<dyn Error>> {
+    use crate::types::*;
+
+    #[derive(Default, Debug, PartialEq, NoSQLRow)]
+    struct Doc {
+        id: i32,
+        #[nosql(skip_if_none)]
+        note: Option<String>,
+        #[nosql(column=rating, skip_if_none)]
+        score: Option<i64>,
+        other: Option<String>,
+    }
+    let doc = Doc {
+        id: 1,
+        ..Default::default()
+    };
+    let mv = doc.to_map_value()?;
+    assert!(!mv.contains_key("note"));
+    assert!(!mv.contains_key("rating"));
+    assert!(!mv.contains_key("score"));
+    assert!(mv.get_field_value("other").unwrap().is_null());
+
+    let doc = Doc {
+        id: 2,
+        note: Some("x".to_string()),
+        score: Some(5),
+        other: None,
+    };
+    let mv = doc.to_map_value()?;
+    assert_eq!(mv.get_string("note"), Some("x".to_string()));
+    assert_eq!(mv.get_i64("rating"), Some(5));
+    let mut doc2 = Doc::default();
+    doc2.from_map_value(&mv)?;
+    assert_eq!(doc, doc2);
+
+    // missing fields read back as None
+    let mut doc3 = Doc::default();
+    doc3.from_map_value(&MapValue::new().i32("id", 3))?;
+    assert_eq!(doc3.note, None);
+    assert_eq!(doc3.score, None);
+    Ok(())
+}
+
+#[test]
+fn test_json_nullable() -> Result<(), Box<dyn Error>> {
+    use crate::types::*;
+
+    #[derive(Default, Debug, PartialEq, NoSQLRow)]
+    struct Doc {
+        a: JsonNullable<String>,
+        b: JsonNullable<String>,
+        c: JsonNullable<String>,
+        d: JsonNullable<String>,
+        e: Option<String>,
+    }
+    let doc = Doc {
+        a: JsonNullable::Absent,
+        b: JsonNullable::Null,
+        c: JsonNullable::JsonNull,
+        d: JsonNullable::Value("x".to_string()),
+        e: None,
+    };
+
+    // round trip through the wire format: absent fields are not written
+    let mut w = Writer::new();
+    w.write_field_value(&FieldValue::Map(doc.to_map_value()?));
+    let mut r = Reader::new().from_bytes(&w.buf);
+    let mv = r.read_field_value()?.get_map_value()?;
+    assert_eq!(mv.len(), 4);
+    assert!(mv.get_field_value("a").is_none());
+    assert_eq!(*mv.get_field_value("b").unwrap(), FieldValue::Null);
+    assert_eq!(*mv.get_field_value("c").unwrap(), FieldValue::JsonNull);
+    let mut doc2 = Doc::default();
+    doc2.from_map_value(&mv)?;
+    assert_eq!(doc, doc2);
+
+    // Option<T> keeps the lenient behavior
+    let opt: Option<String> = None.from_map("c", &mv)?;
+    assert!(opt.is_none());
+    assert!(doc2.c.is_null());
+    assert_eq!(doc2.d.value(), Some(&"x".to_string()));
+    assert_eq!(doc2.b.into_option(), None);
+    Ok(())
+}
+
+#[test]
+fn test_json_column_round_trip() -> Result<(), Box<dyn Error>> {
+    use serde_json::json;
+
+    // a single JSON column can hold a different shape in every row
+    let docs = vec![
+        json!({"name": "jane", "age": 30, "tags": ["a", "b"]}),
+        json!([1, "two", 3.5, true, null, {"x": [[]]}, []]),
+        json!("just a string"),
+        json!(42),
+        json!(-9007199254740993i64),
+        json!(18446744073709551615u64),
+        json!(1.0e-7),
+        json!(false),
+        json!(null),
+        json!({}),
+        json!([]),
+        json!({"a": {"b": {"c": {"d": [{"e": null}, {"é": "ü"}]}}}}),
+    ];
+    for doc in docs {
+        let row = types::MapValue::new()
+            .column("id", 1)
+            .column("doc", doc.to_field_value());
+        let mut w = Writer::new();
+        w.write_map_value(&row);
+        let mut r = Reader::new().from_bytes(&w.buf);
+        let read = r.read_field_value()?.get_map_value()?;
+        assert_eq!(read["doc"].to_json_value(), doc, "JSON value changed");
+    }
+
+    // numbers that do not fit in an i64 stay numbers
+    assert!(matches!(
+        json!(18446744073709551615u64).to_field_value(),
+        FieldValue::Number(_)
+    ));
+
+    let table = crate::TableResult {
+        schema: r#"{"fields":[{"name":"id","type":"INTEGER"},{"name":"doc","type":"JSON"},
+            {"name":"info","type":"json"}],"primaryKey":["id"]}"#
+            .to_string(),
+        ..Default::default()
+    };
+    assert_eq!(table.json_fields(), vec!["doc", "info"]);
+    Ok(())
+}
+
+#[test]
+fn test_hash_consistent_with_eq() -> Result<(), Box<dyn Error>> {
+    use crate::types::MapValue;
+    use bigdecimal::BigDecimal;
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::HashSet;
+    use std::hash::{Hash, Hasher};
+
+    fn hash_of<T: Hash>(v: &T) -> u64 {
+        let mut h = DefaultHasher::new();
+        v.hash(&mut h);
+        h.finish()
+    }
+
+    let equal = [
+        (FieldValue::Integer(5), FieldValue::Long(5)),
+        (FieldValue::Long(5), FieldValue::Double(5.0)),
+        (
+            FieldValue::Double(2.5),
+            FieldValue::Number(BigDecimal::from_str_radix("2.50", 10)?),
+        ),
+        (
+            FieldValue::Number(BigDecimal::from(0)),
+            FieldValue::Double(-0.0),
+        ),
+        (
+            FieldValue::Timestamp(chrono::DateTime::parse_from_rfc3339(
+                "2024-01-02T03:04:05Z",
+            )?),
+            FieldValue::Timestamp(chrono::DateTime::parse_from_rfc3339(
+                "2024-01-02T05:04:05+02:00",
+            )?),
+        ),
+        (FieldValue::Uninitialized, FieldValue::Null),
+    ];
+    for (a, b) in &equal {
+        assert_eq!(a, b);
+        assert_eq!(hash_of(a), hash_of(b), "{:?} {:?}", a, b);
+    }
+    assert_ne!(FieldValue::Null, FieldValue::JsonNull);
+    assert_ne!(FieldValue::String("1".to_string()), FieldValue::Integer(1));
+
+    // field order and numeric types do not matter
+    let row1 = MapValue::new()
+        .i32("id", 1)
+        .str("name", "jane")
+        .column("tags", vec!["a", "b"]);
+    let row2 = MapValue::new()
+        .column("tags", vec!["a", "b"])
+        .str("name", "jane")
+        .i64("id", 1);
+    let row3 = MapValue::new().i32("id", 2).str("name", "jane");
+    assert_eq!(row1, row2);
+    assert_eq!(hash_of(&row1), hash_of(&row2));
+    let rows: HashSet<MapValue> = vec![row1, row2, row3].into_iter().collect();
+    assert_eq!(rows.len(), 2);
+    Ok(())
+}
+
+#[test]
+fn test_json_number_policy() -> Result<(), Box<dyn Error>> {
+    use crate::types::{MapValue, NumberPolicy};
+    use bigdecimal::BigDecimal;
+
+    let exact: serde_json::Value = serde_json::from_str(
+        r#"{"i": 5, "l": 5000000000, "d": 0.5, "e": 1.5e3, "n": 1e400,
+            "u": 18446744073709551615, "a": [1, {"x": 2.25}]}"#,
+    )?;
+    let lenient = MapValue::from_json_object(&exact)?;
+    let strict = MapValue::from_json_object_with(&exact, NumberPolicy::StrictNumber)?;
+    assert_eq!(lenient, strict);
+    assert!(matches!(
+        strict.get_field_value("i"),
+        Some(FieldValue::Integer(5))
+    ));
+    assert!(matches!(
+        strict.get_field_value("l"),
+        Some(FieldValue::Long(5000000000))
+    ));
+    assert!(matches!(strict.get_field_value("d"), Some(FieldValue::Double(d)) if *d == 0.5));
+    assert!(matches!(strict.get_field_value("e"), Some(FieldValue::Double(d)) if *d == 1500.0));
+    assert!(matches!(
+        strict.get_field_value("n"),
+        Some(FieldValue::Number(_))
+    ));
+    assert_eq!(
+        strict.get_field_value("u"),
+        Some(&FieldValue::Number(BigDecimal::from(u64::MAX)))
+    );
+
+    // more digits than a double holds, at any depth
+    for text in [
+        r#"{"d": 0.10000000000000000001}"#,
+        r#"{"a": [1, 3.14159265358979323846264338327950288]}"#,
+        r#"{"m": {"x": 9007199254740993.0}}"#,
+    ] {
+        let json: serde_json::Value = serde_json::from_str(text)?;
+        assert!(MapValue::from_json_object_with(&json, NumberPolicy::Lenient).is_ok());
+        let err =
+            MapValue::from_json_object_with(&json, NumberPolicy::StrictNumber).expect_err(text);
+        assert_eq!(err.code, crate::error::NoSQLErrorCode::IllegalArgument);
+    }
+    let json: serde_json::Value = serde_json::from_str("[0.10000000000000000001]")?;
+    assert!(
+        MapValue::from_json_object_with(&json, NumberPolicy::Lenient).is_err(),
+        "not an object"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_empty_string_round_trip() -> Result<(), Box<dyn Error>> {
+    let value = types::MapValue::new()
+        .str("", "")
+        .str("name", "")
+        .column("tags", vec!["", "a"]);
+    let mut w = Writer::new();
+    w.write_field_value(&FieldValue::Map(value));
+    let mut r = Reader::new().from_bytes(&w.buf);
+    let got = r.read_field_value()?.get_map_value()?;
+    assert_eq!(got.get_string(""), Some(String::new()));
+    assert_eq!(got.get_string("name"), Some(String::new()));
+    assert_eq!(
+        got.get_field_value("tags"),
+        Some(&FieldValue::Array(vec![
+            FieldValue::String(String::new()),
+            FieldValue::String("a".to_string())
+        ]))
+    );
+
+    // a null string (length -1) is read as NULL, not as an empty string
+    let mut w = Writer::new();
+    w.write_field_type(types::FieldType::String);
+    w.write_packed_i32(-1);
+    w.write_field_type(types::FieldType::String);
+    w.write_packed_i32(0);
+    let mut r = Reader::new().from_bytes(&w.buf);
+    assert!(matches!(r.read_field_value()?, FieldValue::Null));
+    assert!(matches!(r.read_field_value()?, FieldValue::String(s) if s.is_empty()));
+    Ok(())
+}
+
+#[test]
+fn test_map_from_pairs() -> Result<(), Box<dyn Error>> {
+    use crate::delete_request::DeleteRequest;
+    use crate::get_request::GetRequest;
+    use crate::types::MapValue;
+
+    assert_eq!(MapValue::from(("id", 5)), MapValue::new().i32("id", 5));
+    assert_eq!(
+        MapValue::from((("region", "us"), ("id", 5i64))),
+        MapValue::new().str("region", "us").i64("id", 5)
+    );
+    let ts = chrono::DateTime::parse_from_rfc3339("2024-05-06T12:30:59Z")?;
+    let key: MapValue = (
+        ("a", 1),
+        ("b", "x"),
+        ("c", true),
+        ("d", 2.5),
+        ("e", ts),
+        ("f", 3),
+    )
+        .into();
+    assert_eq!(key.len(), 6);
+    assert_eq!(key.get_float64("d"), Some(2.5));
+    assert_eq!(key.get_timestamp("e"), Some(ts));
+
+    // the explicit builder still works
+    let req = GetRequest::new("t").key(MapValue::new().str("region", "us").i32("id", 5));
+    assert_eq!(
+        req.key,
+        GetRequest::new("t").key((("region", "us"), ("id", 5))).key
+    );
+    let req = DeleteRequest::new("t", (("region", "us"), ("id", 5)));
+    assert_eq!(req.key, MapValue::new().str("region", "us").i32("id", 5));
+    Ok(())
+}
+
+#[cfg(feature = "uuid")]
+#[test]
+fn test_uuid_conversion() -> Result<(), Box<dyn Error>> {
+    use crate::error::NoSQLErrorCode::IllegalArgument;
+    use crate::types::*;
+    use crate::{DeleteRequest, GetRequest};
+    use uuid::Uuid;
+
+    let id = Uuid::parse_str("67E55044-10B1-426F-9247-BB680E5FE0C8")?;
+    // written in canonical hyphenated form
+    assert_eq!(
+        id.to_field_value(),
+        FieldValue::String("67e55044-10b1-426f-9247-bb680e5fe0c8".to_string())
+    );
+    assert_eq!(Uuid::from_field(&id.to_field_value())?, id);
+    // other valid forms are accepted when reading
+    let simple = FieldValue::String("67e5504410b1426f9247bb680e5fe0c8".to_string());
+    assert_eq!(Uuid::from_field(&simple)?, id);
+    for bad in [
+        FieldValue::String("67e55044-10b1-426f-9247".to_string()),
+        FieldValue::String("not a uuid".to_string()),
+        FieldValue::Integer(1),
+    ] {
+        assert_eq!(Uuid::from_field(&bad).unwrap_err().code, IllegalArgument);
+    }
+
+    #[derive(Default, Debug, NoSQLRow)]
+    struct Session {
+        id: Uuid,
+        parent: Option<Uuid>,
+    }
+    let s = Session { id, parent: None };
+    let mv = s.to_map_value()?;
+    assert_eq!(mv.get_string("id"), Some(id.to_string()));
+    let mut s2 = Session::default();
+    s2.from_map_value(&mv)?;
+    assert_eq!(s2.id, id);
+    assert_eq!(s2.parent, None);
+
+    // keys can be given directly
+    let key = MapValue::from(("id", id));
+    assert_eq!(key.get_string("id"), Some(id.to_string()));
+    let _ = GetRequest::new("sessions").key(("id", id));
+    let _ = DeleteRequest::new("sessions", ("id", id));
+    Ok(())
+}
@@ -0,0 +1,359 @@
+//
+// Copyright (c) 2024, 2025 Oracle and/or its affiliates. All rights reserved.
+//
+// Licensed under the Universal Permissive License v 1.0 as shown at
+//  https://oss.oracle.com/licenses/upl/
+//
+
+// A minimal HTTP server for tests that need to exercise the request/response
+// path of a Handle without a running NoSQL service. The server replies to each
+// request, in order, with one of a canned list of NSON response bodies.
+// It can also serve HTTPS, with the self-signed certificate in tests/fixtures/tls.
+
+use crate::error::NoSQLErrorCode;
+use crate::handle::Handle;
+use crate::handle_builder::{HandleBuilder, HandleMode};
+use crate::nson::{
+    ERROR_CODE, EXCEPTION, HEADER, PAYLOAD, PREPARED_QUERY, PROXY_TOPO_SEQNUM, SHARD_IDS,
+    TOPOLOGY_INFO,
+};
+use crate::reader::Reader;
+use crate::types::{FieldValue, MapValue};
+use crate::writer::Writer;
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::TlsAcceptor;
+
+pub(crate) struct MockServer {
+    pub(crate) endpoint: String,
+    stats: Arc<Stats>,
+}
+
+#[derive(Default)]
+struct Stats {
+    requests: AtomicUsize,
+    bodies: Mutex<Vec<Vec<u8>>>,
+    heads: Mutex<Vec<String>>,
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+}
+
+impl MockServer {
+    /// Start a server on a local port that answers requests with the given bodies.
+    /// Once all bodies are used, connections are closed without a response.
+    pub(crate) async fn start(responses: Vec<Vec<u8>>) -> MockServer {
+        Self::start_with_delay(responses, Duration::ZERO).await
+    }
+
+    /// Start a server that waits for `delay` before sending each response.
+    pub(crate) async fn start_with_delay(responses: Vec<Vec<u8>>, delay: Duration) -> MockServer {
+        Self::start_internal(responses, delay, None).await
+    }
+
+    /// Start an HTTPS server using the self-signed certificate in
+    /// tests/fixtures/tls (see tls_cert_path()), valid for 127.0.0.1.
+    pub(crate) async fn start_tls(responses: Vec<Vec<u8>>) -> MockServer {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls");
+        let cert = pem_contents(&std::fs::read_to_string(format!("{}/cert.pem", dir)).unwrap());
+        let key = pem_contents(&std::fs::read_to_string(format!("{}/key.pem", dir)).unwrap());
+        let config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![CertificateDer::from(cert)],
+                PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key)),
+            )
+            .unwrap();
+        Self::start_internal(
+            responses,
+            Duration::ZERO,
+            Some(TlsAcceptor::from(Arc::new(config))),
+        )
+        .await
+    }
+
+    /// Path of the PEM certificate used by start_tls().
+    pub(crate) fn tls_cert_path() -> String {
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/cert.pem").to_string()
+    }
+
+    async fn start_internal(
+        responses: Vec<Vec<u8>>,
+        delay: Duration,
+        tls: Option<TlsAcceptor>,
+    ) -> MockServer {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let scheme = if tls.is_some() { "https" } else { "http" };
+        let endpoint = format!("{}://{}", scheme, listener.local_addr().unwrap());
+        let responses = Arc::new(Mutex::new(VecDeque::from(responses)));
+        let stats = Arc::new(Stats::default());
+        let server_stats = stats.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = match listener.accept().await {
+                    Ok(s) => s,
+                    Err(_) => return,
+                };
+                let responses = responses.clone();
+                let stats = server_stats.clone();
+                match &tls {
+                    Some(acceptor) => {
+                        let acceptor = acceptor.clone();
+                        tokio::spawn(async move {
+                            // handshake failures (such as an untrusted certificate) just close the connection
+                            if let Ok(stream) = acceptor.accept(stream).await {
+                                Self::serve(stream, responses, stats, delay).await;
+                            }
+                        });
+                    }
+                    None => {
+                        tokio::spawn(Self::serve(stream, responses, stats, delay));
+                    }
+                }
+            }
+        });
+        MockServer { endpoint, stats }
+    }
+
+    /// Pause the tokio clock, so that backoff delays pass without waiting.
+    ///
+    /// A paused clock jumps to the next timer whenever the runtime is idle,
+    /// which would fire the timeout of a request as soon as it waits on the
+    /// server. A background task keeps a timer 1ms ahead, so the clock only
+    /// advances in small steps while requests are in flight.
+    pub(crate) fn pause_time() {
+        tokio::time::pause();
+        tokio::spawn(async {
+            loop {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        });
+    }
+
+    /// A cloudsim handle builder connected to this server.
+    pub(crate) fn builder(&self) -> HandleBuilder {
+        Handle::builder()
+            .mode(HandleMode::Cloudsim)
+            .unwrap()
+            .endpoint(&self.endpoint)
+            .unwrap()
+    }
+
+    /// Build a cloudsim handle connected to this server.
+    pub(crate) async fn handle(&self) -> Handle {
+        self.builder().build().await.unwrap()
+    }
+
+    /// Number of requests received so far.
+    pub(crate) fn requests(&self) -> usize {
+        self.stats.requests.load(Ordering::SeqCst)
+    }
+
+    /// Bodies of all requests received so far, in order.
+    pub(crate) fn request_bodies(&self) -> Vec<Vec<u8>> {
+        self.stats.bodies.lock().unwrap().clone()
+    }
+
+    /// Request lines and headers of all requests received so far, in order.
+    pub(crate) fn request_heads(&self) -> Vec<String> {
+        self.stats.heads.lock().unwrap().clone()
+    }
+
+    /// Request `i`, decoded into its HEADER and PAYLOAD maps.
+    pub(crate) fn request(&self, i: usize) -> MapValue {
+        let body = &self.request_bodies()[i];
+        let mut r = Reader::new().from_bytes(body);
+        r.read_i16().unwrap(); // serial version
+        r.read_field_value().unwrap().get_map_value().unwrap()
+    }
+
+    /// HEADER map of request `i`.
+    pub(crate) fn request_header(&self, i: usize) -> MapValue {
+        let mut req = self.request(i);
+        req.take_field_value(HEADER)
+            .unwrap()
+            .get_map_value()
+            .unwrap()
+    }
+
+    /// PAYLOAD map of request `i`.
+    pub(crate) fn request_payload(&self, i: usize) -> MapValue {
+        let mut req = self.request(i);
+        req.take_field_value(PAYLOAD)
+            .unwrap()
+            .get_map_value()
+            .unwrap()
+    }
+
+    /// Highest number of requests that were being processed at the same time.
+    pub(crate) fn max_in_flight(&self) -> usize {
+        self.stats.max_in_flight.load(Ordering::SeqCst)
+    }
+
+    /// NSON body for a successful response containing the given fields.
+    pub(crate) fn response(fields: MapValue) -> Vec<u8> {
+        let mut w = Writer::new();
+        w.write_map_value(&fields);
+        w.buf
+    }
+
+    /// Fields of a response to a query that had to be prepared: a dummy
+    /// prepared statement and a topology with a single shard. Other fields
+    /// can be added with column().
+    pub(crate) fn prepared_fields() -> MapValue {
+        let topology = MapValue::new()
+            .i32(PROXY_TOPO_SEQNUM, 1)
+            .column(SHARD_IDS, FieldValue::Array(vec![FieldValue::Integer(1)]));
+        MapValue::new()
+            .column(PREPARED_QUERY, FieldValue::Binary(vec![1, 2, 3]))
+            .column(TOPOLOGY_INFO, FieldValue::Map(topology))
+    }
+
+    /// NSON body for a response that only prepares a query (see prepared_fields()).
+    pub(crate) fn prepared_response() -> Vec<u8> {
+        Self::response(Self::prepared_fields())
+    }
+
+    /// NSON body for an error response.
+    pub(crate) fn error_response(code: NoSQLErrorCode, message: &str) -> Vec<u8> {
+        let m = MapValue::new()
+            .column(ERROR_CODE, FieldValue::Integer(code as i32))
+            .column(EXCEPTION, FieldValue::String(message.to_string()));
+        Self::response(m)
+    }
+
+    /// A complete HTTP response with a JSON body, such as from the on-premises
+    /// security service.
+    pub(crate) fn json_response(json: &str) -> Vec<u8> {
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            json.len(),
+            json
+        )
+        .into_bytes()
+    }
+
+    /// A complete HTTP error response with the given status and `Retry-After` header value.
+    pub(crate) fn retry_after_response(status: u16, retry_after: &str) -> Vec<u8> {
+        format!(
+            "HTTP/1.1 {} Error\r\nRetry-After: {}\r\nContent-Length: 0\r\n\r\n",
+            status, retry_after
+        )
+        .into_bytes()
+    }
+
+    /// A complete HTTP redirect response to the given location.
+    pub(crate) fn redirect_response(location: &str) -> Vec<u8> {
+        format!(
+            "HTTP/1.1 307 Temporary Redirect\r\nLocation: {}\r\nContent-Length: 0\r\n\r\n",
+            location
+        )
+        .into_bytes()
+    }
+
+    /// A complete HTTP response with the given status, for testing HTTP-level
+    /// errors. It is sent as-is instead of being wrapped in a 200 response.
+    pub(crate) fn http_status_response(status: u16, text: &str) -> Vec<u8> {
+        format!(
+            "HTTP/1.1 {} Error\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{}",
+            status,
+            text.len(),
+            text
+        )
+        .into_bytes()
+    }
+
+    async fn serve<S: AsyncRead + AsyncWrite + Unpin>(
+        mut stream: S,
+        responses: Arc<Mutex<VecDeque<Vec<u8>>>>,
+        stats: Arc<Stats>,
+        delay: Duration,
+    ) {
+        let mut buf: Vec<u8> = Vec::new();
+        loop {
+            // read headers
+            let header_end = loop {
+                if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                    break pos + 4;
+                }
+                let mut chunk = [0u8; 4096];
+                match stream.read(&mut chunk).await {
+                    Ok(0) | Err(_) => return,
+                    Ok(n) => buf.extend_from_slice(&chunk[..n]),
+                }
+            };
+            let headers = String::from_utf8_lossy(&buf[..header_end]).to_lowercase();
+            let header = |name: &str| -> String {
+                headers
+                    .lines()
+                    .find_map(|l| l.strip_prefix(name))
+                    .map(|v| v.trim().to_string())
+                    .unwrap_or_default()
+            };
+            let content_length: usize = header("content-length:").parse().unwrap_or(0);
+            let request_id = header("x-nosql-request-id:");
+
+            // read body
+            while buf.len() < header_end + content_length {
+                let mut chunk = [0u8; 4096];
+                match stream.read(&mut chunk).await {
+                    Ok(0) | Err(_) => return,
+                    Ok(n) => buf.extend_from_slice(&chunk[..n]),
+                }
+            }
+            let buf_head = buf[..header_end].to_vec();
+            let body: Vec<u8> = buf
+                .drain(..header_end + content_length)
+                .skip(header_end)
+                .collect();
+            stats
+                .heads
+                .lock()
+                .unwrap()
+                .push(String::from_utf8_lossy(&buf_head).to_string());
+            stats.bodies.lock().unwrap().push(body);
+            stats.requests.fetch_add(1, Ordering::SeqCst);
+            let n = stats.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            stats.max_in_flight.fetch_max(n, Ordering::SeqCst);
+            if !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+            stats.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+            let body = match responses.lock().unwrap().pop_front() {
+                Some(b) => b,
+                None => return,
+            };
+            if body.starts_with(b"HTTP/") {
+                if stream.write_all(&body).await.is_err() {
+                    return;
+                }
+                continue;
+            }
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/octet-stream\r\nContent-Length: {}\r\nx-nosql-request-id: {}\r\n\r\n",
+                body.len(),
+                request_id
+            );
+            // a single write, so that the body is not held back waiting for
+            // the client to acknowledge the head (Nagle's algorithm)
+            let mut response = head.into_bytes();
+            response.extend_from_slice(&body);
+            if stream.write_all(&response).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+// Decode the base64 contents of a single-object PEM file.
+fn pem_contents(pem: &str) -> Vec<u8> {
+    let b64: String = pem.lines().filter(|l| !l.starts_with("-----")).collect();
+    BASE64_STANDARD.decode(b64).unwrap()
+}
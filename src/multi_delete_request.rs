@@ -4,16 +4,30 @@
 // Licensed under the Universal Permissive License v 1.0 as shown at
 //  https://oss.oracle.com/licenses/upl/
 //
+use crate::condition::{
+    execute_statement, key_clauses, prepare_statement, rows_affected, validate_path, Condition,
+};
+use crate::delete_request::DeleteRequest;
+use crate::error::ia_err;
 use crate::error::NoSQLError;
 use crate::handle::Handle;
 use crate::handle::SendOptions;
 use crate::nson::*;
 use crate::reader::Reader;
-use crate::types::{Capacity, FieldValue, OpCode};
+use crate::request_options::RequestOptions;
+use crate::table_request::GetTableRequest;
+use crate::types::{
+    row_to_map_value, Capacity, Durability, FieldValue, MapValue, NoSQLRow, OpCode,
+};
+use crate::write_multiple_request::WriteMultipleRequest;
 use crate::writer::Writer;
+use std::collections::BTreeMap;
 use std::result::Result;
 use std::time::Duration;
 
+// Largest number of deletes sent in one WriteMultipleRequest when deleting by condition.
+const MAX_DELETE_BATCH: usize = 50;
+
 /// A range of values to be used in a [`MultiDeleteRequest`] operation.
 ///
 /// `FieldRange` is used as the least significant component in a partially
@@ -75,9 +89,9 @@ pub struct MultiDeleteRequest {
     pub(crate) max_write_kb: i32,
     pub(crate) timeout: Option<Duration>,
     // Durability is currently only used in On-Prem installations.
-    // Added in SDK Version 1.3.0
-    // TODO durability: Option<Something>
-
+    pub(crate) durability: Option<Durability>,
+    // predicate on non-key fields, deleted with a query and batched deletes
+    pub(crate) condition: Option<Condition>,
     // namespace is used on-premises only. It defines a namespace to use
     // for the request. It is optional.
     // If a namespace is specified in the table name for the request
@@ -137,6 +151,24 @@ impl MultiDeleteRequest {
         }
     }
 
+    /// Create a new `MultiDeleteRequest` with a partial key taken from a native Rust struct.
+    ///
+    /// `partial_key` must implement the [`NoSQLRow`] trait, usually with the
+    /// [`derive@NoSQLRow`](crate::NoSQLRow) derive macro. All of its fields are used as the
+    /// partial key, so it should only contain the shard key fields of the table (and optionally
+    /// more primary key fields). If the struct cannot be converted, an `IllegalArgument` error
+    /// is returned.
+    pub fn from_row<T: NoSQLRow>(
+        table_name: &str,
+        partial_key: &T,
+    ) -> Result<MultiDeleteRequest, NoSQLError> {
+        Ok(MultiDeleteRequest {
+            table_name: table_name.to_string(),
+            key: FieldValue::Map(row_to_map_value(partial_key)?),
+            ..Default::default()
+        })
+    }
+
     /// Specify the timeout value for the request.
     ///
     /// This is optional.
@@ -161,6 +193,22 @@ impl MultiDeleteRequest {
         self
     }
 
+    /// On-premises only: specify the [`Durability`] to use for the operation.
+    ///
+    /// If not set, the server's default durability is used.
+    pub fn durability(mut self, d: Durability) -> MultiDeleteRequest {
+        self.durability = Some(d);
+        self
+    }
+
+    /// Apply [`RequestOptions`] to this request, using their timeout, compartment and durability.
+    pub fn options(mut self, options: &RequestOptions) -> Self {
+        options.apply_timeout(&mut self.timeout);
+        options.apply_compartment_id(&mut self.compartment_id);
+        options.apply_durability(&mut self.durability);
+        self
+    }
+
     /// Specifiy the [`FieldRange`] to be used for the operation.
     ///
     /// It is optional, but required to delete a specific range of rows.
@@ -190,10 +238,53 @@ impl MultiDeleteRequest {
         self
     }
 
+    /// Only delete rows that also match a [`Condition`] on fields that are not part of the
+    /// primary key, such as fields with a secondary index.
+    ///
+    /// A multi-delete normally deletes all rows with a partial primary key in a single
+    /// operation on the server. With a condition, the request instead runs in several steps:
+    /// 1. a query reads the primary keys of the rows matching the partial key, the
+    ///    [`FieldRange`] (if any) and the condition, one batch of results at a time,
+    /// 2. the rows of each batch are deleted by key, in
+    ///    [`WriteMultipleRequest`](crate::WriteMultipleRequest) batches of up to 50 rows with
+    ///    the same shard key, before the next batch of keys is read.
+    ///
+    /// [`MultiDeleteResult::num_deleted()`] is the total number of rows deleted, and
+    /// [`MultiDeleteResult::consumed()`] the total capacity consumed by all steps.
+    ///
+    /// Unlike a multi-delete on a partial key, this is not transactional, even within a shard.
+    /// A row that changes between the query and its delete so that it no longer matches the
+    /// condition is still deleted, rows that start to match the condition after the query are
+    /// not deleted, and if a step fails, the rows of the batches before it stay deleted.
+    ///
+    /// With a condition, the partial key may be an empty map to delete matching rows from the
+    /// whole table. The continuation key and `max_write_kb` are not used.
+    ///
+    /// ```no_run
+    /// # use oracle_nosql_rust_sdk::{Condition, Handle, MultiDeleteRequest};
+    /// # use oracle_nosql_rust_sdk::types::*;
+    /// # #[tokio::main]
+    /// # pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let handle = Handle::builder().build().await?;
+    /// let res = MultiDeleteRequest::new("users", &FieldValue::Map(MapValue::new()))
+    ///     .where_condition(Condition::new().eq("status", "inactive"))
+    ///     .execute(&handle)
+    ///     .await?;
+    /// println!("deleted {} inactive users", res.num_deleted());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn where_condition(mut self, condition: Condition) -> MultiDeleteRequest {
+        self.condition = Some(condition);
+        self
+    }
+
     pub async fn execute(&self, h: &Handle) -> Result<MultiDeleteResult, NoSQLError> {
+        if self.condition.is_some() {
+            return self.execute_where(h).await;
+        }
         // TODO: validate: size > 0, etc
-        let mut w: Writer = Writer::new();
-        w.write_i16(h.inner.serial_version);
+        let mut w = h.request_writer();
         let timeout = h.get_timeout(&self.timeout);
         self.serialize_internal(&mut w, &timeout);
         // TODO: namespace in http header?
@@ -208,6 +299,206 @@ impl MultiDeleteRequest {
         Ok(resp)
     }
 
+    // Delete the rows matching a condition: query their primary keys one batch
+    // at a time, and delete the rows of each batch grouped by shard key.
+    async fn execute_where(&self, h: &Handle) -> Result<MultiDeleteResult, NoSQLError> {
+        let table = GetTableRequest::new(&self.table_name)
+            .compartment_id(&self.compartment_id)
+            .execute(h)
+            .await?;
+        let key_fields = table.primary_key_fields();
+        let shard_fields = table.shard_key_fields();
+        if key_fields.is_empty() {
+            return ia_err!(
+                "could not get the primary key of table '{}'",
+                self.table_name
+            );
+        }
+        let (stmt, values) = self.key_statement(&h.table_name(&self.table_name), &key_fields)?;
+        let (mut qreq, mut consumed) =
+            prepare_statement(h, &stmt, &values, &self.timeout, &self.compartment_id, None).await?;
+        let mut num_deleted: i32 = 0;
+        let mut rows: Vec<MapValue> = Vec::new();
+        while !qreq.is_done() {
+            qreq.execute_batch(h, &mut rows).await?;
+            num_deleted += self
+                .delete_keys(h, rows.drain(..), &shard_fields, &mut consumed)
+                .await?;
+        }
+        consumed.add(&qreq.consumed_capacity);
+        Ok(MultiDeleteResult {
+            num_deleted,
+            continuation_key: None,
+            consumed: Some(consumed),
+        })
+    }
+
+    // Delete the rows with the given primary keys. Rows with the same shard key
+    // are deleted in one request. Returns the number of rows deleted.
+    async fn delete_keys(
+        &self,
+        h: &Handle,
+        rows: impl Iterator<Item = MapValue>,
+        shard_fields: &[String],
+        consumed: &mut Capacity,
+    ) -> Result<i32, NoSQLError> {
+        let mut shards: BTreeMap<Vec<FieldValue>, Vec<MapValue>> = BTreeMap::new();
+        for row in rows {
+            let mut shard_key = Vec::with_capacity(shard_fields.len());
+            for f in shard_fields {
+                match row.get_field_value(f) {
+                    Some(v) => shard_key.push(v.clone_internal()),
+                    None => {
+                        return ia_err!("query result is missing primary key field '{}'", f);
+                    }
+                }
+            }
+            shards.entry(shard_key).or_default().push(row);
+        }
+        let mut num_deleted: i32 = 0;
+        for keys in shards.into_values() {
+            let mut keys = keys.into_iter().peekable();
+            while keys.peek().is_some() {
+                let mut wm = WriteMultipleRequest::new(&self.table_name)
+                    .compartment_id(&self.compartment_id);
+                if let Some(t) = &self.timeout {
+                    wm = wm.timeout(t);
+                }
+                if let Some(d) = self.durability {
+                    wm = wm.durability(d);
+                }
+                for key in keys.by_ref().take(MAX_DELETE_BATCH) {
+                    wm = wm.add(Box::new(DeleteRequest::new("", key)));
+                }
+                let res = wm.execute(h).await?;
+                num_deleted += res.results().iter().filter(|r| r.success()).count() as i32;
+                if let Some(c) = res.consumed() {
+                    consumed.add(c);
+                }
+            }
+        }
+        Ok(num_deleted)
+    }
+
+    // Get the query for the primary keys of the rows matching the partial key,
+    // field range and condition.
+    fn key_statement(
+        &self,
+        table_name: &str,
+        key_fields: &[String],
+    ) -> Result<(String, Vec<&FieldValue>), NoSQLError> {
+        validate_path("table name", table_name, ":.")?;
+        let mut fields = Vec::with_capacity(key_fields.len());
+        for k in key_fields {
+            validate_path("key field", k, "")?;
+            fields.push(format!("t.{}", k));
+        }
+        let (clauses, values) = self.where_clauses()?;
+        Ok((
+            format!(
+                "SELECT {} FROM {} t WHERE {}",
+                fields.join(", "),
+                table_name,
+                clauses.join(" AND ")
+            ),
+            values,
+        ))
+    }
+
+    /// Count the rows that this request would delete, without deleting them.
+    ///
+    /// This runs a `SELECT count(*)` query on the table, restricted to the rows matching the
+    /// same partial key, [`FieldRange`] and [`Condition`] as the delete, and returns the number of rows it
+    /// finds. It can be used to check how many rows a large delete would affect before
+    /// running it. The continuation key and `max_write_kb` are not used; all matching rows
+    /// are counted. Rows may be inserted or deleted between the count and the delete.
+    ///
+    /// The count is a query, so it consumes read units for all matching rows.
+    ///
+    /// ```no_run
+    /// # use oracle_nosql_rust_sdk::{Handle, MultiDeleteRequest};
+    /// # use oracle_nosql_rust_sdk::types::*;
+    /// # #[tokio::main]
+    /// # pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let handle = Handle::builder().build().await?;
+    /// let req = MultiDeleteRequest::new("events", &FieldValue::Map(MapValue::new().str("tenant", "acme")));
+    /// let n = req.count_only(&handle).await?;
+    /// if n < 10000 {
+    ///     req.execute(&handle).await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn count_only(&self, h: &Handle) -> Result<i64, NoSQLError> {
+        let (stmt, values) = self.count_statement_for_table(&h.table_name(&self.table_name))?;
+        let (rows, _) =
+            execute_statement(h, &stmt, &values, &self.timeout, &self.compartment_id, None).await?;
+        Ok(rows.first().map_or(0, |r| rows_affected(r, "numRows")))
+    }
+
+    // Get the SQL statement used by count_only().
+    #[cfg(test)]
+    pub(crate) fn count_statement(&self) -> Result<String, NoSQLError> {
+        Ok(self.count_statement_for_table(&self.table_name)?.0)
+    }
+
+    // Get the query counting the rows matched by the partial key and field range,
+    // and its positional bind variable values.
+    fn count_statement_for_table(
+        &self,
+        table_name: &str,
+    ) -> Result<(String, Vec<&FieldValue>), NoSQLError> {
+        validate_path("table name", table_name, ":.")?;
+        let (clauses, values) = self.where_clauses()?;
+        let stmt = format!(
+            "SELECT count(*) AS numRows FROM {} t WHERE {}",
+            table_name,
+            clauses.join(" AND ")
+        );
+        Ok((stmt, values))
+    }
+
+    // Get the WHERE clauses for the rows matched by the partial key, field range
+    // and condition, and their positional bind variable values.
+    fn where_clauses(&self) -> Result<(Vec<String>, Vec<&FieldValue>), NoSQLError> {
+        let key = match &self.key {
+            FieldValue::Map(m) => m,
+            _ => {
+                return ia_err!("MultiDeleteRequest: partial key must be a map");
+            }
+        };
+        let mut clauses = match &self.condition {
+            Some(c) if c.is_empty() => {
+                return ia_err!("MultiDeleteRequest: condition must not be empty");
+            }
+            // a condition may be used on the whole table
+            Some(_) if key.len() == 0 => Vec::new(),
+            _ => key_clauses(key)?,
+        };
+        let mut values: Vec<&FieldValue> = key.iter().map(|(_, v)| v).collect();
+        if let Some(range) = &self.field_range {
+            validate_path("field range", &range.field_path, "")?;
+            if range.start.is_none() && range.end.is_none() {
+                return ia_err!("FieldRange must have a start or end value");
+            }
+            if let Some(start) = &range.start {
+                let op = if range.start_inclusive { ">=" } else { ">" };
+                clauses.push(format!("t.{} {} ?", range.field_path, op));
+                values.push(start);
+            }
+            if let Some(end) = &range.end {
+                let op = if range.end_inclusive { "<=" } else { "<" };
+                clauses.push(format!("t.{} {} ?", range.field_path, op));
+                values.push(end);
+            }
+        }
+        if let Some(c) = &self.condition {
+            c.add_clauses(&mut clauses)?;
+            values.extend(c.values());
+        }
+        Ok((clauses, values))
+    }
+
     fn serialize_internal(&self, w: &mut Writer, timeout: &Duration) {
         let mut ns = NsonSerializer::start_request(w);
         ns.start_header();
@@ -215,7 +506,7 @@ impl MultiDeleteRequest {
         ns.end_header();
 
         ns.start_payload();
-        //ns.write_i32_field(DURABILITY, 0); // TODO
+        ns.write_durability(&self.durability);
 
         ns.write_i32_field(MAX_WRITE_KB, self.max_write_kb);
 
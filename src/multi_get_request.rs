@@ -0,0 +1,240 @@
+//
+// Copyright (c) 2024, 2025 Oracle and/or its affiliates. All rights reserved.
+//
+// Licensed under the Universal Permissive License v 1.0 as shown at
+//  https://oss.oracle.com/licenses/upl/
+//
+use crate::condition::validate_path;
+use crate::error::ia_err;
+use crate::error::NoSQLError;
+use crate::handle::Handle;
+use crate::query_request::QueryRequest;
+use crate::request_options::RequestOptions;
+use crate::types::{Capacity, Consistency, FieldValue, MapValue, NoSQLColumnToFieldValue};
+use std::collections::{HashMap, HashSet};
+use std::result::Result;
+use std::time::Duration;
+
+/// Struct used for getting many rows of a table by primary key, in a single query.
+///
+/// This is for tables with a single-column primary key. Given a list of key values,
+/// a `MultiGetRequest` reads all matching rows with a generated query of the form
+/// `SELECT * FROM <table> t WHERE t.<key> IN $keys[]`, with the keys bound as an array
+/// variable. This takes two round trips (prepare and execute) regardless of the number
+/// of keys, instead of one [`GetRequest`](crate::GetRequest) per key. If the handle has a
+/// prepare cache (see [`HandleBuilder::prepare_cache_size()`](crate::HandleBuilder::prepare_cache_size())),
+/// the query is only prepared once per table, so later requests take a single round trip.
+///
+/// ```no_run
+/// # use oracle_nosql_rust_sdk::{Handle, MultiGetRequest};
+/// # #[tokio::main]
+/// # pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let handle = Handle::builder().build().await?;
+/// let res = MultiGetRequest::new("users", "id")
+///     .keys(&[10, 20, 30])?
+///     .execute(&handle)
+///     .await?;
+/// for (id, row) in res.ordered_rows() {
+///     match row {
+///         Some(r) => println!("{:?}: {:?}", id, r),
+///         None => println!("{:?}: not found", id),
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// As with other queries, the rows are read with the given [`Consistency`], but the read
+/// is not atomic across rows.
+#[derive(Default, Debug)]
+pub struct MultiGetRequest {
+    pub(crate) table_name: String,
+    pub(crate) key_field: String,
+    pub(crate) compartment_id: String,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) consistency: Option<Consistency>,
+    pub(crate) keys: Vec<FieldValue>,
+}
+
+/// Struct representing the result of a [`MultiGetRequest`] execution.
+#[derive(Default, Debug)]
+pub struct MultiGetResult {
+    pub(crate) keys: Vec<FieldValue>,
+    pub(crate) rows: HashMap<FieldValue, MapValue>,
+    pub(crate) consumed: Option<Capacity>,
+}
+
+impl MultiGetRequest {
+    /// Create a new `MultiGetRequest`.
+    ///
+    /// `table_name` is required and must be non-empty. `key_field` is the name of the
+    /// single primary key column of the table.
+    pub fn new(table_name: &str, key_field: &str) -> MultiGetRequest {
+        MultiGetRequest {
+            table_name: table_name.to_string(),
+            key_field: key_field.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Specify the primary key values of the rows to get.
+    ///
+    /// Duplicate values are only read once. Returns an `IllegalArgument` error if any value
+    /// cannot be converted to a [`FieldValue`], or is not an atomic value.
+    pub fn keys(
+        mut self,
+        keys: &[impl NoSQLColumnToFieldValue],
+    ) -> Result<MultiGetRequest, NoSQLError> {
+        let mut values = Vec::with_capacity(keys.len());
+        for k in keys {
+            let v = k.try_to_field_value()?;
+            match v {
+                FieldValue::Map(_)
+                | FieldValue::Array(_)
+                | FieldValue::Null
+                | FieldValue::JsonNull
+                | FieldValue::Empty
+                | FieldValue::Uninitialized => {
+                    return ia_err!("MultiGetRequest: invalid primary key value {:?}", v);
+                }
+                _ => values.push(v),
+            }
+        }
+        self.keys = values;
+        Ok(self)
+    }
+
+    /// Specify the timeout value for the request.
+    ///
+    /// This is optional.
+    /// If set, it must be greater than or equal to 1 millisecond, otherwise an
+    /// IllegalArgument error will be returned.
+    /// If not set, the default timeout value configured for the [`Handle`](crate::HandleBuilder::timeout()) is used.
+    /// The timeout applies to each of the two round trips separately.
+    pub fn timeout(mut self, t: &Duration) -> Self {
+        self.timeout = Some(*t);
+        self
+    }
+
+    /// Cloud Service only: set the name or id of a compartment to be used for this operation.
+    ///
+    /// See [`GetRequest::compartment_id()`](crate::GetRequest::compartment_id()).
+    pub fn compartment_id(mut self, compartment_id: &str) -> Self {
+        self.compartment_id = compartment_id.to_string();
+        self
+    }
+
+    /// Specify the desired [`Consistency`] for the operation.
+    pub fn consistency(mut self, c: Consistency) -> Self {
+        self.consistency = Some(c);
+        self
+    }
+
+    /// Apply [`RequestOptions`] to this request, using their timeout, consistency and compartment.
+    pub fn options(mut self, options: &RequestOptions) -> Self {
+        options.apply_timeout(&mut self.timeout);
+        options.apply_consistency(&mut self.consistency);
+        options.apply_compartment_id(&mut self.compartment_id);
+        self
+    }
+
+    /// Execute the request, returning a [`MultiGetResult`].
+    ///
+    /// If no keys were given, no request is sent and the result is empty.
+    pub async fn execute(&self, h: &Handle) -> Result<MultiGetResult, NoSQLError> {
+        let stmt = self.statement(&h.table_name(&self.table_name))?;
+        let mut res = MultiGetResult {
+            keys: self.keys.iter().map(FieldValue::clone_internal).collect(),
+            ..Default::default()
+        };
+        // only send each key once
+        let mut seen: HashSet<&FieldValue> = HashSet::new();
+        let keys: Vec<FieldValue> = self
+            .keys
+            .iter()
+            .filter(|k| seen.insert(*k))
+            .map(FieldValue::clone_internal)
+            .collect();
+        if keys.is_empty() {
+            return Ok(res);
+        }
+        let timeout = h.get_timeout(&self.timeout);
+        let mut qreq = QueryRequest::new(&stmt)
+            .timeout(&timeout)
+            .compartment_id(&self.compartment_id);
+        if let Some(c) = self.consistency {
+            qreq = qreq.consistency(&c);
+        }
+        let mut consumed = qreq.prepare(h).await?;
+        qreq.set_variable("$keys", &FieldValue::Array(keys))?;
+        let mut qres = qreq.execute(h).await?;
+        consumed.add(&qres.consumed());
+        res.consumed = Some(consumed);
+        for row in qres.take_rows() {
+            match row.get_field_value(&self.key_field) {
+                Some(k) => {
+                    res.rows.insert(k.clone_internal(), row);
+                }
+                None => {
+                    return ia_err!(
+                        "MultiGetRequest: row is missing primary key field '{}'",
+                        self.key_field
+                    );
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    // Get the query used to read the rows.
+    fn statement(&self, table_name: &str) -> Result<String, NoSQLError> {
+        validate_path("table name", table_name, ":.")?;
+        validate_path("key field", &self.key_field, "")?;
+        Ok(format!(
+            "DECLARE $keys ARRAY(ANYATOMIC); SELECT * FROM {} t WHERE t.{} IN $keys[]",
+            table_name, self.key_field
+        ))
+    }
+}
+
+impl MultiGetResult {
+    /// Get the row with the given primary key value, if it was found.
+    pub fn get(&self, key: &FieldValue) -> Option<&MapValue> {
+        self.rows.get(key)
+    }
+
+    /// Get the rows that were found, keyed by their primary key value.
+    pub fn rows(&self) -> &HashMap<FieldValue, MapValue> {
+        &self.rows
+    }
+
+    /// Take the rows that were found, keyed by their primary key value, leaving the
+    /// result empty.
+    pub fn take_rows(&mut self) -> HashMap<FieldValue, MapValue> {
+        std::mem::take(&mut self.rows)
+    }
+
+    /// Get each requested key with its row, in the order the keys were given.
+    ///
+    /// The row is `None` if no row exists for the key. Duplicate keys appear as often
+    /// as they were given.
+    pub fn ordered_rows(&self) -> Vec<(&FieldValue, Option<&MapValue>)> {
+        self.keys.iter().map(|k| (k, self.rows.get(k))).collect()
+    }
+
+    /// Get the number of rows found.
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Return `true` if no rows were found.
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Get the consumed capacity (read/write units) of the operation, including preparing
+    /// the query. This is only valid in the NoSQL Cloud Service.
+    pub fn consumed(&self) -> Option<&Capacity> {
+        self.consumed.as_ref()
+    }
+}
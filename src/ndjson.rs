@@ -0,0 +1,241 @@
+//
+// Copyright (c) 2024, 2025 Oracle and/or its affiliates. All rights reserved.
+//
+// Licensed under the Universal Permissive License v 1.0 as shown at
+//  https://oss.oracle.com/licenses/upl/
+//
+use crate::condition::validate_path;
+use crate::error::ia_err;
+use crate::error::NoSQLError;
+use crate::handle::Handle;
+use crate::put_request::PutRequest;
+use crate::query_request::QueryRequest;
+use crate::table_request::GetTableRequest;
+use crate::types::{FieldValue, MapValue};
+use crate::write_multiple_request::WriteMultipleRequest;
+use crate::writer::Writer;
+use std::collections::HashMap;
+use std::result::Result;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+use tracing::trace;
+
+// Maximum number of operations in a single WriteMultipleRequest.
+const MAX_BATCH_OPS: usize = 50;
+
+// Maximum number of rows buffered by import_table() before all pending
+// batches are written, regardless of their size.
+const MAX_BUFFERED_ROWS: usize = 500;
+
+impl Handle {
+    /// Export all rows of a table as newline-delimited JSON (NDJSON).
+    ///
+    /// This runs a `SELECT *` query on the table, and writes each row as one line of compact
+    /// JSON to the given writer as each batch of results is returned. Only a single batch of
+    /// rows is held in memory at a time, so this can be used to back up or migrate tables of
+    /// any size. Rows are written in no particular order.
+    ///
+    /// Timestamps are written as RFC3339 strings, binary values as base64 strings, and SQL
+    /// `NULL` values as JSON `null` (see [`MapValue::to_json_string()`]). `progress` is called
+    /// with the total number of rows written so far after each batch. Returns the total number
+    /// of rows written.
+    ///
+    /// ```no_run
+    /// # use oracle_nosql_rust_sdk::Handle;
+    /// # #[tokio::main]
+    /// # pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let handle = Handle::builder().build().await?;
+    /// // any tokio::io::AsyncWrite can be used, such as a tokio::fs::File
+    /// let mut out: Vec<u8> = Vec::new();
+    /// let rows = handle
+    ///     .export_table("users", &mut out, |n| println!("exported {} rows", n))
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn export_table<W>(
+        &self,
+        table_name: &str,
+        writer: &mut W,
+        mut progress: impl FnMut(u64),
+    ) -> Result<u64, NoSQLError>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let table_name = self.table_name(table_name);
+        validate_path("table name", &table_name, ".:")?;
+        let mut qreq = QueryRequest::new(&format!("SELECT * FROM {}", table_name));
+        let mut total: u64 = 0;
+        let mut buf: Vec<u8> = Vec::new();
+        while !qreq.is_done() {
+            let mut rows: Vec<MapValue> = Vec::new();
+            qreq.execute_batch(self, &mut rows).await?;
+            if rows.is_empty() {
+                continue;
+            }
+            buf.clear();
+            for row in &rows {
+                buf.extend_from_slice(row.to_json_string().as_bytes());
+                buf.push(b'\n');
+            }
+            if let Err(e) = writer.write_all(&buf).await {
+                return ia_err!("error writing exported rows: {}", e);
+            }
+            total += rows.len() as u64;
+            trace!("export_table: wrote {} rows", total);
+            progress(total);
+        }
+        if let Err(e) = writer.flush().await {
+            return ia_err!("error writing exported rows: {}", e);
+        }
+        Ok(total)
+    }
+
+    /// Import rows from newline-delimited JSON (NDJSON) into a table.
+    ///
+    /// Each non-empty line of the input must be a JSON object, which is written to the table
+    /// as one row, replacing any existing row with the same primary key. This is the
+    /// counterpart of [`export_table()`](Handle::export_table()).
+    ///
+    /// Rows are grouped by shard key and written with [`WriteMultipleRequest`]s of up to 50 rows,
+    /// and at most a few hundred rows are held in memory at a time. A top-level JSON `null`
+    /// is written as SQL `NULL`. `progress` is called with the total number of rows written so
+    /// far after each write. Returns the total number of rows written.
+    ///
+    /// Writes are not atomic across batches: if an error is returned, some of the rows before
+    /// the failing line may already have been written.
+    pub async fn import_table<R>(
+        &self,
+        table_name: &str,
+        reader: R,
+        mut progress: impl FnMut(u64),
+    ) -> Result<u64, NoSQLError>
+    where
+        R: AsyncBufRead + Unpin,
+    {
+        let shard_key = GetTableRequest::new(table_name)
+            .execute(self)
+            .await?
+            .shard_key_fields();
+        let mut batches = ImportBatches {
+            table_name,
+            shard_key,
+            pending: HashMap::new(),
+            buffered: 0,
+            total: 0,
+        };
+        let mut lines = reader.lines();
+        let mut line_num: usize = 0;
+        loop {
+            let line = match lines.next_line().await {
+                Ok(Some(l)) => l,
+                Ok(None) => break,
+                Err(e) => {
+                    return ia_err!("error reading line {}: {}", line_num + 1, e);
+                }
+            };
+            line_num += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let json: serde_json::Value = match serde_json::from_str(&line) {
+                Ok(v) => v,
+                Err(e) => {
+                    return ia_err!("invalid JSON on line {}: {}", line_num, e);
+                }
+            };
+            if !json.is_object() {
+                return ia_err!("line {} is not a JSON object", line_num);
+            }
+            let written = batches.total;
+            batches
+                .add(self, MapValue::from_json_object(&json)?)
+                .await?;
+            if batches.total > written {
+                progress(batches.total);
+            }
+        }
+        let written = batches.total;
+        batches.flush_all(self).await?;
+        if batches.total > written {
+            progress(batches.total);
+        }
+        Ok(batches.total)
+    }
+}
+
+// Rows read by import_table() that have not been written yet, grouped by the
+// serialized values of their shard key fields.
+struct ImportBatches<'a> {
+    table_name: &'a str,
+    shard_key: Vec<String>,
+    pending: HashMap<Vec<u8>, Vec<MapValue>>,
+    buffered: usize,
+    total: u64,
+}
+
+impl ImportBatches<'_> {
+    async fn add(&mut self, h: &Handle, mut row: MapValue) -> Result<(), NoSQLError> {
+        let nulls: Vec<String> = row
+            .iter()
+            .filter(|(_, v)| matches!(v, FieldValue::JsonNull))
+            .map(|(k, _)| k.clone())
+            .collect();
+        for k in nulls {
+            row.put(&k, FieldValue::Null);
+        }
+        let mut w = Writer::new();
+        for f in &self.shard_key {
+            match row.get_field_value(f) {
+                Some(v) => w.write_field_value(v),
+                None => {
+                    return ia_err!("row is missing shard key field '{}'", f);
+                }
+            }
+        }
+        let key = w.buf;
+        let batch = self.pending.entry(key.clone()).or_default();
+        batch.push(row);
+        self.buffered += 1;
+        if batch.len() >= MAX_BATCH_OPS {
+            let rows = self.pending.remove(&key).unwrap_or_default();
+            self.write(h, rows).await?;
+        } else if self.buffered >= MAX_BUFFERED_ROWS {
+            self.flush_all(h).await?;
+        }
+        Ok(())
+    }
+
+    async fn flush_all(&mut self, h: &Handle) -> Result<(), NoSQLError> {
+        let pending = std::mem::take(&mut self.pending);
+        for (_, rows) in pending {
+            self.write(h, rows).await?;
+        }
+        Ok(())
+    }
+
+    async fn write(&mut self, h: &Handle, mut rows: Vec<MapValue>) -> Result<(), NoSQLError> {
+        let n = rows.len();
+        if n == 1 {
+            PutRequest::new(self.table_name)
+                .value(rows.remove(0))
+                .execute(h)
+                .await?;
+        } else {
+            let mut req = WriteMultipleRequest::new(self.table_name);
+            for row in rows {
+                req = req.add(Box::new(PutRequest::new("").value(row)));
+            }
+            let res = req.execute(h).await?;
+            if res.failed_operation_index() >= 0 {
+                return ia_err!(
+                    "import failed writing row {} of a batch",
+                    res.failed_operation_index()
+                );
+            }
+        }
+        self.buffered -= n;
+        self.total += n as u64;
+        trace!("import_table: wrote {} rows", self.total);
+        Ok(())
+    }
+}
@@ -12,11 +12,15 @@ use std::time::Duration;
 
 use crate::error::NoSQLError;
 use crate::error::NoSQLErrorCode::BadProtocolMessage;
+use crate::error::NoSQLErrorCode::ReadLimitExceeded;
 use crate::error::NoSQLErrorCode::UnknownError;
+use crate::error::NoSQLErrorCode::WriteLimitExceeded;
+use crate::handle::prefixed_table_name;
 use crate::reader::Reader;
 use crate::types::Capacity;
 use crate::types::CapacityMode;
 use crate::types::Consistency;
+use crate::types::Durability;
 use crate::types::FieldType;
 use crate::types::FieldValue;
 use crate::types::MapValue;
@@ -27,6 +31,15 @@ use crate::writer::Writer;
 
 pub const V4_VERSION: i32 = 4;
 
+// Serial version written at the start of every request. Any change to how
+// requests or results are encoded must be checked against the fixtures in
+// tests/fixtures/nson (see nson_tests.rs).
+pub(crate) const SERIAL_VERSION: i16 = 4;
+
+// Query version sent with queries. QUERY_V4 adds virtual scans, which the SDK
+// does not support.
+pub(crate) const QUERY_V3: i16 = 3;
+
 // static field names
 pub const ABORT_ON_FAIL: &str = "a";
 pub const BIND_VARIABLES: &str = "bv";
@@ -104,11 +117,14 @@ pub const REACHED_LIMIT: &str = "re";
 pub const READ_KB: &str = "rk";
 pub const READ_THROTTLE_COUNT: &str = "rt";
 pub const READ_UNITS: &str = "ru";
+pub const REGION: &str = "rn";
+pub const REPLICAS: &str = "rc";
 pub const RETRY_HINT: &str = "rh";
 pub const RETURN_INFO: &str = "ri";
 pub const RETURN_ROW: &str = "rr";
 pub const ROW: &str = "r";
 pub const ROW_VERSION: &str = "rv";
+pub const SERVER_MEMORY_CONSUMPTION: &str = "sm";
 pub const SHARD_ID: &str = "si";
 pub const SHARD_IDS: &str = "sa";
 pub const SORT_PHASE1_RESULTS: &str = "p1";
@@ -153,6 +169,10 @@ pub trait NsonRequest {
 }
 
 pub trait NsonSubRequest: std::fmt::Debug + Send + Sync {
+    // Check that the request can be used as a sub request.
+    fn validate(&self) -> Result<(), NoSQLError> {
+        Ok(())
+    }
     fn serialize(&self, w: &mut Writer, timeout: &Duration);
 }
 
@@ -282,6 +302,13 @@ impl<'a> NsonSerializer<'a> {
         self.end_field(key);
     }
 
+    pub fn write_i64_field(&mut self, key: &str, val: i64) {
+        self.start_field(key);
+        self.writer.write_field_type(FieldType::Long);
+        self.writer.write_packed_i64(val);
+        self.end_field(key);
+    }
+
     pub fn write_nonzero_i32_field(&mut self, key: &str, val: i32) {
         if val != 0 {
             self.write_i32_field(key, val);
@@ -347,10 +374,23 @@ impl<'a> NsonSerializer<'a> {
         self.end_map(CONSISTENCY);
     }
 
+    // Durability is only written if it was set, so the server default is used otherwise.
+    pub(crate) fn write_durability(&mut self, d: &Option<Durability>) {
+        if let Some(d) = d {
+            self.write_i32_field(DURABILITY, d.encode());
+        }
+    }
+
+    // Write the table name of a request, with the handle's table prefix applied.
+    pub(crate) fn write_table_name_field(&mut self, table_name: &str) {
+        let name = prefixed_table_name(&self.writer.table_prefix, table_name);
+        self.write_string_field(TABLE_NAME, &name);
+    }
+
     pub(crate) fn write_header(&mut self, op_code: OpCode, timeout: &Duration, table_name: &str) {
         self.write_i32_field(VERSION, V4_VERSION);
         if table_name != "" {
-            self.write_string_field(TABLE_NAME, table_name);
+            self.write_table_name_field(table_name);
         }
         self.write_i32_field(OP_CODE, op_code as i32);
         self.write_i32_field(TIMEOUT, timeout.as_millis() as i32);
@@ -611,16 +651,22 @@ impl<'a> MapWalker<'a> {
             return Ok(());
         }
         // read to end of walker
+        let mut msg: Option<String> = None;
+        let mut retry_hint: Option<i64> = None;
         while self.has_next() {
             self.next()?;
             let name = self.current_name();
             match name.as_str() {
                 EXCEPTION => {
                     //println!("   error: EXCEPTION");
-                    let msg = self.read_nson_string()?;
-                    let err = NoSQLError::from_int(i, &msg);
-                    //println!("Got error: {}", err);
-                    return Err(err);
+                    msg = Some(self.read_nson_string()?);
+                }
+                RETRY_HINT => {
+                    retry_hint = match self.r.read_field_value()? {
+                        FieldValue::Integer(ms) => Some(ms as i64),
+                        FieldValue::Long(ms) => Some(ms),
+                        _ => None,
+                    };
                 }
                 _ => {
                     //println!("   error: skipping field '{}'", name);
@@ -628,7 +674,18 @@ impl<'a> MapWalker<'a> {
                 }
             }
         }
-        let err = NoSQLError::new(UnknownError, "Unknown error");
+        let mut err = match msg {
+            Some(msg) => NoSQLError::from_int(i, &msg),
+            None => NoSQLError::new(UnknownError, "Unknown error"),
+        };
+        // The retry hint is the delay, in milliseconds, the service asks for before
+        // a throttled request is sent again. Throttled requests were not processed,
+        // so it is only kept for those.
+        if let Some(ms) = retry_hint.filter(|ms| *ms > 0) {
+            if err.code == ReadLimitExceeded || err.code == WriteLimitExceeded {
+                err.retry_after = Some(Duration::from_millis(ms as u64));
+            }
+        }
         //println!("Got error: {}", err);
         Err(err)
     }
@@ -0,0 +1,376 @@
+//
+// Copyright (c) 2024, 2025 Oracle and/or its affiliates. All rights reserved.
+//
+// Licensed under the Universal Permissive License v 1.0 as shown at
+//  https://oss.oracle.com/licenses/upl/
+//
+
+// Golden-file tests for the NSON wire format. Representative requests,
+// results and field values are encoded and compared byte for byte against
+// the fixtures in tests/fixtures/nson, so that changes to the encoding (packed
+// integers, field names or field order) are caught before they break interop
+// with the server.
+//
+// If an encoding change is intended, regenerate the fixtures with
+//   NSON_UPDATE_FIXTURES=1 cargo test --lib nson_tests
+// and review the fixture diffs.
+
+use crate::error::NoSQLErrorCode;
+use crate::handle::Handle;
+use crate::mock_server::MockServer;
+use crate::nson::*;
+use crate::reader::Reader;
+use crate::system_request::SystemStatusRequest;
+use crate::types::{Consistency, FieldValue, MapValue, OpCode, TableLimits, TableState};
+use crate::writer::Writer;
+use crate::{
+    DeleteRequest, FieldRange, GetIndexesRequest, GetRequest, GetTableRequest, ListTablesRequest,
+    MultiDeleteRequest, PutRequest, QueryRequest, SystemRequest, TableRequest, TableUsageRequest,
+    WriteMultipleRequest,
+};
+use bigdecimal::BigDecimal;
+use chrono::DateTime;
+use std::error::Error;
+use std::result::Result;
+use std::str::FromStr;
+
+const FIXTURE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/nson");
+
+fn to_hex(bytes: &[u8]) -> String {
+    let mut s = String::new();
+    for line in bytes.chunks(16) {
+        let hex: Vec<String> = line.iter().map(|b| format!("{:02x}", b)).collect();
+        s.push_str(&hex.join(" "));
+        s.push('\n');
+    }
+    s
+}
+
+// Compare encoded bytes against a fixture file, or rewrite the fixture if
+// NSON_UPDATE_FIXTURES is set.
+fn check_fixture(name: &str, bytes: &[u8]) {
+    let path = format!("{}/{}.hex", FIXTURE_DIR, name);
+    let hex = to_hex(bytes);
+    if std::env::var_os("NSON_UPDATE_FIXTURES").is_some() {
+        std::fs::create_dir_all(FIXTURE_DIR).unwrap();
+        std::fs::write(&path, hex).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).unwrap_or_else(|e| {
+        panic!(
+            "cannot read fixture {}: {} (set NSON_UPDATE_FIXTURES=1 to create it)",
+            path, e
+        )
+    });
+    assert_eq!(
+        hex, expected,
+        "encoding of '{}' does not match fixture {}",
+        name, path
+    );
+}
+
+fn row() -> MapValue {
+    MapValue::new().i32("id", 10).str("name", "jane").column(
+        "tags",
+        FieldValue::Array(vec![FieldValue::String("a".to_string())]),
+    )
+}
+
+#[test]
+fn test_nson_field_types() -> Result<(), Box<dyn Error>> {
+    let ts = DateTime::parse_from_rfc3339("2024-01-02T03:04:05.123Z")?;
+    let values = vec![
+        (
+            "field_array",
+            FieldValue::Array(vec![FieldValue::Integer(1), FieldValue::Integer(2)]),
+        ),
+        ("field_binary", FieldValue::Binary(vec![0, 1, 254, 255])),
+        ("field_boolean", FieldValue::Boolean(true)),
+        ("field_double", FieldValue::Double(-1.5e10)),
+        ("field_integer", FieldValue::Integer(-1234567)),
+        ("field_long", FieldValue::Long(i64::MAX)),
+        ("field_map", FieldValue::Map(row())),
+        ("field_string", FieldValue::String("héllo".to_string())),
+        ("field_timestamp", FieldValue::Timestamp(ts)),
+        (
+            "field_number",
+            FieldValue::Number(BigDecimal::from_str("12345678901234567890.5")?),
+        ),
+        ("field_json_null", FieldValue::JsonNull),
+        ("field_null", FieldValue::Null),
+        ("field_empty", FieldValue::Empty),
+    ];
+    for (name, v) in values {
+        let mut w = Writer::new();
+        w.write_field_value(&v);
+        check_fixture(name, &w.buf);
+        let mut r = Reader::new().from_bytes(&w.buf);
+        let read = r.read_field_value()?;
+        assert_eq!(read, v, "'{}' did not read back", name);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_nson_op_codes() {
+    // op codes are part of the protocol, and must never be renumbered
+    let codes = vec![
+        (OpCode::Delete, 0),
+        (OpCode::DeleteIfVersion, 1),
+        (OpCode::Get, 2),
+        (OpCode::Put, 3),
+        (OpCode::PutIfAbsent, 4),
+        (OpCode::PutIfPresent, 5),
+        (OpCode::PutIfVersion, 6),
+        (OpCode::Query, 7),
+        (OpCode::Prepare, 8),
+        (OpCode::WriteMultiple, 9),
+        (OpCode::MultiDelete, 10),
+        (OpCode::GetTable, 11),
+        (OpCode::GetIndexes, 12),
+        (OpCode::GetTableUsage, 13),
+        (OpCode::ListTables, 14),
+        (OpCode::TableRequest, 15),
+        (OpCode::Scan, 16),
+        (OpCode::IndexScan, 17),
+        (OpCode::CreateTable, 18),
+        (OpCode::AlterTable, 19),
+        (OpCode::DropTable, 20),
+        (OpCode::CreateIndex, 21),
+        (OpCode::DropIndex, 22),
+        (OpCode::SystemRequest, 23),
+        (OpCode::SystemStatusRequest, 24),
+        (OpCode::AddReplica, 33),
+        (OpCode::DropReplica, 34),
+    ];
+    for (op, code) in codes {
+        assert_eq!(op as i32, code, "wrong code for {:?}", op);
+    }
+}
+
+#[tokio::test]
+async fn test_nson_requests() -> Result<(), Box<dyn Error>> {
+    let names = [
+        "request_get",
+        "request_put",
+        "request_put_if_absent",
+        "request_put_if_present",
+        "request_put_if_version",
+        "request_delete",
+        "request_delete_if_version",
+        "request_multi_delete",
+        "request_write_multiple",
+        "request_prepare",
+        "request_query",
+        "request_get_table",
+        "request_get_indexes",
+        "request_table_usage",
+        "request_list_tables",
+        "request_table",
+        "request_add_replica",
+        "request_drop_replica",
+        "request_system",
+        "request_system_status",
+    ];
+    // queries need a prepared statement in the response
+    let prepared = || {
+        let topology = MapValue::new()
+            .i32(PROXY_TOPO_SEQNUM, 1)
+            .column(SHARD_IDS, FieldValue::Array(vec![FieldValue::Integer(1)]));
+        MapValue::new()
+            .column(PREPARED_QUERY, FieldValue::Binary(vec![1, 2, 3]))
+            .column(TOPOLOGY_INFO, FieldValue::Map(topology))
+    };
+    let server = MockServer::start(
+        names
+            .iter()
+            .map(|name| match *name {
+                "request_prepare" | "request_query" => MockServer::response(prepared()),
+                _ => MockServer::response(MapValue::new()),
+            })
+            .collect(),
+    )
+    .await;
+    let h: Handle = server.handle().await;
+    let version = vec![1, 2, 3, 4];
+
+    // each request is expected to fail or succeed on the empty response;
+    // only the request bodies matter here
+    let _ = GetRequest::new("users")
+        .key(MapValue::new().i32("id", 10))
+        .consistency(Consistency::Absolute)
+        .execute(&h)
+        .await;
+    let _ = PutRequest::new("users")
+        .value(row())
+        .ttl(&std::time::Duration::from_secs(3 * 86400))
+        .return_row(true)
+        .execute(&h)
+        .await;
+    let _ = PutRequest::new("users")
+        .value(row())
+        .if_absent()
+        .execute(&h)
+        .await;
+    let _ = PutRequest::new("users")
+        .value(row())
+        .if_present()
+        .execute(&h)
+        .await;
+    let _ = PutRequest::new("users")
+        .value(row())
+        .if_version(&version)
+        .execute(&h)
+        .await;
+    let _ = DeleteRequest::new("users", MapValue::new().i32("id", 10))
+        .execute(&h)
+        .await;
+    let _ = DeleteRequest::new("users", MapValue::new().i32("id", 10))
+        .if_version(&version)
+        .return_row(true)
+        .execute(&h)
+        .await;
+    let _ = MultiDeleteRequest::new("users", &FieldValue::Map(MapValue::new().i32("sid", 1)))
+        .field_range(FieldRange {
+            field_path: "id".to_string(),
+            start: Some(FieldValue::Integer(1)),
+            start_inclusive: true,
+            end: Some(FieldValue::Integer(100)),
+            end_inclusive: false,
+        })
+        .max_write_kb(1024)
+        .execute(&h)
+        .await;
+    let _ = WriteMultipleRequest::new("users")
+        .add(Box::new(PutRequest::new("users").value(row()).if_absent()))
+        .add(Box::new(
+            DeleteRequest::new("users", MapValue::new().i32("id", 11)).set_abort_on_fail(true),
+        ))
+        .execute(&h)
+        .await;
+    let _ = QueryRequest::new("select * from users where id > 5")
+        .prepare_only()
+        .execute(&h)
+        .await;
+    let _ = QueryRequest::new("select * from users where id > 5")
+        .consistency(&Consistency::Absolute)
+        .max_read_kb(64)
+        .execute(&h)
+        .await;
+    let _ = GetTableRequest::new("users")
+        .operation_id("op1")
+        .execute(&h)
+        .await;
+    let _ = GetIndexesRequest::new("users")
+        .index_name("idx_name")
+        .execute(&h)
+        .await;
+    let _ = TableUsageRequest::new("users")
+        .start_time(DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z")?)
+        .limit(10)
+        .execute(&h)
+        .await;
+    let _ = ListTablesRequest::new()
+        .namespace("ns1")
+        .limit(20)
+        .execute(&h)
+        .await;
+    let _ = TableRequest::new("users")
+        .statement("create table users(id integer, name string, primary key(id))")
+        .limits(&TableLimits::provisioned(10, 20, 5))
+        .execute(&h)
+        .await;
+    let _ = TableRequest::new("users")
+        .add_replica("us-ashburn-1")
+        .execute(&h)
+        .await;
+    let _ = TableRequest::new("users")
+        .drop_replica("us-ashburn-1")
+        .execute(&h)
+        .await;
+    let _ = SystemRequest::new("create namespace ns1").execute(&h).await;
+    let _ = SystemStatusRequest::new("op1").execute(&h).await;
+
+    let bodies = server.request_bodies();
+    assert_eq!(bodies.len(), names.len());
+    for (name, body) in names.iter().zip(bodies) {
+        let mut r = Reader::new().from_bytes(&body);
+        assert_eq!(r.read_i16()?, SERIAL_VERSION);
+        check_fixture(name, &body);
+    }
+    assert_eq!(h.serial_version(), SERIAL_VERSION);
+    assert_eq!(h.protocol_version(), V4_VERSION);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_nson_results() -> Result<(), Box<dyn Error>> {
+    let consumed = MapValue::new()
+        .i32(READ_UNITS, 2)
+        .i32(READ_KB, 1)
+        .i32(WRITE_KB, 0)
+        .i32(WRITE_UNITS, 0);
+    let get = MockServer::response(
+        MapValue::new()
+            .column(CONSUMED, FieldValue::Map(consumed.clone_internal()))
+            .column(
+                ROW,
+                FieldValue::Map(
+                    MapValue::new()
+                        .i64(MODIFIED, 1700000000000)
+                        .i64(EXPIRATION, 0)
+                        .column(ROW_VERSION, FieldValue::Binary(vec![1, 2, 3, 4]))
+                        .column(VALUE, FieldValue::Map(row())),
+                ),
+            ),
+    );
+    let topology = MapValue::new()
+        .i32(PROXY_TOPO_SEQNUM, 1)
+        .column(SHARD_IDS, FieldValue::Array(vec![FieldValue::Integer(1)]));
+    let query = MockServer::response(
+        MapValue::new()
+            .column(CONSUMED, FieldValue::Map(consumed))
+            .column(PREPARED_QUERY, FieldValue::Binary(vec![5, 6, 7]))
+            .column(TOPOLOGY_INFO, FieldValue::Map(topology))
+            .column(
+                QUERY_RESULTS,
+                FieldValue::Array(vec![FieldValue::Map(row())]),
+            ),
+    );
+    let table = MockServer::response(
+        MapValue::new()
+            .str(TABLE_NAME, "users")
+            .str(OPERATION_ID, "op1")
+            .i32(TABLE_STATE, TableState::Active as i32)
+            .str(ETAG, "etag1"),
+    );
+    let error = MockServer::error_response(NoSQLErrorCode::TableNotFound, "table not found");
+    check_fixture("result_get", &get);
+    check_fixture("result_query", &query);
+    check_fixture("result_table", &table);
+    check_fixture("result_error", &error);
+
+    let server = MockServer::start(vec![get, query, table, error]).await;
+    let h = server.handle().await;
+    let res = GetRequest::new("users")
+        .key(MapValue::new().i32("id", 10))
+        .execute(&h)
+        .await?;
+    assert_eq!(res.row().unwrap().get_i32("id"), Some(10));
+    assert_eq!(res.version(), Some(&vec![1, 2, 3, 4]));
+    assert_eq!(res.modification_time(), 1700000000000);
+    assert_eq!(res.consumed().unwrap().read_units, 2);
+
+    let mut qres = QueryRequest::new("select * from users").execute(&h).await?;
+    let rows = qres.take_rows();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].get_string("name"), Some("jane".to_string()));
+
+    let tres = GetTableRequest::new("users").execute(&h).await?;
+    assert_eq!(tres.state(), TableState::Active);
+    assert_eq!(tres.etag(), Some("etag1".to_string()));
+
+    let err = GetTableRequest::new("users").execute(&h).await.unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::TableNotFound);
+    Ok(())
+}
@@ -0,0 +1,125 @@
+//
+// Copyright (c) 2024, 2025 Oracle and/or its affiliates. All rights reserved.
+//
+// Licensed under the Universal Permissive License v 1.0 as shown at
+//  https://oss.oracle.com/licenses/upl/
+//
+use crate::error::NoSQLError;
+use crate::types::{Capacity, OpCode};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Callbacks for request lifecycle events, set with
+/// [`HandleBuilder::observer()`](crate::HandleBuilder::observer()).
+///
+/// An observer is notified of every request sent to the NoSQL service, so applications can
+/// record metrics or traces with any backend (StatsD, OpenTelemetry, etc) without the SDK
+/// depending on it. All methods have empty default implementations, so an observer only
+/// needs to implement the events it uses.
+///
+/// For each request, `on_request_start()` is called once, then `on_retry()` for each internal
+/// retry, and finally exactly one of `on_response()` or `on_error()`. Each batch of a query is
+/// a separate request.
+///
+/// Callbacks are called synchronously, on the task executing the request, and no locks of
+/// the SDK are held while they run. They delay the request by the time they take, so they
+/// should return quickly and must not block: an observer that does slow work, such as
+/// sending metrics over the network, should hand events off to a channel or background task.
+///
+/// ```no_run
+/// # use oracle_nosql_rust_sdk::{Handle, NoSQLError, RequestEvent, RequestObserver};
+/// # use std::sync::atomic::{AtomicU64, Ordering};
+/// #[derive(Default)]
+/// struct ErrorCounter {
+///     errors: AtomicU64,
+/// }
+///
+/// impl RequestObserver for ErrorCounter {
+///     fn on_error(&self, event: &RequestEvent, error: &NoSQLError) {
+///         self.errors.fetch_add(1, Ordering::Relaxed);
+///         eprintln!("{:?} on '{}' failed: {}", event.op_code(), event.table_name(), error);
+///     }
+/// }
+///
+/// # #[tokio::main]
+/// # pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let handle = Handle::builder()
+///     .from_environment()?
+///     .observer(Box::new(ErrorCounter::default()))?
+///     .build()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait RequestObserver: Send + Sync {
+    /// Called before a request is sent.
+    fn on_request_start(&self, _event: &RequestEvent) {}
+
+    /// Called when a request is retried internally, after the given error.
+    ///
+    /// `delay` is the time the request waits before it is resent, which is zero for
+    /// immediate retries.
+    fn on_retry(&self, _event: &RequestEvent, _error: &NoSQLError, _delay: Duration) {}
+
+    /// Called when a request succeeds, with the capacity consumed, if returned by the server.
+    fn on_response(&self, _event: &RequestEvent, _consumed: Option<&Capacity>) {}
+
+    /// Called when a request fails, after all retries.
+    fn on_error(&self, _event: &RequestEvent, _error: &NoSQLError) {}
+}
+
+/// Details of a request, given to each [`RequestObserver`] callback.
+#[derive(Clone, Debug)]
+pub struct RequestEvent {
+    pub(crate) op: Option<OpCode>,
+    pub(crate) table_name: String,
+    pub(crate) start: Instant,
+    pub(crate) elapsed: Duration,
+    pub(crate) retries: u16,
+    pub(crate) request_id: usize,
+}
+
+impl RequestEvent {
+    pub(crate) fn new(op: Option<OpCode>, table_name: String) -> RequestEvent {
+        RequestEvent {
+            op,
+            table_name,
+            start: Instant::now(),
+            elapsed: Duration::ZERO,
+            retries: 0,
+            request_id: 0,
+        }
+    }
+    /// Get the operation of the request, or `None` if it is not known.
+    pub fn op_code(&self) -> Option<OpCode> {
+        self.op
+    }
+    /// Get the table name of the request, or an empty string if the operation is not for
+    /// a single table (such as queries).
+    pub fn table_name(&self) -> &str {
+        &self.table_name
+    }
+    /// Get the time since the request started, including retries.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+    /// Get the number of internal retries so far.
+    pub fn retries(&self) -> u16 {
+        self.retries
+    }
+    /// Get the `x-nosql-request-id` header of the last attempt of the request, or zero
+    /// if it has not been sent yet.
+    pub fn request_id(&self) -> usize {
+        self.request_id
+    }
+}
+
+// The observer given to HandleBuilder, which must be Debug and Clone.
+#[derive(Clone)]
+pub(crate) struct ObserverRef(pub(crate) Arc<dyn RequestObserver>);
+
+impl std::fmt::Debug for ObserverRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("RequestObserver")
+    }
+}
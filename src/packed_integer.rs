@@ -254,7 +254,7 @@ fn increment_and_check(offset: &mut usize, len: usize) -> Result<(), NoSQLError>
 }
 
 // read a packed i32 from the given vector at the given offset.
-pub fn read_packed_i32(buf: &mut Vec<u8>, offset: &mut usize) -> Result<i32, NoSQLError> {
+pub fn read_packed_i32(buf: &[u8], offset: &mut usize) -> Result<i32, NoSQLError> {
     if buf.len() <= *offset {
         return Err(NoSQLError::new(
             BadProtocolMessage,
@@ -304,7 +304,7 @@ pub fn read_packed_i32(buf: &mut Vec<u8>, offset: &mut usize) -> Result<i32, NoS
 }
 
 // read a packed i64 from the given vector at the given offset.
-pub fn read_packed_i64(buf: &mut Vec<u8>, offset: &mut usize) -> Result<i64, NoSQLError> {
+pub fn read_packed_i64(buf: &[u8], offset: &mut usize) -> Result<i64, NoSQLError> {
     if buf.len() <= *offset {
         return Err(NoSQLError::new(
             BadProtocolMessage,
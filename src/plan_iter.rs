@@ -18,7 +18,7 @@ use crate::group_iter::GroupIter;
 use crate::handle::Handle;
 use crate::query_request::QueryRequest;
 use crate::reader::Reader;
-use crate::receive_iter::ReceiveIter;
+use crate::receive_iter::{DistributionKind, ReceiveIter};
 use crate::sfw_iter::SfwIter;
 use crate::size_iter::SizeIter;
 use crate::sort_iter::SortIter;
@@ -232,6 +232,17 @@ impl PlanIter {
             PlanIter::Size(ref r) => r.get_kind(),
         }
     }
+    // Get the distribution kind of the ReceiveIter at the bottom of the plan, if any.
+    pub(crate) fn distribution_kind(&self) -> Option<DistributionKind> {
+        match self {
+            PlanIter::Receive(ref r) => Some(r.distribution_kind()),
+            PlanIter::Sfw(ref r) => r.from_iter.distribution_kind(),
+            PlanIter::Sorting(ref r) => r.input_iter.distribution_kind(),
+            PlanIter::Group(ref r) => r.input_iter.distribution_kind(),
+            PlanIter::Collect(ref r) => r.input_iter.distribution_kind(),
+            _ => None,
+        }
+    }
     // this will move the result out of the iterator (owned by the caller)
     // This will return FieldValue::Uninitialized if nothing is available
     pub fn get_result(&self, req: &mut QueryRequest) -> FieldValue {
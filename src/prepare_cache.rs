@@ -0,0 +1,85 @@
+//
+// Copyright (c) 2024, 2025 Oracle and/or its affiliates. All rights reserved.
+//
+// Licensed under the Universal Permissive License v 1.0 as shown at
+//  https://oss.oracle.com/licenses/upl/
+//
+use crate::prepared_statement::PreparedStatement;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tracing::trace;
+
+// A handle-level cache of prepared statements, configured with
+// HandleBuilder::prepare_cache_size().
+//
+// Entries are keyed by compartment and SQL text. When the cache is full, the
+// least recently used entry is evicted. Caches are expected to be small (tens
+// or hundreds of statements), so eviction simply scans all entries.
+#[derive(Debug)]
+pub(crate) struct PrepareCache {
+    capacity: usize,
+    state: Mutex<CacheState>,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    // statement and the tick it was last used
+    entries: HashMap<String, (PreparedStatement, u64)>,
+    tick: u64,
+}
+
+impl PrepareCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        PrepareCache {
+            capacity,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    pub(crate) fn key(compartment_id: &str, statement: &str) -> String {
+        format!("{}\0{}", compartment_id, statement)
+    }
+
+    // Get a copy of a cached statement, with its iterators reset.
+    pub(crate) fn get(&self, key: &str) -> Option<PreparedStatement> {
+        let mut state = self.state.lock().unwrap();
+        state.tick += 1;
+        let tick = state.tick;
+        let (ps, used) = state.entries.get_mut(key)?;
+        *used = tick;
+        let mut ps = ps.clone();
+        let _ = ps.reset();
+        Some(ps)
+    }
+
+    pub(crate) fn put(&self, key: String, ps: &PreparedStatement) {
+        let mut ps = ps.clone();
+        if ps.reset().is_err() {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.tick += 1;
+        let tick = state.tick;
+        state.entries.insert(key, (ps, tick));
+        if state.entries.len() > self.capacity {
+            let oldest = state
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(k, _)| k.clone());
+            if let Some(k) = oldest {
+                trace!("prepare cache full: evicting statement");
+                state.entries.remove(&k);
+            }
+        }
+    }
+
+    pub(crate) fn remove(&self, key: &str) {
+        self.state.lock().unwrap().entries.remove(key);
+    }
+
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.state.lock().unwrap().entries.len()
+    }
+}
@@ -6,10 +6,15 @@
 //
 use crate::error::NoSQLError;
 use crate::plan_iter::{PlanIter, PlanIterKind};
+use crate::receive_iter::DistributionKind;
 use crate::types::{FieldValue, TopologyInfo};
 
 use std::collections::HashMap;
 use std::result::Result;
+use std::sync::Arc;
+
+// QUERY_OPERATION code of SELECT statements.
+const SELECT_OPERATION: u8 = 0;
 
 /// A prepared query statement for use in a [`QueryRequest`](crate::QueryRequest).
 ///
@@ -66,8 +71,8 @@ pub struct PreparedStatement {
     // namespace is the namespace returned from a prepared query result, if any.
     pub(crate) namespace: Option<String>,
 
-    // operation is the operation code for the query.
-    pub(crate) operation: u8,
+    // operation is the operation code for the query, if returned by the server.
+    pub(crate) operation: Option<u8>,
 
     // driver_query_plan represents the part of query plan that must be executed at the driver.
     // It is received from the NoSQL database proxy when the query is prepared there.
@@ -77,8 +82,9 @@ pub struct PreparedStatement {
 
     // topology_info represents the NoSQL database topology information that
     // are required for query execution.
-    // This is only used for advanced queries.
-    pub(crate) topology_info: Option<TopologyInfo>,
+    // This is only used for advanced queries. It is shared by all requests
+    // using the statement, since the list of shards can be large.
+    pub(crate) topology_info: Option<Arc<TopologyInfo>>,
 
     // statement represents the serialized PreparedStatement created at the backend store.
     // It is opaque for the driver.
@@ -93,6 +99,10 @@ pub struct PreparedStatement {
     // It is only created when deserializing a prepared statement from a query response.
     pub(crate) variable_to_ids: Option<HashMap<String, i32>>,
 
+    // table_version is the version of the query's table when the statement was
+    // prepared, recorded if QueryRequest::check_schema() was set.
+    pub(crate) table_version: Option<String>,
+
     // for driver plans
     pub(crate) num_registers: i32,
     pub(crate) num_iterators: i32,
@@ -111,6 +121,31 @@ impl std::fmt::Debug for PreparedStatement {
     }
 }
 
+/// A column of the results of a query, from [`PreparedStatement::columns()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryColumn {
+    pub(crate) name: String,
+    pub(crate) type_name: String,
+    pub(crate) precision: Option<u32>,
+}
+
+impl QueryColumn {
+    /// Get the name of the column.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// Get the type of the column as named by the server, in upper case, such as `"INTEGER"`,
+    /// `"TIMESTAMP"` or `"JSON"`. This is `"ANY"` for columns whose type is not known until
+    /// the query runs.
+    pub fn type_name(&self) -> &str {
+        &self.type_name
+    }
+    /// Get the number of fractional second digits of a `TIMESTAMP` column, if known.
+    pub fn timestamp_precision(&self) -> Option<u32> {
+        self.precision
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct PreparedStatementData {
     // bind_variables is a map that associates the name to the value for external
@@ -137,7 +172,178 @@ impl Clone for PreparedStatementData {
     }
 }
 
+/// A summary of a prepared query, from [`PreparedStatement::describe()`].
+///
+/// This gathers the details of a query that are known once it is prepared, such as for
+/// documenting the queries used by an application or reviewing how they are executed.
+#[derive(Debug, Clone, Default)]
+pub struct QueryDescription {
+    pub(crate) distribution_kind: Option<DistributionKind>,
+    pub(crate) plan_text: Option<String>,
+    pub(crate) columns: Vec<QueryColumn>,
+    pub(crate) bound_variable_names: Vec<String>,
+}
+
+impl QueryDescription {
+    /// Get how the query is distributed across the store.
+    ///
+    /// This is `None` for simple queries, which are executed entirely by the server
+    /// and have no driver query plan.
+    pub fn distribution_kind(&self) -> Option<DistributionKind> {
+        self.distribution_kind
+    }
+    /// Get the text of the query plan, if
+    /// [`QueryRequest::include_query_plan()`](crate::QueryRequest::include_query_plan())
+    /// was set when the query was prepared.
+    pub fn plan_text(&self) -> Option<&str> {
+        self.plan_text.as_deref()
+    }
+    /// Get the columns of the query results, as from [`PreparedStatement::columns()`].
+    pub fn columns(&self) -> &[QueryColumn] {
+        &self.columns
+    }
+    /// Get the names of the external variables declared by the query, such as `$id`,
+    /// in declaration order.
+    ///
+    /// This is empty for simple queries, for which the server does not return the
+    /// variable names.
+    pub fn bound_variable_names(&self) -> &[String] {
+        &self.bound_variable_names
+    }
+}
+
 impl PreparedStatement {
+    /// Get a [`QueryDescription`] summarizing the query plan, result columns and
+    /// external variables of this statement.
+    ///
+    /// ```no_run
+    /// # use oracle_nosql_rust_sdk::{Handle, QueryRequest};
+    /// # #[tokio::main]
+    /// # pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let handle = Handle::builder().build().await?;
+    /// let res = QueryRequest::new("declare $id integer; select * from users where id > $id")
+    ///     .prepare_only()
+    ///     .include_query_plan(true)
+    ///     .include_query_schema(true)
+    ///     .execute(&handle)
+    ///     .await?;
+    /// let desc = res.prepared_statement().describe();
+    /// println!("distribution: {:?}", desc.distribution_kind());
+    /// println!("variables: {:?}", desc.bound_variable_names());
+    /// for col in desc.columns() {
+    ///     println!("{}: {}", col.name(), col.type_name());
+    /// }
+    /// println!("{}", desc.plan_text().unwrap_or(""));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn describe(&self) -> QueryDescription {
+        let mut vars: Vec<(&String, &i32)> = match &self.variable_to_ids {
+            Some(m) => m.iter().collect(),
+            None => Vec::new(),
+        };
+        vars.sort_by_key(|(_, id)| **id);
+        QueryDescription {
+            distribution_kind: self.driver_query_plan.distribution_kind(),
+            plan_text: if self.query_plan.is_empty() {
+                None
+            } else {
+                Some(self.query_plan.clone())
+            },
+            columns: self.columns(),
+            bound_variable_names: vars.into_iter().map(|(n, _)| n.clone()).collect(),
+        }
+    }
+    /// Get the ids of the shards in the store topology at the time the statement was prepared.
+    ///
+    /// These are the valid values for [`QueryRequest::target_shard()`](crate::QueryRequest::target_shard()).
+    pub fn shard_ids(&self) -> Vec<i32> {
+        match &self.topology_info {
+            Some(ti) => ti.shard_ids.clone(),
+            None => Vec::new(),
+        }
+    }
+    /// Get the schema of the query results, as returned by the server.
+    ///
+    /// This is a JSON description of the result record. It is only available if
+    /// [`QueryRequest::include_query_schema()`](crate::QueryRequest::include_query_schema())
+    /// was set when the query was prepared.
+    pub fn query_schema(&self) -> Option<&str> {
+        if self.query_schema.is_empty() {
+            return None;
+        }
+        Some(&self.query_schema)
+    }
+    /// Get the name and type of each column of the query results, in `SELECT` order.
+    ///
+    /// This is derived from the [`query_schema()`](PreparedStatement::query_schema()). If the
+    /// schema is not available, the returned vector is empty.
+    pub fn columns(&self) -> Vec<QueryColumn> {
+        let mut v: Vec<QueryColumn> = Vec::new();
+        let schema: serde_json::Value = match serde_json::from_str(&self.query_schema) {
+            Ok(s) => s,
+            Err(_) => {
+                return v;
+            }
+        };
+        if let Some(serde_json::Value::Array(fields)) = schema.get("fields") {
+            for f in fields {
+                let name = match f.get("name").and_then(|n| n.as_str()) {
+                    Some(n) => n,
+                    None => continue,
+                };
+                // the type is either a name, or an object with a "type" name
+                let (type_name, precision) = match f.get("type") {
+                    Some(serde_json::Value::String(t)) => (t.as_str(), None),
+                    Some(t @ serde_json::Value::Object(_)) => (
+                        t.get("type").and_then(|t| t.as_str()).unwrap_or(""),
+                        t.get("precision").and_then(|p| p.as_u64()),
+                    ),
+                    _ => ("", None),
+                };
+                let precision = precision.or_else(|| f.get("precision").and_then(|p| p.as_u64()));
+                v.push(QueryColumn {
+                    name: name.to_string(),
+                    type_name: type_name.to_ascii_uppercase(),
+                    precision: precision.map(|p| p as u32),
+                });
+            }
+        }
+        v
+    }
+    /// Get the variables currently bound to this statement, and their values.
+    ///
+    /// Named variables are keyed by their name, as given to
+    /// [`QueryRequest::set_variable()`](crate::QueryRequest::set_variable()), such as `$id`.
+    /// Positional variables set with
+    /// [`QueryRequest::set_variable_by_id()`](crate::QueryRequest::set_variable_by_id()) are
+    /// keyed by `#` followed by their position, such as `#1`.
+    ///
+    /// Bound variables belong to a single copy of a statement, normally the one held by a
+    /// [`QueryRequest`](crate::QueryRequest), and are never copied: cloning a statement,
+    /// [`QueryResult::prepared_statement()`](crate::QueryResult::prepared_statement()) and
+    /// [`QueryRequest::new_prepared()`](crate::QueryRequest::new_prepared()) all give a
+    /// statement with no bound variables. Within a request, values stay bound across
+    /// executions until they are set again or cleared with
+    /// [`clear_variable()`](PreparedStatement::clear_variable()), so a variable that is only
+    /// set for some executions keeps its last value in the others. See also
+    /// [`QueryRequest::bound_variables()`](crate::QueryRequest::bound_variables()).
+    pub fn bound_variables(&self) -> &HashMap<String, FieldValue> {
+        &self.data.bind_variables
+    }
+    /// Remove the value bound to a variable, using the same names as
+    /// [`bound_variables()`](PreparedStatement::bound_variables()).
+    ///
+    /// Returns `true` if the variable was bound. Executing a query that uses a variable
+    /// with no value bound will fail.
+    pub fn clear_variable(&mut self, name: &str) -> bool {
+        self.data.bind_variables.remove(name).is_some()
+    }
+    // True if the server reported that this is a SELECT statement, which
+    // does not modify any rows.
+    pub(crate) fn is_read_only(&self) -> bool {
+        self.operation == Some(SELECT_OPERATION)
+    }
     pub(crate) fn is_simple(&self) -> bool {
         self.driver_query_plan.get_kind() == PlanIterKind::Empty
     }
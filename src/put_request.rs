@@ -4,15 +4,23 @@
 // Licensed under the Universal Permissive License v 1.0 as shown at
 //  https://oss.oracle.com/licenses/upl/
 //
+use crate::condition::Condition;
+use crate::error::ia_err;
 use crate::error::NoSQLError;
 use crate::handle::Handle;
 use crate::handle::SendOptions;
 use crate::nson::*;
 use crate::reader::Reader;
-use crate::types::{Capacity, FieldValue, MapValue, NoSQLRow, OpCode};
+use crate::request_options::RequestOptions;
+use crate::types::{
+    row_to_map_value, Capacity, Durability, FieldValue, MapValue, NoSQLRow, OpCode,
+};
+use crate::update_request::UpdateRequest;
 use crate::writer::Writer;
+use crate::GetTableRequest;
 use crate::NoSQLErrorCode::IllegalArgument;
 use crate::Version;
+use chrono::{DateTime, FixedOffset};
 use std::result::Result;
 use std::time::Duration;
 
@@ -26,6 +34,7 @@ use std::time::Duration;
 /// - Succeed only if the row does not exist. Use [`if_absent()`](PutRequest::if_absent()) for this case.
 /// - Succeed only if the row exists. Use [`if_present()`](PutRequest::if_present()) for this case.
 /// - Succeed only if the row exists and its [`Version`] matches a specific [`Version`]. Use [`if_version()`](PutRequest::if_version()) for this case.
+/// - Succeed only if the row exists and its field values match a [`Condition`]. Use [`if_condition()`](PutRequest::if_condition()) for this case.
 ///
 /// Information about the existing row can be returned from a put operation using [`return_row(true)`](PutRequest::return_row()). Requesting this information incurs additional cost and may affect operation latency.
 ///
@@ -41,12 +50,13 @@ pub struct PutRequest {
     pub(crate) return_row: bool,
     if_present: bool,
     if_absent: bool,
-    // TODO durability: Option<Version>
+    pub(crate) durability: Option<Durability>,
     pub(crate) ttl: Duration,
     pub(crate) use_table_ttl: bool,
     pub(crate) exact_match: bool,
     // TODO identity_cache_size,
     match_version: Version,
+    condition: Option<Condition>,
     // TODO: limiters, retry stats, etc
 }
 
@@ -61,10 +71,25 @@ pub struct PutResult {
     pub(crate) existing_modification_time: i64,
     pub(crate) existing_value: Option<MapValue>,
     pub(crate) existing_version: Option<Version>,
+    // set for successful puts with if_condition(), which do not return a version
+    pub(crate) condition_success: bool,
+    pub(crate) rate_limit_delayed_ms: u64,
+    // expiration time of the written row in milliseconds since the epoch, as
+    // reported by the server. Zero if not reported or the row does not expire.
+    pub(crate) expiration_time: i64,
     // TODO: stats, etc... (base)
 }
 
 impl PutResult {
+    /// Determine whether the put operation was applied.
+    ///
+    /// For unconditional puts this is always true for a successful call. For
+    /// conditional puts ([`PutRequest::if_absent()`], [`PutRequest::if_present()`],
+    /// [`PutRequest::if_version()`], [`PutRequest::if_condition()`]) this is false if the
+    /// condition was not met and the row was not written.
+    pub fn success(&self) -> bool {
+        self.version.is_some() || self.condition_success
+    }
     /// Get the Version of the now-current record. This value is `Some` if the put operation succeeded. It
     /// may be used in subsequent [`PutRequest::if_version()`] calls.
     pub fn version(&self) -> Option<&Version> {
@@ -73,6 +98,29 @@ impl PutResult {
         }
         None
     }
+    /// Get the time, in milliseconds, this operation was delayed backing off from throttling.
+    ///
+    /// This is only relevant for NoSQL Cloud operation on provisioned tables. If the table's
+    /// write throughput limit is exceeded, the request is retried with backoff within the
+    /// request timeout. This returns the total backoff delay, which is zero if the request
+    /// was not throttled. A nonzero value is a signal to slow down before requests start
+    /// failing with `WriteLimitExceeded` errors.
+    pub fn rate_limit_delayed_ms(&self) -> u64 {
+        self.rate_limit_delayed_ms
+    }
+    /// Get the expiration time of the written row, as computed by the server.
+    ///
+    /// The server rounds the [TTL](PutRequest::ttl()) of a row up to a whole number of
+    /// hours or days, so this is the authoritative time at which the row will expire. This
+    /// is `None` if the row does not expire, if the put was not applied, or if the server did
+    /// not report an expiration time. The time is given in UTC.
+    pub fn expiration_time(&self) -> Option<DateTime<FixedOffset>> {
+        if self.expiration_time <= 0 {
+            return None;
+        }
+        let t = DateTime::from_timestamp_millis(self.expiration_time)?;
+        Some(t.fixed_offset())
+    }
     /// Get the consumed capacity (read/write units) of the operation. This is only valid in the NoSQL Cloud Service.
     pub fn consumed(&self) -> Option<&Capacity> {
         if let Some(c) = &self.consumed {
@@ -139,6 +187,10 @@ impl PutRequest {
     ///
     /// Either this method or [`put()`](PutRequest::put()) must be called for the `PutRequest` to be valid.
     ///
+    /// The value always replaces the entire row: columns that are not given in the value are set
+    /// to their default values (or NULL), not left unchanged. To modify only some fields of an
+    /// existing row, use [`UpdateRequest`](crate::UpdateRequest).
+    ///
     /// The fields of the given value will be mapped to their matching table columns on insertion:
     /// ```no_run
     /// use oracle_nosql_rust_sdk::PutRequest;
@@ -244,20 +296,18 @@ impl PutRequest {
     ///
     /// See the [`GetRequest::execute_into()`](crate::GetRequest::execute_into()) documentation for
     /// another example of using native Rust structs with `NoSQLRow`.
-    pub fn put(mut self, val: impl NoSQLRow) -> Result<PutRequest, NoSQLError> {
-        match val.to_map_value() {
-            Ok(value) => {
-                self.value = value;
-                return Ok(self);
-            }
-            Err(e) => {
-                // TODO: save error as source
-                return Err(NoSQLError::new(
-                    IllegalArgument,
-                    &format!("could not convert struct to MapValue: {}", e.to_string()),
-                ));
-            }
-        }
+    pub fn put(self, val: impl NoSQLRow) -> Result<PutRequest, NoSQLError> {
+        self.value_from_row(&val)
+    }
+
+    /// Set the row value to put from a borrowed native Rust struct.
+    ///
+    /// This is the same as [`put()`](PutRequest::put()), but does not take ownership of
+    /// `row`, so the same struct can be reused or written to several tables. If the struct
+    /// cannot be converted to a row, an `IllegalArgument` error is returned.
+    pub fn value_from_row<T: NoSQLRow>(mut self, row: &T) -> Result<PutRequest, NoSQLError> {
+        self.value = row_to_map_value(row)?;
+        Ok(self)
     }
 
     /// Specify the timeout value for the request.
@@ -284,6 +334,25 @@ impl PutRequest {
         self
     }
 
+    /// On-premises only: specify the [`Durability`] to use for the operation.
+    ///
+    /// This is ignored if the request is part of a [`WriteMultipleRequest`](crate::WriteMultipleRequest),
+    /// which uses its own durability setting.
+    ///
+    /// If not set, the server's default durability is used.
+    pub fn durability(mut self, d: Durability) -> Self {
+        self.durability = Some(d);
+        self
+    }
+
+    /// Apply [`RequestOptions`] to this request, using their timeout, compartment and durability.
+    pub fn options(mut self, options: &RequestOptions) -> Self {
+        options.apply_timeout(&mut self.timeout);
+        options.apply_compartment_id(&mut self.compartment_id);
+        options.apply_durability(&mut self.durability);
+        self
+    }
+
     /// Return information about the existing row, if present.
     /// Requesting this information incurs additional cost and may affect operation latency.
     pub fn return_row(mut self, val: bool) -> PutRequest {
@@ -313,45 +382,164 @@ impl PutRequest {
     }
 
     /// Succeed only if the given row exists and its version matches the given version.
+    ///
+    /// This replaces any previous call to [`if_absent()`](PutRequest::if_absent()),
+    /// [`if_present()`](PutRequest::if_present()) or [`if_condition()`](PutRequest::if_condition()).
+    /// Use [`PutResult::success()`] to determine whether the condition was met.
     pub fn if_version(mut self, version: &Version) -> PutRequest {
         self.match_version = version.clone();
         self.if_present = false;
         self.if_absent = false;
+        self.condition = None;
         self
     }
 
-    /// Succeed only of the given row does not already exist.
+    /// Succeed only if the given row does not already exist (insert-only).
+    ///
+    /// If a row with the same primary key exists, it is left unchanged and
+    /// [`PutResult::success()`] returns false.
+    /// This replaces any previous call to [`if_present()`](PutRequest::if_present()),
+    /// [`if_version()`](PutRequest::if_version()) or [`if_condition()`](PutRequest::if_condition()).
     pub fn if_absent(mut self) -> PutRequest {
         self.if_absent = true;
         self.if_present = false;
         self.match_version.clear();
+        self.condition = None;
         self
     }
 
-    /// Succeed only of the given row already exists.
+    /// Succeed only if the given row already exists (update-only).
+    ///
+    /// If no row with the same primary key exists, no row is written and
+    /// [`PutResult::success()`] returns false.
+    /// This replaces any previous call to [`if_absent()`](PutRequest::if_absent()),
+    /// [`if_version()`](PutRequest::if_version()) or [`if_condition()`](PutRequest::if_condition()).
     pub fn if_present(mut self) -> PutRequest {
         self.if_present = true;
         self.if_absent = false;
         self.match_version.clear();
+        self.condition = None;
         self
     }
 
+    /// Succeed only if the given row already exists and its current field values match the
+    /// given [`Condition`].
+    ///
+    /// The key protocol operations only support version-based conditions, so a conditional
+    /// put is executed as a generated SQL `UPDATE` statement that sets all non-key columns of
+    /// the row. This first reads the table's primary key definition, which takes an extra
+    /// round trip to the server. Unlike a regular put, columns that are not given in the value
+    /// are left unchanged.
+    ///
+    /// [`PutResult::success()`] returns whether the row was updated. No version or existing row
+    /// information is returned, and the row's TTL is not changed, so this cannot be combined
+    /// with [`ttl()`](PutRequest::ttl()) or [`use_table_ttl()`](PutRequest::use_table_ttl()).
+    /// It also cannot be used in a [`WriteMultipleRequest`](crate::WriteMultipleRequest).
+    ///
+    /// This replaces any previous call to [`if_absent()`](PutRequest::if_absent()),
+    /// [`if_present()`](PutRequest::if_present()) or [`if_version()`](PutRequest::if_version()).
+    pub fn if_condition(mut self, condition: Condition) -> PutRequest {
+        self.condition = Some(condition);
+        self.if_present = false;
+        self.if_absent = false;
+        self.match_version.clear();
+        self
+    }
+
+    // Get the operation code for this put, based on its condition (if any).
+    // Only one of if_version/if_absent/if_present may be in effect at a time.
+    pub(crate) fn op_code(&self) -> OpCode {
+        if !self.match_version.is_empty() {
+            return OpCode::PutIfVersion;
+        }
+        if self.if_present {
+            return OpCode::PutIfPresent;
+        }
+        if self.if_absent {
+            return OpCode::PutIfAbsent;
+        }
+        OpCode::Put
+    }
+
     pub async fn execute(&self, h: &Handle) -> Result<PutResult, NoSQLError> {
-        let mut w: Writer = Writer::new();
-        w.write_i16(h.inner.serial_version);
+        if let Some(condition) = &self.condition {
+            return self.execute_conditional(condition, h).await;
+        }
+        let mut w = h.request_writer();
         let timeout = h.get_timeout(&self.timeout);
         self.serialize_internal(&mut w, false, false, &timeout);
         let mut opts = SendOptions {
             timeout: timeout,
             retryable: false,
             compartment_id: self.compartment_id.clone(),
+            // throttled puts are rejected before being applied, so they can be resent
+            retry_throttling: true,
             ..Default::default()
         };
         let mut r = h.send_and_receive(w, &mut opts).await?;
-        let resp = PutRequest::nson_deserialize(&mut r)?;
+        let mut resp = PutRequest::nson_deserialize(&mut r)?;
+        resp.rate_limit_delayed_ms = opts.throttle_delay.as_millis() as u64;
         Ok(resp)
     }
 
+    // Split the row value into an UpdateRequest for its key, setting all other columns.
+    pub(crate) fn conditional_update(
+        &self,
+        condition: &Condition,
+        primary_key: &[String],
+    ) -> Result<UpdateRequest, NoSQLError> {
+        if self.ttl.as_secs() > 0 || self.use_table_ttl {
+            return ia_err!("PutRequest: cannot use TTL settings with if_condition()");
+        }
+        let mut key = MapValue::new();
+        for k in primary_key {
+            match self.value.get_field_value(k) {
+                Some(v) => key.put_field_value(k, v.clone_internal()),
+                None => {
+                    return ia_err!("PutRequest: value is missing primary key field '{}'", k);
+                }
+            }
+        }
+        let mut set: Vec<(String, FieldValue)> = Vec::new();
+        for (k, v) in self.value.iter() {
+            if !primary_key.contains(k) {
+                set.push((k.clone(), v.clone_internal()));
+            }
+        }
+        Ok(UpdateRequest {
+            table_name: self.table_name.clone(),
+            key,
+            set,
+            condition: Some(condition.clone_internal()),
+            timeout: self.timeout,
+            compartment_id: self.compartment_id.clone(),
+            durability: self.durability,
+            ..Default::default()
+        })
+    }
+
+    async fn execute_conditional(
+        &self,
+        condition: &Condition,
+        h: &Handle,
+    ) -> Result<PutResult, NoSQLError> {
+        if let Some(e) = &condition.err {
+            return Err(e.clone());
+        }
+        let mut treq = GetTableRequest::new(&self.table_name).compartment_id(&self.compartment_id);
+        if let Some(t) = &self.timeout {
+            treq = treq.timeout(t);
+        }
+        let table = treq.execute(h).await?;
+        let ureq = self.conditional_update(condition, &table.primary_key_fields())?;
+        let ures = ureq.execute(h).await?;
+        Ok(PutResult {
+            condition_success: ures.num_updated() > 0,
+            consumed: Some(ures.consumed()),
+            ..Default::default()
+        })
+    }
+
     fn serialize_internal(
         &self,
         w: &mut Writer,
@@ -360,18 +548,11 @@ impl PutRequest {
         timeout: &Duration,
     ) {
         let mut ns = NsonSerializer::start_request(w);
-        let mut opcode = OpCode::Put;
-        if self.match_version.len() > 0 {
-            opcode = OpCode::PutIfVersion;
-        } else if self.if_present {
-            opcode = OpCode::PutIfPresent;
-        } else if self.if_absent {
-            opcode = OpCode::PutIfAbsent;
-        }
+        let opcode = self.op_code();
 
         if is_sub_request {
             if add_table_name {
-                ns.write_string_field(TABLE_NAME, &self.table_name);
+                ns.write_table_name_field(&self.table_name);
             }
             ns.write_i32_field(OP_CODE, opcode as i32);
             if self.abort_on_fail {
@@ -382,7 +563,7 @@ impl PutRequest {
             ns.write_header(opcode, timeout, &self.table_name);
             ns.end_header();
             ns.start_payload();
-            //ns.write_i32_field(DURABILITY, 0); // TODO
+            ns.write_durability(&self.durability);
         }
 
         ns.write_true_bool_field(RETURN_ROW, self.return_row);
@@ -443,6 +624,9 @@ impl PutRequest {
                     //println!("   w: ROW_VERSION");
                     res.version = Some(walker.read_nson_binary()?);
                 }
+                EXPIRATION => {
+                    res.expiration_time = walker.read_nson_i64()?;
+                }
                 GENERATED => {
                     //println!("   w: GENERATED");
                     res.generated_value = Some(walker.read_nson_field_value()?);
@@ -501,6 +685,15 @@ impl NsonRequest for PutRequest {
 }
 
 impl NsonSubRequest for PutRequest {
+    fn validate(&self) -> Result<(), NoSQLError> {
+        if self.condition.is_some() {
+            return ia_err!(
+                "PutRequest with if_condition() cannot be used in WriteMultipleRequest"
+            );
+        }
+        Ok(())
+    }
+
     fn serialize(&self, w: &mut Writer, _timeout: &Duration) {
         // For subrequests in WriteMultiple, we need to write a complete operation map
         let mut ns = NsonSerializer::new(w);
@@ -509,14 +702,7 @@ impl NsonSubRequest for PutRequest {
         ns.start_map("");
 
         // Write operation fields
-        let mut opcode = OpCode::Put;
-        if self.match_version.len() > 0 {
-            opcode = OpCode::PutIfVersion;
-        } else if self.if_present {
-            opcode = OpCode::PutIfPresent;
-        } else if self.if_absent {
-            opcode = OpCode::PutIfAbsent;
-        }
+        let opcode = self.op_code();
 
         ns.write_i32_field(OP_CODE, opcode as i32);
 
@@ -0,0 +1,197 @@
+//
+// Copyright (c) 2024, 2025 Oracle and/or its affiliates. All rights reserved.
+//
+// Licensed under the Universal Permissive License v 1.0 as shown at
+//  https://oss.oracle.com/licenses/upl/
+//
+use crate::query_request::QueryResult;
+use crate::types::{FieldValue, MapValue, StringCollation};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::trace;
+
+// A handle-level cache of query results, configured with
+// HandleBuilder::query_cache().
+//
+// Entries are keyed by compartment, normalized SQL text, bind variable values,
+// string collation and row limit, and expire after a fixed time to live. When the cache is full, the
+// least recently used entry is evicted, scanning all entries as PrepareCache does.
+#[derive(Debug)]
+pub(crate) struct QueryCache {
+    capacity: usize,
+    ttl: Duration,
+    state: Mutex<CacheState>,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub(crate) struct QueryCacheKey {
+    compartment_id: String,
+    statement: String,
+    // sorted by name
+    variables: Vec<(String, FieldValue)>,
+    collation: StringCollation,
+    max_rows: Option<usize>,
+}
+
+impl QueryCacheKey {
+    pub(crate) fn new(
+        compartment_id: &str,
+        statement: &str,
+        variables: &HashMap<String, FieldValue>,
+        collation: StringCollation,
+        max_rows: Option<usize>,
+    ) -> QueryCacheKey {
+        let mut variables: Vec<(String, FieldValue)> = variables
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone_internal()))
+            .collect();
+        variables.sort_by(|a, b| a.0.cmp(&b.0));
+        QueryCacheKey {
+            compartment_id: compartment_id.to_string(),
+            statement: normalize_statement(statement),
+            variables,
+            collation,
+            max_rows,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    result: QueryResult,
+    expires: Instant,
+    // tick it was last used
+    used: u64,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    entries: HashMap<QueryCacheKey, CacheEntry>,
+    tick: u64,
+}
+
+impl QueryCache {
+    pub(crate) fn new(capacity: usize, ttl: Duration) -> Self {
+        QueryCache {
+            capacity,
+            ttl,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    // Get a copy of a cached result, if it has not expired.
+    pub(crate) fn get(&self, key: &QueryCacheKey) -> Option<QueryResult> {
+        let mut state = self.state.lock().unwrap();
+        state.tick += 1;
+        let tick = state.tick;
+        let entry = state.entries.get_mut(key)?;
+        if entry.expires <= Instant::now() {
+            trace!("query cache: result expired");
+            state.entries.remove(key);
+            return None;
+        }
+        entry.used = tick;
+        Some(copy_result(&entry.result))
+    }
+
+    pub(crate) fn put(&self, key: QueryCacheKey, result: &QueryResult) {
+        let mut state = self.state.lock().unwrap();
+        state.tick += 1;
+        let entry = CacheEntry {
+            result: copy_result(result),
+            expires: Instant::now() + self.ttl,
+            used: state.tick,
+        };
+        state.entries.insert(key, entry);
+        if state.entries.len() > self.capacity {
+            // drop expired results first, then the least recently used one
+            let now = Instant::now();
+            state.entries.retain(|_, e| e.expires > now);
+            if state.entries.len() > self.capacity {
+                // ticks are unique, so this evicts exactly one entry
+                if let Some(oldest) = state.entries.values().map(|e| e.used).min() {
+                    trace!("query cache full: evicting result");
+                    state.entries.retain(|_, e| e.used != oldest);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn clear(&self) {
+        self.state.lock().unwrap().entries.clear();
+    }
+
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.state.lock().unwrap().entries.len()
+    }
+}
+
+// Copy the rows and statement of a result. A cached result consumed no
+// capacity, so usage values are not copied.
+fn copy_result(result: &QueryResult) -> QueryResult {
+    QueryResult {
+        rows: result.rows.iter().map(MapValue::deep_clone).collect(),
+        prepared_statement: result.prepared_statement.clone(),
+        display_timezone: result.display_timezone,
+        from_cache: true,
+        ..Default::default()
+    }
+}
+
+// Collapse runs of whitespace outside of string literals and quoted
+// identifiers, so statements that differ only in formatting share an entry.
+fn normalize_statement(statement: &str) -> String {
+    let mut out = String::with_capacity(statement.len());
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    let mut space = false;
+    for c in statement.trim().chars() {
+        match quote {
+            Some(q) => {
+                out.push(c);
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None if c.is_whitespace() => space = true,
+            None => {
+                if space {
+                    out.push(' ');
+                    space = false;
+                }
+                if c == '\'' || c == '"' || c == '`' {
+                    quote = Some(c);
+                }
+                out.push(c);
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_normalize_statement() {
+        assert_eq!(
+            normalize_statement("  select *\n\tfrom  users   where name = 'a  b' "),
+            "select * from users where name = 'a  b'"
+        );
+        assert_eq!(
+            normalize_statement("select \"x  y\"  from t"),
+            "select \"x  y\" from t"
+        );
+        assert_eq!(
+            normalize_statement("select 'it\\'s   ok'  from t"),
+            "select 'it\\'s   ok' from t"
+        );
+    }
+}
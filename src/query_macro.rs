@@ -0,0 +1,315 @@
+//
+// Copyright (c) 2024, 2025 Oracle and/or its affiliates. All rights reserved.
+//
+// Licensed under the Universal Permissive License v 1.0 as shown at
+//  https://oss.oracle.com/licenses/upl/
+//
+use crate::error::ia_err;
+use crate::error::NoSQLError;
+use crate::handle::Handle;
+use crate::query_request::{QueryRequest, QueryResult};
+use crate::types::FieldValue;
+use std::result::Result;
+
+/// Execute a query with named parameters, bound as query variables.
+///
+/// `query!(handle, statement, name = value, ...)` prepares the statement, binds each
+/// parameter, and executes the query, returning a future of `Result<QueryResult, NoSQLError>`.
+/// In the statement, `{name}` is replaced by a bind variable for the parameter of the same
+/// name, so parameter values are never interpolated into the SQL text. As with
+/// [`format!`], `{{` and `}}` are used for literal braces, such as in JSON map constructors.
+/// Text in single or double quotes, such as a string literal, is used as is: braces in it
+/// are not placeholders and are not doubled.
+/// Parameter values can be of any type that implements
+/// [`NoSQLColumnToFieldValue`](crate::NoSQLColumnToFieldValue).
+///
+/// ```no_run
+/// # use oracle_nosql_rust_sdk::{query, Handle};
+/// # #[tokio::main]
+/// # pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let handle = Handle::builder().build().await?;
+/// let name = "jane";
+/// let res = query!(
+///     &handle,
+///     "select * from users where id > {min_id} and name = {name}",
+///     min_id = 5,
+///     name = name
+/// )
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The statement must be a string literal, and is checked at compile time: every placeholder
+/// must be a parameter name, every parameter must be used, and braces must be balanced. The
+/// following fails to compile:
+/// ```compile_fail
+/// # use oracle_nosql_rust_sdk::{query, Handle};
+/// # async fn run(handle: &Handle) {
+/// let _ = query!(handle, "select * from users where id = {id}", user_id = 5);
+/// # }
+/// ```
+///
+/// This is equivalent to preparing the statement with [`QueryRequest::prepare_only()`],
+/// and executing it with [`QueryRequest::new_prepared()`] and
+/// [`QueryRequest::set_variable_by_id()`], except that if the handle has a prepare cache
+/// (see [`HandleBuilder::prepare_cache_size()`](crate::HandleBuilder::prepare_cache_size())),
+/// the statement is only prepared the first time it is used. To set other query options,
+/// use those methods directly.
+#[macro_export]
+macro_rules! query {
+    ($handle:expr, $statement:literal $(, $name:ident = $value:expr)* $(,)?) => {{
+        const _: () = $crate::__check_query_template($statement, &[$(stringify!($name)),*]);
+        $crate::__execute_query_template(
+            $handle,
+            $statement,
+            vec![$((
+                stringify!($name),
+                $crate::NoSQLColumnToFieldValue::try_to_field_value(&$value),
+            )),*],
+        )
+    }};
+}
+
+// Find the next placeholder in a query template, starting at byte `from`,
+// which must not be in quoted text. Returns the start and end of the
+// placeholder name, or the template length for both if there are no more
+// placeholders.
+const fn next_placeholder(t: &[u8], from: usize) -> (usize, usize) {
+    let mut i = from;
+    while i < t.len() {
+        if t[i] == b'\'' || t[i] == b'"' {
+            i = quote_end(t, i);
+            continue;
+        }
+        if t[i] == b'{' {
+            if i + 1 < t.len() && t[i + 1] == b'{' {
+                i += 2;
+                continue;
+            }
+            let start = i + 1;
+            let mut end = start;
+            while end < t.len() && t[end] != b'}' {
+                let c = t[end];
+                if !(c.is_ascii_alphanumeric() || c == b'_') {
+                    panic!("query!: placeholders must be parameter names. Use a double brace for a literal brace");
+                }
+                end += 1;
+            }
+            if end == t.len() {
+                panic!(
+                    "query!: unclosed brace in statement. Use a double brace for a literal brace"
+                );
+            }
+            if end == start || t[start].is_ascii_digit() {
+                panic!("query!: placeholders must be parameter names");
+            }
+            return (start, end);
+        }
+        if t[i] == b'}' {
+            if i + 1 < t.len() && t[i + 1] == b'}' {
+                i += 2;
+                continue;
+            }
+            panic!("query!: unmatched closing brace in statement. Use a double brace for a literal brace");
+        }
+        i += 1;
+    }
+    (t.len(), t.len())
+}
+
+// Get the byte after the end of the quoted text starting at byte `start`.
+// Quotes are escaped with a backslash, as in SQL string literals.
+const fn quote_end(t: &[u8], start: usize) -> usize {
+    let quote = t[start];
+    let mut i = start + 1;
+    while i < t.len() {
+        if t[i] == b'\\' {
+            i += 2;
+            continue;
+        }
+        if t[i] == quote {
+            return i + 1;
+        }
+        i += 1;
+    }
+    panic!("query!: unclosed quote in statement");
+}
+
+const fn name_eq(t: &[u8], start: usize, end: usize, name: &[u8]) -> bool {
+    if end - start != name.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < name.len() {
+        if t[start + i] != name[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+// True if the template has a placeholder with the given name.
+const fn has_placeholder(t: &[u8], name: &[u8]) -> bool {
+    let (mut start, mut end) = next_placeholder(t, 0);
+    while start < t.len() {
+        if name_eq(t, start, end, name) {
+            return true;
+        }
+        (start, end) = next_placeholder(t, end + 1);
+    }
+    false
+}
+
+#[doc(hidden)]
+pub const fn __check_query_template(template: &str, names: &[&str]) {
+    let t = template.as_bytes();
+    let (mut start, mut end) = next_placeholder(t, 0);
+    while start < t.len() {
+        let mut n = 0;
+        while n < names.len() && !name_eq(t, start, end, names[n].as_bytes()) {
+            n += 1;
+        }
+        if n == names.len() {
+            panic!("query!: statement has a placeholder with no matching parameter");
+        }
+        (start, end) = next_placeholder(t, end + 1);
+    }
+    let mut n = 0;
+    while n < names.len() {
+        let name = names[n].as_bytes();
+        if !has_placeholder(t, name) {
+            panic!("query!: parameter is not used in the statement");
+        }
+        let mut m = 0;
+        while m < n {
+            if name_eq(names[m].as_bytes(), 0, names[m].len(), name) {
+                panic!("query!: parameter is given more than once");
+            }
+            m += 1;
+        }
+        n += 1;
+    }
+}
+
+// Convert a query template to a statement with positional variables, and the
+// parameter name of each variable, in order.
+fn positional_statement(template: &str) -> (String, Vec<&str>) {
+    let t = template.as_bytes();
+    let mut sql = String::with_capacity(template.len());
+    let mut names: Vec<&str> = Vec::new();
+    let mut pos = 0;
+    let (mut start, mut end) = next_placeholder(t, 0);
+    while start < t.len() {
+        sql.push_str(&unescape(&template[pos..start - 1]));
+        sql.push('?');
+        names.push(&template[start..end]);
+        pos = end + 1;
+        (start, end) = next_placeholder(t, pos);
+    }
+    sql.push_str(&unescape(&template[pos..]));
+    (sql, names)
+}
+
+// Replace double braces by single braces, except in quoted text. The text
+// must not start in quoted text.
+fn unescape(s: &str) -> String {
+    let t = s.as_bytes();
+    let mut out = String::with_capacity(s.len());
+    let mut pos = 0;
+    let mut i = 0;
+    while i < t.len() {
+        match t[i] {
+            b'\'' | b'"' => i = quote_end(t, i),
+            b'{' | b'}' if i + 1 < t.len() && t[i + 1] == t[i] => {
+                out.push_str(&s[pos..=i]);
+                i += 2;
+                pos = i;
+            }
+            _ => i += 1,
+        }
+    }
+    out.push_str(&s[pos..]);
+    out
+}
+
+#[doc(hidden)]
+pub async fn __execute_query_template(
+    h: &Handle,
+    template: &str,
+    params: Vec<(&str, Result<FieldValue, NoSQLError>)>,
+) -> Result<QueryResult, NoSQLError> {
+    let (sql, names) = positional_statement(template);
+    let mut values: Vec<(&str, FieldValue)> = Vec::with_capacity(params.len());
+    for (name, value) in params {
+        match value {
+            Ok(v) => values.push((name, v)),
+            Err(e) => {
+                return ia_err!(
+                    "invalid value for query parameter '{}': {}",
+                    name,
+                    e.message
+                );
+            }
+        }
+    }
+    let mut qreq = QueryRequest::new(&sql);
+    qreq.prepare(h).await?;
+    for (i, name) in names.iter().enumerate() {
+        match values.iter().find(|(n, _)| n == name) {
+            Some((_, v)) => qreq.set_variable_by_id(i as i32 + 1, v)?,
+            None => {
+                return ia_err!("no value given for query parameter '{}'", name);
+            }
+        }
+    }
+    qreq.execute(h).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_positional_statement() {
+        let (sql, names) = positional_statement(
+            "select {{\"a\": t.a}} from t where id = {id} and x > {min_x} or id = {id}",
+        );
+        assert_eq!(
+            sql,
+            "select {\"a\": t.a} from t where id = ? and x > ? or id = ?"
+        );
+        assert_eq!(names, vec!["id", "min_x", "id"]);
+        let (sql, names) = positional_statement(
+            r#"select {{"a": '{x}}'}} from t where s = "it\"s {y}" and id = {id}"#,
+        );
+        assert_eq!(
+            sql,
+            r#"select {"a": '{x}}'} from t where s = "it\"s {y}" and id = ?"#
+        );
+        assert_eq!(names, vec!["id"]);
+        let (sql, names) = positional_statement("select * from t");
+        assert_eq!(sql, "select * from t");
+        assert!(names.is_empty());
+
+        __check_query_template("select {a} + {b} + {a}", &["b", "a"]);
+        __check_query_template("select {{}}", &[]);
+        __check_query_template("select '{a' || \"}\" || {b}", &["b"]);
+        for (template, names) in [
+            ("select {a}", &[][..]),
+            ("select {a}", &["a", "b"][..]),
+            ("select {a}", &["a", "a"][..]),
+            ("select {a b}", &["a"][..]),
+            ("select {1}", &[][..]),
+            ("select {}", &[][..]),
+            ("select {a", &["a"][..]),
+            ("select a}", &[][..]),
+            ("select '{a}", &["a"][..]),
+            ("select {a} || 'b\\'", &["a"][..]),
+        ] {
+            let res = std::panic::catch_unwind(|| __check_query_template(template, names));
+            assert!(res.is_err(), "expected '{}' to be rejected", template);
+        }
+    }
+}
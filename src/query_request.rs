@@ -4,22 +4,40 @@
 // Licensed under the Universal Permissive License v 1.0 as shown at
 //  https://oss.oracle.com/licenses/upl/
 //
+use crate::deadline::Deadline;
 use crate::error::ia_err;
 use crate::error::NoSQLError;
+use crate::error::NoSQLErrorCode::{
+    IllegalArgument, IllegalState, IndexNotFound, SchemaChanged, TableNotFound,
+};
 use crate::handle::Handle;
 use crate::handle::SendOptions;
+use crate::handle_builder::SCHEMA_CHECK_INTERVAL;
 use crate::nson::*;
 use crate::plan_iter::{deserialize_plan_iter, PlanIterKind, PlanIterState};
+use crate::prepare_cache::PrepareCache;
 use crate::prepared_statement::PreparedStatement;
+use crate::query_cache::QueryCacheKey;
 use crate::reader::Reader;
 use crate::receive_iter::ReceiveIterData;
+use crate::request_options::RequestOptions;
+use crate::table_request::GetTableRequest;
 use crate::types::NoSQLColumnToFieldValue;
-use crate::types::{Capacity, Consistency, FieldType, FieldValue, MapValue, OpCode, TopologyInfo};
+use crate::types::{
+    round_timestamp, Capacity, Consistency, Durability, FieldType, FieldValue, MapValue, NoSQLRow,
+    OpCode, StringCollation, TopologyInfo,
+};
 use crate::writer::Writer;
 
+use chrono::{DateTime, FixedOffset};
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 use std::result::Result;
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
 use tracing::trace;
 
 /// Encapsulates a SQL query of a NoSQL Database table.
@@ -27,7 +45,10 @@ use tracing::trace;
 /// A query may be either a string query
 /// statement or a prepared query, which may include bind variables.
 /// A query request cannot have both a string statement and prepared query, but
-/// it must have one or the other.
+/// it must have one or the other: create it with either [`QueryRequest::new()`] or
+/// [`QueryRequest::new_prepared()`]. Executing a request with neither (such as one
+/// created with `QueryRequest::default()`), or with an empty statement, returns an
+/// `IllegalArgument` error.
 ///
 /// See the [SQL for NoSQL Database Guide](https://docs.oracle.com/en/database/other-databases/nosql-database/24.1/sqlfornosql/introduction-sql.html) for details on creating and using queries.
 ///
@@ -51,13 +72,26 @@ use tracing::trace;
 /// For performance reasons, prepared queries are preferred for queries that may
 /// be reused. Prepared queries bypass compilation of the query. They also allow
 /// for parameterized queries using bind variables.
+///
+/// ## Paging
+/// To read all results one page at a time, call
+/// [`execute_batch()`](QueryRequest::execute_batch()) in a loop until
+/// [`is_done()`](QueryRequest::is_done()) returns `true`. Each batch continues from where
+/// the last one stopped, so no rows are read twice.
+///
+/// For random access to deep pages, use keyset paging: order the query by the primary key
+/// or an index, and select the rows after the last key of the previous page, for example
+/// `SELECT * FROM users WHERE id > $last ORDER BY id LIMIT $size`. SQL `OFFSET` also
+/// works, but the server still reads every skipped row, so reading page N costs as much
+/// as reading all pages up to N. To read a whole table in primary key order, see
+/// [`TableScanner`](crate::TableScanner).
 #[derive(Default, Debug)]
 pub struct QueryRequest {
     pub(crate) prepare_only: bool,
     //pub(crate) limit: u32,
     pub(crate) max_read_kb: u32,
     pub(crate) max_write_kb: u32,
-    pub(crate) consistency: Consistency,
+    pub(crate) consistency: Option<Consistency>,
     pub(crate) timeout: Option<Duration>,
     pub(crate) compartment_id: String,
 
@@ -71,17 +105,24 @@ pub struct QueryRequest {
     // The default value is 1GB (1,000,000,000).
     // TODO pub max_memory_consumption: i64,
 
+    // max_server_memory is the maximum amount of memory in bytes that the
+    // server may use for this query. Zero means the server default is used.
+    pub(crate) max_server_memory: i64,
+
+    // string_collation is how strings are compared when results are sorted
+    // by the SDK.
+    pub(crate) string_collation: StringCollation,
+
     // Durability is currently only used in On-Prem installations.
     // This setting only applies if the query modifies
     // a row using an INSERT, UPSERT, or DELETE statement. If the query is
     // read-only it is ignored.
-    // Added in SDK Version 1.4.0
-    // TODO Durability types.Durability
+    pub(crate) durability: Option<Durability>,
 
     // private fields: driver and RCB data
 
     // statement specifies a query statement.
-    statement: Option<String>,
+    pub(crate) statement: Option<String>,
 
     // prepared_statement specifies the prepared query statement.
     pub(crate) prepared_statement: PreparedStatement,
@@ -101,6 +142,18 @@ pub struct QueryRequest {
 
     pub(crate) consumed_capacity: Capacity,
 
+    // capacity consumed by each round trip to the server, in order
+    pub(crate) batch_consumed: Vec<Capacity>,
+
+    // if true, ask the server to return the query plan when preparing
+    pub(crate) include_query_plan: bool,
+
+    // if true, ask the server to return the result schema when preparing
+    pub(crate) include_query_schema: bool,
+
+    // total time spent backing off from throttling errors
+    pub(crate) throttle_delay: Duration,
+
     // memory_consumption represents the amount of memory in bytes that were
     // consumed by the query at the client for operations such as duplicate
     // elimination and sorting.
@@ -117,6 +170,12 @@ pub struct QueryRequest {
 
     pub(crate) shard_id: i32,
 
+    // shard to restrict execution to, set by target_shard()
+    pub(crate) target_shard: Option<i32>,
+
+    // true if the prepared statement was taken from the handle's prepare cache
+    from_prepare_cache: bool,
+
     // total number of batches executed
     pub(crate) batch_counter: i32,
 
@@ -124,7 +183,22 @@ pub struct QueryRequest {
     pub(crate) num_registers: i32,
     pub(crate) registers: Vec<FieldValue>,
 
-    pub(crate) topology_info: TopologyInfo,
+    pub(crate) topology_info: Arc<TopologyInfo>,
+
+    // maximum number of rows execute() returns, set by execute_one(). If None,
+    // execute() returns all results.
+    pub(crate) max_rows: Option<usize>,
+
+    // maximum number of shard/partition fetches to run at the same time for
+    // sorting queries. Values of 0 and 1 both mean sequential fetches.
+    pub(crate) max_concurrent_fetches: usize,
+
+    // if true, queries that need the SDK to sort or deduplicate results are rejected.
+    pub(crate) server_side_only: bool,
+
+    // if true, the table schema is recorded when the query is prepared, and checked
+    // before it is executed
+    pub(crate) check_schema: bool,
 }
 
 /// Struct representing the result of a query operation.
@@ -133,6 +207,12 @@ pub struct QueryResult {
     pub(crate) rows: Vec<MapValue>,
     pub(crate) prepared_statement: PreparedStatement,
     pub(crate) consumed: Capacity,
+    pub(crate) batch_consumed: Vec<Capacity>,
+    pub(crate) throttle_delay: Duration,
+    // from HandleBuilder::display_timezone()
+    pub(crate) display_timezone: Option<FixedOffset>,
+    // true if returned from the handle's query result cache
+    pub(crate) from_cache: bool,
     // TODO: stats, consumed, etc.
 }
 
@@ -152,6 +232,83 @@ impl QueryResult {
     pub fn take_rows(&mut self) -> Vec<MapValue> {
         std::mem::take(&mut self.rows)
     }
+    /// Consume the result, returning its rows as per-column vectors.
+    ///
+    /// The returned map has one entry for each field name that appears in any row, and every
+    /// vector has one value per row, in row order. Rows that do not have a field (for example
+    /// when a query returns rows of different shapes) get a [`FieldValue::Null`] for it. This is
+    /// meant for analytics code that converts results into columnar formats.
+    ///
+    /// ```no_run
+    /// # use oracle_nosql_rust_sdk::{Handle, QueryRequest};
+    /// # #[tokio::main]
+    /// # pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let handle = Handle::builder().build().await?;
+    /// let columns = QueryRequest::new("select id, name from users")
+    ///     .execute(&handle)
+    ///     .await?
+    ///     .into_columns();
+    /// let ids = &columns["id"];
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn into_columns(self) -> HashMap<String, Vec<FieldValue>> {
+        let mut columns: HashMap<String, Vec<FieldValue>> = HashMap::new();
+        for (i, row) in self.rows.into_iter().enumerate() {
+            for (name, value) in row.m {
+                columns
+                    .entry(name)
+                    .or_insert_with(|| {
+                        std::iter::repeat_with(|| FieldValue::Null)
+                            .take(i)
+                            .collect()
+                    })
+                    .push(value);
+            }
+            for values in columns.values_mut() {
+                if values.len() <= i {
+                    values.push(FieldValue::Null);
+                }
+            }
+        }
+        columns
+    }
+    /// Group the result rows by the value of a top-level projected field.
+    ///
+    /// Each entry of the returned map holds the rows with the same value of `field`, in the
+    /// order they were returned by the query. Rows without the field are grouped under
+    /// [`FieldValue::Null`]. Values compare as in queries, so for example an `Integer` and a
+    /// `Long` with the same value are in the same group.
+    ///
+    /// This is done on the client, over the rows of this result, for example to display rows
+    /// in sections by a category column. It is not the same as a SQL `GROUP BY`, which
+    /// aggregates rows on the server. The rows are copied; the result is not changed.
+    ///
+    /// ```no_run
+    /// # use oracle_nosql_rust_sdk::{Handle, QueryRequest};
+    /// # #[tokio::main]
+    /// # pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let handle = Handle::builder().build().await?;
+    /// let res = QueryRequest::new("select category, name, price from products order by name")
+    ///     .execute(&handle)
+    ///     .await?;
+    /// for (category, rows) in res.group_by_field("category") {
+    ///     println!("{:?}: {} products", category, rows.len());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn group_by_field(&self, field: &str) -> HashMap<FieldValue, Vec<MapValue>> {
+        let mut groups: HashMap<FieldValue, Vec<MapValue>> = HashMap::new();
+        for row in &self.rows {
+            let key = match row.get_field_value(field) {
+                Some(v) => v.clone_internal(),
+                None => FieldValue::Null,
+            };
+            groups.entry(key).or_default().push(row.deep_clone());
+        }
+        groups
+    }
     /// Get the prepared statement after execution of a query.
     ///
     /// The prepared statement can then be used in subsequent query requests, saving the
@@ -168,6 +325,72 @@ impl QueryResult {
     pub fn consumed(&self) -> Capacity {
         self.consumed.clone()
     }
+    /// Return the capacity consumed by each round trip to the server during the execution
+    /// of the query, in the order they were made.
+    ///
+    /// This includes the round trip to prepare the query, if it was not already prepared, and
+    /// the internal per-shard or per-partition fetches of sorting queries. Comparing the read
+    /// units of each batch with the number of rows it returned can show queries that read many
+    /// more rows than they return, such as full table scans. Like [`consumed()`](QueryResult::consumed()),
+    /// this is only relevant for NoSQL Cloud operation.
+    pub fn batch_consumed(&self) -> &Vec<Capacity> {
+        &self.batch_consumed
+    }
+    /// Return the query plan, if it was returned by the server.
+    ///
+    /// The plan is only available if [`QueryRequest::include_query_plan()`] was set when the
+    /// query was prepared. The format of the plan is not stable, and is meant for display.
+    pub fn query_plan(&self) -> Option<&str> {
+        if self.prepared_statement.query_plan.is_empty() {
+            return None;
+        }
+        Some(&self.prepared_statement.query_plan)
+    }
+    /// Return the name of the index used by the query, if known.
+    ///
+    /// This is read from the [`query_plan()`](QueryResult::query_plan()), so it is only
+    /// available if [`QueryRequest::include_query_plan()`] was set when the query was prepared.
+    /// For queries that do not use a secondary index, this returns `"primary index"`; without a
+    /// shard key in the `WHERE` clause this means the query scans the whole table. If the query
+    /// uses more than one table, the index of the first table in the plan is returned.
+    pub fn index_used(&self) -> Option<String> {
+        index_from_plan(self.query_plan()?)
+    }
+    /// Return the total time spent waiting on throttling during the execution of the query.
+    ///
+    /// This is only relevant for NoSQL Cloud operation on provisioned tables. If a query batch
+    /// exceeds the table's read or write throughput limits, the batch is retried with backoff
+    /// (within the request timeout) instead of failing the whole query. This returns the sum
+    /// of all such backoff delays, which will be zero if the query was never throttled.
+    pub fn throttle_delay(&self) -> Duration {
+        self.throttle_delay
+    }
+    /// Return [`throttle_delay()`](QueryResult::throttle_delay()) in milliseconds, for
+    /// consistency with [`PutResult::rate_limit_delayed_ms()`](crate::PutResult::rate_limit_delayed_ms())
+    /// and [`GetResult::rate_limit_delayed_ms()`](crate::GetResult::rate_limit_delayed_ms()).
+    pub fn rate_limit_delayed_ms(&self) -> u64 {
+        self.throttle_delay.as_millis() as u64
+    }
+    /// Return `true` if this result was returned from the handle's query result cache,
+    /// without executing the query.
+    ///
+    /// A cached result consumed no capacity: [`consumed()`](QueryResult::consumed()) is zero.
+    /// See [`HandleBuilder::query_cache()`](crate::HandleBuilder::query_cache()).
+    pub fn from_cache(&self) -> bool {
+        self.from_cache
+    }
+    /// Get the value of a top-level timestamp column in the given result row.
+    ///
+    /// If a display timezone was set with [`HandleBuilder::display_timezone()`](crate::HandleBuilder::display_timezone()),
+    /// the timestamp is converted to that timezone. Otherwise it is returned as sent by the
+    /// server (UTC). Returns `None` if the row does not exist, or the column is not a timestamp.
+    pub fn timestamp(&self, row: usize, column: &str) -> Option<DateTime<FixedOffset>> {
+        let ts = self.rows.get(row)?.get_timestamp(column)?;
+        if let Some(tz) = &self.display_timezone {
+            return Some(ts.with_timezone(tz));
+        }
+        Some(ts)
+    }
 }
 
 impl QueryRequest {
@@ -198,8 +421,13 @@ impl QueryRequest {
     /// To set bind variables for query execution, first create the request with this method,
     /// then call [`QueryRequest::set_variable()`] for all desired bind variables. Then execute the
     /// query with [`QueryRequest::execute()`].
+    ///
+    /// The request can be executed any number of times. Options set on it, such as
+    /// [`consistency()`](QueryRequest::consistency()), [`timeout()`](QueryRequest::timeout())
+    /// and [`max_read_kb()`](QueryRequest::max_read_kb()), apply to every execution, so they
+    /// only need to be set once; only the state of the previous execution is cleared.
     pub fn new_prepared(prepared_statement: &PreparedStatement) -> Self {
-        let ti: TopologyInfo;
+        let ti: Arc<TopologyInfo>;
         if let Some(t) = &prepared_statement.topology_info {
             ti = t.clone();
         } else {
@@ -222,11 +450,43 @@ impl QueryRequest {
     /// will result in only the query being prepared, and no result rows being returned.
     /// The prepared statement can then be retrieved using [`QueryResult::prepared_statement()`]
     /// and can be used in subsequent query calls using [`QueryRequest::new_prepared()`].
+    ///
+    /// If the server cannot compile the statement, a
+    /// [`QueryCompileError`](crate::NoSQLErrorCode::QueryCompileError) error is returned,
+    /// with the location of the error available from
+    /// [`NoSQLError::query_error_location()`](crate::NoSQLError::query_error_location()).
+    /// The same error is returned by the first `execute()` of a query that was not prepared.
     pub fn prepare_only(mut self) -> Self {
         self.prepare_only = true;
         self
     }
 
+    /// Specify whether to check that the table of the query has not been altered since the
+    /// query was prepared.
+    ///
+    /// Services that prepare a query once and execute it for a long time may otherwise not
+    /// notice that the table was altered, and receive results that do not match the expected
+    /// schema. If set to `true`, the schema of the table is recorded in the
+    /// [`PreparedStatement`] when the query is prepared, and each `execute()` of a statement
+    /// prepared this way first checks that the schema is unchanged. If it changed, execution
+    /// fails with a [`SchemaChanged`](crate::NoSQLErrorCode::SchemaChanged) error, and the
+    /// query should be prepared again. Statements from the handle's prepare cache (see
+    /// [`HandleBuilder::prepare_cache()`](crate::HandleBuilder::prepare_cache())) are prepared
+    /// again automatically.
+    ///
+    /// The check compares the table's ETag, or a hash of its schema if the server does not
+    /// return ETags (on-premises), so with the cloud service other changes to the table, such
+    /// as its limits, are also reported. The table is read with a
+    /// [`GetTableRequest`](crate::GetTableRequest) at most once per
+    /// [`HandleBuilder::schema_check_interval()`](crate::HandleBuilder::schema_check_interval())
+    /// for all queries of the handle, so a change is detected up to that long after it is made.
+    /// The check only applies to queries on a single table, and is skipped for statements
+    /// prepared without it. The default is `false`.
+    pub fn check_schema(mut self, check: bool) -> Self {
+        self.check_schema = check;
+        self
+    }
+
     /// Specify the timeout value for the request.
     ///
     /// This is optional.
@@ -251,6 +511,26 @@ impl QueryRequest {
         self
     }
 
+    /// On-premises only: specify the [`Durability`] to use for the operation.
+    ///
+    /// This only applies to queries that modify rows using an `INSERT`, `UPSERT`, `UPDATE`
+    /// or `DELETE` statement.
+    ///
+    /// If not set, the server's default durability is used.
+    pub fn durability(mut self, d: Durability) -> Self {
+        self.durability = Some(d);
+        self
+    }
+
+    /// Apply [`RequestOptions`] to this request, using their timeout, consistency, compartment and durability.
+    pub fn options(mut self, options: &RequestOptions) -> Self {
+        options.apply_timeout(&mut self.timeout);
+        options.apply_consistency(&mut self.consistency);
+        options.apply_compartment_id(&mut self.compartment_id);
+        options.apply_durability(&mut self.durability);
+        self
+    }
+
     // Specify a limit on number of items returned by the operation.
     //
     // This allows an operation to return less than the default amount of data.
@@ -259,11 +539,124 @@ impl QueryRequest {
     //self
     //}
 
+    /// On-premises only: specify the maximum amount of memory, in bytes, that the server may
+    /// use to execute this query.
+    ///
+    /// Some query operations require the server (proxy) to hold results in memory: sorting on
+    /// fields that are not indexed, `GROUP BY` on non-indexed fields, and duplicate elimination
+    /// for queries using multi-key indexes. If the server would exceed this amount of memory
+    /// for a single batch of the query, the query fails with an error instead of consuming
+    /// more memory on the shared proxy. Lowering the limit protects other users of the proxy
+    /// from a single heavy query; raising it (up to the server's own maximum) allows larger
+    /// sorts and groupings to succeed.
+    ///
+    /// This is independent of any memory used by the client itself for the final merge,
+    /// sort and grouping of results from multiple shards or partitions. If not set, or set to
+    /// zero or a negative value, the server default is used.
+    pub fn max_server_memory(mut self, bytes: i64) -> Self {
+        self.max_server_memory = bytes;
+        self
+    }
+
+    /// Specify how strings are compared when query results are sorted by the SDK.
+    ///
+    /// Queries with `ORDER BY` that span several shards or partitions are sorted by the
+    /// server within each batch, then merged (and sometimes fully sorted) by the SDK. The
+    /// default, [`StringCollation::Server`], compares strings the same way as the server, so
+    /// results are in the same order however they are split into batches. Use
+    /// [`StringCollation::CodePoint`] to sort strings the way Rust's `String::cmp()` does
+    /// instead; this only makes a difference for strings with characters outside the Basic
+    /// Multilingual Plane, and the merge of server-sorted batches may then be inconsistent.
+    ///
+    /// This only applies to sorting. Other comparisons made by the SDK, such as for
+    /// `MIN()`, `MAX()` and `GROUP BY`, and the [`Ord`] implementation of
+    /// [`FieldValue`](crate::types::FieldValue), always compare strings by code point.
+    pub fn string_collation(mut self, collation: StringCollation) -> Self {
+        self.string_collation = collation;
+        self
+    }
+
+    /// Specify whether the server should return the query plan when preparing the query.
+    ///
+    /// The plan is then available from [`QueryResult::query_plan()`] and
+    /// [`QueryResult::index_used()`], which can be used to check whether a query uses an
+    /// index or scans the whole table. This has no effect if the query was already prepared.
+    /// The default is `false`.
+    pub fn include_query_plan(mut self, include: bool) -> Self {
+        self.include_query_plan = include;
+        self
+    }
+
+    /// Specify whether the server should return the schema of the query results when
+    /// preparing the query.
+    ///
+    /// The schema is then available from [`PreparedStatement::query_schema()`] and
+    /// [`PreparedStatement::columns()`]. This has no effect if the query was already prepared.
+    /// The default is `false`.
+    pub fn include_query_schema(mut self, include: bool) -> Self {
+        self.include_query_schema = include;
+        self
+    }
+
+    /// **Advanced:** restrict execution of the query to a single shard.
+    ///
+    /// This is intended for applications that know, from the design of their data, that all
+    /// rows a query needs are stored on one shard, and want to avoid contacting the others.
+    /// Before the query runs, `shard_id` is checked against the current topology of the
+    /// store, which is available from [`PreparedStatement::shard_ids()`]; if the query has
+    /// not been prepared yet, it is prepared first. An unknown shard id results in an
+    /// IllegalArgument error.
+    ///
+    /// The SDK cannot verify that the rows the query needs are actually on the given shard.
+    /// If they are not, or are spread over several shards, the query silently returns
+    /// incomplete or empty results. Queries whose plan requires a merge across all partitions
+    /// (such as sorting queries that do not use an index) cannot be restricted to a shard,
+    /// and return an IllegalArgument error.
+    pub fn target_shard(mut self, shard_id: i32) -> Self {
+        self.target_shard = Some(shard_id);
+        self
+    }
+
+    /// Specify whether to reject queries that need the SDK to sort or deduplicate results.
+    ///
+    /// Some queries are only partly executed by the server. A query with `ORDER BY` that spans
+    /// several shards or partitions is sorted by the server within each batch, then merged by
+    /// the SDK; a query that sorts on fields that are not indexed (or sorts the results of a
+    /// `GROUP BY`) is fully sorted by the SDK, which holds the whole result set in memory; and
+    /// a query using a multi-key index (on an array or map) keeps the primary key of every
+    /// result it returns, to remove duplicates. For very large result sets this client memory
+    /// can become a problem.
+    ///
+    /// If set to `true`, such queries fail when they are executed with an IllegalArgument
+    /// error that describes the operation, before any results are fetched. The query can then
+    /// be changed to avoid it, for example by adding an index matching the `ORDER BY`, giving
+    /// the complete shard key, or using [`target_shard()`](QueryRequest::target_shard()).
+    /// Queries executed entirely by the server are not affected. The default is `false`.
+    pub fn server_side_only(mut self, server_side_only: bool) -> Self {
+        self.server_side_only = server_side_only;
+        self
+    }
+
+    /// Specify the maximum number of shard or partition fetches to run concurrently.
+    ///
+    /// Sorting queries that span all shards or all partitions merge the results of one
+    /// internal request per shard or partition. By default these requests are sent one at
+    /// a time. Setting a higher value allows up to `max` of them to be sent at the same time
+    /// (as separate tokio tasks), which can greatly reduce the wall-clock time of wide queries,
+    /// at the cost of more concurrent connections and more results held in memory.
+    ///
+    /// This has no effect on queries that do not need to merge results from multiple shards
+    /// or partitions. The default is 1 (sequential fetches).
+    pub fn max_concurrent_fetches(mut self, max: usize) -> Self {
+        self.max_concurrent_fetches = max;
+        self
+    }
+
     /// Specify the desired consistency policy for the request.
     ///
     /// If not set, the default consistency of [`Consistency::Eventual`] is used.
     pub fn consistency(mut self, c: &Consistency) -> Self {
-        self.consistency = c.clone();
+        self.consistency = Some(*c);
         self
     }
 
@@ -387,6 +780,7 @@ impl QueryRequest {
             is_internal: true,
             prepared_statement: self.prepared_statement.copy_for_internal(),
             shard_id: self.shard_id,
+            target_shard: self.target_shard,
             //limit: self.limit,
             // purposefully not copying registers
             num_registers: -1,
@@ -395,16 +789,23 @@ impl QueryRequest {
             compartment_id: self.compartment_id.clone(),
             max_read_kb: self.max_read_kb,
             max_write_kb: self.max_write_kb,
-            consistency: self.consistency.clone(),
+            max_server_memory: self.max_server_memory,
+            string_collation: self.string_collation,
+            consistency: self.consistency,
+            durability: self.durability,
             ..Default::default()
         }
     }
 
+    // Clear the execution state before executing the query again. Options set by the
+    // user (consistency, timeout, limits, etc) must not be changed here.
     pub(crate) fn reset(&mut self) -> Result<(), NoSQLError> {
         self.is_done = false;
         self.reached_limit = false;
         self.batch_counter = 0;
         self.consumed_capacity = Capacity::default();
+        self.batch_consumed.clear();
+        self.throttle_delay = Duration::ZERO;
         // clear prepared statement iterators
         self.prepared_statement.reset()
     }
@@ -420,10 +821,103 @@ impl QueryRequest {
         if self.prepared_statement.is_empty() {
             return ia_err!("cannot set bind variables: no prepared statement in QueryRequest");
         }
-        let fv = value.to_field_value();
+        let fv = value.try_to_field_value()?;
         self.prepared_statement.set_variable(name, &fv)
     }
 
+    /// Set a named timestamp bind variable, rounded to the given precision.
+    ///
+    /// `precision` is the number of fractional second digits (0-9), and should match the
+    /// precision of the column the variable is compared against, as in `TIMESTAMP(3)`. The
+    /// server rounds stored values to the precision of their column, so binding a value with
+    /// more precision can give unexpected results for exact matches and range boundaries.
+    /// For example, a row written with a timestamp of `12:00:00.0006` is stored as
+    /// `12:00:00.001` in a `TIMESTAMP(3)` column, so `ts = $t` with the same unrounded value
+    /// would not match it. With this method, the value is rounded exactly as the server
+    /// rounds it on insert.
+    ///
+    /// Timestamps are always sent to the server in UTC, regardless of their offset.
+    pub fn set_timestamp_variable(
+        &mut self,
+        name: &str,
+        value: &DateTime<FixedOffset>,
+        precision: u8,
+    ) -> Result<(), NoSQLError> {
+        if precision > 9 {
+            return ia_err!(
+                "timestamp precision must be between 0 and 9, got {}",
+                precision
+            );
+        }
+        self.set_variable(name, &round_timestamp(value, precision))
+    }
+
+    /// Set a pair of named timestamp bind variables for a range query, rounded to the given
+    /// precision.
+    ///
+    /// This is intended for half-open range queries on a timestamp column, such as
+    /// `WHERE t.ts >= $start AND t.ts < $end`. Both values are rounded the same way as by
+    /// [`set_timestamp_variable()`](QueryRequest::set_timestamp_variable()), so the range
+    /// boundaries compare against stored values exactly as the original values would: a row
+    /// is in the range if and only if the value it was written with, rounded by the server
+    /// to the column precision, is in the rounded range. `precision` should match the
+    /// precision of the column, as in `TIMESTAMP(3)`.
+    ///
+    /// ```no_run
+    /// # use oracle_nosql_rust_sdk::{Handle, QueryRequest};
+    /// # use chrono::DateTime;
+    /// # #[tokio::main]
+    /// # pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let handle = Handle::builder().build().await?;
+    /// let prep = QueryRequest::new(
+    ///     "declare $start timestamp; $end timestamp; \
+    ///      select * from events where ts >= $start and ts < $end",
+    /// )
+    /// .prepare_only()
+    /// .execute(&handle)
+    /// .await?;
+    /// let start = DateTime::parse_from_rfc3339("2024-05-06T00:00:00Z")?;
+    /// let end = DateTime::parse_from_rfc3339("2024-05-07T00:00:00Z")?;
+    /// let mut qreq = QueryRequest::new_prepared(&prep.prepared_statement());
+    /// qreq.set_timestamp_range("$start", "$end", &start, &end, 3)?;
+    /// let res = qreq.execute(&handle).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Returns an `IllegalArgument` error, and sets neither variable, if `start` is after
+    /// `end` or the precision is not between 0 and 9.
+    pub fn set_timestamp_range(
+        &mut self,
+        start_name: &str,
+        end_name: &str,
+        start: &DateTime<FixedOffset>,
+        end: &DateTime<FixedOffset>,
+        precision: u8,
+    ) -> Result<(), NoSQLError> {
+        if precision > 9 {
+            return ia_err!(
+                "timestamp precision must be between 0 and 9, got {}",
+                precision
+            );
+        }
+        if start > end {
+            return ia_err!(
+                "timestamp range start {} is after its end {}",
+                start.to_rfc3339(),
+                end.to_rfc3339()
+            );
+        }
+        if start_name == end_name {
+            return ia_err!("timestamp range start and end must be different variables");
+        }
+        if self.prepared_statement.is_empty() {
+            return ia_err!("cannot set bind variables: no prepared statement in QueryRequest");
+        }
+        self.set_variable(start_name, &round_timestamp(start, precision))?;
+        self.set_variable(end_name, &round_timestamp(end, precision))
+    }
+
     /// Set a positional bind variable for execution of a prepared query.
     ///
     /// This is similar to [`set_variable()`](QueryRequest::set_variable()) but uses integer-based positional parameters:
@@ -455,10 +949,27 @@ impl QueryRequest {
         if self.prepared_statement.is_empty() {
             return ia_err!("cannot set bind variables: no prepared statement in QueryRequest");
         }
-        let fv = value.to_field_value();
+        let fv = value.try_to_field_value()?;
         self.prepared_statement.set_variable_by_id(id, &fv)
     }
 
+    /// Get the variables currently bound for execution of a prepared query.
+    ///
+    /// Values set with [`set_variable()`](QueryRequest::set_variable()) and
+    /// [`set_variable_by_id()`](QueryRequest::set_variable_by_id()) stay bound across
+    /// executions of this request. See [`PreparedStatement::bound_variables()`] for the
+    /// names used and for details.
+    pub fn bound_variables(&self) -> &HashMap<String, FieldValue> {
+        self.prepared_statement.bound_variables()
+    }
+
+    /// Remove the value bound to a variable, so it is not sent with the next execution.
+    ///
+    /// Returns `true` if the variable was bound. See [`PreparedStatement::clear_variable()`].
+    pub fn clear_variable(&mut self, name: &str) -> bool {
+        self.prepared_statement.clear_variable(name)
+    }
+
     /// Execute the query to full completion.
     ///
     /// This is the preferred method for execution of a query. Internally, this method will loop
@@ -470,17 +981,29 @@ impl QueryRequest {
     /// [`MapValue`](crate::types::MapValue) structs in the order specified by the
     /// query statement.
     pub async fn execute(&mut self, h: &Handle) -> Result<QueryResult, NoSQLError> {
+        let cache_key = self.query_cache_key(h);
+        if let (Some(cache), Some(key)) = (&h.inner.query_cache, &cache_key) {
+            if let Some(mut qres) = cache.get(key) {
+                trace!("using cached query result");
+                qres.display_timezone = h.inner.builder.display_timezone;
+                self.is_done = true;
+                return Ok(qres);
+            }
+        }
         let mut iter_data = ReceiveIterData::default();
         let mut results: Vec<MapValue> = Vec::new();
         self.reset()?;
         while self.is_done == false {
             //println!("execute_internal doing next batch");
-            self.execute_batch_internal(h, &mut results, &mut iter_data)
+            self.execute_batch_restarting(h, &mut results, &mut iter_data)
                 .await?;
             self.batch_counter += 1;
             if self.batch_counter > 10000 {
                 panic!("Batch_internal infinite loop detected: self={:?}", self);
             }
+            if self.truncate_to_max_rows(&mut results) {
+                break;
+            }
         }
 
         if self.prepared_statement.is_empty() {
@@ -491,12 +1014,133 @@ impl QueryRequest {
         let mut qres = QueryResult {
             prepared_statement: self.prepared_statement.clone(),
             consumed: self.consumed_capacity.clone(),
+            batch_consumed: self.batch_consumed.clone(),
+            throttle_delay: self.throttle_delay,
+            display_timezone: h.inner.builder.display_timezone,
             rows: results,
+            from_cache: false,
         };
         let _ = qres.prepared_statement.reset();
+        if let (Some(cache), Some(key)) = (&h.inner.query_cache, cache_key) {
+            if qres.prepared_statement.is_read_only() {
+                cache.put(key, &qres);
+            }
+        }
         Ok(qres)
     }
 
+    /// Execute a query that is expected to return at most one row, such as a lookup by a
+    /// unique field.
+    ///
+    /// Returns the first row, or `None` if the query returned no rows. Execution stops as soon
+    /// as the first row is returned, so any further rows are never fetched from the server.
+    ///
+    /// If `strict` is `true`, the query is executed until a second row is found, and an
+    /// `IllegalState` error is returned if there is one. Use this when more than one row
+    /// indicates a data or query error, rather than an arbitrary choice of row.
+    ///
+    /// See also [`Handle::query_one()`].
+    pub async fn execute_one(
+        &mut self,
+        h: &Handle,
+        strict: bool,
+    ) -> Result<Option<MapValue>, NoSQLError> {
+        self.max_rows = Some(if strict { 2 } else { 1 });
+        let res = self.execute(h).await;
+        self.max_rows = None;
+        let mut rows = res?.take_rows();
+        if rows.len() > 1 {
+            return Err(NoSQLError::new(
+                IllegalState,
+                "query expected to return one row returned more than one row",
+            ));
+        }
+        Ok(rows.pop())
+    }
+
+    /// Execute a query that is expected to return at most one row, and convert the row to a
+    /// native struct.
+    ///
+    /// This is the same as [`execute_one()`](QueryRequest::execute_one()), with the row
+    /// converted using the [`macro@NoSQLRow`](crate::NoSQLRow) derive macro.
+    pub async fn execute_one_as<T: NoSQLRow + Default>(
+        &mut self,
+        h: &Handle,
+        strict: bool,
+    ) -> Result<Option<T>, NoSQLError> {
+        let row = match self.execute_one(h, strict).await? {
+            Some(r) => r,
+            None => {
+                return Ok(None);
+            }
+        };
+        let mut val = T::default();
+        if let Err(e) = val.from_map_value(&row) {
+            return Err(NoSQLError::new(
+                IllegalArgument,
+                &format!("could not convert MapValue to native struct: {}", e),
+            ));
+        }
+        Ok(Some(val))
+    }
+
+    /// Execute the query to full completion, passing each result row to a callback.
+    ///
+    /// The callback is called for each row, in order, as each batch of results arrives from
+    /// the server. Rows are not kept after the callback returns, so at most one batch of rows
+    /// is held in memory at a time. This can be used to fold the results of a large query,
+    /// such as to compute a sum or write rows to a file, without collecting all rows.
+    ///
+    /// If the callback returns an error, execution stops, no further batches are fetched,
+    /// and that error is returned.
+    ///
+    /// ```no_run
+    /// # use oracle_nosql_rust_sdk::{Handle, QueryRequest};
+    /// # #[tokio::main]
+    /// # pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let handle = Handle::builder().build().await?;
+    /// let mut total: i64 = 0;
+    /// QueryRequest::new("select amount from orders")
+    ///     .for_each(&handle, |row| {
+    ///         total += row.get_i64("amount").unwrap_or(0);
+    ///         Ok(())
+    ///     })
+    ///     .await?;
+    /// println!("total: {}", total);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn for_each<F>(&mut self, h: &Handle, mut f: F) -> Result<(), NoSQLError>
+    where
+        F: FnMut(MapValue) -> Result<(), NoSQLError>,
+    {
+        let mut iter_data = ReceiveIterData::default();
+        let mut rows: Vec<MapValue> = Vec::new();
+        self.reset()?;
+        while !self.is_done {
+            self.execute_batch_restarting(h, &mut rows, &mut iter_data)
+                .await?;
+            self.batch_counter += 1;
+            trace!("for_each: got {} rows", rows.len());
+            for row in rows.drain(..) {
+                f(row)?;
+            }
+        }
+        Ok(())
+    }
+
+    // If execute() is limited to max_rows, drop any rows after the limit.
+    // Returns true if the limit is reached and execution can stop.
+    pub(crate) fn truncate_to_max_rows(&self, results: &mut Vec<MapValue>) -> bool {
+        match self.max_rows {
+            Some(max) if results.len() >= max => {
+                results.truncate(max);
+                true
+            }
+            _ => false,
+        }
+    }
+
     /// Execute one batch of a query.
     ///
     /// This will execute at most one round-trip to the server. It should be called in a loop
@@ -515,6 +1159,70 @@ impl QueryRequest {
             .await
     }
 
+    /// Execute the query in a background task, sending result rows to a bounded channel.
+    ///
+    /// The returned receiver yields each row of the query results, in order. At most
+    /// `buffer_size` rows are held in the channel: the background task only fetches the next
+    /// batch of results from the server once the consumer has made room for its first row, so
+    /// a slow consumer slows down the query instead of causing results to build up in memory.
+    /// A `buffer_size` of 0 is treated as 1.
+    ///
+    /// If the query fails, the error is sent as the last item. If the receiver is dropped, the
+    /// background task stops, abandoning any request in progress. This must be called from
+    /// within a tokio runtime.
+    ///
+    /// ```no_run
+    /// # use oracle_nosql_rust_sdk::{Handle, QueryRequest};
+    /// # #[tokio::main]
+    /// # pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let handle = Handle::builder().build().await?;
+    /// let mut rx = QueryRequest::new("select * from users").execute_channel(&handle, 100);
+    /// while let Some(row) = rx.recv().await {
+    ///     let row = row?;
+    ///     println!("row: {:?}", row.get_i32("id"));
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn execute_channel(
+        mut self,
+        handle: &Handle,
+        buffer_size: usize,
+    ) -> mpsc::Receiver<Result<MapValue, NoSQLError>> {
+        let (tx, rx) = mpsc::channel(buffer_size.max(1));
+        let handle = handle.clone();
+        tokio::spawn(Deadline::inherit(Deadline::current(), async move {
+            while !self.is_done() {
+                // wait for room in the channel before fetching another batch
+                let permit = match tx.reserve().await {
+                    Ok(p) => p,
+                    Err(_) => return,
+                };
+                let mut rows: Vec<MapValue> = Vec::new();
+                let res = tokio::select! {
+                    res = self.execute_batch(&handle, &mut rows) => res,
+                    _ = tx.closed() => return,
+                };
+                if let Err(e) = res {
+                    permit.send(Err(e));
+                    return;
+                }
+                trace!("execute_channel: got {} rows", rows.len());
+                let mut rows = rows.into_iter();
+                match rows.next() {
+                    Some(row) => permit.send(Ok(row)),
+                    None => continue,
+                }
+                for row in rows {
+                    if tx.send(Ok(row)).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        }));
+        rx
+    }
+
     /// Determine if the query is complete.
     ///
     /// If using [`QueryRequest::execute_batch()`] in a loop, this method determines when
@@ -524,6 +1232,271 @@ impl QueryRequest {
         self.is_done
     }
 
+    // Make sure exactly one of a statement or a prepared statement was given.
+    // A request created with new() keeps its statement once it is prepared, so
+    // both may be set, but only if they are for the same SQL text.
+    fn check_statement(&self) -> Result<(), NoSQLError> {
+        match &self.statement {
+            None if self.prepared_statement.is_empty() => {
+                ia_err!("QueryRequest has no statement: create it with QueryRequest::new() or QueryRequest::new_prepared()")
+            }
+            Some(stmt) if stmt.trim().is_empty() => {
+                ia_err!("QueryRequest statement must not be empty")
+            }
+            Some(stmt)
+                if !self.prepared_statement.is_empty()
+                    && !self.prepared_statement.sql_text.is_empty()
+                    && self.prepared_statement.sql_text != *stmt =>
+            {
+                ia_err!(
+                    "QueryRequest cannot have both a statement and a prepared statement for a different statement (statement: '{}', prepared: '{}')",
+                    stmt,
+                    self.prepared_statement.sql_text
+                )
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Get the key for this query in the handle's query result cache, or None
+    // if its results should not be cached. Only queries that the server reported
+    // as SELECT statements are added to the cache, so a key found in the cache
+    // is always for a read-only query.
+    fn query_cache_key(&self, handle: &Handle) -> Option<QueryCacheKey> {
+        handle.inner.query_cache.as_ref()?;
+        if self.prepare_only
+            || self.include_query_plan
+            || self.include_query_schema
+            || self.target_shard.is_some()
+            // absolute reads ask for the latest data, which a cached result may not be
+            || self.consistency == Some(Consistency::Absolute)
+        {
+            return None;
+        }
+        let statement = match &self.statement {
+            Some(s) => s.as_str(),
+            None if !self.prepared_statement.sql_text.is_empty() => {
+                self.prepared_statement.sql_text.as_str()
+            }
+            None => return None,
+        };
+        Some(QueryCacheKey::new(
+            &self.compartment_id,
+            statement,
+            &self.prepared_statement.data.bind_variables,
+            self.string_collation,
+            self.max_rows,
+        ))
+    }
+
+    // Use a statement from the handle's prepare cache, if there is one for
+    // this query's SQL text.
+    fn use_cached_statement(&mut self, handle: &Handle) {
+        if self.prepare_only || self.include_query_plan || self.include_query_schema {
+            return;
+        }
+        let (cache, stmt) = match (&handle.inner.prepare_cache, &self.statement) {
+            (Some(c), Some(s)) => (c, s),
+            _ => return,
+        };
+        if let Some(ps) = cache.get(&PrepareCache::key(&self.compartment_id, stmt)) {
+            trace!("using cached prepared statement");
+            if let Some(ti) = &ps.topology_info {
+                self.topology_info = ti.clone();
+            }
+            self.prepared_statement = ps;
+            self.from_prepare_cache = true;
+        }
+    }
+
+    // Execute the next batch of the query. A cached statement may be stale: if the
+    // first batch fails because of it, before any rows were returned, the query is
+    // restarted once with a newly prepared statement. Bound variables are kept, and
+    // are only sent with a prepared statement, so if there are any the query is
+    // prepared again before it is restarted.
+    async fn execute_batch_restarting(
+        &mut self,
+        handle: &Handle,
+        results: &mut Vec<MapValue>,
+        iter_data: &mut ReceiveIterData,
+    ) -> Result<(), NoSQLError> {
+        loop {
+            match self
+                .execute_batch_internal(handle, results, iter_data)
+                .await
+            {
+                Err(e) if self.batch_counter == 0 && results.is_empty() => {
+                    let variables = match self.discard_cached_statement(handle, &e) {
+                        Some(v) => v,
+                        None => return Err(e),
+                    };
+                    *iter_data = ReceiveIterData::default();
+                    self.reset()?;
+                    if !variables.is_empty() {
+                        let consumed = self.prepare(handle).await?;
+                        self.consumed_capacity.add(&consumed);
+                        self.batch_consumed.push(consumed);
+                        self.prepared_statement.data.bind_variables = variables;
+                    }
+                }
+                res => return res,
+            }
+        }
+    }
+
+    // If the query failed using a cached prepared statement, with an error that
+    // may be caused by a change to the table since the statement was prepared,
+    // remove the statement from the cache so the query is prepared again.
+    // Returns the bound variables of the discarded statement if the query should
+    // be retried.
+    fn discard_cached_statement(
+        &mut self,
+        handle: &Handle,
+        err: &NoSQLError,
+    ) -> Option<HashMap<String, FieldValue>> {
+        if !self.from_prepare_cache
+            || !matches!(
+                err.code,
+                TableNotFound | IndexNotFound | IllegalArgument | SchemaChanged
+            )
+        {
+            return None;
+        }
+        if let (Some(cache), Some(stmt)) = (&handle.inner.prepare_cache, &self.statement) {
+            cache.remove(&PrepareCache::key(&self.compartment_id, stmt));
+        }
+        trace!(
+            "query using cached statement failed ({:?}): preparing again",
+            err.code
+        );
+        let variables = std::mem::take(&mut self.prepared_statement.data.bind_variables);
+        self.prepared_statement = PreparedStatement::default();
+        self.from_prepare_cache = false;
+        self.has_driver = false;
+        self.registers.clear();
+        Some(variables)
+    }
+
+    // Return a SchemaChanged error if the table of the prepared statement was altered
+    // since its version was recorded when it was prepared.
+    async fn check_table_schema(&self, handle: &Handle) -> Result<(), NoSQLError> {
+        let expected = match &self.prepared_statement.table_version {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+        if self.read_table_version(handle, true).await?.as_ref() != Some(expected) {
+            return Err(NoSQLError::new(
+                SchemaChanged,
+                &format!(
+                    "table '{}' was altered after the query was prepared: prepare the query again",
+                    self.prepared_statement
+                        .table_name
+                        .as_deref()
+                        .unwrap_or_default()
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    // Get the current version of the table of the prepared statement, if the
+    // server returned the table name: its ETag, or a hash of its schema if the
+    // server does not return ETags. If `cached` is true, a version read by this
+    // handle within its schema check interval is used instead of reading the table.
+    async fn read_table_version(
+        &self,
+        handle: &Handle,
+        cached: bool,
+    ) -> Result<Option<String>, NoSQLError> {
+        let table_name = match &self.prepared_statement.table_name {
+            Some(t) if !t.is_empty() => handle.logical_table_name(t),
+            _ => return Ok(None),
+        };
+        let key = format!("{}\0{}", self.compartment_id, table_name);
+        if cached {
+            let interval = handle
+                .inner
+                .builder
+                .schema_check_interval
+                .unwrap_or(SCHEMA_CHECK_INTERVAL);
+            if let Some((read_at, version)) = handle.inner.table_versions.lock().unwrap().get(&key)
+            {
+                if read_at.elapsed() < interval {
+                    return Ok(Some(version.clone()));
+                }
+            }
+        }
+        let table = GetTableRequest::new(&table_name)
+            .compartment_id(&self.compartment_id)
+            .timeout(&handle.get_timeout(&self.timeout))
+            .execute(handle)
+            .await?;
+        let version = match table.match_etag {
+            Some(etag) if !etag.is_empty() => etag,
+            _ => {
+                let mut hasher = DefaultHasher::new();
+                table.schema.hash(&mut hasher);
+                format!("{:016x}", hasher.finish())
+            }
+        };
+        handle
+            .inner
+            .table_versions
+            .lock()
+            .unwrap()
+            .insert(key, (Instant::now(), version.clone()));
+        Ok(Some(version))
+    }
+
+    // Prepare the query, if it has not been prepared yet, so that bind variables
+    // can be set before it is executed. A statement from the handle's prepare
+    // cache is used if there is one. Returns the capacity consumed by preparing.
+    pub(crate) async fn prepare(&mut self, handle: &Handle) -> Result<Capacity, NoSQLError> {
+        if self.prepared_statement.is_empty() {
+            self.use_cached_statement(handle);
+        }
+        if !self.prepared_statement.is_empty() {
+            return Ok(Capacity::default());
+        }
+        let stmt = match &self.statement {
+            Some(s) => s.clone(),
+            None => return ia_err!("no statement or prepared statement"),
+        };
+        let mut prep = QueryRequest::new(&stmt)
+            .prepare_only()
+            .include_query_plan(self.include_query_plan)
+            .include_query_schema(self.include_query_schema)
+            .compartment_id(&self.compartment_id);
+        prep.timeout = self.timeout;
+        // boxed, since execute() calls this method
+        let pres = Box::pin(prep.execute(handle)).await?;
+        self.prepared_statement = pres.prepared_statement();
+        self.topology_info = prep.topology_info.clone();
+        Ok(pres.consumed())
+    }
+
+    // Prepare the query, if it has not been prepared yet, and verify that the
+    // target shard is part of the current topology.
+    async fn check_target_shard(&mut self, handle: &Handle) -> Result<(), NoSQLError> {
+        let shard_id = match self.target_shard {
+            Some(s) => s,
+            None => return Ok(()),
+        };
+        if self.prepared_statement.is_empty() {
+            let consumed = self.prepare(handle).await?;
+            self.consumed_capacity.add(&consumed);
+            self.batch_consumed.push(consumed);
+        }
+        if !self.topology_info.shard_ids.contains(&shard_id) {
+            return ia_err!(
+                "invalid target shard {}: current shards are {:?}",
+                shard_id,
+                self.topology_info.shard_ids
+            );
+        }
+        Ok(())
+    }
+
     pub(crate) async fn execute_batch_internal(
         &mut self,
         handle: &Handle,
@@ -540,6 +1513,8 @@ impl QueryRequest {
 
         // internal queries do not use plan iterators/etc - they just return plain results.
         if self.is_internal == false {
+            self.check_statement()?;
+
             /*
              * The following "if" may be true for advanced queries only. For
              * such queries, the "if" will be true (i.e., the QueryRequest will
@@ -548,6 +1523,29 @@ impl QueryRequest {
              * empty QueryResult. Actual computation of a result batch will take
              * place when the app calls getResults() on the QueryResult.
              */
+            if self.prepared_statement.is_empty() {
+                self.use_cached_statement(handle);
+            }
+
+            // the table of a query is only known once it is prepared, so with a
+            // table prefix, queries are prepared and checked before they are run
+            if !handle.inner.builder.table_prefix.is_empty()
+                && !self.prepare_only
+                && self.prepared_statement.is_empty()
+            {
+                let consumed = self.prepare(handle).await?;
+                self.consumed_capacity.add(&consumed);
+                self.batch_consumed.push(consumed);
+            }
+
+            if self.target_shard.is_some() && !self.has_driver {
+                self.check_target_shard(handle).await?;
+            }
+
+            if self.check_schema && self.batch_counter == 0 {
+                self.check_table_schema(handle).await?;
+            }
+
             if self.has_driver {
                 //trace("QueryRequest has QueryDriver", 2);
                 return self.get_results(handle, results).await;
@@ -590,19 +1588,45 @@ impl QueryRequest {
             //self.batch_counter += 1;
         }
 
-        let mut w: Writer = Writer::new();
-        w.write_i16(handle.inner.serial_version);
         let timeout = handle.get_timeout(&self.timeout);
+        let mut w = handle.request_writer();
         self.serialize_internal(&mut w, &timeout)?;
         let mut opts = SendOptions {
-            timeout: timeout,
+            timeout,
             retryable: true,
             compartment_id: self.compartment_id.clone(),
+            retry_throttling: true,
             ..Default::default()
         };
-        let mut r = handle.send_and_receive(w, &mut opts).await?;
+        let res = handle.send_and_receive(w, &mut opts).await;
+        self.throttle_delay += opts.throttle_delay;
+        let was_prepared = !self.prepared_statement.is_empty();
+        let mut r = match res {
+            Ok(r) => r,
+            Err(e) if !was_prepared => {
+                return Err(e.into_query_compile_error());
+            }
+            Err(e) => return Err(e),
+        };
         self.continuation_key = None;
         self.nson_deserialize(&mut r, results, iter_data)?;
+        if !was_prepared && !self.is_internal {
+            if let Some(t) = &self.prepared_statement.table_name {
+                if !t.is_empty() {
+                    handle.check_table_prefix(t)?;
+                }
+            }
+            if self.check_schema {
+                self.prepared_statement.table_version =
+                    self.read_table_version(handle, false).await?;
+            }
+            if let (Some(cache), Some(stmt)) = (&handle.inner.prepare_cache, &self.statement) {
+                if !self.prepared_statement.is_empty() {
+                    let key = PrepareCache::key(&self.compartment_id, stmt);
+                    cache.put(key, &self.prepared_statement);
+                }
+            }
+        }
         if self.continuation_key.is_none() {
             trace!("continuation key is None, setting is_done");
             self.is_done = true;
@@ -621,11 +1645,8 @@ impl QueryRequest {
         ns.end_header();
         ns.start_payload();
 
-        //TODO writeConsistency(ns, rq.getConsistency());
-        //if (rq.getDurability() != null) {
-        //writeMapField(ns, DURABILITY,
-        //getDurability(rq.getDurability()));
-        //}
+        ns.write_consistency(self.consistency.unwrap_or_default());
+        ns.write_durability(&self.durability);
 
         if self.max_read_kb > 0 {
             ns.write_i32_field(MAX_READ_KB, self.max_read_kb as i32);
@@ -642,7 +1663,7 @@ impl QueryRequest {
         //writeMapField(ns, TRACE_AT_LOG_FILES, rq.getLogFileTracing());
         //writeMapField(ns, BATCH_COUNTER, rq.getBatchCounter());
 
-        ns.write_i32_field(QUERY_VERSION, 3); // TODO: QUERY_V4
+        ns.write_i32_field(QUERY_VERSION, QUERY_V3 as i32);
         if self.prepared_statement.is_empty() == false {
             ns.write_bool_field(IS_PREPARED, true);
             ns.write_bool_field(IS_SIMPLE_QUERY, self.prepared_statement.is_simple());
@@ -662,6 +1683,12 @@ impl QueryRequest {
         } else {
             if let Some(s) = &self.statement {
                 ns.write_string_field(STATEMENT, &s);
+                if self.include_query_plan {
+                    ns.write_bool_field(GET_QUERY_PLAN, true);
+                }
+                if self.include_query_schema {
+                    ns.write_bool_field(GET_QUERY_SCHEMA, true);
+                }
             } else {
                 return ia_err!("no statement or prepared statement");
             }
@@ -673,13 +1700,16 @@ impl QueryRequest {
             }
         }
 
-        //writeLongMapFieldNZ(ns, SERVER_MEMORY_CONSUMPTION,
-        //rq.getMaxServerMemoryConsumption());
+        if self.max_server_memory > 0 {
+            ns.write_i64_field(SERVER_MEMORY_CONSUMPTION, self.max_server_memory);
+        }
         //writeMathContext(ns, rq.getMathContext());
 
         if self.shard_id > -1 {
             //println!("Q: SHARD_ID={}", self.shard_id);
             ns.write_i32_field(SHARD_ID, self.shard_id);
+        } else if let Some(shard_id) = self.target_shard {
+            ns.write_i32_field(SHARD_ID, shard_id);
         }
         //if (queryVersion >= QueryDriver.QUERY_V4) {
         //if (rq.getQueryName() != null) {
@@ -696,7 +1726,6 @@ impl QueryRequest {
     }
 
     // TODO
-    //theRCB.tallyRateLimitDelayedMs(result.getRateLimitDelayedMs());
     //theRCB.tallyRetryStats(result.getRetryStats());
     // TODO: support deduping of results
 
@@ -739,6 +1768,7 @@ impl QueryRequest {
         let is_prepared_request = !self.prepared_statement.is_empty();
 
         let mut ti = TopologyInfo::default();
+        let mut batch_consumed = Capacity::default();
         self.continuation_key = None;
         iter_data.continuation_key = None;
 
@@ -753,6 +1783,7 @@ impl QueryRequest {
                 CONSUMED => {
                     let cap = walker.read_nson_consumed_capacity()?;
                     self.consumed_capacity.add(&cap);
+                    batch_consumed = cap;
                 }
                 QUERY_RESULTS => {
                     self.add_results(&mut walker, results)?;
@@ -773,6 +1804,9 @@ impl QueryRequest {
                         return ia_err!("got prepared query in result for already prepared query");
                     }
                     self.prepared_statement.statement = walker.read_nson_binary()?;
+                    if let Some(stmt) = &self.statement {
+                        self.prepared_statement.sql_text = stmt.clone();
+                    }
                 }
                 DRIVER_QUERY_PLAN => {
                     if is_prepared_request {
@@ -798,12 +1832,12 @@ impl QueryRequest {
                     self.prepared_statement.query_schema = walker.read_nson_string()?;
                 }
                 QUERY_OPERATION => {
-                    // TODO: is this an enum? try_from()?
-                    self.prepared_statement.operation = walker.read_nson_i32()? as u8;
+                    self.prepared_statement.operation = Some(walker.read_nson_i32()? as u8);
                 }
                 TOPOLOGY_INFO => {
                     //println!("deser: TOPOLOGY_INFO");
-                    self.prepared_statement.topology_info = Some(walker.read_nson_topology_info()?);
+                    self.prepared_statement.topology_info =
+                        Some(Arc::new(walker.read_nson_topology_info()?));
                 }
                 /* QUERY_V3 and earlier return topo differently */
                 PROXY_TOPO_SEQNUM => {
@@ -844,8 +1878,10 @@ impl QueryRequest {
             }
         }
 
+        self.batch_consumed.push(batch_consumed);
+
         if ti.is_valid() {
-            self.prepared_statement.topology_info = Some(ti);
+            self.prepared_statement.topology_info = Some(Arc::new(ti));
         }
 
         if let Some(ti) = &self.prepared_statement.topology_info {
@@ -953,3 +1989,12 @@ impl QueryRequest {
         self.registers[reg as usize] = val;
     }
 }
+
+// Get the name of the first index used in a query plan, from its
+// `"index used" : "name"` entry.
+fn index_from_plan(plan: &str) -> Option<String> {
+    let rest = &plan[plan.find("\"index used\"")? + "\"index used\"".len()..];
+    let rest = rest.trim_start().strip_prefix(':')?.trim_start();
+    let rest = rest.strip_prefix('"')?;
+    Some(rest[..rest.find('"')?].to_string())
+}
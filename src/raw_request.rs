@@ -0,0 +1,156 @@
+//
+// Copyright (c) 2024, 2025 Oracle and/or its affiliates. All rights reserved.
+//
+// Licensed under the Universal Permissive License v 1.0 as shown at
+//  https://oss.oracle.com/licenses/upl/
+//
+use crate::error::NoSQLError;
+use crate::handle::Handle;
+use crate::handle::SendOptions;
+use crate::nson::*;
+use crate::types::MapValue;
+use crate::writer::Writer;
+use std::result::Result;
+use std::time::Duration;
+
+/// **Advanced, unstable:** a low-level request for server operations that the SDK does
+/// not yet support.
+///
+/// New operations are sometimes added to the Oracle NoSQL Database service before the SDK
+/// has a typed request for them. `RawRequest` sends any operation code with a payload given
+/// as a [`MapValue`], and returns the response map as-is, without any interpretation. It
+/// uses the same connection, authentication and retry handling as all other requests, and
+/// server errors in the response are returned as a [`NoSQLError`].
+///
+/// The payload field names and types and the operation code must match the server's
+/// protocol exactly, and are not validated by the SDK. The protocol is not a stable, public
+/// interface: code using this request may need changes for new server or SDK versions, and
+/// should be replaced by the typed request once one is available.
+///
+/// ```no_run
+/// # use oracle_nosql_rust_sdk::{Handle, RawRequest};
+/// # use oracle_nosql_rust_sdk::types::MapValue;
+/// # async fn run(handle: &Handle) -> Result<(), Box<dyn std::error::Error>> {
+/// // 11 is the operation code of a GetTable request
+/// let res = RawRequest::new(11)
+///     .table_name("users")
+///     .payload(MapValue::new())
+///     .execute(handle)
+///     .await?;
+/// println!("table state: {:?}", res.get_i32("as"));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default, Debug)]
+pub struct RawRequest {
+    pub(crate) op_code: i32,
+    pub(crate) table_name: String,
+    pub(crate) payload: MapValue,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) compartment_id: String,
+    pub(crate) retryable: bool,
+}
+
+impl RawRequest {
+    /// Create a new `RawRequest` for the given protocol operation code.
+    pub fn new(op_code: i32) -> RawRequest {
+        RawRequest {
+            op_code,
+            ..Default::default()
+        }
+    }
+
+    /// Specify the table name to send in the request header, if the operation applies to a table.
+    pub fn table_name(mut self, table_name: &str) -> Self {
+        self.table_name = table_name.to_string();
+        self
+    }
+
+    /// Specify the request payload. Each field of the map is written as a field of the
+    /// request payload, with its given type.
+    pub fn payload(mut self, payload: MapValue) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    /// Specify the timeout value for the request.
+    ///
+    /// This is optional.
+    /// If set, it must be greater than or equal to 1 millisecond, otherwise an
+    /// IllegalArgument error will be returned.
+    /// If not set, the default timeout value configured for the [`Handle`](crate::HandleBuilder::timeout()) is used.
+    pub fn timeout(mut self, t: &Duration) -> Self {
+        self.timeout = Some(*t);
+        self
+    }
+
+    /// Cloud Service only: set the name or id of a compartment to be used for this operation.
+    ///
+    /// See [`PutRequest::compartment_id()`](crate::PutRequest::compartment_id()) for details.
+    pub fn compartment_id(mut self, compartment_id: &str) -> Self {
+        self.compartment_id = compartment_id.to_string();
+        self
+    }
+
+    /// Specify whether the operation is idempotent, and may be safely retried internally
+    /// after a transient error. The default is `false`.
+    pub fn retryable(mut self, retryable: bool) -> Self {
+        self.retryable = retryable;
+        self
+    }
+
+    /// Execute the request, returning the full response map.
+    pub async fn execute(&self, h: &Handle) -> Result<MapValue, NoSQLError> {
+        let mut w = h.request_writer();
+        let timeout = h.get_timeout(&self.timeout);
+        self.nson_serialize(&mut w, &timeout);
+        let mut opts = SendOptions {
+            timeout,
+            retryable: self.retryable,
+            compartment_id: self.compartment_id.clone(),
+            ..Default::default()
+        };
+        let mut r = h.send_and_receive(w, &mut opts).await?;
+        r.read_field_value()?.get_map_value()
+    }
+
+    pub(crate) fn nson_serialize(&self, w: &mut Writer, timeout: &Duration) {
+        let mut ns = NsonSerializer::start_request(w);
+        ns.start_header();
+        ns.write_i32_field(VERSION, V4_VERSION);
+        if !self.table_name.is_empty() {
+            ns.write_table_name_field(&self.table_name);
+        }
+        ns.write_i32_field(OP_CODE, self.op_code);
+        ns.write_i32_field(TIMEOUT, timeout.as_millis() as i32);
+        ns.end_header();
+
+        ns.start_payload();
+        for (k, v) in self.payload.iter() {
+            ns.write_field(k, v);
+        }
+        ns.end_payload();
+
+        ns.end_request();
+    }
+}
+
+impl Handle {
+    /// **Advanced, unstable:** send a raw protocol request for an operation the SDK does not
+    /// yet support, and return the full response map.
+    ///
+    /// This is a shortcut for a [`RawRequest`] with the default timeout. See [`RawRequest`]
+    /// for details and caveats.
+    pub async fn raw_request(
+        &self,
+        op_code: i32,
+        table_name: &str,
+        payload: MapValue,
+    ) -> Result<MapValue, NoSQLError> {
+        RawRequest::new(op_code)
+            .table_name(table_name)
+            .payload(payload)
+            .execute(self)
+            .await
+    }
+}
@@ -6,7 +6,9 @@
 //
 use bigdecimal::BigDecimal;
 use bigdecimal::Num;
+use bytes::Bytes;
 use chrono::{DateTime, FixedOffset};
+use std::collections::HashMap;
 use std::result;
 use std::str;
 
@@ -23,26 +25,37 @@ use crate::types::MapValue;
 // bytes to construct in-memory representations according to the Binary Protocol
 // which defines the data exchange format between the Oracle NoSQL Database
 // proxy and drivers.
+//
+// The buffer is reference counted, so binary values can be handed out as
+// slices of the buffer without copying (see read_binary_shared()).
 pub struct Reader {
     // The underlying byte buffer.
-    pub buf: Vec<u8>,
+    pub buf: Bytes,
     pub offset: usize,
 }
 
 impl Reader {
     pub fn new() -> Reader {
         Reader {
-            buf: Vec::with_capacity(256),
+            buf: Bytes::new(),
             offset: 0,
         }
     }
 
     pub fn from_bytes(mut self, val: &[u8]) -> Self {
-        self.buf.clear();
-        self.buf.extend_from_slice(val);
+        self.buf = Bytes::copy_from_slice(val);
+        self.offset = 0;
         self
     }
 
+    // Use the given buffer directly, without copying.
+    pub(crate) fn from_shared(val: Bytes) -> Self {
+        Reader {
+            buf: val,
+            offset: 0,
+        }
+    }
+
     pub fn read_byte(&mut self) -> result::Result<u8, NoSQLError> {
         //println!("Read_byte: offset={} len={}", self.offset, self.buf.len());
         if self.offset >= self.buf.len() {
@@ -139,18 +152,26 @@ impl Reader {
     }
 
     pub fn read_packed_i32(&mut self) -> Result<i32, NoSQLError> {
-        packed_integer::read_packed_i32(&mut self.buf, &mut self.offset)
+        packed_integer::read_packed_i32(&self.buf, &mut self.offset)
     }
 
     pub fn read_packed_i64(&mut self) -> Result<i64, NoSQLError> {
-        packed_integer::read_packed_i64(&mut self.buf, &mut self.offset)
+        packed_integer::read_packed_i64(&self.buf, &mut self.offset)
     }
 
     pub fn read_string(&mut self) -> Result<String, NoSQLError> {
-        let slen = packed_integer::read_packed_i32(&mut self.buf, &mut self.offset)?;
-        if slen <= 0 {
-            // TODO: how to simulate null string for len < 0?
-            return Ok("".to_string());
+        Ok(self.read_nullable_string()?.unwrap_or_default())
+    }
+
+    // Read a string that may be null, written with a length of -1. An empty
+    // string (length 0) is returned as Some("").
+    pub(crate) fn read_nullable_string(&mut self) -> Result<Option<String>, NoSQLError> {
+        let slen = packed_integer::read_packed_i32(&self.buf, &mut self.offset)?;
+        if slen < 0 {
+            return Ok(None);
+        }
+        if slen == 0 {
+            return Ok(Some(String::new()));
         }
         let ulen = slen as usize;
         if (self.offset + ulen) > self.buf.len() {
@@ -165,19 +186,17 @@ impl Reader {
         match str::from_utf8(&self.buf[self.offset..(self.offset + ulen)]) {
             Ok(s) => {
                 self.offset += ulen;
-                return Ok(std::string::String::from(s));
-            }
-            Err(_) => {
-                return Err(NoSQLError::new(
-                    BadProtocolMessage,
-                    "invalid utf8 in read_string",
-                ));
+                Ok(Some(std::string::String::from(s)))
             }
+            Err(_) => Err(NoSQLError::new(
+                BadProtocolMessage,
+                "invalid utf8 in read_string",
+            )),
         }
     }
 
     pub fn read_timestamp(&mut self) -> Result<DateTime<FixedOffset>, NoSQLError> {
-        let slen = packed_integer::read_packed_i32(&mut self.buf, &mut self.offset)?;
+        let slen = packed_integer::read_packed_i32(&self.buf, &mut self.offset)?;
         if slen <= 0 {
             return Err(NoSQLError::new(
                 BadProtocolMessage,
@@ -206,7 +225,7 @@ impl Reader {
     }
 
     pub fn read_binary(&mut self) -> Result<Vec<u8>, NoSQLError> {
-        let slen = packed_integer::read_packed_i32(&mut self.buf, &mut self.offset)?;
+        let slen = packed_integer::read_packed_i32(&self.buf, &mut self.offset)?;
         if slen <= 0 {
             return Ok(Vec::new());
         }
@@ -221,6 +240,24 @@ impl Reader {
         Ok(Vec::from(&self.buf[(self.offset - ulen)..self.offset]))
     }
 
+    // Same as read_binary(), but returns a slice of the underlying buffer
+    // instead of a copy of the data.
+    pub fn read_binary_shared(&mut self) -> Result<Bytes, NoSQLError> {
+        let slen = packed_integer::read_packed_i32(&self.buf, &mut self.offset)?;
+        if slen <= 0 {
+            return Ok(Bytes::new());
+        }
+        let ulen = slen as usize;
+        if (self.offset + ulen) > self.buf.len() {
+            return Err(NoSQLError::new(
+                BadProtocolMessage,
+                "read_binary_shared reached end of byte buffer",
+            ));
+        }
+        self.offset += ulen;
+        Ok(self.buf.slice((self.offset - ulen)..self.offset))
+    }
+
     pub(crate) fn read_field_value(&mut self) -> Result<FieldValue, NoSQLError> {
         // read field type
         let u = self.read_byte()?;
@@ -245,8 +282,9 @@ impl Reader {
                 return Ok(FieldValue::Double(f64));
             }
             FieldType::String => {
-                let str = self.read_string()?;
-                return Ok(FieldValue::String(str));
+                // a null string is never returned as an empty string
+                let str = self.read_nullable_string()?;
+                Ok(str.map_or(FieldValue::Null, FieldValue::String))
             }
             FieldType::Array => {
                 let arr = self.read_array()?;
@@ -364,6 +402,29 @@ impl Reader {
         Ok(mv)
     }
 
+    // Same as read_map(), but top-level binary values are not copied into
+    // the returned map: they are added to `binaries` as slices of the
+    // underlying buffer instead.
+    pub(crate) fn read_map_shared_binary(
+        &mut self,
+        binaries: &mut HashMap<String, Bytes>,
+    ) -> Result<MapValue, NoSQLError> {
+        let _num_bytes = self.read_i32()?;
+        let num_items = self.read_i32()?;
+        let mut mv = MapValue::new();
+        for _i in 0..num_items {
+            let key = self.read_string()?;
+            if self.offset < self.buf.len() && self.buf[self.offset] == FieldType::Binary as u8 {
+                self.offset += 1;
+                binaries.insert(key, self.read_binary_shared()?);
+                continue;
+            }
+            let val = self.read_field_value()?;
+            mv.put_field_value(&key, val);
+        }
+        Ok(mv)
+    }
+
     pub(crate) fn reset(&mut self) {
         self.offset = 0;
     }
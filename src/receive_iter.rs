@@ -6,6 +6,7 @@
 //
 use oracle_nosql_rust_sdk_derive::add_planiter_fields;
 
+use crate::deadline::Deadline;
 use crate::error::ia_err;
 use crate::error::NoSQLError;
 use crate::error::NoSQLErrorCode::BadProtocolMessage;
@@ -22,6 +23,7 @@ use std::cmp::Ordering;
 use std::collections::{BTreeSet, HashSet, VecDeque};
 use std::mem::take;
 use std::result::Result;
+use tokio::task::JoinSet;
 use tracing::trace;
 
 // ReceiveIter requests and receives results from the proxy. For sorting
@@ -136,20 +138,21 @@ impl Clone for ReceiveIterData {
     }
 }
 
-#[derive(Debug, Clone, Default, Eq, PartialEq, TryFromPrimitive)]
+/// How a query is distributed across the store, from [`QueryDescription::distribution_kind()`](crate::QueryDescription::distribution_kind()).
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, TryFromPrimitive)]
 #[repr(i16)]
-pub(crate) enum DistributionKind {
-    // The query predicates specify a complete shard key, and as a result,
-    // the query goes to a single partition and uses the primary index for
-    // its execution.
+pub enum DistributionKind {
+    /// The query predicates specify a complete shard key, and as a result,
+    /// the query goes to a single partition and uses the primary index for
+    /// its execution.
     #[default]
     SinglePartition = 0,
-    // The query uses the primary index for its execution, but does not
-    // specify a complete shard key. As a result, it must be sent to all
-    // partitions.
+    /// The query uses the primary index for its execution, but does not
+    /// specify a complete shard key. As a result, it must be sent to all
+    /// partitions.
     AllPartitions = 1,
-    // The query uses a secondary index for its execution. As a result,
-    // it must be sent to all shards.
+    /// The query uses a secondary index for its execution. As a result,
+    /// it must be sent to all shards.
     AllShards = 2,
 }
 
@@ -184,6 +187,18 @@ struct RemoteScanner {
     sort_specs: Vec<SortSpec>,
 }
 
+// Ordering of scanners in the sorted_scanners set used for the merge sort.
+//
+// Scanners with no local results sort first, so they are fetched from before
+// any results are returned. Otherwise scanners are ordered by the sort fields
+// of their first cached result. If those are equal, ties are broken by the
+// shard or partition id of the scanner (lower id first), and within a single
+// scanner results are always returned in the order they were received. This
+// makes the order of rows with equal sort values deterministic across runs.
+//
+// Note that the tie-breaker is also required for correctness: BTreeSet
+// considers Equal elements to be the same element, so two scanners whose
+// first results have equal sort values would otherwise collide on insert.
 impl Ord for RemoteScanner {
     fn cmp(&self, other: &Self) -> Ordering {
         if self.has_local_results() == false {
@@ -204,7 +219,7 @@ impl Ord for RemoteScanner {
         if comp != Ordering::Equal {
             return comp;
         }
-        Ordering::Equal
+        self.tie_break(other)
     }
 }
 
@@ -240,6 +255,18 @@ impl RemoteScanner {
         }
     }
 
+    // Deterministic tie-breaker for scanners whose current results have
+    // equal sort values: shard scanners before partition scanners, then by id.
+    fn tie_break(&self, other: &Self) -> Ordering {
+        if self.is_for_shard != other.is_for_shard {
+            if self.is_for_shard {
+                return Ordering::Less;
+            }
+            return Ordering::Greater;
+        }
+        self.shard_or_part_id.cmp(&other.shard_or_part_id)
+    }
+
     //pub fn reset(&mut self) {
     //self.more_remote_results = true;
     //self.continuation_key = None;
@@ -305,8 +332,24 @@ impl RemoteScanner {
         req: &mut QueryRequest,
         handle: &Handle,
     ) -> Result<(), NoSQLError> {
+        let mut req_copy = self.fetch_request(req);
+
+        trace!("\nReceiveIter executing internal request copy:\n");
+        let mut vr: Vec<MapValue> = Vec::new();
+        req_copy
+            .execute_batch_internal(handle, &mut vr, data)
+            .await?;
+        self.fetch_done(req, req_copy, vr)
+    }
+
+    // Create the internal request used to fetch the next batch of results for this scanner.
+    fn fetch_request(&self, req: &mut QueryRequest) -> QueryRequest {
         req.batch_counter += 1;
         let mut req_copy = req.copy_for_internal();
+        // The continuation key is copied, not shared like the topology: it is small
+        // (a serialized position in one shard or partition), it is replaced by the
+        // key returned with the batch, and keeping it here lets the batch be fetched
+        // again if this request fails.
         req_copy.continuation_key = self.continuation_key.clone();
         if self.is_for_shard {
             req_copy.shard_id = self.shard_or_part_id;
@@ -321,22 +364,27 @@ impl RemoteScanner {
         //assert(doesSort());
         //reqCopy.setVirtualScan(theVirtualScan);
         //}
-
-        trace!("\nReceiveIter executing internal request copy:\n");
-        let mut vr: Vec<MapValue> = Vec::new();
         req_copy
-            .execute_batch_internal(handle, &mut vr, data)
-            .await?;
+    }
+
+    // Add the results of an internal request created by fetch_request().
+    fn fetch_done(
+        &mut self,
+        req: &mut QueryRequest,
+        req_copy: QueryRequest,
+        vr: Vec<MapValue>,
+    ) -> Result<(), NoSQLError> {
         trace!("EBI returned {} results : {:?}", vr.len(), vr);
         self.add_results(VecDeque::from(vr), req_copy.continuation_key);
         req.consumed_capacity.add(&req_copy.consumed_capacity);
+        req.batch_consumed.extend(req_copy.batch_consumed);
+        req.throttle_delay += req_copy.throttle_delay;
 
         // TODO: if (theVirtualScan != null && theVirtualScan.isFirstBatch()) {
         // theVirtualScan.theFirstBatch = false;
         // }
         // theVirtualScans = result.getVirtualScans();
 
-        //theRCB.tallyRateLimitDelayedMs(result.getRateLimitDelayedMs());
         //theRCB.tallyRetryStats(result.getRetryStats());
         //origRequest.addQueryTraces(result.getQueryTraces());
 
@@ -382,6 +430,9 @@ impl RemoteScanner {
 //}
 
 impl ReceiveIter {
+    pub(crate) fn distribution_kind(&self) -> DistributionKind {
+        self.distribution_kind
+    }
     pub fn new(r: &mut Reader) -> Result<Self, NoSQLError> {
         // state_pos is now ignored, in the rust driver implementation
         let rr = r.read_i32()?; // result_reg
@@ -410,9 +461,31 @@ impl ReceiveIter {
         if self.data.state == PlanIterState::Open {
             return Ok(());
         }
+        for ss in self.sort_specs.iter_mut() {
+            ss.collation = req.string_collation;
+        }
+        if req.server_side_only {
+            self.check_server_side_only(req)?;
+        }
         if self.does_sort() && self.distribution_kind == DistributionKind::AllPartitions {
+            if req.target_shard.is_some() {
+                return ia_err!(
+                    "a target shard cannot be used for queries that sort across all partitions"
+                );
+            }
             // Nothing to do; done later
         } else if self.does_sort() && self.distribution_kind == DistributionKind::AllShards {
+            if let Some(shard_id) = req.target_shard {
+                self.data.sorted_scanners.insert(RemoteScanner::new(
+                    true,
+                    shard_id,
+                    &self.sort_fields,
+                    &self.sort_specs,
+                ));
+                self.data.base_vsid = shard_id;
+                self.data.state = PlanIterState::Open;
+                return Ok(());
+            }
             let ti = &req.topology_info;
             if !ti.is_valid() {
                 return ia_err!("invalid TopologyInfo passed into ReceiveIterData::reset");
@@ -465,6 +538,35 @@ impl ReceiveIter {
     fn does_sort(&self) -> bool {
         self.sort_fields.len() > 0
     }
+    // Return an error if results must be merge-sorted or deduplicated by the SDK.
+    // Sorting queries on a single partition, or restricted to a single shard, are
+    // sorted by the server.
+    fn check_server_side_only(&self, req: &QueryRequest) -> Result<(), NoSQLError> {
+        if self.does_sort()
+            && self.distribution_kind != DistributionKind::SinglePartition
+            && req.target_shard.is_none()
+        {
+            let scope = match self.distribution_kind {
+                DistributionKind::AllShards => "all shards",
+                _ => "all partitions",
+            };
+            return ia_err!(
+                "query requires the SDK to merge-sort results from {} on {:?}, \
+                 which is not allowed with server_side_only: include the complete shard key \
+                 in the query, or use a target shard",
+                scope,
+                self.sort_fields
+            );
+        }
+        if !self.prim_key_fields.is_empty() {
+            return ia_err!(
+                "query requires the SDK to remove duplicate results using a multi-key index, \
+                 which is not allowed with server_side_only: use an index that is not on an \
+                 array or map, or include the complete primary key in the query"
+            );
+        }
+        Ok(())
+    }
     pub fn get_result(&self, req: &mut QueryRequest) -> FieldValue {
         trace!("ReceiveIter.get_result");
         req.get_result(self.result_reg)
@@ -576,6 +678,33 @@ impl ReceiveIter {
                 continue;
             }
 
+            if req.max_concurrent_fetches > 1 {
+                // Scanners with no local results sort first, so any others
+                // that also need a fetch are at the front of the set.
+                let mut scanners = vec![scanner];
+                while scanners.len() < req.max_concurrent_fetches {
+                    match self.data.sorted_scanners.first() {
+                        Some(sc) if !sc.has_local_results() => {
+                            let sc = self.data.sorted_scanners.pop_first().unwrap();
+                            if !sc.is_done() {
+                                scanners.push(sc);
+                            }
+                        }
+                        _ => break,
+                    }
+                }
+                if scanners.len() > 1 {
+                    trace!(
+                        "ReceiveIter.sorting_next() fetching from {} scanners concurrently",
+                        scanners.len()
+                    );
+                    self.fetch_concurrent(scanners, req, handle).await?;
+                    req.reached_limit = true;
+                    break;
+                }
+                scanner = scanners.pop().unwrap();
+            }
+
             let mut data = take(&mut self.data);
 
             trace!("ReceiveIter.sorting_next() calling next scanner fetch");
@@ -609,6 +738,59 @@ impl ReceiveIter {
         Ok(false)
     }
 
+    // Fetch the next batch of results for all of the given scanners, running at
+    // most req.max_concurrent_fetches requests at the same time, and put the
+    // scanners back into the sorted set.
+    async fn fetch_concurrent(
+        &mut self,
+        mut scanners: Vec<RemoteScanner>,
+        req: &mut QueryRequest,
+        handle: &Handle,
+    ) -> Result<(), NoSQLError> {
+        let mut tasks = JoinSet::new();
+        let deadline = Deadline::current();
+        for (i, scanner) in scanners.iter().enumerate() {
+            let mut req_copy = scanner.fetch_request(req);
+            let h = handle.clone();
+            tasks.spawn(Deadline::inherit(deadline, async move {
+                let mut vr: Vec<MapValue> = Vec::new();
+                // These fetches are never in sort phase 1, so the iter data
+                // returned by each one is not needed.
+                let mut data = ReceiveIterData::default();
+                let res = req_copy
+                    .execute_batch_internal(&h, &mut vr, &mut data)
+                    .await;
+                (i, req_copy, vr, res)
+            }));
+        }
+        let mut err: Option<NoSQLError> = None;
+        while let Some(joined) = tasks.join_next().await {
+            let (i, req_copy, vr, res) = match joined {
+                Ok(j) => j,
+                Err(e) => {
+                    return ia_err!("internal query fetch failed: {}", e);
+                }
+            };
+            if let Err(e) = res {
+                // TODO: if err is retryable, put back and try again
+                err.get_or_insert(e);
+                continue;
+            }
+            if let Err(e) = scanners[i].fetch_done(req, req_copy, vr) {
+                err.get_or_insert(e);
+            }
+        }
+        if let Some(e) = err {
+            return Err(e);
+        }
+        for scanner in scanners {
+            if !scanner.is_done() {
+                self.data.sorted_scanners.insert(scanner);
+            }
+        }
+        Ok(())
+    }
+
     async fn init_partition_sort(
         &mut self,
         req: &mut QueryRequest,
@@ -627,8 +809,9 @@ impl ReceiveIter {
             .await?;
         let mut results = VecDeque::from(vr);
         req.consumed_capacity.add(&req_copy.consumed_capacity);
+        req.batch_consumed.extend(req_copy.batch_consumed);
+        req.throttle_delay += req_copy.throttle_delay;
 
-        //rcb.tallyRateLimitDelayedMs(result.getRateLimitDelayedMs());
         //rcb.tallyRetryStats(result.getRetryStats());
 
         // For each partition P that was accessed during the execution of
@@ -714,3 +897,200 @@ impl ReceiveIter {
         Ok(w.buf)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+
+    fn scanner(id: i32, vals: &[i32]) -> RemoteScanner {
+        let mut sc = RemoteScanner::new(
+            false,
+            id,
+            &vec!["a".to_string()],
+            &vec![SortSpec::default()],
+        );
+        let mut res = VecDeque::new();
+        for v in vals {
+            res.push_back(MapValue::new().i32("a", *v).i32("pid", id));
+        }
+        sc.add_results(res, None);
+        sc
+    }
+
+    #[test]
+    fn test_sorted_scanners_tie_break() {
+        // insert in varying orders: equal sort values must not collide
+        // and must always come out ordered by partition id
+        for order in [[3, 1, 2], [2, 3, 1], [1, 2, 3]] {
+            let mut set: BTreeSet<RemoteScanner> = BTreeSet::new();
+            for id in order {
+                set.insert(scanner(id, &[5, 6]));
+            }
+            assert_eq!(set.len(), 3);
+            let mut pids = Vec::new();
+            while let Some(mut sc) = set.pop_first() {
+                if let Some(mv) = sc.next_local() {
+                    pids.push(mv.get_i32("pid").unwrap());
+                    if !sc.is_done() {
+                        set.insert(sc);
+                    }
+                }
+            }
+            assert_eq!(pids, vec![1, 2, 3, 1, 2, 3]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sorted_scanners_string_collation() -> Result<(), NoSQLError> {
+        use crate::mock_server::MockServer;
+        use crate::types::{compare_atomics_total_order, StringCollation};
+
+        // U+FF21 sorts before U+1F600 by code point, but after it by UTF-16 code units
+        let server = MockServer::start(vec![]).await;
+        let handle = server.handle().await;
+        for (collation, expected) in [
+            (StringCollation::Server, ["\u{1F600}", "\u{FF21}"]),
+            (StringCollation::CodePoint, ["\u{FF21}", "\u{1F600}"]),
+        ] {
+            let req = QueryRequest::new("select * from t order by a").string_collation(collation);
+            let mut iter = ReceiveIter {
+                distribution_kind: DistributionKind::AllPartitions,
+                sort_fields: vec!["a".to_string()],
+                sort_specs: vec![SortSpec::default()],
+                ..Default::default()
+            };
+            iter.open(&req, &handle)?;
+            assert_eq!(iter.sort_specs[0].collation, collation);
+
+            let mut set: BTreeSet<RemoteScanner> = BTreeSet::new();
+            for (id, val) in [(1, "\u{FF21}"), (2, "\u{1F600}")] {
+                let mut sc = RemoteScanner::new(false, id, &iter.sort_fields, &iter.sort_specs);
+                sc.add_results(VecDeque::from([MapValue::new().str("a", val)]), None);
+                set.insert(sc);
+            }
+            let mut vals = Vec::new();
+            while let Some(mut sc) = set.pop_first() {
+                if let Some(mv) = sc.next_local() {
+                    vals.push(mv.get_string("a").unwrap());
+                }
+            }
+            assert_eq!(vals, expected);
+        }
+
+        // the total order of values compares strings by code point, the same way
+        // as for strings in the Basic Multilingual Plane
+        let s = |v: &str| FieldValue::String(v.to_string());
+        let cmp = |a: &str, b: &str| compare_atomics_total_order(&s(a), &s(b), false);
+        assert_eq!(cmp("\u{FF21}", "\u{1F600}"), cmp("a", "b"));
+        assert_eq!(s("\u{FF21}").cmp(&s("\u{1F600}")), s("a").cmp(&s("b")));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_server_side_only() -> Result<(), NoSQLError> {
+        use crate::error::NoSQLErrorCode;
+        use crate::mock_server::MockServer;
+
+        let server = MockServer::start(vec![]).await;
+        let handle = server.handle().await;
+        let mut req = QueryRequest::new("select * from t order by a").server_side_only(true);
+        Arc::make_mut(&mut req.topology_info).shard_ids = vec![1, 2];
+        let iter = |kind: DistributionKind, sort: bool, dedup: bool| ReceiveIter {
+            distribution_kind: kind,
+            sort_fields: if sort { vec!["a".to_string()] } else { vec![] },
+            sort_specs: if sort {
+                vec![SortSpec::default()]
+            } else {
+                vec![]
+            },
+            prim_key_fields: if dedup {
+                vec!["id".to_string()]
+            } else {
+                vec![]
+            },
+            ..Default::default()
+        };
+
+        // sorted by the server
+        iter(DistributionKind::SinglePartition, true, false).open(&req, &handle)?;
+        iter(DistributionKind::AllShards, false, false).open(&req, &handle)?;
+
+        // merge-sorted or deduplicated by the SDK
+        for (kind, sort, dedup) in [
+            (DistributionKind::AllShards, true, false),
+            (DistributionKind::AllPartitions, true, false),
+            (DistributionKind::AllShards, false, true),
+        ] {
+            let err = iter(kind, sort, dedup).open(&req, &handle).unwrap_err();
+            assert_eq!(err.code, NoSQLErrorCode::IllegalArgument);
+            assert!(err.message.contains("server_side_only"), "{}", err.message);
+        }
+
+        // a single shard is sorted by the server
+        let mut single = req.target_shard(1);
+        Arc::make_mut(&mut single.topology_info).shard_ids = vec![1, 2];
+        iter(DistributionKind::AllShards, true, false).open(&single, &handle)?;
+
+        // allowed by default
+        let req = QueryRequest::new("select * from t order by a");
+        iter(DistributionKind::AllPartitions, true, true).open(&req, &handle)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_max_concurrent_fetches() -> Result<(), NoSQLError> {
+        use crate::mock_server::MockServer;
+        use crate::nson::QUERY_RESULTS;
+        use std::time::Duration;
+
+        let sort_fields = vec!["a".to_string()];
+        let sort_specs = vec![SortSpec::default()];
+        for (max, expected) in [(0, 1), (1, 1), (2, 2), (4, 4), (8, 4)] {
+            let responses = (0..4)
+                .map(|v| {
+                    let row = FieldValue::Map(MapValue::new().i32("a", v));
+                    MockServer::response(
+                        MapValue::new().column(QUERY_RESULTS, FieldValue::Array(vec![row])),
+                    )
+                })
+                .collect();
+            let server = MockServer::start_with_delay(responses, Duration::from_millis(50)).await;
+            let handle = server.handle().await;
+            let mut req =
+                QueryRequest::new("select * from t order by a").max_concurrent_fetches(max);
+            req.prepared_statement.statement = vec![1, 2, 3];
+
+            let mut iter = ReceiveIter {
+                distribution_kind: DistributionKind::AllShards,
+                sort_fields: sort_fields.clone(),
+                sort_specs: sort_specs.clone(),
+                ..Default::default()
+            };
+            for shard in 1..=4 {
+                iter.data.sorted_scanners.insert(RemoteScanner::new(
+                    true,
+                    shard,
+                    &sort_fields,
+                    &sort_specs,
+                ));
+            }
+
+            // one batch: fetches from up to max scanners, then stops
+            assert!(!iter.next(&mut req, &handle).await?);
+            assert!(req.reached_limit);
+            assert_eq!(server.requests(), expected);
+            assert_eq!(server.max_in_flight(), expected);
+            assert_eq!(req.batch_counter as usize, expected);
+            assert_eq!(iter.data.sorted_scanners.len(), 4);
+            let fetched = iter
+                .data
+                .sorted_scanners
+                .iter()
+                .filter(|sc| sc.has_local_results())
+                .count();
+            assert_eq!(fetched, expected);
+        }
+        Ok(())
+    }
+}
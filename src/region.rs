@@ -11,7 +11,22 @@ use std::result::Result;
 use crate::auth_common::file_utils::expand_user_home;
 use crate::error::{ia_err, NoSQLError};
 
-#[derive(Default, Debug, Clone, Deserialize)]
+/// An Oracle Cloud Infrastructure region where the NoSQL Cloud Service is available.
+///
+/// Regions are resolved the same way as by
+/// [`HandleBuilder::cloud_region()`](crate::HandleBuilder::cloud_region()): first from the
+/// SDK's internal list of regions, then from the `OCI_REGION_METADATA` environment variable,
+/// then from a `~/.oci/regions-config.json` file. This can be used to validate region input
+/// or to present a list of regions without creating a [`Handle`](crate::Handle).
+///
+/// ```
+/// use oracle_nosql_rust_sdk::Region;
+/// let r = Region::from_id("us-ashburn-1")?;
+/// assert_eq!(r.realm(), "oc1");
+/// assert_eq!(r.nosql_endpoint(), "nosql.us-ashburn-1.oci.oraclecloud.com");
+/// # Ok::<(), oracle_nosql_rust_sdk::NoSQLError>(())
+/// ```
+#[derive(Default, Debug, Clone, PartialEq, Eq, Deserialize)]
 pub struct Region {
     // The main identifier string, e.g. "us-ashburn-1"
     #[serde(rename = "regionIdentifier")]
@@ -27,18 +42,13 @@ pub struct Region {
     realm_domain: String,
 }
 
-pub fn string_to_region(id: &str) -> Result<Region, NoSQLError> {
+pub(crate) fn string_to_region(id: &str) -> Result<Region, NoSQLError> {
     // Note: this could be faster if we build a hash table, but it's
     // expected that this will be called infrequently (typically once on startup).
     let lower_id = id.to_lowercase();
-    for r in STATIC_REGIONS {
+    for r in STATIC_REGIONS.iter() {
         if r.id == lower_id {
-            return Ok(Region {
-                id: r.id.to_string(),
-                key: r.key.to_string(),
-                realm: r.realm.to_string(),
-                realm_domain: r.realm_domain.to_string(),
-            });
+            return Ok(r.to_region());
         }
     }
 
@@ -63,30 +73,89 @@ pub fn string_to_region(id: &str) -> Result<Region, NoSQLError> {
     ia_err!("no region found matching identifier '{}'", id)
 }
 
+// Find the region whose NoSQL endpoint is the given host name.
+pub(crate) fn region_for_host(host: &str) -> Option<Region> {
+    Region::all_regions()
+        .into_iter()
+        .find(|r| r.nosql_endpoint().eq_ignore_ascii_case(host))
+}
+
 impl Region {
+    /// Find a region by its identifier, such as `us-ashburn-1`.
+    ///
+    /// The identifier is not case sensitive. An `IllegalArgument` error is returned if no
+    /// region matches the identifier.
+    pub fn from_id(id: &str) -> Result<Region, NoSQLError> {
+        string_to_region(id)
+    }
+
+    /// Get all known regions.
+    ///
+    /// This includes the SDK's internal list of regions, followed by any additional regions
+    /// given in the `OCI_REGION_METADATA` environment variable or in a
+    /// `~/.oci/regions-config.json` file. Each region identifier appears only once, resolved
+    /// in the same order as [`Region::from_id()`].
+    pub fn all_regions() -> Vec<Region> {
+        let mut regions: Vec<Region> = STATIC_REGIONS.iter().map(|r| r.to_region()).collect();
+        let extra = get_oci_region_metadata()
+            .into_iter()
+            .chain(get_oci_regions_list().unwrap_or_default());
+        for r in extra {
+            if !regions.iter().any(|e| e.id == r.id) {
+                regions.push(r);
+            }
+        }
+        regions
+    }
+
+    /// Get the region identifier, such as `us-ashburn-1`.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Get the region key, a short code such as `iad`.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Get the identifier of the realm the region belongs to, such as `oc1`.
+    pub fn realm(&self) -> &str {
+        &self.realm
+    }
+
+    /// Get the domain of the realm the region belongs to, such as `oraclecloud.com`.
+    pub fn realm_domain(&self) -> &str {
+        &self.realm_domain
+    }
+
+    /// Get the host name of the NoSQL Cloud Service endpoint for this region.
     pub fn nosql_endpoint(&self) -> String {
-        return format!("nosql.{}.oci.{}", self.id, self.realm_domain).to_string();
+        format!("nosql.{}.oci.{}", self.id, self.realm_domain)
     }
-    #[allow(dead_code)]
+
+    /// Get the host name of the authentication service endpoint for this region.
     pub fn auth_endpoint(&self) -> String {
-        return format!("auth.{}.{}", self.id, self.realm_domain).to_string();
+        format!("auth.{}.{}", self.id, self.realm_domain)
     }
-    #[allow(dead_code)]
-    pub fn to_string(&self) -> String {
-        format!(
+}
+
+impl std::fmt::Display for Region {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
             "id={} key={} realm={} realmDomain={}",
             self.id, self.key, self.realm, self.realm_domain
         )
     }
 }
 
-pub fn get_oci_region_metadata() -> Option<Region> {
+pub(crate) fn get_oci_region_metadata() -> Option<Region> {
     let val = env::var("OCI_REGION_METADATA").ok()?;
     let region: Region = serde_json::from_str(&val).ok()?;
     Some(region)
 }
 
-pub fn get_oci_regions_list() -> Option<Vec<Region>> {
+pub(crate) fn get_oci_regions_list() -> Option<Vec<Region>> {
     let json = file_to_string("~/.oci/regions-config.json").ok()?;
     let regions: Vec<Region> = serde_json::from_str(&json).ok()?;
     Some(regions)
@@ -106,7 +175,11 @@ pub(crate) fn file_to_string(filename: &str) -> Result<String, NoSQLError> {
 
 #[test]
 fn test_region_lookup() -> Result<(), Box<dyn std::error::Error>> {
-    let _ = string_to_region("us-ashburn-1")?;
+    let r = Region::from_id("US-Ashburn-1")?;
+    assert_eq!(r.id(), "us-ashburn-1");
+    assert_eq!(r.key(), "iad");
+    assert_eq!(r.realm(), "oc1");
+    assert_eq!(r.nosql_endpoint(), "nosql.us-ashburn-1.oci.oraclecloud.com");
     if string_to_region("us-foobar-1").is_ok() {
         return Err("us-foobar-1 should have returned an error".into());
     }
@@ -119,6 +192,9 @@ fn test_region_lookup() -> Result<(), Box<dyn std::error::Error>> {
 	}"#;
     std::env::set_var("OCI_REGION_METADATA", eval);
     let _ = string_to_region("us-foobar-1")?;
+    let all = Region::all_regions();
+    assert_eq!(all.iter().filter(|r| r.id() == "us-foobar-1").count(), 1);
+    assert_eq!(all.iter().filter(|r| r.id() == "us-ashburn-1").count(), 1);
     std::env::remove_var("OCI_REGION_METADATA");
     if string_to_region("us-foobar-1").is_ok() {
         return Err("us-foobar-1 should have returned an error".into());
@@ -161,6 +237,17 @@ struct StaticRegion {
     realm_domain: &'static str,
 }
 
+impl StaticRegion {
+    fn to_region(&self) -> Region {
+        Region {
+            id: self.id.to_string(),
+            key: self.key.to_string(),
+            realm: self.realm.to_string(),
+            realm_domain: self.realm_domain.to_string(),
+        }
+    }
+}
+
 // === Begin autogenerated regions ===
 const STATIC_REGIONS: [StaticRegion; 82] = [
     StaticRegion {
@@ -0,0 +1,130 @@
+//
+// Copyright (c) 2024, 2025 Oracle and/or its affiliates. All rights reserved.
+//
+// Licensed under the Universal Permissive License v 1.0 as shown at
+//  https://oss.oracle.com/licenses/upl/
+//
+use crate::types::{Consistency, Durability};
+use std::time::Duration;
+
+/// A reusable set of common request settings.
+///
+/// `RequestOptions` bundles the settings shared by many requests, so they can be defined
+/// once and applied to any number of requests with their `options()` method:
+///
+/// ```no_run
+/// # use oracle_nosql_rust_sdk::{GetRequest, PutRequest, RequestOptions};
+/// # use oracle_nosql_rust_sdk::types::*;
+/// # use std::time::Duration;
+/// # #[tokio::main]
+/// # pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let handle = oracle_nosql_rust_sdk::Handle::builder().build().await?;
+/// let opts = RequestOptions::new()
+///     .timeout(&Duration::from_secs(2))
+///     .consistency(Consistency::Absolute)
+///     .compartment_id("mycompartment");
+///
+/// let getres = GetRequest::new("users")
+///     .key(MapValue::new().i32("id", 10))
+///     .options(&opts)
+///     .execute(&handle)
+///     .await?;
+///
+/// // settings given directly to a request take precedence over its options
+/// let putres = PutRequest::new("users")
+///     .value(MapValue::new().i32("id", 10).str("name", "jane"))
+///     .options(&opts)
+///     .timeout(&Duration::from_secs(5))
+///     .execute(&handle)
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Applying options only sets values that were not already set on the request, so
+/// settings given directly to a request always take precedence, whether they are given
+/// before or after the call to `options()`. Settings that do not apply to a request type
+/// are ignored: for example, consistency is only used by reads and queries, durability
+/// only by writes and queries, and namespace only by table and index requests.
+#[derive(Clone, Debug, Default)]
+pub struct RequestOptions {
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) consistency: Option<Consistency>,
+    pub(crate) compartment_id: Option<String>,
+    pub(crate) namespace: Option<String>,
+    pub(crate) durability: Option<Durability>,
+}
+
+impl RequestOptions {
+    /// Create a new, empty `RequestOptions`.
+    pub fn new() -> RequestOptions {
+        RequestOptions::default()
+    }
+
+    /// Specify the timeout value for requests.
+    ///
+    /// See [`HandleBuilder::timeout()`](crate::HandleBuilder::timeout()) for the default value.
+    pub fn timeout(mut self, t: &Duration) -> Self {
+        self.timeout = Some(*t);
+        self
+    }
+
+    /// Specify the [`Consistency`] for read operations and queries.
+    pub fn consistency(mut self, c: Consistency) -> Self {
+        self.consistency = Some(c);
+        self
+    }
+
+    /// Cloud Service only: set the name or id of a compartment to be used for requests.
+    ///
+    /// See [`GetRequest::compartment_id()`](crate::GetRequest::compartment_id()) for details.
+    pub fn compartment_id(mut self, compartment_id: &str) -> Self {
+        self.compartment_id = Some(compartment_id.to_string());
+        self
+    }
+
+    /// On-premises only: set the namespace to be used for table and index requests.
+    pub fn namespace(mut self, namespace: &str) -> Self {
+        self.namespace = Some(namespace.to_string());
+        self
+    }
+
+    /// On-premises only: set the [`Durability`] for write operations and queries that
+    /// modify rows.
+    pub fn durability(mut self, d: Durability) -> Self {
+        self.durability = Some(d);
+        self
+    }
+
+    // Helpers used by the options() methods of requests, to set a value only
+    // if it was not already set on the request.
+    pub(crate) fn apply_timeout(&self, t: &mut Option<Duration>) {
+        if t.is_none() {
+            *t = self.timeout;
+        }
+    }
+
+    pub(crate) fn apply_consistency(&self, c: &mut Option<Consistency>) {
+        if c.is_none() {
+            *c = self.consistency;
+        }
+    }
+
+    pub(crate) fn apply_compartment_id(&self, compartment_id: &mut String) {
+        if let (true, Some(c)) = (compartment_id.is_empty(), &self.compartment_id) {
+            *compartment_id = c.clone();
+        }
+    }
+
+    pub(crate) fn apply_namespace(&self, namespace: &mut String) {
+        if let (true, Some(n)) = (namespace.is_empty(), &self.namespace) {
+            *namespace = n.clone();
+        }
+    }
+
+    pub(crate) fn apply_durability(&self, d: &mut Option<Durability>) {
+        if d.is_none() {
+            *d = self.durability;
+        }
+    }
+}
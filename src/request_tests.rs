@@ -6,6 +6,9 @@
 //
 use crate::get_request::*;
 use crate::put_request::*;
+use crate::query_request::*;
+use crate::system_request::*;
+use crate::table_request::*;
 use crate::{nson::NsonRequest, reader::Reader, types::*, writer::Writer};
 use std::error::Error;
 use std::time::Duration;
@@ -118,3 +121,4180 @@ fn test_put_result() -> Result<(), Box<dyn Error>> {
     println!("PutResult: cons={:?}", resp1.consumed);
     Ok(())
 }
+
+#[tokio::test]
+async fn test_put_expiration_time() -> Result<(), Box<dyn Error>> {
+    use crate::mock_server::MockServer;
+    use crate::nson::{EXPIRATION, ROW_VERSION};
+
+    // 2024-03-02T00:00:00Z
+    let expires = 1709337600000i64;
+    let server = MockServer::start(vec![
+        MockServer::response(
+            MapValue::new()
+                .column(ROW_VERSION, FieldValue::Binary(vec![1]))
+                .column(EXPIRATION, FieldValue::Long(expires)),
+        ),
+        MockServer::response(MapValue::new().column(ROW_VERSION, FieldValue::Binary(vec![2]))),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let put = || {
+        PutRequest::new("users")
+            .value(MapValue::new().i32("id", 1))
+            .ttl(&Duration::from_secs(86400))
+    };
+    let res = put().execute(&handle).await?;
+    let t = res.expiration_time().expect("expiration time");
+    assert_eq!(t.to_rfc3339(), "2024-03-02T00:00:00+00:00");
+    let res = put().execute(&handle).await?;
+    assert_eq!(res.expiration_time(), None);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_onprem_auth_tls() -> Result<(), Box<dyn Error>> {
+    use crate::mock_server::MockServer;
+    use crate::Handle;
+
+    let login = format!(
+        "{{\"token\":\"abc\",\"expireAt\":{}}}",
+        chrono::Utc::now().timestamp_millis() + 3600000
+    );
+    let server = MockServer::start_tls(vec![
+        MockServer::json_response(&login),
+        MockServer::response(MapValue::new()),
+        MockServer::json_response(&login),
+        MockServer::response(MapValue::new()),
+    ])
+    .await;
+    let get = || GetRequest::new("users").key(MapValue::new().i32("id", 1));
+
+    // onprem_auth() before endpoint(): the login must still use the https endpoint,
+    // and trust the certificate added to the handle
+    let handle = Handle::builder()
+        .onprem_auth("user", "pass")?
+        .endpoint(&server.endpoint)?
+        .add_cert_from_pemfile(&MockServer::tls_cert_path())?
+        .build()
+        .await?;
+    get().execute(&handle).await?;
+    let heads = server.request_heads();
+    assert_eq!(heads.len(), 2);
+    let login_head = heads[0].to_lowercase();
+    assert!(
+        login_head.starts_with("get /v2/nosql/security/login "),
+        "{}",
+        heads[0]
+    );
+    // base64("user:pass")
+    assert!(
+        login_head.contains("authorization: basic dxnlcjpwyxnz"),
+        "{}",
+        heads[0]
+    );
+    let data_head = heads[1].to_lowercase();
+    assert!(
+        data_head.starts_with("post /v2/nosql/data "),
+        "{}",
+        heads[1]
+    );
+    assert!(
+        data_head.contains("authorization: bearer abc"),
+        "{}",
+        heads[1]
+    );
+
+    // a handle that does not trust the certificate cannot log in
+    let handle = Handle::builder()
+        .endpoint(&server.endpoint)?
+        .onprem_auth("user", "pass")?
+        .build()
+        .await?;
+    assert!(get().execute(&handle).await.is_err());
+    assert_eq!(server.requests(), 2);
+
+    // ... unless certificates are not checked at all
+    let handle = Handle::builder()
+        .endpoint(&server.endpoint)?
+        .onprem_auth("user", "pass")?
+        .danger_accept_invalid_certs(true)?
+        .build()
+        .await?;
+    get().execute(&handle).await?;
+    assert_eq!(server.requests(), 4);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_onprem_token_refresh_ahead() -> Result<(), Box<dyn Error>> {
+    use crate::mock_server::MockServer;
+    use crate::Handle;
+
+    MockServer::pause_time();
+    let now = chrono::Utc::now().timestamp_millis();
+    let server = MockServer::start(vec![
+        MockServer::json_response(&format!(
+            "{{\"token\":\"abc\",\"expireAt\":{}}}",
+            now + 1000
+        )),
+        MockServer::response(MapValue::new()),
+        MockServer::json_response(&format!(
+            "{{\"token\":\"def\",\"expireAt\":{}}}",
+            now + 3600000
+        )),
+        MockServer::response(MapValue::new()),
+    ])
+    .await;
+    let handle = Handle::builder()
+        .onprem_auth("user", "pass")?
+        .endpoint(&server.endpoint)?
+        .build()
+        .await?;
+    let get = || GetRequest::new("users").key(MapValue::new().i32("id", 1));
+    get().execute(&handle).await?;
+    assert_eq!(server.requests(), 2);
+
+    // the token is renewed in the background at 80% of its lifetime, before
+    // any request needs it
+    tokio::time::sleep(Duration::from_millis(1200)).await;
+    assert_eq!(server.requests(), 3);
+    get().execute(&handle).await?;
+    let heads: Vec<String> = server
+        .request_heads()
+        .iter()
+        .map(|h| h.to_lowercase())
+        .collect();
+    assert_eq!(heads.len(), 4);
+    assert!(heads[0].starts_with("get /v2/nosql/security/login "));
+    assert!(heads[1].contains("authorization: bearer abc"));
+    assert!(heads[2].starts_with("get /v2/nosql/security/renew "));
+    assert!(heads[2].contains("authorization: bearer abc"));
+    assert!(heads[3].contains("authorization: bearer def"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_retry_after() -> Result<(), Box<dyn Error>> {
+    use crate::error::{parse_retry_after, NoSQLErrorCode};
+    use crate::mock_server::MockServer;
+    use std::time::Instant;
+
+    assert_eq!(parse_retry_after(" 3 "), Some(Duration::from_secs(3)));
+    assert_eq!(
+        parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+        Some(Duration::ZERO)
+    );
+    let later = (chrono::Utc::now() + chrono::Duration::seconds(120)).to_rfc2822();
+    let d = parse_retry_after(&later).unwrap();
+    assert!(d > Duration::from_secs(110) && d <= Duration::from_secs(120));
+    assert_eq!(parse_retry_after("soon"), None);
+
+    let server = MockServer::start(vec![
+        MockServer::retry_after_response(503, "1"),
+        MockServer::response(MapValue::new()),
+        MockServer::retry_after_response(429, "60"),
+        MockServer::http_status_response(429, "slow down"),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let get = || {
+        GetRequest::new("users")
+            .key(MapValue::new().i32("id", 1))
+            .timeout(&Duration::from_secs(5))
+    };
+
+    // the request is resent after the requested delay
+    let start = Instant::now();
+    get().execute(&handle).await?;
+    assert!(start.elapsed() >= Duration::from_secs(1));
+    assert_eq!(server.requests(), 2);
+
+    // a delay longer than the request timeout is returned to the caller
+    let start = Instant::now();
+    let err = get().execute(&handle).await.unwrap_err();
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert_eq!(err.code, NoSQLErrorCode::OperationLimitExceeded);
+    assert_eq!(err.retry_after(), Some(Duration::from_secs(60)));
+    assert_eq!(server.requests(), 3);
+
+    // no delay without the header
+    let err = get().execute(&handle).await.unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::OperationLimitExceeded);
+    assert_eq!(err.retry_after(), None);
+    assert_eq!(server.requests(), 4);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_table_exists() -> Result<(), Box<dyn Error>> {
+    use crate::error::NoSQLErrorCode;
+    use crate::mock_server::MockServer;
+    use crate::nson::{TABLE_NAME, TABLE_STATE};
+
+    let table = |state: TableState| {
+        MockServer::response(
+            MapValue::new()
+                .str(TABLE_NAME, "users")
+                .i32(TABLE_STATE, state as i32),
+        )
+    };
+    let server = MockServer::start(vec![
+        table(TableState::Active),
+        table(TableState::Creating),
+        table(TableState::Dropped),
+        MockServer::error_response(NoSQLErrorCode::TableNotFound, "table not found"),
+        MockServer::error_response(NoSQLErrorCode::InsufficientPermission, "not allowed"),
+    ])
+    .await;
+    let handle = server.handle().await;
+    assert!(handle.table_exists("users").await?);
+    assert!(handle.table_exists("users").await?);
+    assert!(!handle.table_exists("users").await?);
+    assert!(!handle.table_exists("users").await?);
+    let err = handle.table_exists("users").await.unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::InsufficientPermission);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_table_storage_usage() -> Result<(), Box<dyn Error>> {
+    use crate::mock_server::MockServer;
+    use crate::nson::{
+        LIMITS, OP_CODE, READ_UNITS, STORAGE_GB, TABLE_NAME, TABLE_USAGE, WRITE_UNITS,
+    };
+    use crate::types::OpCode;
+
+    let table = || {
+        MockServer::response(
+            MapValue::new().str(TABLE_NAME, "users").column(
+                LIMITS,
+                FieldValue::Map(
+                    MapValue::new()
+                        .i32(READ_UNITS, 100)
+                        .i32(WRITE_UNITS, 100)
+                        .i32(STORAGE_GB, 50),
+                ),
+            ),
+        )
+    };
+    let server = MockServer::start(vec![
+        table(),
+        table(),
+        MockServer::response(MapValue::new().str(TABLE_NAME, "users").column(
+            TABLE_USAGE,
+            FieldValue::Array(vec![FieldValue::Map(
+                MapValue::new().i32(STORAGE_GB, 12).i32(READ_UNITS, 3),
+            )]),
+        )),
+    ])
+    .await;
+    let handle = server.handle().await;
+
+    // usage is only requested if asked for
+    let res = GetTableRequest::new("users").execute(&handle).await?;
+    assert_eq!(res.storage_used_gb(), None);
+    assert_eq!(res.storage_used_percent(), None);
+    assert_eq!(server.requests(), 1);
+
+    let res = GetTableRequest::new("users")
+        .include_storage_usage(true)
+        .execute(&handle)
+        .await?;
+    assert_eq!(res.limits().unwrap().storage_gb, 50);
+    assert_eq!(res.storage_used_gb(), Some(12.0));
+    assert_eq!(res.storage_used_percent(), Some(24.0));
+    assert_eq!(server.requests(), 3);
+    assert_eq!(
+        server.request_header(2).get_i32(OP_CODE),
+        Some(OpCode::GetTableUsage as i32)
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_conditional_success() -> Result<(), Box<dyn Error>> {
+    use crate::delete_request::DeleteRequest;
+    use crate::mock_server::MockServer;
+    use crate::nson::{EXISTING_VERSION, RETURN_INFO, ROW_VERSION, SUCCESS};
+
+    let applied =
+        || MockServer::response(MapValue::new().column(ROW_VERSION, FieldValue::Binary(vec![2])));
+    let not_applied = || {
+        MockServer::response(MapValue::new().column(
+            RETURN_INFO,
+            FieldValue::Map(MapValue::new().column(EXISTING_VERSION, FieldValue::Binary(vec![1]))),
+        ))
+    };
+    let deleted = |success: bool| {
+        MockServer::response(MapValue::new().column(SUCCESS, success).column(
+            RETURN_INFO,
+            FieldValue::Map(MapValue::new().column(EXISTING_VERSION, FieldValue::Binary(vec![1]))),
+        ))
+    };
+    let server = MockServer::start(vec![
+        applied(),
+        not_applied(),
+        applied(),
+        not_applied(),
+        applied(),
+        not_applied(),
+        deleted(true),
+        deleted(false),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let put = || PutRequest::new("users").value(MapValue::new().i32("id", 1));
+    let version: crate::Version = vec![1];
+
+    for applies in [true, false] {
+        let res = put().if_absent().return_row(true).execute(&handle).await?;
+        assert_eq!(res.success(), applies);
+        assert_eq!(res.existing_version().is_some(), !applies);
+    }
+    for applies in [true, false] {
+        let res = put().if_present().execute(&handle).await?;
+        assert_eq!(res.success(), applies);
+    }
+    for applies in [true, false] {
+        let res = put().if_version(&version).execute(&handle).await?;
+        assert_eq!(res.success(), applies);
+    }
+    for applies in [true, false] {
+        let res = DeleteRequest::new("users", MapValue::new().i32("id", 1))
+            .if_version(&version)
+            .return_row(true)
+            .execute(&handle)
+            .await?;
+        assert_eq!(res.success(), applies);
+        assert_eq!(res.existing_version(), Some(&vec![1]));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_system_request_builders() -> Result<(), Box<dyn Error>> {
+    let r = SystemRequest::create_user("some_user", "S3cret!pw")?;
+    assert_eq!(
+        r.statement(),
+        "CREATE USER some_user IDENTIFIED BY \"S3cret!pw\""
+    );
+    let r = SystemRequest::grant_role("some_user", "some_role")?;
+    assert_eq!(r.statement(), "GRANT some_role TO USER some_user");
+    let r = SystemRequest::drop_namespace("myns", true)?;
+    assert_eq!(r.statement(), "DROP NAMESPACE myns CASCADE");
+
+    // identifiers and passwords that could alter the statement are rejected
+    assert!(SystemRequest::drop_user("bob; DROP USER admin").is_err());
+    assert!(SystemRequest::create_namespace("1ns").is_err());
+    assert!(SystemRequest::create_role("").is_err());
+    assert!(SystemRequest::create_user("bob", "pw\" ADMIN").is_err());
+    assert!(SystemRequest::alter_user_password("bob", "").is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_system_status_request() -> Result<(), Box<dyn Error>> {
+    use crate::mock_server::MockServer;
+    use crate::nson::{OPERATION_ID, SYSOP_RESULT, SYSOP_STATE};
+
+    let status = |state: OperationState| {
+        MockServer::response(
+            MapValue::new()
+                .column(OPERATION_ID, "op-42")
+                .column(SYSOP_STATE, state as i32)
+                .column(SYSOP_RESULT, "done"),
+        )
+    };
+    let server = MockServer::start(vec![
+        status(OperationState::Working),
+        status(OperationState::Working),
+        status(OperationState::Complete),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let mut res = SystemStatusRequest::new("op-42").execute(&handle).await?;
+    assert_eq!(res.state(), OperationState::Working);
+    assert_eq!(res.operation_id(), "op-42");
+    res.wait_for_completion_ms(&handle, 5000, 1).await?;
+    assert_eq!(res.state(), OperationState::Complete);
+    assert_eq!(res.result_string(), "done");
+    assert_eq!(server.requests(), 3);
+    for i in 0..3 {
+        assert_eq!(
+            server.request_payload(i).get_string(OPERATION_ID),
+            Some("op-42".to_string())
+        );
+    }
+
+    assert!(SystemStatusRequest::new("").execute(&handle).await.is_err());
+    assert_eq!(server.requests(), 3);
+    Ok(())
+}
+
+#[test]
+fn test_put_request_conditions() -> Result<(), Box<dyn Error>> {
+    let r = PutRequest::new("testusers");
+    assert_eq!(r.op_code(), OpCode::Put);
+    let r = PutRequest::new("testusers").if_absent();
+    assert_eq!(r.op_code(), OpCode::PutIfAbsent);
+    let r = PutRequest::new("testusers").if_present();
+    assert_eq!(r.op_code(), OpCode::PutIfPresent);
+    let r = PutRequest::new("testusers").if_version(&vec![1, 2, 3]);
+    assert_eq!(r.op_code(), OpCode::PutIfVersion);
+
+    // the last condition given wins
+    let r = PutRequest::new("testusers").if_absent().if_present();
+    assert_eq!(r.op_code(), OpCode::PutIfPresent);
+    let r = PutRequest::new("testusers")
+        .if_version(&vec![1, 2, 3])
+        .if_absent();
+    assert_eq!(r.op_code(), OpCode::PutIfAbsent);
+    let r = PutRequest::new("testusers")
+        .if_present()
+        .if_version(&vec![1, 2, 3]);
+    assert_eq!(r.op_code(), OpCode::PutIfVersion);
+
+    // a result with no version means the condition was not met
+    let res = PutResult::default();
+    assert!(!res.success());
+    Ok(())
+}
+
+#[test]
+fn test_table_result_key_fields() -> Result<(), Box<dyn Error>> {
+    let mut res = TableResult::default();
+    assert!(res.primary_key_fields().is_empty());
+    assert!(res.shard_key_fields().is_empty());
+
+    res.schema = r#"{"json_version":1,"type":"table","name":"users","shardKey":["shard"],"primaryKey":["shard","id"],"fields":[{"name":"shard","type":"INTEGER","nullable":false},{"name":"id","type":"LONG","nullable":false},{"name":"name","type":"STRING","nullable":true}]}"#.to_string();
+    assert_eq!(res.primary_key_fields(), vec!["shard", "id"]);
+    assert_eq!(res.shard_key_fields(), vec!["shard"]);
+
+    // no shard key in schema: same as primary key
+    res.schema = r#"{"name":"t","primaryKey":["id"]}"#.to_string();
+    assert_eq!(res.shard_key_fields(), vec!["id"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_query_throttled_mid_execution() -> Result<(), Box<dyn Error>> {
+    use crate::error::NoSQLErrorCode;
+    use crate::mock_server::MockServer;
+    use crate::nson::{
+        CONTINUATION_KEY, ERROR_CODE, EXCEPTION, PREPARED_QUERY, QUERY_RESULTS, RETRY_HINT,
+    };
+
+    MockServer::pause_time();
+    let rows = |ids: &[i32]| -> FieldValue {
+        FieldValue::Array(
+            ids.iter()
+                .map(|id| FieldValue::Map(MapValue::new().i32("id", *id)))
+                .collect(),
+        )
+    };
+    let throttled = |retry_hint: i32| {
+        MockServer::response(
+            MapValue::new()
+                .i32(ERROR_CODE, NoSQLErrorCode::ReadLimitExceeded as i32)
+                .str(EXCEPTION, "read throttled")
+                .i32(RETRY_HINT, retry_hint),
+        )
+    };
+    let server = MockServer::start(vec![
+        MockServer::response(
+            MapValue::new()
+                .column(PREPARED_QUERY, FieldValue::Binary(vec![1, 2, 3]))
+                .column(QUERY_RESULTS, rows(&[1, 2]))
+                .column(CONTINUATION_KEY, FieldValue::Binary(vec![4, 5, 6])),
+        ),
+        MockServer::error_response(NoSQLErrorCode::ReadLimitExceeded, "read throttled"),
+        MockServer::error_response(NoSQLErrorCode::ReadLimitExceeded, "read throttled"),
+        MockServer::response(MapValue::new().column(QUERY_RESULTS, rows(&[3]))),
+    ])
+    .await;
+    let handle = server.handle().await;
+
+    let res = QueryRequest::new("select * from testusers")
+        .timeout(&Duration::from_secs(10))
+        .execute(&handle)
+        .await?;
+    assert_eq!(server.requests(), 4);
+    assert_eq!(res.rows().len(), 3);
+    assert_eq!(res.rows()[2].get_i32("id"), Some(3));
+    // two backoffs: 100ms, then 200ms
+    assert_eq!(res.throttle_delay(), Duration::from_millis(300));
+
+    // the retry hint of the service is honored when longer than the backoff
+    let server = MockServer::start(vec![
+        throttled(50),
+        throttled(700),
+        MockServer::response(
+            MapValue::new()
+                .column(PREPARED_QUERY, FieldValue::Binary(vec![1, 2, 3]))
+                .column(QUERY_RESULTS, rows(&[1])),
+        ),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let res = QueryRequest::new("select * from testusers")
+        .timeout(&Duration::from_secs(10))
+        .execute(&handle)
+        .await?;
+    assert_eq!(res.rows().len(), 1);
+    assert_eq!(res.throttle_delay(), Duration::from_millis(100 + 700));
+
+    // throttling that outlasts the timeout is still returned as an error
+    let server = MockServer::start(vec![
+        MockServer::error_response(
+            NoSQLErrorCode::ReadLimitExceeded,
+            "read throttled"
+        );
+        10
+    ])
+    .await;
+    let handle = server.handle().await;
+    let err = QueryRequest::new("select * from testusers")
+        .timeout(&Duration::from_millis(500))
+        .execute(&handle)
+        .await
+        .unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::ReadLimitExceeded);
+    assert!(server.requests() < 10);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_rate_limit_delay() -> Result<(), Box<dyn Error>> {
+    use crate::error::NoSQLErrorCode;
+    use crate::mock_server::MockServer;
+    use crate::nson::{ROW, ROW_VERSION, VALUE};
+
+    let server = MockServer::start(vec![
+        MockServer::error_response(NoSQLErrorCode::WriteLimitExceeded, "write throttled"),
+        MockServer::response(MapValue::new().column(ROW_VERSION, FieldValue::Binary(vec![1]))),
+        MockServer::error_response(NoSQLErrorCode::ReadLimitExceeded, "read throttled"),
+        MockServer::error_response(NoSQLErrorCode::ReadLimitExceeded, "read throttled"),
+        MockServer::response(MapValue::new().column(
+            ROW,
+            FieldValue::Map(MapValue::new().column(VALUE, MapValue::new().i32("id", 1))),
+        )),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let pres = PutRequest::new("users")
+        .value(MapValue::new().i32("id", 1))
+        .execute(&handle)
+        .await?;
+    assert!(pres.success());
+    assert_eq!(pres.rate_limit_delayed_ms(), 100);
+    let gres = GetRequest::new("users")
+        .key(MapValue::new().i32("id", 1))
+        .execute(&handle)
+        .await?;
+    assert_eq!(gres.row().unwrap().get_i32("id"), Some(1));
+    // two backoffs: 100ms, then 200ms
+    assert_eq!(gres.rate_limit_delayed_ms(), 300);
+    assert_eq!(handle.rate_limit_delayed_ms(), 400);
+    assert_eq!(server.requests(), 5);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_prepare_cache() -> Result<(), Box<dyn Error>> {
+    use crate::error::NoSQLErrorCode;
+    use crate::mock_server::MockServer;
+    use crate::nson::{IS_PREPARED, QUERY_RESULTS, STATEMENT};
+
+    let rows = || FieldValue::Array(vec![FieldValue::Map(MapValue::new().i32("id", 1))]);
+    let prepared =
+        || MockServer::response(MockServer::prepared_fields().column(QUERY_RESULTS, rows()));
+    let server = MockServer::start(vec![
+        prepared(),
+        MockServer::response(MapValue::new().column(QUERY_RESULTS, rows())),
+        MockServer::error_response(NoSQLErrorCode::TableNotFound, "table not found"),
+        prepared(),
+    ])
+    .await;
+    let handle = server.builder().prepare_cache_size(10)?.build().await?;
+    let stmt = "select * from users where id = 1";
+
+    // the first execution prepares the statement, the second reuses it
+    for _ in 0..2 {
+        let mut res = QueryRequest::new(stmt).execute(&handle).await?;
+        assert_eq!(res.take_rows().len(), 1);
+    }
+    assert!(server
+        .request_payload(0)
+        .get_field_value(IS_PREPARED)
+        .is_none());
+    assert_eq!(server.request_payload(1).get_bool(IS_PREPARED), Some(true));
+    assert!(server
+        .request_payload(1)
+        .get_field_value(STATEMENT)
+        .is_none());
+    assert_eq!(handle.inner.prepare_cache.as_ref().unwrap().len(), 1);
+
+    // a stale cached statement is discarded and the query is prepared again
+    let mut res = QueryRequest::new(stmt).execute(&handle).await?;
+    assert_eq!(res.take_rows().len(), 1);
+    assert_eq!(server.requests(), 4);
+    assert_eq!(server.request_payload(2).get_bool(IS_PREPARED), Some(true));
+    assert_eq!(
+        server.request_payload(3).get_string(STATEMENT),
+        Some(stmt.to_string())
+    );
+
+    // the least recently used statement is evicted
+    let cache = crate::prepare_cache::PrepareCache::new(2);
+    let ps = res.prepared_statement();
+    cache.put("a".to_string(), &ps);
+    cache.put("b".to_string(), &ps);
+    assert!(cache.get("a").is_some());
+    cache.put("c".to_string(), &ps);
+    assert_eq!(cache.len(), 2);
+    assert!(cache.get("b").is_none());
+    assert!(cache.get("a").is_some());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_query_result_cache() -> Result<(), Box<dyn Error>> {
+    use crate::mock_server::MockServer;
+    use crate::nson::{PREPARED_QUERY, QUERY_OPERATION, QUERY_RESULTS};
+    use crate::query_cache::{QueryCache, QueryCacheKey};
+    use std::collections::HashMap;
+
+    // operation 0 is SELECT, 1 is INSERT
+    let response = |op: i32| {
+        MockServer::response(
+            MapValue::new()
+                .column(PREPARED_QUERY, FieldValue::Binary(vec![1, 2, 3]))
+                .i32(QUERY_OPERATION, op)
+                .column(
+                    QUERY_RESULTS,
+                    FieldValue::Array(vec![FieldValue::Map(MapValue::new().i32("id", 1))]),
+                ),
+        )
+    };
+    let server = MockServer::start(vec![
+        response(0),
+        response(0),
+        response(0),
+        response(1),
+        response(1),
+        response(0),
+    ])
+    .await;
+    let handle = server
+        .builder()
+        .query_cache(10, Duration::from_secs(60))?
+        .build()
+        .await?;
+
+    // a read-only query is cached, ignoring whitespace
+    let res = QueryRequest::new("select * from users")
+        .execute(&handle)
+        .await?;
+    assert!(!res.from_cache());
+    let res = QueryRequest::new("select *\n  from users ")
+        .execute(&handle)
+        .await?;
+    assert!(res.from_cache());
+    assert_eq!(res.rows()[0].get_i32("id"), Some(1));
+    assert_eq!(res.consumed().read_units, 0);
+    assert_eq!(server.requests(), 1);
+
+    // absolute reads are never cached, and results sorted with another string
+    // collation are cached separately
+    let res = QueryRequest::new("select * from users")
+        .consistency(&Consistency::Absolute)
+        .execute(&handle)
+        .await?;
+    assert!(!res.from_cache());
+    for from_cache in [false, true] {
+        let res = QueryRequest::new("select * from users")
+            .string_collation(StringCollation::CodePoint)
+            .execute(&handle)
+            .await?;
+        assert_eq!(res.from_cache(), from_cache);
+    }
+    assert_eq!(server.requests(), 3);
+
+    // mutations are never cached
+    let stmt = "insert into users values(1)";
+    for _ in 0..2 {
+        let res = QueryRequest::new(stmt).execute(&handle).await?;
+        assert!(!res.from_cache());
+    }
+    assert_eq!(server.requests(), 5);
+
+    // invalidating the cache executes the query again
+    handle.invalidate_query_cache();
+    let res = QueryRequest::new("select * from users")
+        .execute(&handle)
+        .await?;
+    assert!(!res.from_cache());
+    assert_eq!(server.requests(), 6);
+
+    // results expire, bind variables are part of the key, and the least
+    // recently used result is evicted
+    let cache = QueryCache::new(2, Duration::from_millis(100));
+    let mut vars: HashMap<String, FieldValue> = HashMap::new();
+    let key = |vars: &HashMap<String, FieldValue>| {
+        QueryCacheKey::new("", "select 1", vars, StringCollation::default(), None)
+    };
+    cache.put(key(&vars), &res);
+    vars.insert("$id".to_string(), FieldValue::Integer(1));
+    assert!(cache.get(&key(&vars)).is_none());
+    cache.put(key(&vars), &res);
+    vars.insert("$id".to_string(), FieldValue::Long(1));
+    assert!(cache.get(&key(&vars)).unwrap().from_cache());
+    assert!(cache.get(&key(&HashMap::new())).is_some());
+    vars.insert("$id".to_string(), FieldValue::Integer(2));
+    cache.put(key(&vars), &res);
+    assert_eq!(cache.len(), 2);
+    vars.insert("$id".to_string(), FieldValue::Integer(1));
+    assert!(cache.get(&key(&vars)).is_none());
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert!(cache.get(&key(&HashMap::new())).is_none());
+    Ok(())
+}
+
+#[test]
+fn test_typed_write_requests() -> Result<(), Box<dyn Error>> {
+    use crate::error::NoSQLErrorCode::IllegalArgument;
+    use crate::{MultiDeleteRequest, NoSQLError, WriteMultipleRequest};
+
+    #[derive(Default, Debug, NoSQLRow)]
+    struct User {
+        id: i64,
+        name: String,
+    }
+    // a row type whose conversion always fails
+    struct BadRow;
+    impl NoSQLRow for BadRow {
+        fn to_map_value(&self) -> Result<MapValue, NoSQLError> {
+            Err(NoSQLError::new(IllegalArgument, "bad field"))
+        }
+        fn from_map_value(&mut self, _value: &MapValue) -> Result<(), NoSQLError> {
+            Ok(())
+        }
+    }
+
+    let user = User {
+        id: 1,
+        name: "a".to_string(),
+    };
+    let req = PutRequest::new("users").value_from_row(&user)?;
+    assert_eq!(req.value.get_i64("id"), Some(1));
+    assert_eq!(req.value.get_string("name"), Some("a".to_string()));
+    let req = WriteMultipleRequest::new("users")
+        .add_put_row(&user)?
+        .add_put_row(&User::default())?;
+    assert_eq!(req.sub_requests.len(), 2);
+    let req = MultiDeleteRequest::from_row("users", &user)?;
+    assert_eq!(req.key.get_map_value_ref()?.get_i64("id"), Some(1));
+
+    // conversion errors are returned, not written as NULL
+    let err = PutRequest::new("users")
+        .value_from_row(&BadRow)
+        .unwrap_err();
+    assert_eq!(err.code, IllegalArgument);
+    assert!(err.message.contains("bad field"));
+    assert!(PutRequest::new("users").put(BadRow).is_err());
+    assert!(WriteMultipleRequest::new("users")
+        .add_put_row(&BadRow)
+        .is_err());
+    assert!(WriteMultipleRequest::new("users")
+        .delete(vec![BadRow])
+        .is_err());
+    assert!(MultiDeleteRequest::from_row("users", &BadRow).is_err());
+    assert!(GetRequest::new("users").row_key(&BadRow).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_query_result_into_columns() {
+    let result = QueryResult {
+        rows: vec![
+            MapValue::new().i32("id", 1).str("name", "a"),
+            MapValue::new().i32("id", 2),
+            MapValue::new()
+                .i32("id", 3)
+                .str("name", "c")
+                .i64("extra", 7),
+        ],
+        ..Default::default()
+    };
+    let columns = result.into_columns();
+    assert_eq!(columns.len(), 3);
+    use FieldValue::{Integer, Long, Null};
+    assert_eq!(columns["id"], vec![Integer(1), Integer(2), Integer(3)]);
+    assert_eq!(
+        columns["name"],
+        vec![
+            FieldValue::String("a".into()),
+            Null,
+            FieldValue::String("c".into())
+        ]
+    );
+    assert_eq!(columns["extra"], vec![Null, Null, Long(7)]);
+
+    assert!(QueryResult::default().into_columns().is_empty());
+}
+
+#[test]
+fn test_query_result_group_by_field() {
+    let row = |id: i32, cat: &str| MapValue::new().i32("id", id).str("category", cat);
+    let result = QueryResult {
+        rows: vec![
+            row(1, "b"),
+            row(2, "a"),
+            row(3, "b"),
+            MapValue::new().i32("id", 4),
+            row(5, "a"),
+            row(6, "b"),
+        ],
+        ..Default::default()
+    };
+    let ids = |rows: &Vec<MapValue>| -> Vec<i32> {
+        rows.iter().map(|r| r.get_i32("id").unwrap()).collect()
+    };
+    let groups = result.group_by_field("category");
+    assert_eq!(groups.len(), 3);
+    // rows keep their order within each group
+    assert_eq!(ids(&groups[&FieldValue::String("a".into())]), vec![2, 5]);
+    assert_eq!(ids(&groups[&FieldValue::String("b".into())]), vec![1, 3, 6]);
+    assert_eq!(ids(&groups[&FieldValue::Null]), vec![4]);
+    assert_eq!(result.rows().len(), 6);
+
+    // numeric values of different types with the same value are one group
+    let result = QueryResult {
+        rows: vec![
+            MapValue::new().i32("n", 1).i32("id", 1),
+            MapValue::new().i64("n", 1).i32("id", 2),
+        ],
+        ..Default::default()
+    };
+    let groups = result.group_by_field("n");
+    assert_eq!(groups.len(), 1);
+    assert_eq!(ids(&groups[&FieldValue::Long(1)]), vec![1, 2]);
+
+    assert!(QueryResult::default().group_by_field("x").is_empty());
+}
+
+#[test]
+fn test_prepared_statement_describe() {
+    use crate::plan_iter::PlanIter;
+    use crate::prepared_statement::PreparedStatement;
+    use crate::receive_iter::{DistributionKind, ReceiveIter};
+    use std::collections::HashMap;
+
+    // a simple query has no driver plan or variable names
+    let desc = PreparedStatement::default().describe();
+    assert_eq!(desc.distribution_kind(), None);
+    assert_eq!(desc.plan_text(), None);
+    assert!(desc.columns().is_empty());
+    assert!(desc.bound_variable_names().is_empty());
+
+    let ps = PreparedStatement {
+        query_plan: "{ \"iterator kind\" : \"RECEIVE\" }".to_string(),
+        query_schema: r#"{"type":"RECORD","fields":[
+            {"name":"id","type":"INTEGER"},
+            {"name":"name","type":"STRING"}]}"#
+            .to_string(),
+        driver_query_plan: Box::new(PlanIter::Receive(ReceiveIter::default())),
+        variable_to_ids: Some(HashMap::from([
+            ("$name".to_string(), 1),
+            ("$id".to_string(), 0),
+            ("$age".to_string(), 2),
+        ])),
+        ..Default::default()
+    };
+    let desc = ps.describe();
+    assert_eq!(
+        desc.distribution_kind(),
+        Some(DistributionKind::SinglePartition)
+    );
+    assert_eq!(
+        desc.plan_text(),
+        Some("{ \"iterator kind\" : \"RECEIVE\" }")
+    );
+    let cols: Vec<(&str, &str)> = desc
+        .columns()
+        .iter()
+        .map(|c| (c.name(), c.type_name()))
+        .collect();
+    assert_eq!(cols, vec![("id", "INTEGER"), ("name", "STRING")]);
+    assert_eq!(desc.bound_variable_names(), ["$id", "$name", "$age"]);
+}
+
+#[cfg(feature = "arrow")]
+#[test]
+fn test_to_record_batch() -> Result<(), Box<dyn Error>> {
+    use crate::prepared_statement::PreparedStatement;
+    use arrow_array::{Array, Decimal128Array, Int64Array, StringArray, TimestampMillisecondArray};
+    use arrow_schema::{DataType, TimeUnit};
+    use bigdecimal::BigDecimal;
+    use chrono::DateTime;
+    use std::str::FromStr;
+
+    let ts = DateTime::parse_from_rfc3339("2024-01-02T03:04:05.123Z")?;
+    let rows = vec![
+        MapValue::new()
+            .i32("id", 1)
+            .str("name", "a")
+            .column("price", FieldValue::Number(BigDecimal::from_str("1.5")?))
+            .column("created", FieldValue::Timestamp(ts))
+            .column("info", FieldValue::Map(MapValue::new().i32("x", 1))),
+        MapValue::new()
+            .i32("id", 2)
+            .column("price", FieldValue::Number(BigDecimal::from_str("-10.25")?))
+            .column("info", FieldValue::JsonNull),
+    ];
+
+    // with a query schema, columns are in SELECT order with declared types
+    let ps = PreparedStatement {
+        query_schema: r#"{"type":"RECORD","fields":[
+            {"name":"id","type":"LONG"},
+            {"name":"name","type":"STRING"},
+            {"name":"price","type":"NUMBER"},
+            {"name":"created","type":{"type":"TIMESTAMP","precision":3}},
+            {"name":"info","type":"JSON"}]}"#
+            .to_string(),
+        ..Default::default()
+    };
+    let result = QueryResult {
+        rows,
+        prepared_statement: ps,
+        ..Default::default()
+    };
+    let batch = result.to_record_batch()?;
+    assert_eq!(batch.num_rows(), 2);
+    let schema = batch.schema();
+    let names: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+    assert_eq!(names, vec!["id", "name", "price", "created", "info"]);
+    assert_eq!(schema.field(0).data_type(), &DataType::Int64);
+    assert_eq!(schema.field(2).data_type(), &DataType::Decimal128(38, 2));
+    assert_eq!(
+        schema.field(3).data_type(),
+        &DataType::Timestamp(TimeUnit::Millisecond, Some("+00:00".into()))
+    );
+    let ids = batch
+        .column(0)
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .unwrap();
+    assert_eq!(ids.values().to_vec(), vec![1, 2]);
+    let names = batch
+        .column(1)
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap();
+    assert_eq!(names.value(0), "a");
+    assert!(names.is_null(1));
+    let prices = batch
+        .column(2)
+        .as_any()
+        .downcast_ref::<Decimal128Array>()
+        .unwrap();
+    assert_eq!(prices.values().to_vec(), vec![150, -1025]);
+    let created = batch.column(3).as_any();
+    let created = created.downcast_ref::<TimestampMillisecondArray>().unwrap();
+    assert_eq!(created.value(0), ts.timestamp_millis());
+    assert!(created.is_null(1));
+    let info = batch
+        .column(4)
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap();
+    assert_eq!(info.value(0), r#"{"x":1}"#);
+    assert!(info.is_null(1));
+
+    // without a schema, types are inferred from the values
+    let result = QueryResult {
+        rows: vec![
+            MapValue::new().i32("b", 1).column("a", FieldValue::Null),
+            MapValue::new().i32("b", 2).str("c", "x"),
+        ],
+        ..Default::default()
+    };
+    let batch = result.to_record_batch()?;
+    let schema = batch.schema();
+    let types: Vec<(&str, &DataType)> = schema
+        .fields()
+        .iter()
+        .map(|f| (f.name().as_str(), f.data_type()))
+        .collect();
+    assert_eq!(
+        types,
+        vec![
+            ("a", &DataType::Null),
+            ("b", &DataType::Int32),
+            ("c", &DataType::Utf8)
+        ]
+    );
+
+    // values that do not fit their column are an error
+    let result = QueryResult {
+        rows: vec![
+            MapValue::new().i32("id", 1),
+            MapValue::new().str("id", "two"),
+        ],
+        ..Default::default()
+    };
+    assert!(result.to_record_batch().is_err());
+
+    assert_eq!(QueryResult::default().to_record_batch()?.num_columns(), 0);
+    Ok(())
+}
+
+// Response to a query batch with the rows {"id": first} and {"id": first + 1}.
+// The first batch (first == 0) also prepares the query.
+fn query_batch(first: i32, more: bool) -> Vec<u8> {
+    use crate::nson::{CONTINUATION_KEY, PREPARED_QUERY, QUERY_RESULTS};
+    let rows = (first..first + 2)
+        .map(|i| FieldValue::Map(MapValue::new().i32("id", i)))
+        .collect();
+    let mut m = MapValue::new().column(QUERY_RESULTS, FieldValue::Array(rows));
+    if first == 0 {
+        m = m.column(PREPARED_QUERY, FieldValue::Binary(vec![1, 2, 3]));
+    }
+    if more {
+        m = m.column(CONTINUATION_KEY, FieldValue::Binary(vec![first as u8]));
+    }
+    crate::mock_server::MockServer::response(m)
+}
+
+#[tokio::test]
+async fn test_query_execute_channel() -> Result<(), Box<dyn Error>> {
+    use crate::mock_server::MockServer;
+
+    let server = MockServer::start(vec![
+        query_batch(0, true),
+        query_batch(2, true),
+        query_batch(4, false),
+    ])
+    .await;
+    let handle = server.handle().await;
+
+    let mut rx = QueryRequest::new("select * from users").execute_channel(&handle, 1);
+    assert_eq!(rx.recv().await.unwrap()?.get_i32("id"), Some(0));
+    // the next batch is not fetched until the consumer makes room for it
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(server.requests(), 1);
+    let mut ids = vec![0];
+    while let Some(row) = rx.recv().await {
+        ids.push(row?.get_i32("id").unwrap());
+    }
+    assert_eq!(ids, vec![0, 1, 2, 3, 4, 5]);
+    assert_eq!(server.requests(), 3);
+
+    // dropping the receiver stops the query
+    let server = MockServer::start(vec![
+        query_batch(0, true),
+        query_batch(2, true),
+        query_batch(4, false),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let mut rx = QueryRequest::new("select * from users").execute_channel(&handle, 1);
+    assert!(rx.recv().await.is_some());
+    drop(rx);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(server.requests(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_query_for_each() -> Result<(), Box<dyn Error>> {
+    use crate::error::{NoSQLError, NoSQLErrorCode};
+    use crate::mock_server::MockServer;
+
+    let server = MockServer::start(vec![
+        query_batch(0, true),
+        query_batch(2, true),
+        query_batch(4, false),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let mut sum = 0;
+    let mut count = 0;
+    QueryRequest::new("select * from users")
+        .for_each(&handle, |row| {
+            sum += row.get_i32("id").unwrap();
+            count += 1;
+            Ok(())
+        })
+        .await?;
+    assert_eq!((sum, count), (15, 6));
+    assert_eq!(server.requests(), 3);
+
+    // an error from the callback stops the query
+    let server = MockServer::start(vec![
+        query_batch(0, true),
+        query_batch(2, true),
+        query_batch(4, false),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let mut ids = Vec::new();
+    let err = QueryRequest::new("select * from users")
+        .for_each(&handle, |row| {
+            let id = row.get_i32("id").unwrap();
+            if id == 2 {
+                return Err(NoSQLError::new(NoSQLErrorCode::IllegalState, "stop"));
+            }
+            ids.push(id);
+            Ok(())
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.message, "stop");
+    assert_eq!(ids, vec![0, 1]);
+    assert_eq!(server.requests(), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_query_statement_validation() -> Result<(), Box<dyn Error>> {
+    use crate::error::NoSQLErrorCode;
+    use crate::mock_server::MockServer;
+
+    let server = MockServer::start(vec![MockServer::prepared_response()]).await;
+    let handle = server.handle().await;
+
+    let err = QueryRequest::default().execute(&handle).await.unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::IllegalArgument);
+    assert!(err.message.contains("no statement"), "{}", err.message);
+    let err = QueryRequest::new(" ").execute(&handle).await.unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::IllegalArgument);
+
+    let ps = QueryRequest::new("select * from users")
+        .prepare_only()
+        .execute(&handle)
+        .await?
+        .prepared_statement();
+    assert_eq!(ps.sql_text, "select * from users");
+    let mut req = QueryRequest::new_prepared(&ps);
+    req.statement = Some("select * from orders".to_string());
+    let err = req.execute(&handle).await.unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::IllegalArgument);
+    assert!(
+        err.message.contains("select * from orders"),
+        "{}",
+        err.message
+    );
+    assert_eq!(server.requests(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_query_compile_error() -> Result<(), Box<dyn Error>> {
+    use crate::error::NoSQLErrorCode;
+    use crate::mock_server::MockServer;
+
+    let msg = "Error: at (1, 15) mismatched input 'form' expecting {<EOF>, ','}";
+    let server = MockServer::start(vec![
+        MockServer::error_response(NoSQLErrorCode::IllegalArgument, msg),
+        MockServer::error_response(NoSQLErrorCode::IllegalArgument, msg),
+        MockServer::error_response(NoSQLErrorCode::IllegalArgument, "invalid limit"),
+    ])
+    .await;
+    let handle = server.handle().await;
+
+    let err = QueryRequest::new("select * form users")
+        .prepare_only()
+        .execute(&handle)
+        .await
+        .unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::QueryCompileError);
+    assert_eq!(err.message, msg);
+    assert_eq!(err.query_error_location(), Some((1, 15)));
+
+    let err = QueryRequest::new("select * form users")
+        .execute(&handle)
+        .await
+        .unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::QueryCompileError);
+
+    // errors without a location are left as they are
+    let err = QueryRequest::new("select * from users")
+        .execute(&handle)
+        .await
+        .unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::IllegalArgument);
+    assert_eq!(err.query_error_location(), None);
+    assert_eq!(server.requests(), 3);
+
+    let err = crate::NoSQLError::new(NoSQLErrorCode::IllegalArgument, "at (x, 1) at (12, 3)");
+    assert_eq!(err.query_error_location(), Some((12, 3)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_request_options() -> Result<(), Box<dyn Error>> {
+    use crate::mock_server::MockServer;
+    use crate::nson::DURABILITY;
+    use crate::types::{Durability, ReplicaAckPolicy, SyncPolicy};
+    use crate::RequestOptions;
+
+    let durability = Durability::new(
+        SyncPolicy::Sync,
+        SyncPolicy::NoSync,
+        ReplicaAckPolicy::SimpleMajority,
+    );
+    let opts = RequestOptions::new()
+        .timeout(&Duration::from_secs(3))
+        .consistency(Consistency::Absolute)
+        .compartment_id("c1")
+        .namespace("ns1")
+        .durability(durability);
+
+    // explicit settings take precedence, whether given before or after the options
+    let get = GetRequest::new("users")
+        .timeout(&Duration::from_secs(5))
+        .options(&opts)
+        .compartment_id("c2");
+    assert_eq!(get.timeout, Some(Duration::from_secs(5)));
+    assert_eq!(get.consistency, Some(Consistency::Absolute));
+    assert_eq!(get.compartment_id, "c2");
+    let get = GetRequest::new("users")
+        .consistency(Consistency::Eventual)
+        .options(&opts);
+    assert_eq!(get.consistency, Some(Consistency::Eventual));
+    assert_eq!(get.timeout, Some(Duration::from_secs(3)));
+    assert_eq!(get.compartment_id, "c1");
+
+    let table = TableRequest::new("users").options(&opts);
+    assert_eq!(table.namespace, "ns1");
+    let query = QueryRequest::new("select * from users").options(&opts);
+    assert_eq!(query.consistency, Some(Consistency::Absolute));
+    assert_eq!(query.durability, Some(durability));
+
+    // durability is only sent if set
+    let server = MockServer::start(vec![
+        MockServer::response(MapValue::new()),
+        MockServer::response(MapValue::new()),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let value = MapValue::new().i32("id", 1);
+    PutRequest::new("users")
+        .value(value.clone_internal())
+        .options(&opts)
+        .execute(&handle)
+        .await?;
+    PutRequest::new("users")
+        .value(value)
+        .execute(&handle)
+        .await?;
+    assert_eq!(
+        server.request_payload(0).get_i32(DURABILITY),
+        Some(1 | 2 << 2 | 3 << 4)
+    );
+    assert!(server
+        .request_payload(1)
+        .get_field_value(DURABILITY)
+        .is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_query_display_timezone() -> Result<(), Box<dyn Error>> {
+    use crate::mock_server::MockServer;
+    use crate::nson::{PREPARED_QUERY, QUERY_RESULTS};
+    use chrono::{DateTime, FixedOffset};
+
+    let ts = DateTime::parse_from_rfc3339("2024-03-01T23:30:00Z")?;
+    let response = || {
+        MockServer::response(
+            MapValue::new()
+                .column(PREPARED_QUERY, FieldValue::Binary(vec![1, 2, 3]))
+                .column(
+                    QUERY_RESULTS,
+                    FieldValue::Array(vec![FieldValue::Map(
+                        MapValue::new().column("created", FieldValue::Timestamp(ts)),
+                    )]),
+                ),
+        )
+    };
+    let server = MockServer::start(vec![response(), response()]).await;
+
+    // without a display timezone, timestamps are returned as-is
+    let handle = server.handle().await;
+    let res = QueryRequest::new("select created from t")
+        .execute(&handle)
+        .await?;
+    assert_eq!(
+        res.timestamp(0, "created").unwrap().to_rfc3339(),
+        "2024-03-01T23:30:00+00:00"
+    );
+
+    let tokyo = FixedOffset::east_opt(9 * 3600).unwrap();
+    let handle = server.builder().display_timezone(tokyo)?.build().await?;
+    let res = QueryRequest::new("select created from t")
+        .execute(&handle)
+        .await?;
+    let shifted = res.timestamp(0, "created").unwrap();
+    assert_eq!(shifted.to_rfc3339(), "2024-03-02T08:30:00+09:00");
+    assert_eq!(shifted, ts);
+    // the row itself is unchanged
+    assert_eq!(res.rows()[0].get_timestamp("created"), Some(ts));
+    assert!(res.timestamp(1, "created").is_none());
+    assert!(res.timestamp(0, "missing").is_none());
+    Ok(())
+}
+
+#[test]
+fn test_update_request_statement() -> Result<(), Box<dyn Error>> {
+    use crate::update_request::UpdateRequest;
+
+    let key = || MapValue::new().i32("shard", 1).i64("id", 10);
+    let stmt = UpdateRequest::new("users", key())
+        .set("name", "jane")
+        .set("info.address.city", "Springfield")
+        .remove("info.nickname")
+        .statement()?;
+    assert_eq!(
+        stmt,
+        "UPDATE users t SET t.name = ?, t.info.address.city = ?, REMOVE t.info.nickname WHERE t.id = ? AND t.shard = ?"
+    );
+    let stmt = UpdateRequest::new("ns1:parent.child", key())
+        .remove("info.a")
+        .remove("info.b")
+        .return_row(true)
+        .statement()?;
+    assert_eq!(
+        stmt,
+        "UPDATE ns1:parent.child t REMOVE t.info.a, t.info.b WHERE t.id = ? AND t.shard = ? RETURNING *"
+    );
+
+    // invalid requests
+    assert!(UpdateRequest::new("users", MapValue::new())
+        .set("name", "jane")
+        .statement()
+        .is_err());
+    assert!(UpdateRequest::new("users", key()).statement().is_err());
+    assert!(UpdateRequest::new("users", key())
+        .remove("name")
+        .statement()
+        .is_err());
+    assert!(UpdateRequest::new("users", key())
+        .set("name = 'x', t.id", 1)
+        .statement()
+        .is_err());
+    assert!(UpdateRequest::new("users; drop table x", key())
+        .set("name", 1)
+        .statement()
+        .is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_table_operation_error() -> Result<(), Box<dyn Error>> {
+    use crate::error::NoSQLErrorCode;
+    use crate::mock_server::MockServer;
+    use crate::nson::{OPERATION_ID, TABLE_NAME, TABLE_STATE};
+
+    let state = |s: TableState| {
+        MockServer::response(
+            MapValue::new()
+                .column(TABLE_NAME, "t1")
+                .column(OPERATION_ID, "op1")
+                .column(TABLE_STATE, s as i32),
+        )
+    };
+    let create =
+        || TableRequest::new("t1").statement("create table t1(id integer, primary key(id))");
+
+    // operation fails while polling
+    let server = MockServer::start(vec![
+        state(TableState::Creating),
+        state(TableState::Creating),
+        MockServer::error_response(NoSQLErrorCode::IllegalArgument, "invalid shard key"),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let mut res = create().execute(&handle).await?;
+    assert!(res.operation_error().is_none());
+    let err = res
+        .wait_for_completion_ms(&handle, 5000, 1)
+        .await
+        .unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::IllegalArgument);
+    assert_eq!(res.operation_error(), Some("invalid shard key".to_string()));
+
+    // table is dropped while being created
+    let server = MockServer::start(vec![
+        state(TableState::Creating),
+        state(TableState::Dropped),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let mut res = create().execute(&handle).await?;
+    let err = res
+        .wait_for_completion_ms(&handle, 5000, 1)
+        .await
+        .unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::IllegalState);
+    assert!(res.operation_error().unwrap().contains("t1"));
+
+    // transient errors are not operation errors
+    for code in [
+        NoSQLErrorCode::ServiceUnavailable,
+        NoSQLErrorCode::OperationLimitExceeded,
+    ] {
+        let server = MockServer::start(vec![
+            state(TableState::Creating),
+            MockServer::error_response(code, "transient"),
+        ])
+        .await;
+        let handle = server.handle().await;
+        let mut res = create().execute(&handle).await?;
+        assert!(res.wait_for_completion_ms(&handle, 5000, 1).await.is_err());
+        assert!(res.operation_error().is_none(), "{:?}", code);
+    }
+
+    // success
+    let server =
+        MockServer::start(vec![state(TableState::Creating), state(TableState::Active)]).await;
+    let handle = server.handle().await;
+    let mut res = create().execute(&handle).await?;
+    res.wait_for_completion_ms(&handle, 5000, 1).await?;
+    assert_eq!(res.state(), TableState::Active);
+    assert!(res.operation_error().is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_query_max_server_memory() -> Result<(), Box<dyn Error>> {
+    use crate::mock_server::MockServer;
+    use crate::nson::{PREPARED_QUERY, SERVER_MEMORY_CONSUMPTION};
+
+    let response = || {
+        MockServer::response(
+            MapValue::new().column(PREPARED_QUERY, FieldValue::Binary(vec![1, 2, 3])),
+        )
+    };
+    let server = MockServer::start(vec![response(), response()]).await;
+    let handle = server.handle().await;
+    QueryRequest::new("select * from t")
+        .max_server_memory(10 * 1024 * 1024)
+        .execute(&handle)
+        .await?;
+    QueryRequest::new("select * from t")
+        .execute(&handle)
+        .await?;
+
+    assert_eq!(
+        server.request_payload(0).get_i64(SERVER_MEMORY_CONSUMPTION),
+        Some(10 * 1024 * 1024)
+    );
+    assert!(server
+        .request_payload(1)
+        .get_field_value(SERVER_MEMORY_CONSUMPTION)
+        .is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_circuit_breaker_fails_fast() -> Result<(), Box<dyn Error>> {
+    use crate::error::NoSQLErrorCode;
+    use crate::mock_server::MockServer;
+
+    let cooldown = Duration::from_millis(200);
+    let server = MockServer::start(vec![
+        MockServer::error_response(NoSQLErrorCode::ServiceUnavailable, "unavailable"),
+        MockServer::error_response(NoSQLErrorCode::ServiceUnavailable, "unavailable"),
+    ])
+    .await;
+    let handle = server
+        .builder()
+        .circuit_breaker(2, cooldown)?
+        .build()
+        .await?;
+    let get = || GetRequest::new("testusers").key(MapValue::new().i32("id", 1));
+
+    for _ in 0..2 {
+        let err = get().execute(&handle).await.unwrap_err();
+        assert_eq!(err.code, NoSQLErrorCode::ServiceUnavailable);
+    }
+    // the breaker is now open: requests fail without reaching the server
+    let err = get().execute(&handle).await.unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::CircuitOpen);
+    assert_eq!(server.requests(), 2);
+
+    // after the cooldown a probe is sent; the server has no responses left,
+    // so the connection is closed and the breaker opens again
+    tokio::time::sleep(cooldown).await;
+    let err = get().execute(&handle).await.unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::ServerError);
+    assert_eq!(server.requests(), 3);
+    let err = get().execute(&handle).await.unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::CircuitOpen);
+    assert_eq!(server.requests(), 3);
+
+    // zero thresholds are rejected
+    assert!(crate::Handle::builder()
+        .circuit_breaker(0, cooldown)
+        .is_err());
+    assert!(crate::Handle::builder()
+        .circuit_breaker(1, Duration::ZERO)
+        .is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_empty_string_values() -> Result<(), Box<dyn Error>> {
+    use crate::mock_server::MockServer;
+    use crate::nson::{KEY, PREPARED_QUERY, QUERY_RESULTS, ROW, ROW_VERSION, VALUE};
+
+    let row = || {
+        MapValue::new()
+            .str("id", "")
+            .str("name", "")
+            .column("note", FieldValue::Null)
+    };
+    let server = MockServer::start(vec![
+        MockServer::response(MapValue::new().column(ROW_VERSION, FieldValue::Binary(vec![1]))),
+        MockServer::response(
+            MapValue::new().column(ROW, FieldValue::Map(MapValue::new().column(VALUE, row()))),
+        ),
+        MockServer::response(
+            MapValue::new()
+                .column(PREPARED_QUERY, FieldValue::Binary(vec![1, 2, 3]))
+                .column(
+                    QUERY_RESULTS,
+                    FieldValue::Array(vec![FieldValue::Map(row())]),
+                ),
+        ),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let is_empty_string =
+        |v: Option<&FieldValue>| matches!(v, Some(FieldValue::String(s)) if s.is_empty());
+
+    // empty strings are sent as strings, in keys and values
+    PutRequest::new("users")
+        .value(row())
+        .execute(&handle)
+        .await?;
+    let sent = server.request_payload(0);
+    let value = sent.get_map(VALUE).unwrap();
+    assert!(is_empty_string(value.get_field_value("id")));
+    assert!(is_empty_string(value.get_field_value("name")));
+    assert!(matches!(
+        value.get_field_value("note"),
+        Some(FieldValue::Null)
+    ));
+
+    // and read back as strings
+    let res = GetRequest::new("users")
+        .key(MapValue::new().str("id", ""))
+        .execute(&handle)
+        .await?;
+    assert!(is_empty_string(
+        server
+            .request_payload(1)
+            .get_map(KEY)
+            .unwrap()
+            .get_field_value("id")
+    ));
+    let got = res.row().unwrap();
+    assert_eq!(got.get_string("id"), Some(String::new()));
+    assert_eq!(got.get_string("name"), Some(String::new()));
+    assert!(matches!(
+        got.get_field_value("note"),
+        Some(FieldValue::Null)
+    ));
+
+    let res = QueryRequest::new("select * from users where name = ''")
+        .execute(&handle)
+        .await?;
+    let got = &res.rows()[0];
+    assert!(is_empty_string(got.get_field_value("id")));
+    assert!(matches!(
+        got.get_field_value("note"),
+        Some(FieldValue::Null)
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_region_redirect() -> Result<(), Box<dyn Error>> {
+    use crate::error::NoSQLErrorCode;
+    use crate::mock_server::MockServer;
+    use crate::nson::{ROW, VALUE};
+
+    let row = || {
+        MockServer::response(MapValue::new().column(
+            ROW,
+            FieldValue::Map(MapValue::new().column(VALUE, MapValue::new().i32("id", 1))),
+        ))
+    };
+    let host = "nosql.us-phoenix-1.oci.oraclecloud.com";
+    let target = MockServer::start_tls(vec![row(), row()]).await;
+    let port = target.endpoint.rsplit(':').next().unwrap();
+    let location = format!("https://{}:{}/V2/nosql/data", host, port);
+    let get = || GetRequest::new("users").key(MapValue::new().i32("id", 1));
+
+    // by default, a region redirect is an error naming the region
+    let server = MockServer::start(vec![
+        MockServer::redirect_response(&location),
+        MockServer::redirect_response("https://example.com/V2/nosql/data"),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let err = get().execute(&handle).await.unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::RegionMismatch);
+    assert!(err.message.contains("us-phoenix-1"), "{}", err.message);
+    // redirects to other hosts are never followed
+    let err = get().execute(&handle).await.unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::IllegalArgument);
+    assert!(err.message.contains("example.com"), "{}", err.message);
+    assert_eq!(server.requests(), 2);
+    assert_eq!(target.requests(), 0);
+
+    // when enabled, the redirect is followed and the handle switches regions
+    let server = MockServer::start(vec![MockServer::redirect_response(&location)]).await;
+    let client = reqwest::Client::builder()
+        .resolve(host, "127.0.0.1:0".parse()?)
+        .danger_accept_invalid_certs(true)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?;
+    let handle = server
+        .builder()
+        .reqwest_client(&client)?
+        .follow_region_redirects(true)?
+        .build()
+        .await?;
+    for _ in 0..2 {
+        let res = get().execute(&handle).await?;
+        assert_eq!(res.row().unwrap().get_i32("id"), Some(1));
+    }
+    assert_eq!(server.requests(), 1);
+    assert_eq!(target.requests(), 2);
+    assert_eq!(
+        handle.endpoint(),
+        format!("https://{}:{}/V2/nosql/data", host, port)
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_request_observer() -> Result<(), Box<dyn Error>> {
+    use crate::error::{NoSQLError, NoSQLErrorCode};
+    use crate::mock_server::MockServer;
+    use crate::nson::{CONSUMED, READ_KB, READ_UNITS, WRITE_KB};
+    use crate::observer::{RequestEvent, RequestObserver};
+    use crate::types::Capacity;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Arc<Mutex<Vec<String>>>,
+    }
+    impl RequestObserver for Recorder {
+        fn on_request_start(&self, event: &RequestEvent) {
+            self.events.lock().unwrap().push(format!(
+                "start {:?} {}",
+                event.op_code().unwrap(),
+                event.table_name()
+            ));
+        }
+        fn on_retry(&self, event: &RequestEvent, error: &NoSQLError, delay: Duration) {
+            self.events.lock().unwrap().push(format!(
+                "retry {} {:?} {}",
+                event.retries(),
+                error.code,
+                delay.as_millis()
+            ));
+        }
+        fn on_response(&self, event: &RequestEvent, consumed: Option<&Capacity>) {
+            assert!(event.request_id() > 0);
+            self.events.lock().unwrap().push(format!(
+                "response {} {}",
+                event.retries(),
+                consumed.map_or(0, |c| c.read_units)
+            ));
+        }
+        fn on_error(&self, event: &RequestEvent, error: &NoSQLError) {
+            self.events.lock().unwrap().push(format!(
+                "error {:?} {:?}",
+                event.op_code().unwrap(),
+                error.code
+            ));
+        }
+    }
+
+    let consumed = MapValue::new()
+        .i32(READ_UNITS, 2)
+        .i32(READ_KB, 1)
+        .i32(WRITE_KB, 0);
+    let server = MockServer::start(vec![
+        MockServer::error_response(NoSQLErrorCode::ReadLimitExceeded, "throttled"),
+        MockServer::response(MapValue::new().column(CONSUMED, FieldValue::Map(consumed))),
+        MockServer::error_response(NoSQLErrorCode::TableNotFound, "no such table"),
+    ])
+    .await;
+    let recorder = Recorder::default();
+    let events = recorder.events.clone();
+    let handle = server
+        .builder()
+        .observer(Box::new(recorder))?
+        .build()
+        .await?;
+    GetRequest::new("users")
+        .key(MapValue::new().i32("id", 1))
+        .execute(&handle)
+        .await?;
+    assert!(PutRequest::new("other")
+        .value(MapValue::new().i32("id", 1))
+        .execute(&handle)
+        .await
+        .is_err());
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![
+            "start Get users",
+            "retry 1 ReadLimitExceeded 100",
+            "response 1 2",
+            "start Put other",
+            "error Put TableNotFound",
+        ]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_default_headers() -> Result<(), Box<dyn Error>> {
+    use crate::mock_server::MockServer;
+    use reqwest::header::{HeaderMap, HeaderValue};
+
+    let server = MockServer::start(vec![MockServer::response(MapValue::new())]).await;
+    let mut headers = HeaderMap::new();
+    headers.insert("x-api-key", HeaderValue::from_static("gateway-key"));
+    headers.append("x-route", HeaderValue::from_static("a"));
+    headers.append("x-route", HeaderValue::from_static("b"));
+    let handle = server.builder().default_headers(headers)?.build().await?;
+    GetRequest::new("users")
+        .key(MapValue::new().i32("id", 1))
+        .execute(&handle)
+        .await?;
+    let head = server.request_heads()[0].to_lowercase();
+    assert!(head.contains("x-api-key: gateway-key"), "{}", head);
+    assert!(head.contains("x-route: a"), "{}", head);
+    assert!(head.contains("x-route: b"), "{}", head);
+    assert!(head.contains("authorization: bearer rust"), "{}", head);
+
+    // headers managed by the SDK cannot be given
+    for name in ["Authorization", "x-nosql-request-id", "User-Agent"] {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_static("x"));
+        assert!(server.builder().default_headers(headers).is_err());
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_protocol_versions() -> Result<(), Box<dyn Error>> {
+    use crate::error::NoSQLErrorCode;
+    use crate::mock_server::MockServer;
+    use crate::nson::{PREPARED_QUERY, QUERY_RESULTS, QUERY_V3, QUERY_VERSION, SERIAL_VERSION};
+
+    let server = MockServer::start(vec![
+        MockServer::response(
+            MapValue::new()
+                .column(QUERY_RESULTS, FieldValue::Array(vec![]))
+                .column(PREPARED_QUERY, FieldValue::Binary(vec![1, 2, 3])),
+        ),
+        MockServer::error_response(NoSQLErrorCode::UnsupportedQueryVersion, "too new"),
+        MockServer::error_response(NoSQLErrorCode::UnsupportedProtocol, "too new"),
+    ])
+    .await;
+    let handle = server.handle().await;
+    assert_eq!(handle.serial_version(), SERIAL_VERSION);
+    assert_eq!(handle.query_version(), QUERY_V3);
+
+    // queries send the query version
+    QueryRequest::new("select * from users")
+        .execute(&handle)
+        .await?;
+    let body = &server.request_bodies()[0];
+    assert_eq!(Reader::new().from_bytes(body).read_i16()?, SERIAL_VERSION);
+    assert_eq!(
+        server.request_payload(0).get_i32(QUERY_VERSION),
+        Some(QUERY_V3 as i32)
+    );
+
+    // the SDK supports a single version of each, so these are not resent
+    let err = QueryRequest::new("select * from users")
+        .execute(&handle)
+        .await
+        .unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::UnsupportedQueryVersion);
+    let err = GetRequest::new("users")
+        .key(MapValue::new().i32("id", 1))
+        .execute(&handle)
+        .await
+        .unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::UnsupportedProtocol);
+    assert_eq!(server.requests(), 3);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_deadline() -> Result<(), Box<dyn Error>> {
+    use crate::error::NoSQLErrorCode;
+    use crate::mock_server::MockServer;
+    use crate::nson::TIMEOUT;
+    use crate::Deadline;
+
+    let server = MockServer::start(vec![
+        MockServer::response(MapValue::new()),
+        MockServer::response(MapValue::new()),
+        MockServer::response(MapValue::new()),
+    ])
+    .await;
+    let handle = server
+        .builder()
+        .timeout(Duration::from_secs(20))?
+        .build()
+        .await?;
+    let get = || GetRequest::new("users").key(("id", 1));
+    let sent_timeout = |i: usize| server.request_header(i).get_i32(TIMEOUT).unwrap();
+
+    // each request uses at most the time remaining
+    let deadline = Deadline::after(Duration::from_secs(5));
+    deadline
+        .scope(async {
+            assert_eq!(Deadline::current(), Some(deadline));
+            get().execute(&handle).await?;
+            get()
+                .timeout(&Duration::from_secs(1))
+                .execute(&handle)
+                .await
+        })
+        .await?;
+    let t = sent_timeout(0);
+    assert!(t > 4000 && t <= 5000, "timeout {}", t);
+    assert_eq!(sent_timeout(1), 1000);
+    // no deadline outside the scope
+    assert_eq!(Deadline::current(), None);
+    get().execute(&handle).await?;
+    assert_eq!(sent_timeout(2), 20000);
+
+    // nested scopes use the earliest deadline
+    let outer = Deadline::after(Duration::from_millis(100));
+    let inner = outer
+        .scope(Deadline::after(Duration::from_secs(10)).scope(async { Deadline::current() }))
+        .await;
+    assert_eq!(inner, Some(outer));
+
+    // requests after the deadline fail without being sent
+    let err = Deadline::after(Duration::ZERO)
+        .scope(get().execute(&handle))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::RequestTimeout);
+    assert_eq!(server.requests(), 3);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_get_projection() -> Result<(), Box<dyn Error>> {
+    use crate::mock_server::MockServer;
+    use crate::nson::{QUERY_RESULTS, STATEMENT};
+
+    let server = MockServer::start(vec![
+        MockServer::prepared_response(),
+        MockServer::response(MapValue::new().column(
+            QUERY_RESULTS,
+            FieldValue::Array(vec![FieldValue::Map(
+                MapValue::new().str("name", "jane").i32("zip", 12345),
+            )]),
+        )),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let key = || MapValue::new().i32("shard", 1).i64("id", 10);
+    let res = GetRequest::new("users")
+        .key(key())
+        .project(&["name", "zip"])
+        .execute(&handle)
+        .await?;
+    let row = res.row().unwrap();
+    assert_eq!(row.len(), 2);
+    assert_eq!(row.get_string("name"), Some("jane".to_string()));
+    assert!(res.version().is_none());
+
+    assert_eq!(
+        server.request_payload(0).get_string(STATEMENT),
+        Some("SELECT t.name, t.zip FROM users t WHERE t.id = ? AND t.shard = ?".to_string())
+    );
+
+    // invalid fields are rejected before sending
+    assert!(GetRequest::new("users")
+        .key(key())
+        .project(&["name, t.secret"])
+        .execute(&handle)
+        .await
+        .is_err());
+    assert!(GetRequest::new("users")
+        .key(key())
+        .project(&["name"])
+        .execute_borrowed(&handle)
+        .await
+        .is_err());
+    assert_eq!(server.requests(), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_get_binary_stream() -> Result<(), Box<dyn Error>> {
+    use crate::mock_server::MockServer;
+    use crate::nson::{ROW, VALUE};
+    use tokio::io::AsyncReadExt;
+
+    let data: Vec<u8> = (0..200u32).map(|i| (i % 251) as u8).collect();
+    let row = |content: FieldValue| {
+        MockServer::response(MapValue::new().column(
+            ROW,
+            FieldValue::Map(MapValue::new().column(
+                VALUE,
+                FieldValue::Map(MapValue::new().i64("id", 10).column("content", content)),
+            )),
+        ))
+    };
+    let server = MockServer::start(vec![
+        row(FieldValue::Binary(data.clone())),
+        row(FieldValue::Binary(data.clone())),
+        row(FieldValue::Null),
+        row(FieldValue::String("text".into())),
+        MockServer::response(MapValue::new()),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let req = GetRequest::new("media").key(MapValue::new().i64("id", 10));
+
+    // chunks of at most the chunk size, in order
+    let stream = req
+        .binary_field_as_stream(&handle, "content")
+        .await?
+        .unwrap()
+        .chunk_size(64);
+    assert_eq!(stream.len(), 200);
+    let chunks: Vec<bytes::Bytes> = stream.collect();
+    let sizes: Vec<usize> = chunks.iter().map(|c| c.len()).collect();
+    assert_eq!(sizes, vec![64, 64, 64, 8]);
+    assert_eq!(chunks.concat(), data);
+
+    let mut stream = req
+        .binary_field_as_stream(&handle, "content")
+        .await?
+        .unwrap();
+    let mut read = Vec::new();
+    stream.read_to_end(&mut read).await?;
+    assert_eq!(read, data);
+    assert!(stream.is_empty());
+
+    // NULL column, non-binary column and missing row
+    assert!(req
+        .binary_field_as_stream(&handle, "content")
+        .await?
+        .is_none());
+    let err = req
+        .binary_field_as_stream(&handle, "content")
+        .await
+        .unwrap_err();
+    assert_eq!(err.code, crate::error::NoSQLErrorCode::IllegalArgument);
+    assert!(req
+        .binary_field_as_stream(&handle, "content")
+        .await?
+        .is_none());
+    assert_eq!(server.requests(), 5);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_multi_get() -> Result<(), Box<dyn Error>> {
+    use crate::mock_server::MockServer;
+    use crate::multi_get_request::MultiGetRequest;
+    use crate::nson::{BIND_VARIABLES, QUERY_RESULTS, STATEMENT, VALUE};
+
+    let server = MockServer::start(vec![
+        MockServer::prepared_response(),
+        MockServer::response(MapValue::new().column(
+            QUERY_RESULTS,
+            FieldValue::Array(vec![
+                FieldValue::Map(MapValue::new().i64("id", 30).str("name", "joe")),
+                FieldValue::Map(MapValue::new().i64("id", 10).str("name", "jane")),
+            ]),
+        )),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let res = MultiGetRequest::new("users", "id")
+        .keys(&[10, 20, 30, 10])?
+        .execute(&handle)
+        .await?;
+    assert_eq!(res.len(), 2);
+    assert_eq!(
+        res.get(&FieldValue::Integer(30))
+            .unwrap()
+            .get_string("name"),
+        Some("joe".to_string())
+    );
+    assert!(res.get(&FieldValue::Long(20)).is_none());
+    let ordered: Vec<Option<String>> = res
+        .ordered_rows()
+        .iter()
+        .map(|(_, r)| r.and_then(|r| r.get_string("name")))
+        .collect();
+    assert_eq!(
+        ordered,
+        vec![
+            Some("jane".to_string()),
+            None,
+            Some("joe".to_string()),
+            Some("jane".to_string())
+        ]
+    );
+
+    assert_eq!(
+        server.request_payload(0).get_string(STATEMENT),
+        Some(
+            "DECLARE $keys ARRAY(ANYATOMIC); SELECT * FROM users t WHERE t.id IN $keys[]"
+                .to_string()
+        )
+    );
+    // duplicate keys are only sent once
+    let vars = server
+        .request_payload(1)
+        .get_array(BIND_VARIABLES)
+        .unwrap()
+        .iter()
+        .map(|v| v.get_map_value_ref().unwrap().deep_clone())
+        .collect::<Vec<MapValue>>();
+    assert_eq!(vars.len(), 1);
+    assert_eq!(
+        vars[0][VALUE],
+        FieldValue::Array(vec![
+            FieldValue::Integer(10),
+            FieldValue::Integer(20),
+            FieldValue::Integer(30)
+        ])
+    );
+
+    // no keys sends no requests, and invalid names are rejected before sending
+    let empty: [i32; 0] = [];
+    let res = MultiGetRequest::new("users", "id")
+        .keys(&empty)?
+        .execute(&handle)
+        .await?;
+    assert!(res.is_empty());
+    assert!(res.ordered_rows().is_empty());
+    assert!(MultiGetRequest::new("users", "id or 1=1")
+        .keys(&[1])?
+        .execute(&handle)
+        .await
+        .is_err());
+    assert!(MultiGetRequest::new("users", "id")
+        .keys(&[FieldValue::Null])
+        .is_err());
+    assert_eq!(server.requests(), 2);
+
+    // with a prepare cache, later requests for the same table only execute the query
+    let rows = || {
+        MockServer::response(MapValue::new().column(
+            QUERY_RESULTS,
+            FieldValue::Array(vec![FieldValue::Map(MapValue::new().i64("id", 10))]),
+        ))
+    };
+    let server = MockServer::start(vec![MockServer::prepared_response(), rows(), rows()]).await;
+    let handle = server.builder().prepare_cache_size(10)?.build().await?;
+    for keys in [[10, 20], [10, 30]] {
+        let res = MultiGetRequest::new("users", "id")
+            .keys(&keys)?
+            .execute(&handle)
+            .await?;
+        assert_eq!(res.len(), 1);
+    }
+    assert_eq!(server.requests(), 3);
+    assert!(server
+        .request_payload(2)
+        .get_field_value(STATEMENT)
+        .is_none());
+    assert!(server
+        .request_payload(2)
+        .get_array(BIND_VARIABLES)
+        .is_some());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_multi_delete_count_only() -> Result<(), Box<dyn Error>> {
+    use crate::mock_server::MockServer;
+    use crate::multi_delete_request::{FieldRange, MultiDeleteRequest};
+    use crate::nson::{OP_CODE, QUERY_RESULTS};
+
+    let key = || FieldValue::Map(MapValue::new().str("tenant", "acme"));
+    assert_eq!(
+        MultiDeleteRequest::new("events", &key()).count_statement()?,
+        "SELECT count(*) AS numRows FROM events t WHERE t.tenant = ?"
+    );
+    let range = FieldRange {
+        field_path: "ts".to_string(),
+        start: Some(FieldValue::Long(100)),
+        start_inclusive: true,
+        end: Some(FieldValue::Long(200)),
+        end_inclusive: false,
+    };
+    assert_eq!(
+        MultiDeleteRequest::new("events", &key())
+            .field_range(range)
+            .count_statement()?,
+        "SELECT count(*) AS numRows FROM events t WHERE t.tenant = ? AND t.ts >= ? AND t.ts < ?"
+    );
+    let range = FieldRange {
+        field_path: "ts".to_string(),
+        end: Some(FieldValue::Long(200)),
+        end_inclusive: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        MultiDeleteRequest::new("events", &key())
+            .field_range(range)
+            .count_statement()?,
+        "SELECT count(*) AS numRows FROM events t WHERE t.tenant = ? AND t.ts <= ?"
+    );
+    assert!(MultiDeleteRequest::new("events", &key())
+        .field_range(FieldRange {
+            field_path: "ts".to_string(),
+            ..Default::default()
+        })
+        .count_statement()
+        .is_err());
+    assert!(MultiDeleteRequest::new("events", &key())
+        .field_range(FieldRange {
+            field_path: "ts > 0 or 1".to_string(),
+            start: Some(FieldValue::Long(1)),
+            ..Default::default()
+        })
+        .count_statement()
+        .is_err());
+    assert!(MultiDeleteRequest::new("events", &FieldValue::Integer(1))
+        .count_statement()
+        .is_err());
+
+    // executed as a prepared query; nothing is deleted
+    let server = MockServer::start(vec![
+        MockServer::prepared_response(),
+        MockServer::response(MapValue::new().column(
+            QUERY_RESULTS,
+            FieldValue::Array(vec![FieldValue::Map(MapValue::new().i64("numRows", 42))]),
+        )),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let n = MultiDeleteRequest::new("events", &key())
+        .count_only(&handle)
+        .await?;
+    assert_eq!(n, 42);
+    assert_eq!(server.requests(), 2);
+    let ops: Vec<Option<i32>> = (0..2)
+        .map(|i| server.request_header(i).get_i32(OP_CODE))
+        .collect();
+    assert_eq!(
+        ops,
+        vec![Some(OpCode::Prepare as i32), Some(OpCode::Query as i32)]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_multi_delete_where() -> Result<(), Box<dyn Error>> {
+    use crate::condition::Condition;
+    use crate::mock_server::MockServer;
+    use crate::multi_delete_request::MultiDeleteRequest;
+    use crate::nson::{
+        CONTINUATION_KEY, KEY, OPERATIONS, QUERY_RESULTS, STATEMENT, SUCCESS, TABLE_NAME,
+        TABLE_SCHEMA, WM_SUCCESS,
+    };
+
+    let all = || FieldValue::Map(MapValue::new());
+    let inactive = || Condition::new().eq("status", "inactive");
+    assert_eq!(
+        MultiDeleteRequest::new("users", &all())
+            .where_condition(inactive())
+            .count_statement()?,
+        "SELECT count(*) AS numRows FROM users t WHERE t.status = ?"
+    );
+    assert_eq!(
+        MultiDeleteRequest::new("users", &FieldValue::Map(MapValue::new().i32("shard", 1)))
+            .where_condition(inactive())
+            .count_statement()?,
+        "SELECT count(*) AS numRows FROM users t WHERE t.shard = ? AND t.status = ?"
+    );
+    assert!(MultiDeleteRequest::new("users", &all())
+        .where_condition(Condition::new())
+        .count_statement()
+        .is_err());
+
+    let schema = r#"{"name":"users","shardKey":["shard"],"primaryKey":["shard","id"]}"#;
+    let key =
+        |shard: i32, id: i32| FieldValue::Map(MapValue::new().i32("shard", shard).i32("id", id));
+    let deleted = |n: usize| {
+        MockServer::response(
+            MapValue::new().column(
+                WM_SUCCESS,
+                FieldValue::Array(
+                    (0..n)
+                        .map(|_| FieldValue::Map(MapValue::new().column(SUCCESS, true)))
+                        .collect(),
+                ),
+            ),
+        )
+    };
+    let server = MockServer::start(vec![
+        MockServer::response(
+            MapValue::new()
+                .column(TABLE_NAME, "users")
+                .column(TABLE_SCHEMA, schema),
+        ),
+        MockServer::prepared_response(),
+        MockServer::response(
+            MapValue::new()
+                .column(QUERY_RESULTS, FieldValue::Array(vec![key(2, 3), key(1, 1)]))
+                .column(CONTINUATION_KEY, FieldValue::Binary(vec![1])),
+        ),
+        deleted(1),
+        deleted(1),
+        MockServer::response(
+            MapValue::new().column(QUERY_RESULTS, FieldValue::Array(vec![key(1, 2)])),
+        ),
+        deleted(1),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let res = MultiDeleteRequest::new("users", &all())
+        .where_condition(inactive())
+        .execute(&handle)
+        .await?;
+    assert_eq!(res.num_deleted(), 3);
+    assert!(res.continuation_key().is_none());
+    assert_eq!(server.requests(), 7);
+
+    assert_eq!(
+        server.request_payload(1).get_string(STATEMENT).unwrap(),
+        "SELECT t.shard, t.id FROM users t WHERE t.status = ?"
+    );
+    // the keys of each query batch are deleted before the next batch is read,
+    // with one request per shard key
+    let deleted_keys = |i: usize| -> Vec<(i32, i32)> {
+        server
+            .request_payload(i)
+            .get_array(OPERATIONS)
+            .unwrap()
+            .iter()
+            .map(|op| {
+                let key = op.get_map_value_ref().unwrap().get_map(KEY).unwrap();
+                (key.get_i32("shard").unwrap(), key.get_i32("id").unwrap())
+            })
+            .collect()
+    };
+    let mut first = vec![deleted_keys(3), deleted_keys(4)];
+    first.sort();
+    assert_eq!(first, vec![vec![(1, 1)], vec![(2, 3)]]);
+    assert_eq!(
+        server.request_payload(5).get_binary(CONTINUATION_KEY),
+        Some(&vec![1])
+    );
+    assert_eq!(deleted_keys(6), vec![(1, 2)]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_conditional_writes() -> Result<(), Box<dyn Error>> {
+    use crate::condition::Condition;
+    use crate::delete_request::DeleteRequest;
+    use crate::mock_server::MockServer;
+    use crate::nson::QUERY_RESULTS;
+    use crate::types::{Durability, ReplicaAckPolicy, SyncPolicy};
+    use crate::update_request::UpdateRequest;
+    use crate::write_multiple_request::WriteMultipleRequest;
+
+    let key = || MapValue::new().i32("id", 10);
+    let cond = || {
+        Condition::new()
+            .eq("status", "inactive")
+            .lt("info.logins", 5)
+    };
+
+    let del = DeleteRequest::new("users", key()).if_condition(cond());
+    assert_eq!(
+        del.conditional_statement(&cond())?,
+        "DELETE FROM users t WHERE t.id = ? AND t.status = ? AND t.info.logins < ?"
+    );
+    let del = DeleteRequest::new("users", key())
+        .return_row(true)
+        .if_condition(Condition::new().is_null("deleted"));
+    assert_eq!(
+        del.conditional_statement(&Condition::new().is_null("deleted"))?,
+        "DELETE FROM users t WHERE t.id = ? AND t.deleted IS NULL RETURNING *"
+    );
+    assert!(DeleteRequest::new("users", key())
+        .if_version(&vec![1, 2, 3])
+        .conditional_statement(&cond())
+        .is_err());
+    assert!(DeleteRequest::new("users", key())
+        .conditional_statement(&Condition::new().eq("a = 1 or 1", 1))
+        .is_err());
+
+    let stmt = UpdateRequest::new("users", key())
+        .set("name", "jane")
+        .if_condition(cond())
+        .statement()?;
+    assert_eq!(
+        stmt,
+        "UPDATE users t SET t.name = ? WHERE t.id = ? AND t.status = ? AND t.info.logins < ?"
+    );
+
+    // a conditional put updates all non-key columns
+    let put = PutRequest::new("users")
+        .value(key().str("name", "jane").i32("shard", 1))
+        .if_condition(cond());
+    let upd = put.conditional_update(&cond(), &["shard".to_string(), "id".to_string()])?;
+    assert_eq!(
+        upd.statement()?,
+        "UPDATE users t SET t.name = ? WHERE t.id = ? AND t.shard = ? AND t.status = ? AND t.info.logins < ?"
+    );
+    assert!(put
+        .conditional_update(&cond(), &["missing".to_string()])
+        .is_err());
+    let durability = Durability::new(
+        SyncPolicy::WriteNoSync,
+        SyncPolicy::NoSync,
+        ReplicaAckPolicy::All,
+    );
+    let upd = PutRequest::new("users")
+        .value(key().str("name", "jane"))
+        .durability(durability)
+        .conditional_update(&cond(), &["id".to_string()])?;
+    assert_eq!(upd.durability, Some(durability));
+    assert!(PutRequest::new("users")
+        .value(key().str("name", "jane"))
+        .ttl(&Duration::from_secs(7200))
+        .conditional_update(&cond(), &["id".to_string()])
+        .is_err());
+
+    // executed as a prepared query
+    let server = MockServer::start(vec![
+        MockServer::prepared_response(),
+        MockServer::response(MapValue::new().column(
+            QUERY_RESULTS,
+            FieldValue::Array(vec![FieldValue::Map(
+                MapValue::new().i64("numRowsDeleted", 1),
+            )]),
+        )),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let res = DeleteRequest::new("users", key())
+        .if_condition(cond())
+        .execute(&handle)
+        .await?;
+    assert!(res.success());
+    assert_eq!(server.requests(), 2);
+
+    // conditions cannot be used in WriteMultipleRequest
+    let err = WriteMultipleRequest::new("users")
+        .add(Box::new(
+            DeleteRequest::new("users", key()).if_condition(cond()),
+        ))
+        .execute(&handle)
+        .await
+        .unwrap_err();
+    assert_eq!(err.code, crate::NoSQLErrorCode::IllegalArgument);
+    assert_eq!(server.requests(), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_key_size_limit() -> Result<(), Box<dyn Error>> {
+    use crate::delete_request::DeleteRequest;
+    use crate::error::{truncate_utf8, NoSQLErrorCode};
+    use crate::mock_server::MockServer;
+
+    assert_eq!(truncate_utf8("abcdef", 3), "abc");
+    assert_eq!(truncate_utf8("abc", 10), "abc");
+    // 'é' is 2 bytes: never split it
+    assert_eq!(truncate_utf8("aéé", 2), "a");
+    assert_eq!(truncate_utf8("aéé", 3), "aé");
+
+    let server = MockServer::start(vec![]).await;
+    let handle = server.handle().await;
+    let key = MapValue::new().i32("shard", 1).str("name", &"é".repeat(40));
+    let err = GetRequest::new("users")
+        .key(key.clone_internal())
+        .execute(&handle)
+        .await
+        .unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::KeySizeLimitExceeded);
+    assert!(err.message.contains("at least 81 bytes"));
+    assert!(err
+        .message
+        .contains(&format!("name=\"{}...\"", "é".repeat(16))));
+    let err = DeleteRequest::new("users", key)
+        .execute(&handle)
+        .await
+        .unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::KeySizeLimitExceeded);
+    assert_eq!(server.requests(), 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_query_one() -> Result<(), Box<dyn Error>> {
+    use crate::error::{NoSQLError, NoSQLErrorCode};
+    use crate::mock_server::MockServer;
+    use crate::nson::{PREPARED_QUERY, QUERY_RESULTS};
+
+    #[derive(Default)]
+    struct User {
+        id: i32,
+    }
+    impl NoSQLRow for User {
+        fn to_map_value(&self) -> Result<MapValue, NoSQLError> {
+            Ok(MapValue::new().i32("id", self.id))
+        }
+        fn from_map_value(&mut self, value: &MapValue) -> Result<(), NoSQLError> {
+            self.id = value.get_i32("id").unwrap_or_default();
+            Ok(())
+        }
+    }
+
+    let response = |ids: &[i32]| {
+        MockServer::response(
+            MapValue::new()
+                .column(PREPARED_QUERY, FieldValue::Binary(vec![1, 2, 3]))
+                .column(
+                    QUERY_RESULTS,
+                    FieldValue::Array(
+                        ids.iter()
+                            .map(|id| FieldValue::Map(MapValue::new().i32("id", *id)))
+                            .collect(),
+                    ),
+                ),
+        )
+    };
+    let server = MockServer::start(vec![
+        response(&[1, 2]),
+        response(&[1, 2]),
+        response(&[]),
+        response(&[3]),
+    ])
+    .await;
+    let handle = server.handle().await;
+
+    let row = handle.query_one("select * from users", false).await?;
+    assert_eq!(row.unwrap().get_i32("id"), Some(1));
+    let err = handle
+        .query_one("select * from users", true)
+        .await
+        .unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::IllegalState);
+    assert!(handle
+        .query_one("select * from users", true)
+        .await?
+        .is_none());
+    let user: Option<User> = handle.query_one_as("select * from users", true).await?;
+    assert_eq!(user.unwrap().id, 3);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_table_wait_backoff() -> Result<(), Box<dyn Error>> {
+    use crate::mock_server::MockServer;
+    use crate::nson::{OPERATION_ID, TABLE_NAME, TABLE_STATE};
+    use std::time::Instant;
+
+    let state = |s: TableState| {
+        MockServer::response(
+            MapValue::new()
+                .column(TABLE_NAME, "t1")
+                .column(OPERATION_ID, "op1")
+                .column(TABLE_STATE, s as i32),
+        )
+    };
+    let server = MockServer::start(vec![
+        state(TableState::Creating),
+        state(TableState::Creating),
+        state(TableState::Creating),
+        state(TableState::Creating),
+        state(TableState::Active),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let mut res = TableRequest::new("t1")
+        .statement("create table t1(id integer, primary key(id))")
+        .execute(&handle)
+        .await?;
+    let start = Instant::now();
+    res.wait_for_completion_backoff(
+        &handle,
+        Duration::from_secs(5),
+        Duration::from_millis(20),
+        Duration::from_millis(40),
+    )
+    .await?;
+    // first poll is immediate, then delays of 20ms, 40ms, 40ms
+    assert!(start.elapsed() >= Duration::from_millis(100));
+    assert_eq!(server.requests(), 5);
+    assert_eq!(res.state(), TableState::Active);
+
+    // invalid intervals
+    let mut res = TableResult {
+        state: TableState::Creating,
+        ..Default::default()
+    };
+    assert!(res
+        .wait_for_completion_backoff(
+            &handle,
+            Duration::from_secs(5),
+            Duration::from_millis(40),
+            Duration::from_millis(20),
+        )
+        .await
+        .is_err());
+    assert!(res
+        .wait_for_completion_backoff(
+            &handle,
+            Duration::from_secs(5),
+            Duration::ZERO,
+            Duration::from_millis(20),
+        )
+        .await
+        .is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_raw_request() -> Result<(), Box<dyn Error>> {
+    use crate::error::NoSQLErrorCode;
+    use crate::mock_server::MockServer;
+    use crate::nson::{OP_CODE, TABLE_NAME, TIMEOUT};
+    use crate::raw_request::RawRequest;
+
+    let server = MockServer::start(vec![
+        MockServer::response(MapValue::new().column("xx", "result").i32("yy", 7)),
+        MockServer::error_response(NoSQLErrorCode::TableNotFound, "no table"),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let res = RawRequest::new(99)
+        .table_name("t1")
+        .payload(MapValue::new().i64("aa", 42).column("bb", "value"))
+        .timeout(&Duration::from_millis(1500))
+        .execute(&handle)
+        .await?;
+    assert_eq!(res.get_string("xx"), Some("result".to_string()));
+    assert_eq!(res.get_i32("yy"), Some(7));
+
+    let header = server.request_header(0);
+    assert_eq!(header.get_i32(OP_CODE), Some(99));
+    assert_eq!(header.get_i32(TIMEOUT), Some(1500));
+    assert_eq!(header.get_string(TABLE_NAME), Some("t1".to_string()));
+    let payload = server.request_payload(0);
+    assert_eq!(payload.get_i64("aa"), Some(42));
+    assert_eq!(payload.get_string("bb"), Some("value".to_string()));
+
+    let err = handle
+        .raw_request(99, "t1", MapValue::new())
+        .await
+        .unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::TableNotFound);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_query_batch_stats() -> Result<(), Box<dyn Error>> {
+    use crate::mock_server::MockServer;
+    use crate::nson::{
+        CONSUMED, CONTINUATION_KEY, GET_QUERY_PLAN, PREPARED_QUERY, QUERY_PLAN_STRING,
+        QUERY_RESULTS, READ_KB, READ_UNITS, WRITE_KB,
+    };
+
+    let consumed = |units: i32| {
+        MapValue::new()
+            .i32(READ_UNITS, units)
+            .i32(READ_KB, units)
+            .i32(WRITE_KB, 0)
+    };
+    let rows = FieldValue::Array(vec![FieldValue::Map(MapValue::new().i32("id", 1))]);
+    let plan = r#"{
+  "iterator kind" : "TABLE",
+  "target table" : "users",
+  "row variable" : "$$u",
+  "index used" : "idx_age",
+  "covering index" : false
+}"#;
+    let server = MockServer::start(vec![
+        MockServer::response(
+            MapValue::new()
+                .column(PREPARED_QUERY, FieldValue::Binary(vec![1, 2, 3]))
+                .column(QUERY_PLAN_STRING, plan)
+                .column(CONSUMED, consumed(10))
+                .column(QUERY_RESULTS, rows.clone_internal())
+                .column(CONTINUATION_KEY, FieldValue::Binary(vec![4, 5, 6])),
+        ),
+        MockServer::response(
+            MapValue::new()
+                .column(CONSUMED, consumed(4))
+                .column(QUERY_RESULTS, rows),
+        ),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let res = QueryRequest::new("select * from users u where age > 30")
+        .include_query_plan(true)
+        .execute(&handle)
+        .await?;
+    let units: Vec<i32> = res.batch_consumed().iter().map(|c| c.read_units).collect();
+    assert_eq!(units, vec![10, 4]);
+    assert_eq!(res.consumed().read_units, 14);
+    assert_eq!(res.query_plan(), Some(plan));
+    assert_eq!(res.index_used(), Some("idx_age".to_string()));
+
+    assert_eq!(
+        server.request_payload(0).get_bool(GET_QUERY_PLAN),
+        Some(true)
+    );
+    Ok(())
+}
+
+// Prepare a statement for a store with `shards` shards, then create `requests`
+// requests from it. Returns the time taken, and the number of allocations and
+// bytes allocated, per request.
+async fn new_prepared_cost(
+    shards: i32,
+    requests: u32,
+) -> Result<(std::time::Duration, u64, u64), Box<dyn Error>> {
+    use crate::alloc_counter::allocations;
+    use crate::mock_server::MockServer;
+    use crate::nson::{PROXY_TOPO_SEQNUM, SHARD_IDS, TOPOLOGY_INFO};
+    use std::time::Instant;
+
+    let topology = MapValue::new().i32(PROXY_TOPO_SEQNUM, 1).column(
+        SHARD_IDS,
+        FieldValue::Array((1..=shards).map(FieldValue::Integer).collect()),
+    );
+    let server = MockServer::start(vec![MockServer::response(
+        MockServer::prepared_fields().column(TOPOLOGY_INFO, FieldValue::Map(topology)),
+    )])
+    .await;
+    let handle = server.handle().await;
+    let ps = QueryRequest::new("select * from users")
+        .prepare_only()
+        .execute(&handle)
+        .await?
+        .prepared_statement();
+    assert_eq!(ps.shard_ids().len(), shards as usize);
+
+    let (count, bytes) = allocations();
+    let start = Instant::now();
+    for _ in 0..requests {
+        std::hint::black_box(QueryRequest::new_prepared(&ps));
+    }
+    let elapsed = start.elapsed();
+    let (count2, bytes2) = allocations();
+    let n = requests as u64;
+    Ok((
+        elapsed / requests,
+        (count2 - count) / n,
+        (bytes2 - bytes) / n,
+    ))
+}
+
+#[tokio::test]
+async fn test_new_prepared_shares_topology() -> Result<(), Box<dyn Error>> {
+    // the shard list is not copied for each request, so what a request
+    // allocates does not depend on the number of shards
+    let (_, count, bytes) = new_prepared_cost(1, 100).await?;
+    let (_, count_1000, bytes_1000) = new_prepared_cost(1000, 100).await?;
+    assert_eq!((count_1000, bytes_1000), (count, bytes));
+    Ok(())
+}
+
+// Time creating requests from a prepared statement for a store with many shards,
+// which share the topology of the statement, and count their allocations. Run with
+// `cargo test --release --lib -- --ignored --nocapture bench_new_prepared`.
+#[tokio::test]
+#[ignore]
+async fn bench_new_prepared() -> Result<(), Box<dyn Error>> {
+    const SHARDS: i32 = 1000;
+    const REQUESTS: u32 = 100_000;
+    let (elapsed, count, bytes) = new_prepared_cost(SHARDS, REQUESTS).await?;
+    println!(
+        "new_prepared with {} shards: {:?}, {} allocations and {} bytes per request",
+        SHARDS, elapsed, count, bytes
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_query_target_shard() -> Result<(), Box<dyn Error>> {
+    use crate::error::NoSQLErrorCode;
+    use crate::mock_server::MockServer;
+    use crate::nson::{PROXY_TOPO_SEQNUM, QUERY_RESULTS, SHARD_ID, SHARD_IDS, TOPOLOGY_INFO};
+
+    // a topology with two shards
+    let prepared = || {
+        let topology = MapValue::new().i32(PROXY_TOPO_SEQNUM, 1).column(
+            SHARD_IDS,
+            FieldValue::Array(vec![FieldValue::Integer(1), FieldValue::Integer(2)]),
+        );
+        MockServer::response(
+            MockServer::prepared_fields().column(TOPOLOGY_INFO, FieldValue::Map(topology)),
+        )
+    };
+    let rows = FieldValue::Array(vec![FieldValue::Map(MapValue::new().i32("id", 1))]);
+    let server = MockServer::start(vec![
+        prepared(),
+        MockServer::response(MapValue::new().column(QUERY_RESULTS, rows)),
+        prepared(),
+    ])
+    .await;
+    let handle = server.handle().await;
+
+    // the query is prepared first, then sent to the target shard only
+    let mut res = QueryRequest::new("select * from users where sid = 5")
+        .target_shard(2)
+        .execute(&handle)
+        .await?;
+    assert_eq!(res.take_rows().len(), 1);
+    assert_eq!(res.prepared_statement().shard_ids(), vec![1, 2]);
+    assert_eq!(server.requests(), 2);
+    // requests using the statement share its topology, instead of copying it
+    let ps = res.prepared_statement();
+    let (q1, q2) = (
+        QueryRequest::new_prepared(&ps),
+        QueryRequest::new_prepared(&ps),
+    );
+    assert!(std::sync::Arc::ptr_eq(&q1.topology_info, &q2.topology_info));
+    assert_eq!(server.request_payload(1).get_i32(SHARD_ID), Some(2));
+
+    // shards that are not part of the topology are rejected
+    let err = QueryRequest::new("select * from users where sid = 5")
+        .target_shard(7)
+        .execute(&handle)
+        .await
+        .unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::IllegalArgument);
+    assert_eq!(server.requests(), 3);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_table_match_etag() -> Result<(), Box<dyn Error>> {
+    use crate::error::NoSQLErrorCode;
+    use crate::mock_server::MockServer;
+    use crate::nson::{ETAG, OPERATION_ID, TABLE_NAME, TABLE_STATE};
+
+    let table = |state: TableState, etag: &str| {
+        MockServer::response(
+            MapValue::new()
+                .column(TABLE_NAME, "t1")
+                .column(OPERATION_ID, "op1")
+                .column(TABLE_STATE, state as i32)
+                .column(ETAG, etag),
+        )
+    };
+    let server = MockServer::start(vec![
+        table(TableState::Active, "etag1"),
+        table(TableState::Updating, "etag1"),
+        table(TableState::Active, "etag2"),
+        MockServer::error_response(NoSQLErrorCode::EtagMismatch, "etag mismatch"),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let res = GetTableRequest::new("t1").execute(&handle).await?;
+    assert_eq!(res.etag(), Some("etag1".to_string()));
+
+    let alter = |etag: &str| {
+        TableRequest::new("t1")
+            .statement("alter table t1 (add age integer)")
+            .match_etag(etag)
+    };
+    let mut res = alter(&res.etag().unwrap()).execute(&handle).await?;
+    res.wait_for_completion_ms(&handle, 5000, 10).await?;
+    // the table version changed with the alter
+    assert_eq!(res.etag(), Some("etag2".to_string()));
+
+    let err = alter("etag1").execute(&handle).await.unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::EtagMismatch);
+    assert!(alter("").execute(&handle).await.is_err());
+    assert_eq!(server.requests(), 4);
+
+    assert_eq!(
+        server.request_payload(1).get_string(ETAG),
+        Some("etag1".to_string())
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_table_tag_updates() -> Result<(), Box<dyn Error>> {
+    use crate::mock_server::MockServer;
+    use crate::nson::{ETAG, FREE_FORM_TAGS, STATEMENT, TABLE_NAME, TABLE_STATE};
+    use std::collections::HashMap;
+
+    let table = |tags: &str, etag: &str| {
+        MockServer::response(
+            MapValue::new()
+                .column(TABLE_NAME, "t1")
+                .column(TABLE_STATE, TableState::Active as i32)
+                .column(FREE_FORM_TAGS, tags)
+                .column(ETAG, etag),
+        )
+    };
+    let server = MockServer::start(vec![
+        table(r#"{"env":"dev","owner":"a","tmp":"x"}"#, "etag1"),
+        table(
+            r#"{"cost-center":"1234","env":"prod","owner":"a"}"#,
+            "etag2",
+        ),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let res = TableRequest::new("t1")
+        .update_freeform_tag("env", "prod")
+        .update_freeform_tag("cost-center", "1234")
+        .remove_tag("tmp")
+        .remove_tag("missing")
+        .execute(&handle)
+        .await?;
+    let expected: HashMap<String, String> =
+        [("cost-center", "1234"), ("env", "prod"), ("owner", "a")]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+    assert_eq!(res.freeform_tags(), &expected);
+    assert_eq!(server.requests(), 2);
+
+    // the current tags are read, and the whole updated set is sent with their etag
+    let payload = server.request_payload(1);
+    let sent: HashMap<String, String> =
+        serde_json::from_str(&payload.get_string(FREE_FORM_TAGS).unwrap())?;
+    assert_eq!(sent, expected);
+    assert_eq!(payload.get_string(ETAG), Some("etag1".to_string()));
+    assert!(payload.get_field_value(STATEMENT).is_none());
+
+    // tag updates must be on their own, with non-empty keys
+    let err = TableRequest::new("t1")
+        .statement("alter table t1 (add age integer)")
+        .update_freeform_tag("env", "prod")
+        .execute(&handle)
+        .await
+        .unwrap_err();
+    assert_eq!(err.code, crate::error::NoSQLErrorCode::IllegalArgument);
+    assert!(TableRequest::new("t1")
+        .remove_tag("")
+        .execute(&handle)
+        .await
+        .is_err());
+    assert_eq!(server.requests(), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_table_operation_id() -> Result<(), Box<dyn Error>> {
+    use crate::mock_server::MockServer;
+    use crate::nson::{OPERATION_ID, TABLE_NAME, TABLE_STATE};
+
+    let table = |state: TableState, op_id: Option<&str>| {
+        let mut m = MapValue::new()
+            .column(TABLE_NAME, "t1")
+            .column(TABLE_STATE, state as i32);
+        if let Some(id) = op_id {
+            m = m.column(OPERATION_ID, id);
+        }
+        MockServer::response(m)
+    };
+    let server = MockServer::start(vec![
+        table(TableState::Creating, Some("op1")),
+        table(TableState::Creating, None),
+        table(TableState::Active, None),
+        table(TableState::Active, None),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let res = TableRequest::new("t1")
+        .statement("create table t1 (id integer, primary key(id))")
+        .execute(&handle)
+        .await?;
+    let op_id = res.operation_id().expect("operation id");
+    assert_eq!(op_id, "op1");
+
+    // resume waiting for the operation with the saved id
+    let mut res = GetTableRequest::by_operation_id("t1", &op_id)
+        .execute(&handle)
+        .await?;
+    assert_eq!(res.operation_id(), Some("op1".to_string()));
+    res.wait_for_completion_ms(&handle, 5000, 10).await?;
+    assert_eq!(res.state(), TableState::Active);
+    for i in 1..3 {
+        assert_eq!(
+            server.request_payload(i).get_string(OPERATION_ID),
+            Some("op1".to_string())
+        );
+    }
+
+    let res = GetTableRequest::new("t1").execute(&handle).await?;
+    assert_eq!(res.operation_id(), None);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_table_ocid() -> Result<(), Box<dyn Error>> {
+    use crate::mock_server::MockServer;
+    use crate::nson::{COMPARTMENT_OCID, TABLE_NAME, TABLE_OCID, TABLE_STATE};
+
+    let table = MapValue::new()
+        .column(TABLE_NAME, "t1")
+        .column(TABLE_STATE, TableState::Active as i32);
+    let server = MockServer::start(vec![
+        MockServer::response(
+            table
+                .deep_clone()
+                .column(TABLE_OCID, "ocid1.nosqltable.oc1.phx.aaaa")
+                .column(COMPARTMENT_OCID, "ocid1.compartment.oc1..bbbb"),
+        ),
+        MockServer::response(table),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let res = GetTableRequest::new("t1").execute(&handle).await?;
+    assert_eq!(
+        res.ocid(),
+        Some("ocid1.nosqltable.oc1.phx.aaaa".to_string())
+    );
+    assert_eq!(res.table_ocid(), "ocid1.nosqltable.oc1.phx.aaaa");
+    assert_eq!(
+        res.compartment_id(),
+        Some("ocid1.compartment.oc1..bbbb".to_string())
+    );
+
+    // on-premises stores return neither
+    let res = GetTableRequest::new("t1").execute(&handle).await?;
+    assert_eq!(res.ocid(), None);
+    assert_eq!(res.compartment_id(), None);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_table_prefix() -> Result<(), Box<dyn Error>> {
+    use crate::error::NoSQLErrorCode;
+    use crate::handle::prefixed_table_name;
+    use crate::mock_server::MockServer;
+    use crate::nson::{QUERY_RESULTS, ROW_VERSION, STATEMENT, TABLE_NAME};
+    use crate::update_request::UpdateRequest;
+    use crate::write_multiple_request::WriteMultipleRequest;
+
+    assert_eq!(prefixed_table_name("acme_", "users"), "acme_users");
+    assert_eq!(prefixed_table_name("acme_", "ns1:users"), "ns1:acme_users");
+    assert_eq!(
+        prefixed_table_name("acme_", "users.addresses"),
+        "acme_users.addresses"
+    );
+    assert_eq!(prefixed_table_name("acme_", "/settings"), "settings");
+    assert_eq!(prefixed_table_name("", "users"), "users");
+    assert!(crate::Handle::builder().table_prefix("1x").is_err());
+    assert!(crate::Handle::builder().table_prefix("a-b").is_err());
+
+    let server = MockServer::start(vec![
+        MockServer::response(MapValue::new()),
+        MockServer::response(MapValue::new()),
+        MockServer::response(MapValue::new().column(ROW_VERSION, FieldValue::Binary(vec![1]))),
+        MockServer::response(MapValue::new().str(TABLE_NAME, "ACME_users")),
+        MockServer::response(MapValue::new().str(TABLE_NAME, "settings")),
+        MockServer::prepared_response(),
+        MockServer::response(MapValue::new()),
+    ])
+    .await;
+    let handle = server.builder().table_prefix("acme_")?.build().await?;
+    assert_eq!(handle.table_name("users"), "acme_users");
+    assert_eq!(handle.logical_table_name("ns1:acme_users"), "ns1:users");
+
+    let key = || MapValue::new().i32("id", 1);
+    GetRequest::new("users").key(key()).execute(&handle).await?;
+    GetRequest::new("/settings")
+        .key(key())
+        .execute(&handle)
+        .await?;
+    let mut req = WriteMultipleRequest::new("users");
+    req = req.add(Box::new(PutRequest::new("users").value(key())));
+    req.execute(&handle).await?;
+    let res = GetTableRequest::new("users").execute(&handle).await?;
+    assert_eq!(res.table_name(), "users");
+    let res = GetTableRequest::new("/settings").execute(&handle).await?;
+    assert_eq!(res.table_name(), "/settings");
+    UpdateRequest::new("users", key())
+        .set("name", "jane")
+        .execute(&handle)
+        .await?;
+
+    let header_table = |i: usize| server.request_header(i).get_string(TABLE_NAME);
+    assert_eq!(header_table(0), Some("acme_users".to_string()));
+    assert_eq!(header_table(1), Some("settings".to_string()));
+    assert_eq!(header_table(3), Some("acme_users".to_string()));
+    assert_eq!(header_table(2), Some("acme_users".to_string()));
+    let stmt = server.request_payload(5).get_string(STATEMENT);
+    assert!(stmt.unwrap().starts_with("UPDATE acme_users t SET"));
+
+    // SQL text must use prefixed table names
+    assert_eq!(
+        ddl_table_name("CREATE TABLE IF NOT EXISTS users(id INTEGER, PRIMARY KEY(id))"),
+        Some("users")
+    );
+    assert_eq!(
+        ddl_table_name("alter table ns1:users (ADD age INTEGER)"),
+        Some("ns1:users")
+    );
+    assert_eq!(ddl_table_name("DROP TABLE IF EXISTS users;"), Some("users"));
+    assert_eq!(
+        ddl_table_name("CREATE INDEX IF NOT EXISTS idx ON users.addresses(city)"),
+        Some("users.addresses")
+    );
+    assert_eq!(ddl_table_name("drop index idx on users"), Some("users"));
+    assert_eq!(ddl_table_name("CREATE NAMESPACE ns1"), None);
+
+    let prepared =
+        |table: &str| MockServer::response(MockServer::prepared_fields().str(TABLE_NAME, table));
+    let server = MockServer::start(vec![
+        MockServer::response(MapValue::new().str(TABLE_NAME, "acme_users")),
+        prepared("users"),
+        prepared("ACME_users"),
+        MockServer::response(MapValue::new().column(QUERY_RESULTS, FieldValue::Array(vec![]))),
+    ])
+    .await;
+    let handle = server.builder().table_prefix("acme_")?.build().await?;
+    let err = TableRequest::new("")
+        .statement("CREATE TABLE users (id INTEGER, PRIMARY KEY(id))")
+        .execute(&handle)
+        .await
+        .unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::IllegalArgument);
+    TableRequest::new("")
+        .statement("CREATE TABLE acme_users (id INTEGER, PRIMARY KEY(id))")
+        .execute(&handle)
+        .await?;
+    // queries are prepared first, and not executed on an unprefixed table
+    let err = QueryRequest::new("delete from users")
+        .execute(&handle)
+        .await
+        .unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::IllegalArgument);
+    QueryRequest::new("select * from acme_users")
+        .execute(&handle)
+        .await?;
+    assert_eq!(server.requests(), 4);
+    let stmt = |i: usize| server.request_payload(i).get_string(STATEMENT);
+    assert_eq!(stmt(1), Some("delete from users".to_string()));
+    assert_eq!(stmt(2), Some("select * from acme_users".to_string()));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bound_variables() -> Result<(), Box<dyn Error>> {
+    use crate::mock_server::MockServer;
+    use crate::nson::{BIND_VARIABLES, NAME, QUERY_RESULTS};
+
+    let results =
+        || MockServer::response(MapValue::new().column(QUERY_RESULTS, FieldValue::Array(vec![])));
+    let server =
+        MockServer::start(vec![MockServer::prepared_response(), results(), results()]).await;
+    let handle = server.handle().await;
+    let ps = QueryRequest::new("declare $a integer; $b string; select * from t")
+        .prepare_only()
+        .execute(&handle)
+        .await?
+        .prepared_statement();
+    assert!(ps.bound_variables().is_empty());
+
+    let mut qreq = QueryRequest::new_prepared(&ps);
+    qreq.set_variable("$a", &1)?;
+    qreq.set_variable("$b", &"x")?;
+    qreq.set_variable_by_id(3, &true)?;
+    let mut names: Vec<&String> = qreq.bound_variables().keys().collect();
+    names.sort();
+    assert_eq!(names, vec!["#3", "$a", "$b"]);
+    assert_eq!(
+        qreq.bound_variables()["$b"],
+        FieldValue::String("x".to_string())
+    );
+    qreq.execute(&handle).await?;
+
+    // bindings stay set across executions until cleared
+    assert_eq!(qreq.bound_variables().len(), 3);
+    assert!(qreq.clear_variable("$b"));
+    assert!(qreq.clear_variable("#3"));
+    assert!(!qreq.clear_variable("$b"));
+    qreq.execute(&handle).await?;
+
+    let sent = |i: usize| {
+        let mut names: Vec<String> = server
+            .request_payload(i)
+            .get_array(BIND_VARIABLES)
+            .unwrap()
+            .iter()
+            .map(|v| {
+                v.get_map_value_ref()
+                    .unwrap()
+                    .get_string(NAME)
+                    .unwrap()
+                    .to_string()
+            })
+            .collect();
+        names.sort();
+        names
+    };
+    assert_eq!(sent(1), vec!["#3", "$a", "$b"]);
+    assert_eq!(sent(2), vec!["$a"]);
+
+    // copies of a statement never carry bound variables
+    let mut ps = ps.clone();
+    assert!(QueryRequest::new_prepared(&ps).bound_variables().is_empty());
+    assert!(!ps.clear_variable("$a"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_prepared_query_options_persist() -> Result<(), Box<dyn Error>> {
+    use crate::mock_server::MockServer;
+    use crate::nson::{CONSISTENCY, MAX_READ_KB, QUERY_RESULTS, TIMEOUT, TYPE};
+
+    let results =
+        || MockServer::response(MapValue::new().column(QUERY_RESULTS, FieldValue::Array(vec![])));
+    let server = MockServer::start(vec![
+        MockServer::prepared_response(),
+        results(),
+        results(),
+        results(),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let ps = QueryRequest::new("declare $id integer; select * from users where id = $id")
+        .prepare_only()
+        .execute(&handle)
+        .await?
+        .prepared_statement();
+    let mut qreq = QueryRequest::new_prepared(&ps)
+        .consistency(&Consistency::Absolute)
+        .timeout(&Duration::from_millis(4321))
+        .max_read_kb(7);
+    // options set once apply to every execution of the request
+    for id in 1..=3 {
+        qreq.set_variable("$id", &id)?;
+        qreq.execute(&handle).await?;
+    }
+    assert_eq!(server.requests(), 4);
+    for i in 1..4 {
+        assert_eq!(server.request_header(i).get_i32(TIMEOUT), Some(4321));
+        let payload = server.request_payload(i);
+        assert_eq!(payload.get_i32(MAX_READ_KB), Some(7));
+        let consistency = payload.get_map(CONSISTENCY).unwrap();
+        assert_eq!(
+            consistency.get_i32(TYPE),
+            Some(Consistency::Absolute as i32 - 1)
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_query_check_schema() -> Result<(), Box<dyn Error>> {
+    use crate::error::NoSQLErrorCode;
+    use crate::mock_server::MockServer;
+    use crate::nson::{ETAG, QUERY_RESULTS, TABLE_NAME, TABLE_SCHEMA, TABLE_STATE};
+
+    let table = |schema: &str, etag: Option<&str>| {
+        let mut m = MapValue::new()
+            .column(TABLE_NAME, "users")
+            .column(TABLE_STATE, TableState::Active as i32)
+            .column(TABLE_SCHEMA, schema);
+        if let Some(e) = etag {
+            m = m.column(ETAG, e);
+        }
+        MockServer::response(m)
+    };
+    let prepared =
+        || MockServer::response(MockServer::prepared_fields().column(TABLE_NAME, "users"));
+    let results =
+        || MockServer::response(MapValue::new().column(QUERY_RESULTS, FieldValue::Array(vec![])));
+    let server = MockServer::start(vec![
+        prepared(),
+        table("schema1", None),
+        results(),
+        table("schema1", None),
+        results(),
+        table("schema2", None),
+        prepared(),
+        table("schema1", Some("etag1")),
+        table("schema1", Some("etag2")),
+    ])
+    .await;
+    let prepare = |handle: crate::Handle| async move {
+        QueryRequest::new("select * from users")
+            .prepare_only()
+            .check_schema(true)
+            .execute(&handle)
+            .await
+            .map(|res| res.prepared_statement())
+    };
+    let handle = server.handle().await;
+    let ps = prepare(handle.clone()).await?;
+    assert_eq!(server.requests(), 2);
+
+    // the version read when preparing is reused within the schema check interval
+    let mut qreq = QueryRequest::new_prepared(&ps).check_schema(true);
+    qreq.execute(&handle).await?;
+    assert_eq!(server.requests(), 3);
+
+    // with a short interval, the table is read before each execution
+    assert!(server
+        .builder()
+        .schema_check_interval(Duration::ZERO)
+        .is_err());
+    let handle = server
+        .builder()
+        .schema_check_interval(Duration::from_nanos(1))?
+        .build()
+        .await?;
+    qreq.execute(&handle).await?;
+    assert_eq!(server.requests(), 5);
+    let err = qreq.execute(&handle).await.unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::SchemaChanged);
+    assert!(err.message.contains("users"), "{}", err.message);
+    assert_eq!(server.requests(), 6);
+
+    // the ETag is compared if the server returns one
+    let ps = prepare(handle.clone()).await?;
+    let mut qreq = QueryRequest::new_prepared(&ps).check_schema(true);
+    let err = qreq.execute(&handle).await.unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::SchemaChanged);
+    assert_eq!(server.requests(), 9);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_timestamp_range() -> Result<(), Box<dyn Error>> {
+    use crate::mock_server::MockServer;
+    use crate::nson::{BIND_VARIABLES, NAME, QUERY_RESULTS, VALUE};
+    use chrono::DateTime;
+
+    let server = MockServer::start(vec![
+        MockServer::prepared_response(),
+        MockServer::response(MapValue::new().column(QUERY_RESULTS, FieldValue::Array(vec![]))),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let ps = QueryRequest::new(
+        "declare $start timestamp; $end timestamp; \
+         select * from events where ts >= $start and ts < $end",
+    )
+    .prepare_only()
+    .execute(&handle)
+    .await?
+    .prepared_statement();
+    let mut qreq = QueryRequest::new_prepared(&ps);
+    let start = DateTime::parse_from_rfc3339("2024-05-06T21:30:59.1234+09:00")?;
+    let end = DateTime::parse_from_rfc3339("2024-05-06T12:31:59.9996Z")?;
+
+    // invalid ranges set neither variable
+    assert!(qreq
+        .set_timestamp_range("$start", "$end", &end, &start, 3)
+        .is_err());
+    assert!(qreq
+        .set_timestamp_range("$start", "$end", &start, &end, 10)
+        .is_err());
+    assert!(qreq
+        .set_timestamp_range("$start", "$start", &start, &end, 3)
+        .is_err());
+    assert!(qreq.bound_variables().is_empty());
+
+    // both boundaries use the server encoding for a TIMESTAMP(3) column
+    qreq.set_timestamp_range("$start", "$end", &start, &end, 3)?;
+    qreq.execute(&handle).await?;
+    let mut vars: Vec<(String, String)> = server
+        .request_payload(1)
+        .get_array(BIND_VARIABLES)
+        .unwrap()
+        .iter()
+        .map(|v| {
+            let m = v.get_map_value_ref().unwrap();
+            (
+                m.get_string(NAME).unwrap(),
+                m.get_timestamp(VALUE).unwrap().to_rfc3339(),
+            )
+        })
+        .collect();
+    vars.sort();
+    assert_eq!(
+        vars,
+        vec![
+            ("$end".to_string(), "2024-05-06T12:32:00+00:00".to_string()),
+            (
+                "$start".to_string(),
+                "2024-05-06T12:30:59.123+00:00".to_string()
+            ),
+        ]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bulk_loader() -> Result<(), Box<dyn Error>> {
+    use crate::error::NoSQLErrorCode;
+    use crate::mock_server::MockServer;
+    use crate::nson::{OPERATIONS, SUCCESS, TABLE_NAME, TABLE_SCHEMA, WM_SUCCESS};
+    use crate::BulkLoader;
+
+    let schema = r#"{"name":"users","shardKey":["shard"],"primaryKey":["shard","id"]}"#;
+    let written = |n: usize| {
+        MockServer::response(
+            MapValue::new().column(
+                WM_SUCCESS,
+                FieldValue::Array(
+                    (0..n)
+                        .map(|_| FieldValue::Map(MapValue::new().column(SUCCESS, true)))
+                        .collect(),
+                ),
+            ),
+        )
+    };
+    let server = MockServer::start(vec![
+        MockServer::response(
+            MapValue::new()
+                .column(TABLE_NAME, "users")
+                .column(TABLE_SCHEMA, schema),
+        ),
+        written(2),
+        // retried by the loader
+        MockServer::error_response(NoSQLErrorCode::ServerError, "try again"),
+        written(1),
+        // not retryable: the batch fails
+        MockServer::error_response(NoSQLErrorCode::IllegalArgument, "bad row"),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let row = |shard: i32, id: i32| MapValue::new().i32("shard", shard).i32("id", id);
+    let rows = vec![
+        row(1, 1),
+        row(1, 2),
+        row(1, 3),
+        MapValue::new().i32("id", 5),
+        row(2, 4),
+    ];
+    let res = BulkLoader::new(&handle, "users")
+        .batch_size(2)
+        .concurrency(1)
+        .load(rows)
+        .await?;
+    assert_eq!(res.rows_written(), 3);
+    assert_eq!(res.rows_failed(), 2);
+    assert_eq!(res.batches(), 3);
+    assert_eq!(res.retries(), 1);
+    assert_eq!(res.errors().len(), 2);
+    assert_eq!(res.errors()[0].code, NoSQLErrorCode::IllegalArgument);
+    assert!(res.errors()[0].message.contains("shard"));
+    assert_eq!(res.errors()[1].code, NoSQLErrorCode::IllegalArgument);
+    assert_eq!(server.requests(), 5);
+
+    // full batches are sent as soon as they are complete, the rest at the end
+    let sizes: Vec<usize> = (1..5)
+        .map(|i| {
+            let payload = server.request_payload(i);
+            payload.get_array(OPERATIONS).unwrap().len()
+        })
+        .collect();
+    assert_eq!(sizes, vec![2, 1, 1, 1]);
+
+    // rows can be sent from another task
+    let server = MockServer::start(vec![
+        MockServer::response(
+            MapValue::new()
+                .column(TABLE_NAME, "users")
+                .column(TABLE_SCHEMA, schema),
+        ),
+        written(1),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let (tx, rx) = tokio::sync::mpsc::channel(10);
+    tokio::spawn(async move { tx.send(row(1, 1)).await });
+    let res = BulkLoader::new(&handle, "users").load_channel(rx).await?;
+    assert_eq!(res.rows_written(), 1);
+    assert_eq!(res.batches(), 1);
+
+    // with a distinct shard key per row, partial batches are sent once 64 keys are
+    // waiting, without waiting for the end of the rows
+    let schema = r#"{"name":"users","shardKey":["id"],"primaryKey":["id"]}"#;
+    let mut responses = vec![MockServer::response(
+        MapValue::new()
+            .column(TABLE_NAME, "users")
+            .column(TABLE_SCHEMA, schema),
+    )];
+    responses.extend((0..65).map(|_| written(1)));
+    let server = MockServer::start(responses).await;
+    let handle = server.handle().await;
+    let (tx, rx) = tokio::sync::mpsc::channel(100);
+    let send = async {
+        for id in 0..65 {
+            tx.send(MapValue::new().i32("id", id)).await.unwrap();
+        }
+        let start = std::time::Instant::now();
+        while server.requests() < 2 && start.elapsed() < Duration::from_secs(5) {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let sent_early = server.requests() >= 2;
+        drop(tx);
+        sent_early
+    };
+    let loader = BulkLoader::new(&handle, "users");
+    let (res, sent_early) = tokio::join!(loader.load_channel(rx), send);
+    assert!(sent_early);
+    assert_eq!(res?.rows_written(), 65);
+    Ok(())
+}
+
+#[test]
+fn test_bulk_loader_write_limit() {
+    use crate::bulk_loader::BulkLoadResult;
+
+    // 10 units per second: nothing written yet, 5 rows about to be sent
+    let mut res = BulkLoadResult::default();
+    assert_eq!(
+        res.write_limit_delay(5, 10.0, Duration::ZERO),
+        Duration::from_millis(500)
+    );
+    // batches in flight are counted, at least one unit per row
+    assert_eq!(
+        res.write_limit_delay(20, 10.0, Duration::from_millis(500)),
+        Duration::from_millis(1500)
+    );
+    // completed rows are counted with the units they consumed: 10 rows used
+    // 30 units, so the 10 rows in flight are expected to use 30 more
+    res.rows_written = 10;
+    res.consumed.write_kb = 30;
+    assert_eq!(
+        res.write_limit_delay(10, 10.0, Duration::from_secs(2)),
+        Duration::from_secs(4)
+    );
+    assert_eq!(
+        res.write_limit_delay(0, 10.0, Duration::from_secs(5)),
+        Duration::ZERO
+    );
+}
+
+#[tokio::test]
+async fn test_bulk_loader_failed_task() {
+    use crate::bulk_loader::{BatchResult, BulkLoadResult};
+    use std::collections::HashMap;
+    use tokio::task::JoinSet;
+
+    // the rows of a batch whose task panicked are counted as failed
+    let mut tasks: JoinSet<BatchResult> = JoinSet::new();
+    let ok = tasks.spawn(async { BatchResult::default() }).id();
+    let failed = tasks.spawn(async { panic!("batch task panicked") }).id();
+    let mut batch_rows = HashMap::from([(ok, 0), (failed, 5)]);
+    let mut res = BulkLoadResult::default();
+    let mut rows = 0;
+    while let Some(r) = tasks.join_next_with_id().await {
+        rows += res.add_batch(r, &mut batch_rows);
+    }
+    assert_eq!(rows, 5);
+    assert_eq!((res.rows_failed(), res.batches()), (5, 2));
+    assert_eq!(res.errors().len(), 1);
+    assert!(batch_rows.is_empty());
+}
+
+#[tokio::test]
+async fn test_write_multiple_versions() -> Result<(), Box<dyn Error>> {
+    use crate::delete_request::DeleteRequest;
+    use crate::mock_server::MockServer;
+    use crate::nson::{
+        OPERATIONS, OP_CODE, ROW_VERSION, SUCCESS, WM_FAILURE, WM_FAIL_INDEX, WM_FAIL_RESULT,
+        WM_SUCCESS,
+    };
+    use crate::types::OpCode;
+    use crate::write_multiple_request::WriteMultipleRequest;
+
+    let put_ok = |v: u8| {
+        FieldValue::Map(
+            MapValue::new()
+                .column(SUCCESS, true)
+                .column(ROW_VERSION, FieldValue::Binary(vec![v])),
+        )
+    };
+    let not_written = || FieldValue::Map(MapValue::new().column(SUCCESS, false));
+    let server = MockServer::start(vec![
+        MockServer::response(MapValue::new().column(
+            WM_SUCCESS,
+            FieldValue::Array(vec![
+                put_ok(1),
+                FieldValue::Map(MapValue::new().column(SUCCESS, true)),
+                not_written(),
+                put_ok(4),
+            ]),
+        )),
+        MockServer::response(
+            MapValue::new().column(
+                WM_FAILURE,
+                FieldValue::Map(
+                    MapValue::new()
+                        .i32(WM_FAIL_INDEX, 1)
+                        .column(WM_FAIL_RESULT, not_written()),
+                ),
+            ),
+        ),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let row = |id: i32| MapValue::new().i32("id", id).str("name", "x");
+    let res = WriteMultipleRequest::new("users")
+        .add(Box::new(PutRequest::new("").value(row(1))))
+        .add(Box::new(DeleteRequest::new(
+            "",
+            MapValue::new().i32("id", 2),
+        )))
+        .add(Box::new(PutRequest::new("").value(row(3)).if_absent()))
+        .add(Box::new(PutRequest::new("").value(row(4))))
+        .execute(&handle)
+        .await?;
+    assert_eq!(res.failed_operation_index(), -1);
+    let success: Vec<bool> = res.results().iter().map(|r| r.success()).collect();
+    assert_eq!(success, vec![true, true, false, true]);
+    let versions = res.versions();
+    assert_eq!(versions.len(), 4);
+    assert_eq!(versions[0], Some(&vec![1u8]));
+    assert_eq!(versions[1], None);
+    assert_eq!(versions[2], None);
+    assert_eq!(versions[3], Some(&vec![4u8]));
+    assert_eq!(res.results()[3].version(), versions[3]);
+
+    // the operations were sent in the order they were added
+    let ops: Vec<i32> = server
+        .request_payload(0)
+        .get_array(OPERATIONS)
+        .unwrap()
+        .iter()
+        .map(|op| op.get_map_value_ref().unwrap().get_i32(OP_CODE).unwrap())
+        .collect();
+    assert_eq!(
+        ops,
+        vec![
+            OpCode::Put as i32,
+            OpCode::Delete as i32,
+            OpCode::PutIfAbsent as i32,
+            OpCode::Put as i32
+        ]
+    );
+
+    // nothing is written if the operation is aborted
+    let res = WriteMultipleRequest::new("users")
+        .add(Box::new(PutRequest::new("").value(row(1))))
+        .add(Box::new(PutRequest::new("").value(row(3)).if_absent()))
+        .execute(&handle)
+        .await?;
+    assert_eq!(res.failed_operation_index(), 1);
+    assert_eq!(res.results().len(), 1);
+    assert!(res.versions().is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_query_macro() -> Result<(), Box<dyn Error>> {
+    use crate::error::NoSQLErrorCode;
+    use crate::mock_server::MockServer;
+    use crate::nson::{BIND_VARIABLES, IS_PREPARED, NAME, QUERY_RESULTS, STATEMENT, VALUE};
+
+    let server = MockServer::start(vec![
+        MockServer::prepared_response(),
+        MockServer::response(MapValue::new().column(
+            QUERY_RESULTS,
+            FieldValue::Array(vec![FieldValue::Map(MapValue::new().i32("id", 7))]),
+        )),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let name = "x' or '1'='1";
+    let res = crate::query!(
+        &handle,
+        "select * from users where id > {min_id} and name = {name} and id != {min_id}",
+        min_id = 5,
+        name = name,
+    )
+    .await?;
+    assert_eq!(res.rows()[0].get_i32("id"), Some(7));
+
+    // values are bound, not part of the statement
+    assert_eq!(
+        server.request_payload(0).get_string(STATEMENT).unwrap(),
+        "select * from users where id > ? and name = ? and id != ?"
+    );
+    let mut binds: Vec<(String, String)> = server
+        .request_payload(1)
+        .get_array(BIND_VARIABLES)
+        .unwrap()
+        .iter()
+        .map(|b| {
+            let b = b.get_map_value_ref().unwrap();
+            (
+                b.get_string(NAME).unwrap().to_string(),
+                format!("{:?}", b.get_field_value(VALUE).unwrap()),
+            )
+        })
+        .collect();
+    binds.sort();
+    assert_eq!(
+        binds,
+        vec![
+            ("#1".to_string(), format!("{:?}", FieldValue::Integer(5))),
+            (
+                "#2".to_string(),
+                format!("{:?}", FieldValue::String(name.to_string()))
+            ),
+            ("#3".to_string(), format!("{:?}", FieldValue::Integer(5))),
+        ]
+    );
+
+    // with a prepare cache, the statement is prepared once, and prepared again with
+    // the same variables if the cached statement is stale
+    let rows = || {
+        MockServer::response(MapValue::new().column(
+            QUERY_RESULTS,
+            FieldValue::Array(vec![FieldValue::Map(MapValue::new().i32("id", 7))]),
+        ))
+    };
+    let server = MockServer::start(vec![
+        MockServer::prepared_response(),
+        rows(),
+        rows(),
+        MockServer::error_response(NoSQLErrorCode::TableNotFound, "table not found"),
+        MockServer::prepared_response(),
+        rows(),
+    ])
+    .await;
+    let handle = server.builder().prepare_cache_size(10)?.build().await?;
+    for id in [1, 2, 3] {
+        let res = crate::query!(&handle, "select * from users where id = {id}", id = id).await?;
+        assert_eq!(res.rows().len(), 1);
+    }
+    assert_eq!(server.requests(), 6);
+    let bound_id = |i: usize| {
+        let payload = server.request_payload(i);
+        assert_eq!(payload.get_bool(IS_PREPARED), Some(true));
+        payload.get_array(BIND_VARIABLES).unwrap()[0]
+            .get_map_value_ref()
+            .unwrap()
+            .get_i32(VALUE)
+    };
+    assert_eq!(bound_id(1), Some(1));
+    assert_eq!(bound_id(2), Some(2));
+    assert_eq!(bound_id(3), Some(3));
+    assert_eq!(
+        server.request_payload(4).get_string(STATEMENT).unwrap(),
+        "select * from users where id = ?"
+    );
+    assert_eq!(bound_id(5), Some(3));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_table_scanner() -> Result<(), Box<dyn Error>> {
+    use crate::mock_server::MockServer;
+    use crate::nson::{
+        BIND_VARIABLES, NAME, QUERY_RESULTS, STATEMENT, TABLE_NAME, TABLE_SCHEMA, TIMEOUT, VALUE,
+    };
+    use crate::TableScanner;
+
+    let schema = r#"{"name":"users","shardKey":["shard"],"primaryKey":["shard","id"]}"#;
+    let get_table = || {
+        MockServer::response(
+            MapValue::new()
+                .column(TABLE_NAME, "users")
+                .column(TABLE_SCHEMA, schema),
+        )
+    };
+    let prepared = MockServer::prepared_response;
+    let rows = |ids: &[i32]| {
+        let rows = ids
+            .iter()
+            .map(|id| FieldValue::Map(MapValue::new().i32("shard", 1).i32("id", *id)))
+            .collect();
+        MockServer::response(MapValue::new().column(QUERY_RESULTS, FieldValue::Array(rows)))
+    };
+    let server = MockServer::start(vec![
+        get_table(),
+        prepared(),
+        rows(&[1, 2]),
+        prepared(),
+        rows(&[3]),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let mut scanner = TableScanner::new(&handle, "users");
+    let ids = |rows: &Vec<MapValue>| -> Vec<i32> {
+        rows.iter().map(|r| r.get_i32("id").unwrap()).collect()
+    };
+    assert_eq!(ids(&scanner.next_batch(2).await?), vec![1, 2]);
+    let checkpoint = scanner.checkpoint();
+    assert_eq!(ids(&scanner.next_batch(2).await?), vec![3]);
+    assert!(scanner.is_done());
+    assert!(scanner.next_batch(2).await?.is_empty());
+    assert_eq!(server.requests(), 5);
+
+    let statement = |i: usize| server.request_payload(i).get_string(STATEMENT).unwrap();
+    let after = "SELECT * FROM users t WHERE t.shard >= ? AND ((t.shard > ?) OR (t.shard = ? AND t.id > ?)) ORDER BY t.shard, t.id LIMIT ?";
+    assert_eq!(
+        statement(1),
+        "SELECT * FROM users t ORDER BY t.shard, t.id LIMIT ?"
+    );
+    assert_eq!(statement(3), after);
+    // values of the positional variables, in order
+    let binds = |server: &MockServer, i: usize| -> Vec<FieldValue> {
+        let payload = server.request_payload(i);
+        let mut vars: Vec<(String, FieldValue)> = payload
+            .get_array(BIND_VARIABLES)
+            .unwrap()
+            .iter()
+            .map(|v| {
+                let v = v.get_map_value_ref().unwrap();
+                let name = v.get_string(NAME).unwrap();
+                (name, v.get_field_value(VALUE).unwrap().clone_internal())
+            })
+            .collect();
+        vars.sort_by(|a, b| a.0.cmp(&b.0));
+        vars.into_iter().map(|(_, v)| v).collect()
+    };
+    assert_eq!(binds(&server, 2), vec![FieldValue::Long(2)]);
+    assert_eq!(
+        binds(&server, 4),
+        vec![
+            FieldValue::Integer(1),
+            FieldValue::Integer(1),
+            FieldValue::Integer(1),
+            FieldValue::Integer(2),
+            FieldValue::Long(2)
+        ]
+    );
+
+    // resume after the second row
+    let server = MockServer::start(vec![get_table(), prepared(), rows(&[3])]).await;
+    let handle = server.handle().await;
+    let mut scanner = TableScanner::resume(&handle, "users", &checkpoint)?.batch_size(10);
+    assert_eq!(scanner.next_row().await?.unwrap().get_i32("id"), Some(3));
+    assert!(scanner.next_row().await?.is_none());
+    assert_eq!(
+        server.request_payload(1).get_string(STATEMENT).unwrap(),
+        after
+    );
+    assert_eq!(binds(&server, 2).last(), Some(&FieldValue::Long(10)));
+
+    assert!(TableScanner::resume(&handle, "orders", &checkpoint).is_err());
+    assert!(TableScanner::resume(&handle, "users", "not a checkpoint").is_err());
+
+    // the table is read with the compartment and timeout of the scanner
+    let server = MockServer::start(vec![get_table(), prepared(), rows(&[])]).await;
+    let handle = server.handle().await;
+    let mut scanner = TableScanner::new(&handle, "users")
+        .compartment_id("c1")
+        .timeout(&Duration::from_secs(3));
+    assert!(scanner.next_row().await?.is_none());
+    for (i, head) in server.request_heads().iter().enumerate() {
+        assert!(head.to_lowercase().contains("x-nosql-compartment-id: c1"));
+        assert_eq!(server.request_header(i).get_i32(TIMEOUT), Some(3000));
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_slow_request_logging() -> Result<(), Box<dyn Error>> {
+    use crate::mock_server::MockServer;
+    use crate::nson::{CONSUMED, READ_KB, READ_UNITS, WRITE_KB};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct LogBuffer(Arc<Mutex<Vec<u8>>>);
+    impl std::io::Write for LogBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    let logs = LogBuffer::default();
+    let writer = logs.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+    let take_logs = || String::from_utf8(std::mem::take(&mut *logs.0.lock().unwrap())).unwrap();
+
+    let consumed = MapValue::new()
+        .i32(READ_UNITS, 3)
+        .i32(READ_KB, 2)
+        .i32(WRITE_KB, 0);
+    let response = || {
+        MockServer::response(
+            MapValue::new().column(CONSUMED, FieldValue::Map(consumed.clone_internal())),
+        )
+    };
+    let server = MockServer::start(vec![response(), response(), response()]).await;
+    let key = MapValue::new().i32("id", 1);
+
+    assert!(server
+        .builder()
+        .slow_request_threshold(Duration::ZERO)
+        .is_err());
+
+    // every request is slower than a 1ns threshold
+    let handle = server
+        .builder()
+        .slow_request_threshold(Duration::from_nanos(1))?
+        .build()
+        .await?;
+    GetRequest::new("users")
+        .key(key.clone_internal())
+        .execute(&handle)
+        .await?;
+    let out = take_logs();
+    assert!(
+        out.contains(" WARN oracle_nosql_rust_sdk::slow_request: slow request"),
+        "{}",
+        out
+    );
+    assert!(out.contains("op=Get table=users elapsed_ms="), "{}", out);
+    assert!(
+        out.contains("read_units=3 read_kb=2 write_kb=0 request_id="),
+        "{}",
+        out
+    );
+
+    // below the threshold, or below the subscriber's level: nothing is logged
+    let handle = server
+        .builder()
+        .slow_request_threshold(Duration::from_secs(3600))?
+        .build()
+        .await?;
+    GetRequest::new("users")
+        .key(key.clone_internal())
+        .execute(&handle)
+        .await?;
+    let handle = server
+        .builder()
+        .slow_request_threshold(Duration::from_nanos(1))?
+        .slow_request_log_level(crate::LogLevel::Debug)?
+        .build()
+        .await?;
+    GetRequest::new("users").key(key).execute(&handle).await?;
+    assert_eq!(take_logs(), "");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ddl_timeout() -> Result<(), Box<dyn Error>> {
+    use crate::mock_server::MockServer;
+    use crate::nson::TIMEOUT;
+
+    assert!(crate::Handle::builder()
+        .ddl_timeout(Duration::ZERO)
+        .is_err());
+    let server = MockServer::start(vec![
+        MockServer::response(MapValue::new()),
+        MockServer::response(MapValue::new()),
+        MockServer::response(MapValue::new()),
+        MockServer::response(MapValue::new()),
+    ])
+    .await;
+    let handle = server
+        .builder()
+        .timeout(Duration::from_secs(5))?
+        .ddl_timeout(Duration::from_secs(120))?
+        .build()
+        .await?;
+    let key = MapValue::new().i32("id", 1);
+    GetRequest::new("users").key(key).execute(&handle).await?;
+    let ddl = "create table if not exists users(id integer, primary key(id))";
+    TableRequest::new("users")
+        .statement(ddl)
+        .execute(&handle)
+        .await?;
+    TableRequest::new("users")
+        .statement(ddl)
+        .timeout(&Duration::from_secs(7))
+        .execute(&handle)
+        .await?;
+    SystemRequest::new("show namespaces")
+        .execute(&handle)
+        .await?;
+
+    let header_timeout = |i: usize| server.request_header(i).get_i32(TIMEOUT);
+    assert_eq!(header_timeout(0), Some(5000));
+    assert_eq!(header_timeout(1), Some(120000));
+    assert_eq!(header_timeout(2), Some(7000));
+    assert_eq!(header_timeout(3), Some(120000));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_export_import_ndjson() -> Result<(), Box<dyn Error>> {
+    use crate::mock_server::MockServer;
+    use crate::nson::{
+        CONTINUATION_KEY, PREPARED_QUERY, QUERY_RESULTS, TABLE_NAME, TABLE_SCHEMA, TABLE_STATE,
+    };
+
+    let rows = |ids: &[i32]| -> FieldValue {
+        FieldValue::Array(
+            ids.iter()
+                .map(|id| {
+                    FieldValue::Map(
+                        MapValue::new()
+                            .i32("id", *id)
+                            .str("name", "x")
+                            .column("age", FieldValue::Null),
+                    )
+                })
+                .collect(),
+        )
+    };
+    let server = MockServer::start(vec![
+        MockServer::response(
+            MapValue::new()
+                .column(PREPARED_QUERY, FieldValue::Binary(vec![1, 2, 3]))
+                .column(QUERY_RESULTS, rows(&[1, 2]))
+                .column(CONTINUATION_KEY, FieldValue::Binary(vec![4, 5, 6])),
+        ),
+        MockServer::response(MapValue::new().column(QUERY_RESULTS, rows(&[3]))),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let mut out: Vec<u8> = Vec::new();
+    let mut progress: Vec<u64> = Vec::new();
+    let n = handle
+        .export_table("users", &mut out, |n| progress.push(n))
+        .await?;
+    assert_eq!(n, 3);
+    assert_eq!(progress, vec![2, 3]);
+    let text = String::from_utf8(out)?;
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], r#"{"age":null,"id":1,"name":"x"}"#);
+    assert!(handle
+        .export_table("users; drop table users", &mut Vec::new(), |_| {})
+        .await
+        .is_err());
+
+    // import: rows are grouped by shard key
+    let schema = r#"{"name":"users","shardKey":["shard"],"primaryKey":["shard","id"]}"#;
+    let server = MockServer::start(vec![
+        MockServer::response(
+            MapValue::new()
+                .column(TABLE_NAME, "users")
+                .column(TABLE_STATE, TableState::Active as i32)
+                .column(TABLE_SCHEMA, schema),
+        ),
+        MockServer::response(MapValue::new()),
+        MockServer::response(MapValue::new()),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let input = "{\"shard\":1,\"id\":1,\"age\":null}\n\n{\"shard\":2,\"id\":2}\n{\"shard\":1,\"id\":3}\n{\"shard\":1,\"id\":4}\n";
+    let mut progress: Vec<u64> = Vec::new();
+    let n = handle
+        .import_table("users", input.as_bytes(), |n| progress.push(n))
+        .await?;
+    assert_eq!(n, 4);
+    assert_eq!(progress, vec![4]);
+    // one GetTable, one WriteMultiple for shard 1, one Put for shard 2
+    assert_eq!(server.requests(), 3);
+
+    let server = MockServer::start(vec![MockServer::response(
+        MapValue::new()
+            .column(TABLE_NAME, "users")
+            .column(TABLE_SCHEMA, schema),
+    )])
+    .await;
+    let handle = server.handle().await;
+    let err = handle
+        .import_table(
+            "users",
+            "{\"shard\":1,\"id\":1}\nnot json\n".as_bytes(),
+            |_| {},
+        )
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("line 2"), "{}", err.message);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_transport_error_codes() -> Result<(), Box<dyn Error>> {
+    use crate::error::{NoSQLError, NoSQLErrorCode};
+    use crate::mock_server::MockServer;
+
+    let server = MockServer::start(vec![
+        MockServer::http_status_response(401, "not authorized"),
+        MockServer::http_status_response(503, "try later"),
+        MockServer::http_status_response(400, "bad request"),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let get = || GetRequest::new("t1").key(MapValue::new().i32("id", 1));
+    let err = get().execute(&handle).await.unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::InvalidAuthorization);
+    assert!(!err.is_retryable());
+    assert!(err.message.contains("not authorized"));
+    let err = get().execute(&handle).await.unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::ServiceUnavailable);
+    assert!(err.is_retryable());
+    let err = get().execute(&handle).await.unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::IllegalArgument);
+
+    // connection refused: the cause is kept in the message
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let endpoint = format!("http://{}", listener.local_addr()?);
+    drop(listener);
+    let handle = crate::Handle::builder()
+        .mode(crate::HandleMode::Cloudsim)?
+        .endpoint(&endpoint)?
+        .build()
+        .await?;
+    let err = get().execute(&handle).await.unwrap_err();
+    assert_eq!(err.code, NoSQLErrorCode::ServiceUnavailable);
+    assert!(err.is_retryable());
+    assert!(!err.is_timeout());
+    assert!(
+        err.message.to_lowercase().contains("refused"),
+        "{}",
+        err.message
+    );
+
+    let io = |kind| NoSQLError::from(std::io::Error::new(kind, "io"));
+    assert!(io(std::io::ErrorKind::TimedOut).is_timeout());
+    assert!(io(std::io::ErrorKind::ConnectionReset).is_retryable());
+    assert_eq!(
+        io(std::io::ErrorKind::NotFound).code,
+        NoSQLErrorCode::IllegalArgument
+    );
+    assert!(!NoSQLErrorCode::TableNotFound.is_retryable());
+    assert!(NoSQLErrorCode::ReadLimitExceeded.is_retryable());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_table_replica_units() -> Result<(), Box<dyn Error>> {
+    use crate::mock_server::MockServer;
+    use crate::nson::{
+        LIMITS, LIMITS_MODE, OP_CODE, READ_UNITS, REGION, REPLICAS, STORAGE_GB, TABLE_NAME,
+        TABLE_OCID, TABLE_STATE, WRITE_UNITS,
+    };
+
+    let table = |mode: CapacityMode| {
+        MockServer::response(
+            MapValue::new()
+                .column(TABLE_NAME, "t1")
+                .column(TABLE_STATE, TableState::Active as i32)
+                .column(
+                    LIMITS,
+                    MapValue::new()
+                        .i32(READ_UNITS, 100)
+                        .i32(WRITE_UNITS, 100)
+                        .i32(STORAGE_GB, 10)
+                        .i32(LIMITS_MODE, mode as i32),
+                ),
+        )
+    };
+    let replica = |region: &str, units: i32| {
+        FieldValue::Map(
+            MapValue::new()
+                .column(REGION, region)
+                .column(TABLE_OCID, format!("ocid.{}", region))
+                .i32(WRITE_UNITS, units)
+                .i32(LIMITS_MODE, CapacityMode::Provisioned as i32)
+                .i32(TABLE_STATE, TableState::Updating as i32),
+        )
+    };
+    let server = MockServer::start(vec![
+        table(CapacityMode::Provisioned),
+        MockServer::response(
+            MapValue::new()
+                .column(TABLE_NAME, "t1")
+                .column(TABLE_STATE, TableState::Updating as i32)
+                .column(
+                    REPLICAS,
+                    FieldValue::Array(vec![
+                        replica("us-ashburn-1", 100),
+                        replica("eu-frankfurt-1", 20),
+                    ]),
+                ),
+        ),
+        table(CapacityMode::OnDemand),
+    ])
+    .await;
+    let handle = server.handle().await;
+    let res = TableRequest::new("t1")
+        .add_replica("eu-frankfurt-1")
+        .replica_write_units(20)
+        .execute(&handle)
+        .await?;
+    assert_eq!(res.replicas().len(), 2);
+    let r = &res.replicas()[1];
+    assert_eq!(r.region(), "eu-frankfurt-1");
+    assert_eq!(r.table_ocid(), "ocid.eu-frankfurt-1");
+    assert_eq!(r.write_units(), 20);
+    assert_eq!(r.capacity_mode(), CapacityMode::Provisioned);
+    assert_eq!(r.state(), TableState::Updating);
+
+    assert_eq!(server.request_header(1).get_i32(OP_CODE), Some(33));
+    let payload = server.request_payload(1);
+    assert_eq!(
+        payload.get_string(REGION),
+        Some("eu-frankfurt-1".to_string())
+    );
+    assert_eq!(payload.get_i32(WRITE_UNITS), Some(20));
+    assert!(payload.get_field_value(READ_UNITS).is_none());
+
+    // units are rejected for on-demand tables
+    let err = TableRequest::new("t1")
+        .add_replica("eu-frankfurt-1")
+        .replica_read_units(50)
+        .execute(&handle)
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("on-demand"), "{}", err.message);
+    assert_eq!(server.requests(), 3);
+
+    // invalid combinations are rejected before sending
+    assert!(TableRequest::new("t1")
+        .add_replica("eu-frankfurt-1")
+        .replica_write_units(0)
+        .execute(&handle)
+        .await
+        .is_err());
+    assert!(TableRequest::new("t1")
+        .statement("alter table t1 (add x integer)")
+        .drop_replica("eu-frankfurt-1")
+        .execute(&handle)
+        .await
+        .is_err());
+    assert_eq!(server.requests(), 3);
+    Ok(())
+}
@@ -4,7 +4,10 @@
 // Licensed under the Universal Permissive License v 1.0 as shown at
 //  https://oss.oracle.com/licenses/upl/
 //
+use crate::types::{FieldValue, MapValue};
 use crate::{reader::Reader, writer::Writer};
+use bytes::Bytes;
+use std::collections::HashMap;
 use std::error::Error;
 use std::result::Result;
 
@@ -118,3 +121,124 @@ fn test_rw_with_offsets() -> Result<(), Box<dyn Error>> {
     assert_eq!(reader.read_packed_i32()?, 98765);
     Ok(())
 }
+
+#[test]
+fn test_shared_binary_rw() -> Result<(), Box<dyn Error>> {
+    let blob: Vec<u8> = (0..=255).collect();
+    let mut mv = MapValue::new();
+    mv.put_field_value("id", FieldValue::Integer(10));
+    mv.put_field_value("data", FieldValue::Binary(blob.clone()));
+    mv.put_field_value("nested", FieldValue::Map(MapValue::new().i32("a", 1)));
+    let mut writer = Writer::new();
+    writer.write_map(&mv);
+
+    let mut reader = Reader::from_shared(Bytes::copy_from_slice(writer.bytes()));
+    let mut binaries = HashMap::new();
+    let row = reader.read_map_shared_binary(&mut binaries)?;
+    // binary column is moved out of the row, other columns are unchanged
+    assert!(row.get_field_value("data").is_none());
+    assert_eq!(row.get_i32("id"), Some(10));
+    assert!(row.get_field_value("nested").is_some());
+    let data = binaries.get("data").unwrap();
+    assert_eq!(data.as_ref(), blob.as_slice());
+    // the returned bytes point into the reader's buffer (no copy)
+    let start = reader.buf.as_ptr() as usize;
+    let p = data.as_ptr() as usize;
+    assert!(p >= start && p + data.len() <= start + reader.buf.len());
+    Ok(())
+}
+
+#[test]
+fn test_timestamp_rw() {
+    use crate::types::round_timestamp;
+    use chrono::DateTime;
+
+    let ts = DateTime::parse_from_rfc3339("2024-05-06T21:30:59.123456789+09:00").unwrap();
+    let mut w = Writer::new();
+    w.write_timestamp(&ts);
+    let mut r = Reader::new().from_bytes(&w.buf);
+    assert_eq!(r.read_string().unwrap(), "2024-05-06T12:30:59.123456789Z");
+    let mut r = Reader::new().from_bytes(&w.buf);
+    assert_eq!(r.read_timestamp().unwrap(), ts);
+
+    let round = |p: u8| {
+        let mut w = Writer::new();
+        w.write_timestamp(&round_timestamp(&ts, p));
+        Reader::new().from_bytes(&w.buf).read_string().unwrap()
+    };
+    assert_eq!(round(0), "2024-05-06T12:30:59Z");
+    assert_eq!(round(3), "2024-05-06T12:30:59.123Z");
+    assert_eq!(round(4), "2024-05-06T12:30:59.123500Z");
+    assert_eq!(round(9), "2024-05-06T12:30:59.123456789Z");
+
+    // rounding can carry into the next second
+    let ts = DateTime::parse_from_rfc3339("2024-12-31T23:59:59.9996Z").unwrap();
+    assert_eq!(
+        round_timestamp(&ts, 3),
+        DateTime::parse_from_rfc3339("2025-01-01T00:00:00Z").unwrap()
+    );
+}
+
+#[test]
+fn test_timestamp_key_encoding() {
+    use crate::sort_iter::SortSpec;
+    use crate::types::{sort_results, FieldValue, MapValue};
+    use chrono::{DateTime, FixedOffset, NaiveDate, TimeZone};
+    use std::cmp::Ordering;
+
+    // binary primary keys, as used to remove duplicate query results
+    let key = |s: &str| {
+        let mut w = Writer::new();
+        w.write_field_value(&FieldValue::Timestamp(
+            DateTime::parse_from_rfc3339(s).unwrap(),
+        ));
+        w.buf
+    };
+    // the same instant has the same key in any time zone and with any precision
+    let utc = key("2024-05-06T12:30:59.120Z");
+    assert_eq!(key("2024-05-06T21:30:59.12+09:00"), utc);
+    assert_eq!(key("2024-05-06T07:30:59.120000000-05:00"), utc);
+    assert_ne!(key("2024-05-06T12:30:59.121Z"), utc);
+    assert_ne!(key("2024-05-06T12:30:59.120+01:00"), utc);
+    // across a date boundary
+    assert_eq!(
+        key("2024-12-31T23:30:00-01:00"),
+        key("2025-01-01T00:30:00Z")
+    );
+
+    // rows are sorted by instant, not by time zone
+    let row = |s: &str| MapValue::new().column("ts", DateTime::parse_from_rfc3339(s).unwrap());
+    let fields = vec!["ts".to_string()];
+    let specs = vec![SortSpec::default()];
+    assert_eq!(
+        sort_results(
+            &row("2024-05-06T21:30:00+09:00"),
+            &row("2024-05-06T13:00:00Z"),
+            &fields,
+            &specs
+        ),
+        Ordering::Less
+    );
+    assert_eq!(
+        sort_results(
+            &row("2024-05-06T21:30:00+09:00"),
+            &row("2024-05-06T12:30:00Z"),
+            &fields,
+            &specs
+        ),
+        Ordering::Equal
+    );
+
+    // the server has no leap seconds: they are written as the last second of the minute
+    let leap = NaiveDate::from_ymd_opt(2016, 12, 31)
+        .unwrap()
+        .and_hms_nano_opt(23, 59, 59, 1_500_000_000)
+        .unwrap();
+    let ts = FixedOffset::east_opt(3600)
+        .unwrap()
+        .from_utc_datetime(&leap);
+    let mut w = Writer::new();
+    w.write_timestamp(&ts);
+    let mut r = Reader::new().from_bytes(&w.buf);
+    assert_eq!(r.read_string().unwrap(), "2016-12-31T23:59:59.500Z");
+}
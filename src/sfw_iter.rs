@@ -32,7 +32,7 @@ use tracing::{debug, trace};
 #[add_planiter_fields]
 #[derive(Debug, Default, Clone)]
 pub(crate) struct SfwIter {
-    from_iter: Box<PlanIter>,
+    pub(crate) from_iter: Box<PlanIter>,
     from_var_name: String,
     column_iters: Vec<Box<PlanIter>>,
     column_names: Vec<String>,
@@ -11,7 +11,7 @@ use crate::plan_iter::{deserialize_plan_iter, PlanIter};
 use crate::plan_iter::{Location, PlanIterKind, PlanIterState};
 use crate::query_request::QueryRequest;
 use crate::reader::Reader;
-use crate::types::{sort_results, FieldValue, MapValue};
+use crate::types::{sort_results, FieldValue, MapValue, StringCollation};
 use oracle_nosql_rust_sdk_derive::add_planiter_fields;
 
 use std::result::Result;
@@ -22,13 +22,29 @@ use tracing::trace;
 // The order-by clause, for each sort expression allows for an optional "sort spec",
 // which specifies the relative order of NULLs (less than or greater than all other values)
 // and whether the values returned by the sort expression should be sorted in ascending or descending order.
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Clone)]
 pub(crate) struct SortSpec {
     // is_desc specifies if the desired sorting order is in descending order.
     pub is_desc: bool,
 
     // nulls_first specifies if NULL values should sort before all other values.
     pub nulls_first: bool,
+
+    // collation specifies how strings are compared. It is not part of the query
+    // plan; it is set from the QueryRequest when the plan is opened. Otherwise
+    // strings are compared by code point, the same as the total order of
+    // FieldValues.
+    pub collation: StringCollation,
+}
+
+impl Default for SortSpec {
+    fn default() -> Self {
+        SortSpec {
+            is_desc: false,
+            nulls_first: false,
+            collation: StringCollation::CodePoint,
+        }
+    }
 }
 
 impl SortSpec {
@@ -38,6 +54,7 @@ impl SortSpec {
         Ok(SortSpec {
             is_desc: desc,
             nulls_first: nulls,
+            ..Default::default()
         })
     }
     pub fn read_sort_specs(r: &mut Reader) -> Result<Vec<SortSpec>, NoSQLError> {
@@ -59,7 +76,7 @@ impl SortSpec {
 #[add_planiter_fields]
 #[derive(Debug, Default, Clone)]
 pub(crate) struct SortIter {
-    input_iter: Box<PlanIter>,
+    pub(crate) input_iter: Box<PlanIter>,
     sort_fields: Vec<String>,
     sort_specs: Vec<SortSpec>,
     count_memory: bool,
@@ -117,6 +134,16 @@ impl SortIter {
     }
 
     pub fn open(&mut self, req: &mut QueryRequest, handle: &Handle) -> Result<(), NoSQLError> {
+        if req.server_side_only {
+            return ia_err!(
+                "query requires the SDK to sort all results on {:?}, which is not allowed \
+                 with server_side_only: add an index matching the ORDER BY clause",
+                self.sort_fields
+            );
+        }
+        for ss in self.sort_specs.iter_mut() {
+            ss.collation = req.string_collation;
+        }
         self.data.state = PlanIterState::Open;
         self.input_iter.open(req, handle)
     }
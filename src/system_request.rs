@@ -40,10 +40,30 @@ pub struct SystemRequest {
 }
 
 /// Struct used to query the status of an in-progress [`SystemRequest`].
+///
+/// This is used by [`SystemResult::wait_for_completion()`], and can also be used directly,
+/// for example to check on an operation started by another process using its operation
+/// id, as returned by [`SystemResult::operation_id()`]:
+/// ```no_run
+/// # use oracle_nosql_rust_sdk::{Handle, SystemStatusRequest};
+/// # use oracle_nosql_rust_sdk::types::OperationState;
+/// # #[tokio::main]
+/// # pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let handle = Handle::builder().build().await?;
+/// # let saved_operation_id = String::new();
+/// let mut res = SystemStatusRequest::new(&saved_operation_id)
+///     .execute(&handle)
+///     .await?;
+/// if res.state() == OperationState::Working {
+///     res.wait_for_completion_ms(&handle, 60000, 1000).await?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
 #[derive(Default, Debug)]
-pub(crate) struct SystemStatusRequest {
-    pub operation_id: String,
-    pub timeout: Option<Duration>,
+pub struct SystemStatusRequest {
+    pub(crate) operation_id: String,
+    pub(crate) timeout: Option<Duration>,
 }
 
 /// Struct representing the result of a [`SystemRequest`].
@@ -64,12 +84,116 @@ impl SystemRequest {
         }
     }
 
+    /// Create a SystemRequest that creates a new user with the given password.
+    ///
+    /// This generates `CREATE USER <name> IDENTIFIED BY "<password>"`.
+    /// `name` must be a valid identifier (a letter followed by letters, digits or
+    /// underscores). `password` must be non-empty and may not contain double quotes,
+    /// backslashes or control characters.
+    pub fn create_user(name: &str, password: &str) -> Result<SystemRequest, NoSQLError> {
+        validate_identifier("user name", name)?;
+        validate_password(password)?;
+        Ok(SystemRequest::new(&format!(
+            "CREATE USER {} IDENTIFIED BY \"{}\"",
+            name, password
+        )))
+    }
+
+    /// Create a SystemRequest that changes the password of an existing user.
+    ///
+    /// This generates `ALTER USER <name> IDENTIFIED BY "<password>"`. The same
+    /// validation rules as [`SystemRequest::create_user()`] apply.
+    pub fn alter_user_password(name: &str, password: &str) -> Result<SystemRequest, NoSQLError> {
+        validate_identifier("user name", name)?;
+        validate_password(password)?;
+        Ok(SystemRequest::new(&format!(
+            "ALTER USER {} IDENTIFIED BY \"{}\"",
+            name, password
+        )))
+    }
+
+    /// Create a SystemRequest that drops an existing user.
+    ///
+    /// This generates `DROP USER <name>`.
+    pub fn drop_user(name: &str) -> Result<SystemRequest, NoSQLError> {
+        validate_identifier("user name", name)?;
+        Ok(SystemRequest::new(&format!("DROP USER {}", name)))
+    }
+
+    /// Create a SystemRequest that creates a new role.
+    ///
+    /// This generates `CREATE ROLE <name>`.
+    pub fn create_role(name: &str) -> Result<SystemRequest, NoSQLError> {
+        validate_identifier("role name", name)?;
+        Ok(SystemRequest::new(&format!("CREATE ROLE {}", name)))
+    }
+
+    /// Create a SystemRequest that drops an existing role.
+    ///
+    /// This generates `DROP ROLE <name>`.
+    pub fn drop_role(name: &str) -> Result<SystemRequest, NoSQLError> {
+        validate_identifier("role name", name)?;
+        Ok(SystemRequest::new(&format!("DROP ROLE {}", name)))
+    }
+
+    /// Create a SystemRequest that grants a role to a user.
+    ///
+    /// This generates `GRANT <role> TO USER <user>`.
+    pub fn grant_role(user: &str, role: &str) -> Result<SystemRequest, NoSQLError> {
+        validate_identifier("user name", user)?;
+        validate_identifier("role name", role)?;
+        Ok(SystemRequest::new(&format!(
+            "GRANT {} TO USER {}",
+            role, user
+        )))
+    }
+
+    /// Create a SystemRequest that revokes a role from a user.
+    ///
+    /// This generates `REVOKE <role> FROM USER <user>`.
+    pub fn revoke_role(user: &str, role: &str) -> Result<SystemRequest, NoSQLError> {
+        validate_identifier("user name", user)?;
+        validate_identifier("role name", role)?;
+        Ok(SystemRequest::new(&format!(
+            "REVOKE {} FROM USER {}",
+            role, user
+        )))
+    }
+
+    /// Create a SystemRequest that creates a new namespace.
+    ///
+    /// This generates `CREATE NAMESPACE <name>`.
+    pub fn create_namespace(name: &str) -> Result<SystemRequest, NoSQLError> {
+        validate_identifier("namespace", name)?;
+        Ok(SystemRequest::new(&format!("CREATE NAMESPACE {}", name)))
+    }
+
+    /// Create a SystemRequest that drops an existing namespace.
+    ///
+    /// This generates `DROP NAMESPACE <name>`. If `cascade` is true, all tables
+    /// in the namespace are dropped as well.
+    pub fn drop_namespace(name: &str, cascade: bool) -> Result<SystemRequest, NoSQLError> {
+        validate_identifier("namespace", name)?;
+        if cascade {
+            return Ok(SystemRequest::new(&format!(
+                "DROP NAMESPACE {} CASCADE",
+                name
+            )));
+        }
+        Ok(SystemRequest::new(&format!("DROP NAMESPACE {}", name)))
+    }
+
+    /// Get the statement that will be sent to the system by this request.
+    pub fn statement(&self) -> &str {
+        &self.statement
+    }
+
     /// Specify the timeout value for the request.
     ///
     /// This is optional.
     /// If set, it must be greater than or equal to 1 millisecond, otherwise an
     /// IllegalArgument error will be returned.
-    /// If not set, the default timeout value configured for the [`Handle`](crate::HandleBuilder::timeout()) is used.
+    /// If not set, the DDL timeout configured for the [`Handle`](crate::HandleBuilder::ddl_timeout()) is used.
     pub fn timeout(mut self, t: &Duration) -> Self {
         self.timeout = Some(t.clone());
         self
@@ -81,9 +205,8 @@ impl SystemRequest {
     /// used to wait for completion by calling [`SystemResult::wait_for_completion()`].
     pub async fn execute(&self, h: &Handle) -> Result<SystemResult, NoSQLError> {
         // TODO: validate
-        let mut w: Writer = Writer::new();
-        w.write_i16(h.inner.serial_version);
-        let timeout = h.get_timeout(&self.timeout);
+        let mut w = h.request_writer();
+        let timeout = h.get_ddl_timeout(&self.timeout);
         self.nson_serialize(&mut w, &timeout);
         let mut opts = SendOptions {
             timeout: timeout,
@@ -146,6 +269,41 @@ impl SystemRequest {
     }
 }
 
+// Identifiers used in generated DDL must be a letter followed by letters,
+// digits or underscores. This rejects anything that could alter the statement.
+fn validate_identifier(what: &str, name: &str) -> Result<(), NoSQLError> {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() => {}
+        _ => {
+            return ia_err!("invalid {} '{}': must start with a letter", what, name);
+        }
+    }
+    if !chars.all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return ia_err!(
+            "invalid {} '{}': may only contain letters, digits and underscores",
+            what,
+            name
+        );
+    }
+    Ok(())
+}
+
+fn validate_password(password: &str) -> Result<(), NoSQLError> {
+    if password.is_empty() {
+        return ia_err!("password must be non-empty");
+    }
+    if password
+        .chars()
+        .any(|c| c == '"' || c == '\\' || c.is_control())
+    {
+        return ia_err!(
+            "password may not contain double quotes, backslashes or control characters"
+        );
+    }
+    Ok(())
+}
+
 impl NsonRequest for SystemRequest {
     fn serialize(&self, w: &mut Writer, timeout: &Duration) {
         self.nson_serialize(w, timeout);
@@ -153,6 +311,8 @@ impl NsonRequest for SystemRequest {
 }
 
 impl SystemStatusRequest {
+    /// Create a new SystemStatusRequest for the operation with the given id.
+    /// `operation_id` must be non-empty.
     pub fn new(operation_id: &str) -> SystemStatusRequest {
         SystemStatusRequest {
             operation_id: operation_id.to_string(),
@@ -166,16 +326,22 @@ impl SystemStatusRequest {
     /// If set, it must be greater than or equal to 1 millisecond, otherwise an
     /// IllegalArgument error will be returned.
     /// If not set, the default timeout value configured for the [`Handle`](crate::HandleBuilder::timeout()) is used.
-    #[allow(dead_code)]
     pub fn timeout(mut self, t: &Duration) -> Self {
         self.timeout = Some(t.clone());
         self
     }
 
+    /// Execute the request, returning a [`SystemResult`] with the current
+    /// [`state()`](SystemResult::state()) of the operation.
+    ///
+    /// This only checks the state once, and can be repeated to poll the operation. The
+    /// result can also be used to wait for completion by calling
+    /// [`SystemResult::wait_for_completion()`].
     pub async fn execute(&self, h: &Handle) -> Result<SystemResult, NoSQLError> {
-        // TODO: validate
-        let mut w: Writer = Writer::new();
-        w.write_i16(h.inner.serial_version);
+        if self.operation_id.is_empty() {
+            return ia_err!("SystemStatusRequest: operation id must be non-empty");
+        }
+        let mut w = h.request_writer();
         let timeout = h.get_timeout(&self.timeout);
         self.nson_serialize(&mut w, &timeout);
         let mut opts = SendOptions {
@@ -277,6 +443,10 @@ impl SystemResult {
         .await
     }
 
+    /// Get the id of the operation.
+    ///
+    /// This can be saved and passed to [`SystemStatusRequest::new()`] to check the state
+    /// of the operation later, from any process.
     pub fn operation_id(&self) -> String {
         self.operation_id.clone()
     }
@@ -4,17 +4,25 @@
 // Licensed under the Universal Permissive License v 1.0 as shown at
 //  https://oss.oracle.com/licenses/upl/
 //
-use crate::error::NoSQLErrorCode::RequestTimeout;
+use crate::error::NoSQLErrorCode;
+use crate::error::NoSQLErrorCode::{
+    IllegalState, ReadLimitExceeded, RequestTimeout, RetryAuthentication, SecurityInfoUnavailable,
+    ServerError, ServiceUnavailable, TableBusy, WriteLimitExceeded,
+};
 use crate::error::{ia_err, NoSQLError};
 use crate::handle::Handle;
 use crate::handle::SendOptions;
+use crate::handle_builder::HandleMode;
 use crate::nson::*;
 use crate::reader::Reader;
-use crate::types::{OpCode, TableLimits, TableState};
+use crate::request_options::RequestOptions;
+use crate::table_usage_request::TableUsageRequest;
+use crate::types::{CapacityMode, FieldType, OpCode, TableLimits, TableState};
 use crate::writer::Writer;
+use std::collections::{BTreeMap, HashMap};
 use std::result::Result;
-use std::thread::sleep;
 use std::time::{Duration, Instant};
+use tokio::time::sleep;
 
 /// Struct used for creating or modifying a table in the NoSQL Database.
 ///
@@ -56,7 +64,31 @@ pub struct TableRequest {
     pub(crate) statement: String,
     pub(crate) limits: Option<TableLimits>,
     pub(crate) match_etag: Option<String>,
-    // TODO: tags
+    // set by add_replica() or drop_replica()
+    pub(crate) replica: Option<ReplicaOp>,
+    // set by update_freeform_tag() or remove_tag(), applied in order
+    pub(crate) tag_updates: Vec<TagUpdate>,
+    // TODO: defined tags
+}
+
+// A change to one free-form tag of a table.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum TagUpdate {
+    Set { key: String, value: String },
+    Remove { key: String },
+}
+
+// A Global Active Table replica operation.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ReplicaOp {
+    Add {
+        region: String,
+        read_units: Option<i32>,
+        write_units: Option<i32>,
+    },
+    Drop {
+        region: String,
+    },
 }
 
 /// Struct used to get information about a table in the NoSQL Database.
@@ -67,6 +99,7 @@ pub struct GetTableRequest {
     pub(crate) namespace: String,
     pub(crate) operation_id: String,
     pub(crate) timeout: Option<Duration>,
+    pub(crate) include_storage_usage: bool,
     // TODO: tags
 }
 
@@ -74,8 +107,8 @@ pub struct GetTableRequest {
 #[derive(Default, Debug)]
 pub struct TableResult {
     pub(crate) table_name: String,
-    pub(crate) compartment_id: String, // TODO: Option<>?
-    pub(crate) namespace: String,      // TODO: Option<>?
+    pub(crate) compartment_id: String,
+    pub(crate) namespace: String, // TODO: Option<>?
     pub(crate) table_ocid: String,
     pub(crate) ddl: String,
     pub(crate) operation_id: String, // TODO: Option<>?
@@ -83,9 +116,56 @@ pub struct TableResult {
     pub(crate) state: TableState,
     pub(crate) limits: Option<TableLimits>,
     pub(crate) match_etag: Option<String>,
+    // set by wait_for_completion() if the operation failed
+    pub(crate) operation_error: Option<String>,
+    pub(crate) replicas: Vec<ReplicaInfo>,
+    // set by GetTableRequest::include_storage_usage()
+    pub(crate) storage_used_gb: Option<f64>,
+    pub(crate) freeform_tags: HashMap<String, String>,
     // TODO: MRT fields
 }
 
+/// Information about one regional replica of a Global Active Table.
+///
+/// See [`TableResult::replicas()`].
+#[derive(Default, Debug, Clone)]
+pub struct ReplicaInfo {
+    pub(crate) region: String,
+    pub(crate) table_ocid: String,
+    pub(crate) read_units: i32,
+    pub(crate) write_units: i32,
+    pub(crate) mode: CapacityMode,
+    pub(crate) state: TableState,
+}
+
+impl ReplicaInfo {
+    /// Get the name of the region of the replica, such as `us-ashburn-1`.
+    pub fn region(&self) -> String {
+        self.region.clone()
+    }
+    /// Get the OCID of the table in the replica region.
+    pub fn table_ocid(&self) -> String {
+        self.table_ocid.clone()
+    }
+    /// Get the read units of the replica. This is zero if it is not reported by the service,
+    /// or the table uses on-demand capacity.
+    pub fn read_units(&self) -> i32 {
+        self.read_units
+    }
+    /// Get the write units of the replica. This is zero if the table uses on-demand capacity.
+    pub fn write_units(&self) -> i32 {
+        self.write_units
+    }
+    /// Get the capacity mode of the replica.
+    pub fn capacity_mode(&self) -> CapacityMode {
+        self.mode
+    }
+    /// Get the state of the replica table.
+    pub fn state(&self) -> TableState {
+        self.state
+    }
+}
+
 impl TableRequest {
     /// Create a new TableRequest.
     ///
@@ -102,7 +182,7 @@ impl TableRequest {
     /// This is optional.
     /// If set, it must be greater than or equal to 1 millisecond, otherwise an
     /// IllegalArgument error will be returned.
-    /// If not set, the default timeout value configured for the [`Handle`](crate::HandleBuilder::timeout()) is used.
+    /// If not set, the DDL timeout configured for the [`Handle`](crate::HandleBuilder::ddl_timeout()) is used.
     ///
     /// Note this is just the timeout for the initial request. The actual operation may take significantly longer,
     /// and its completion should be waited for by calling [`TableResult::wait_for_completion()`].
@@ -124,6 +204,14 @@ impl TableRequest {
         self
     }
 
+    /// Apply [`RequestOptions`] to this request, using their timeout, compartment and namespace.
+    pub fn options(mut self, options: &RequestOptions) -> Self {
+        options.apply_timeout(&mut self.timeout);
+        options.apply_compartment_id(&mut self.compartment_id);
+        options.apply_namespace(&mut self.namespace);
+        self
+    }
+
     /// On-premises only: set the namespace for the operation.
     pub fn namespace(mut self, namespace: &str) -> TableRequest {
         self.namespace = namespace.to_string();
@@ -148,6 +236,109 @@ impl TableRequest {
         self
     }
 
+    /// Cloud only: add a replica of this table in another region, making it a Global Active
+    /// Table (or adding a region to an existing one).
+    ///
+    /// By default the replica uses the same read and write units as the table. For tables with
+    /// provisioned capacity, use [`replica_read_units()`](TableRequest::replica_read_units())
+    /// and [`replica_write_units()`](TableRequest::replica_write_units()) to size the replica
+    /// for its own regional load. A replica operation cannot be combined with a
+    /// [`statement()`](TableRequest::statement()) or [`limits()`](TableRequest::limits()).
+    /// ```no_run
+    /// # use oracle_nosql_rust_sdk::{Handle, TableRequest};
+    /// # #[tokio::main]
+    /// # pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let handle = Handle::builder().build().await?;
+    /// TableRequest::new("users")
+    ///     .add_replica("eu-frankfurt-1")
+    ///     .replica_write_units(200)
+    ///     .execute(&handle)
+    ///     .await?
+    ///     .wait_for_completion_ms(&handle, 300000, 5000)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn add_replica(mut self, region: &str) -> TableRequest {
+        self.replica = Some(ReplicaOp::Add {
+            region: region.to_string(),
+            read_units: None,
+            write_units: None,
+        });
+        self
+    }
+
+    /// Cloud only: remove the replica of this table in the given region.
+    pub fn drop_replica(mut self, region: &str) -> TableRequest {
+        self.replica = Some(ReplicaOp::Drop {
+            region: region.to_string(),
+        });
+        self
+    }
+
+    /// Cloud only: set the read units of a replica added with [`add_replica()`](TableRequest::add_replica()).
+    ///
+    /// This is only valid for tables with provisioned capacity, and must be greater than zero.
+    pub fn replica_read_units(mut self, units: i32) -> TableRequest {
+        if let Some(ReplicaOp::Add { read_units, .. }) = &mut self.replica {
+            *read_units = Some(units);
+        }
+        self
+    }
+
+    /// Cloud only: set the write units of a replica added with [`add_replica()`](TableRequest::add_replica()).
+    ///
+    /// This is only valid for tables with provisioned capacity, and must be greater than zero.
+    pub fn replica_write_units(mut self, units: i32) -> TableRequest {
+        if let Some(ReplicaOp::Add { write_units, .. }) = &mut self.replica {
+            *write_units = Some(units);
+        }
+        self
+    }
+
+    /// Cloud only: set the free-form tag `key` of the table to `value`.
+    ///
+    /// Only the given tags are changed: when the request is executed, the current tags of
+    /// the table are read, the updates (this and [`remove_tag()`](TableRequest::remove_tag()))
+    /// are applied to them in the order they were given, and the resulting set of tags is
+    /// sent. Unless [`match_etag()`](TableRequest::match_etag()) is also set, the update is
+    /// only applied if the table has not changed since its tags were read, so concurrent tag
+    /// updates are not lost; otherwise it fails with an
+    /// [`EtagMismatch`](crate::NoSQLErrorCode::EtagMismatch) error, and can be retried.
+    ///
+    /// Tag updates cannot be combined with a statement, limits or a replica operation.
+    /// ```no_run
+    /// # use oracle_nosql_rust_sdk::{Handle, TableRequest};
+    /// # #[tokio::main]
+    /// # pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let handle = Handle::builder().build().await?;
+    /// let res = TableRequest::new("users")
+    ///     .update_freeform_tag("cost-center", "1234")
+    ///     .remove_tag("temporary")
+    ///     .execute(&handle)
+    ///     .await?;
+    /// println!("tags: {:?}", res.freeform_tags());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn update_freeform_tag(mut self, key: &str, value: &str) -> TableRequest {
+        self.tag_updates.push(TagUpdate::Set {
+            key: key.to_string(),
+            value: value.to_string(),
+        });
+        self
+    }
+
+    /// Cloud only: remove the free-form tag `key` from the table, if it is set.
+    ///
+    /// See [`update_freeform_tag()`](TableRequest::update_freeform_tag()).
+    pub fn remove_tag(mut self, key: &str) -> TableRequest {
+        self.tag_updates.push(TagUpdate::Remove {
+            key: key.to_string(),
+        });
+        self
+    }
+
     /// Cloud only: set a matching tag for the operation to succeed.
     ///
     /// This method sets an ETag in the request that must be matched for the operation
@@ -155,6 +346,34 @@ impl TableRequest {
     /// previous [`TableResult`]. This is a form of optimistic concurrency
     /// control, allowing an application to ensure that no unexpected modifications
     /// have been made to the table.
+    ///
+    /// If the table has changed since the ETag was returned, the request fails with an
+    /// [`EtagMismatch`](crate::NoSQLErrorCode::EtagMismatch) error and is not applied.
+    /// For example, to only alter a table if its schema is the one that was checked:
+    /// ```no_run
+    /// # use oracle_nosql_rust_sdk::{GetTableRequest, Handle, TableRequest};
+    /// # use oracle_nosql_rust_sdk::NoSQLErrorCode;
+    /// # use std::time::Duration;
+    /// # #[tokio::main]
+    /// # pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let handle = Handle::builder().build().await?;
+    /// let table = GetTableRequest::new("users").execute(&handle).await?;
+    /// // ... check table.schema() ...
+    /// let res = TableRequest::new("users")
+    ///     .statement("ALTER TABLE users (ADD age INTEGER)")
+    ///     .match_etag(&table.etag().unwrap_or_default())
+    ///     .execute(&handle)
+    ///     .await;
+    /// match res {
+    ///     Ok(mut r) => r.wait_for_completion_ms(&handle, 30000, 1000).await?,
+    ///     Err(e) if e.code == NoSQLErrorCode::EtagMismatch => {
+    ///         println!("table was changed by someone else");
+    ///     }
+    ///     Err(e) => return Err(e.into()),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
     pub fn match_etag(mut self, match_etag: &str) -> TableRequest {
         self.match_etag = Some(match_etag.to_string());
         self
@@ -166,10 +385,25 @@ impl TableRequest {
     /// used to wait for completion by calling [`TableResult::wait_for_completion()`].
     pub async fn execute(&self, h: &Handle) -> Result<TableResult, NoSQLError> {
         // TODO: validate
-        let mut w: Writer = Writer::new();
-        w.write_i16(h.inner.serial_version);
-        let timeout = h.get_timeout(&self.timeout);
-        self.nson_serialize(&mut w, &timeout);
+        if let Some(etag) = &self.match_etag {
+            if etag.is_empty() {
+                return ia_err!("match_etag must not be empty");
+            }
+        }
+        if let Some(table) = ddl_table_name(&self.statement) {
+            h.check_table_prefix(table)?;
+        }
+        if let Some(op) = &self.replica {
+            self.validate_replica(h, op).await?;
+        }
+        let tags = if self.tag_updates.is_empty() {
+            None
+        } else {
+            Some(self.updated_tags(h).await?)
+        };
+        let mut w = h.request_writer();
+        let timeout = h.get_ddl_timeout(&self.timeout);
+        self.nson_serialize(&mut w, &timeout, tags.as_ref());
         let mut opts = SendOptions {
             timeout: timeout,
             retryable: false,
@@ -177,22 +411,143 @@ impl TableRequest {
             ..Default::default()
         };
         let mut r = h.send_and_receive(w, &mut opts).await?;
-        let resp = TableRequest::nson_deserialize(&mut r)?;
+        let mut resp = TableRequest::nson_deserialize(&mut r)?;
+        resp.table_name = h.logical_table_name(&resp.table_name);
         Ok(resp)
     }
 
-    pub(crate) fn nson_serialize(&self, w: &mut Writer, timeout: &Duration) {
+    // Check a replica operation before sending it. Replica units are checked
+    // against the capacity mode of the table, which requires fetching it.
+    async fn validate_replica(&self, h: &Handle, op: &ReplicaOp) -> Result<(), NoSQLError> {
+        if !self.statement.is_empty() || self.limits.is_some() {
+            return ia_err!("a replica operation cannot be combined with a statement or limits");
+        }
+        let (region, read_units, write_units) = match op {
+            ReplicaOp::Add {
+                region,
+                read_units,
+                write_units,
+            } => (region, *read_units, *write_units),
+            ReplicaOp::Drop { region } => (region, None, None),
+        };
+        if region.is_empty() {
+            return ia_err!("replica region must not be empty");
+        }
+        if read_units.is_none() && write_units.is_none() {
+            return Ok(());
+        }
+        if read_units.is_some_and(|u| u < 1) || write_units.is_some_and(|u| u < 1) {
+            return ia_err!("replica read and write units must be greater than zero");
+        }
+        let table = GetTableRequest::new(&self.table_name)
+            .compartment_id(&self.compartment_id)
+            .timeout(&h.get_timeout(&self.timeout))
+            .execute(h)
+            .await?;
+        if let Some(limits) = table.limits {
+            if limits.mode == CapacityMode::OnDemand {
+                return ia_err!(
+                    "cannot set replica units for table '{}': it uses on-demand capacity",
+                    self.table_name
+                );
+            }
+        }
+        Ok(())
+    }
+
+    // Get the free-form tags of the table with the tag updates applied, and the
+    // ETag to send with them.
+    async fn updated_tags(
+        &self,
+        h: &Handle,
+    ) -> Result<(BTreeMap<String, String>, Option<String>), NoSQLError> {
+        if !self.statement.is_empty() || self.limits.is_some() || self.replica.is_some() {
+            return ia_err!(
+                "tag updates cannot be combined with a statement, limits or a replica operation"
+            );
+        }
+        for u in &self.tag_updates {
+            let key = match u {
+                TagUpdate::Set { key, .. } | TagUpdate::Remove { key } => key,
+            };
+            if key.is_empty() {
+                return ia_err!("tag key must not be empty");
+            }
+        }
+        let table = GetTableRequest::new(&self.table_name)
+            .compartment_id(&self.compartment_id)
+            .timeout(&h.get_timeout(&self.timeout))
+            .execute(h)
+            .await?;
+        let mut tags: BTreeMap<String, String> = table.freeform_tags.into_iter().collect();
+        for u in &self.tag_updates {
+            match u {
+                TagUpdate::Set { key, value } => {
+                    tags.insert(key.clone(), value.clone());
+                }
+                TagUpdate::Remove { key } => {
+                    tags.remove(key);
+                }
+            }
+        }
+        let etag = self.match_etag.clone().or(table.match_etag);
+        Ok((tags, etag))
+    }
+
+    // Serialize the request. If tag updates were given, `tags` has the updated
+    // free-form tags of the table and the ETag to send with them, which replace
+    // the statement and the match ETag of the request.
+    pub(crate) fn nson_serialize(
+        &self,
+        w: &mut Writer,
+        timeout: &Duration,
+        tags: Option<&(BTreeMap<String, String>, Option<String>)>,
+    ) {
         let mut ns = NsonSerializer::start_request(w);
         ns.start_header();
-        ns.write_header(OpCode::TableRequest, timeout, &self.table_name);
+        let op = match &self.replica {
+            Some(ReplicaOp::Add { .. }) => OpCode::AddReplica,
+            Some(ReplicaOp::Drop { .. }) => OpCode::DropReplica,
+            None => OpCode::TableRequest,
+        };
+        ns.write_header(op, timeout, &self.table_name);
         ns.end_header();
 
         // payload
         ns.start_payload();
-        ns.write_string_field(STATEMENT, &self.statement);
+        match &self.replica {
+            Some(ReplicaOp::Add {
+                region,
+                read_units,
+                write_units,
+            }) => {
+                ns.write_string_field(REGION, region);
+                if let Some(u) = read_units {
+                    ns.write_i32_field(READ_UNITS, *u);
+                }
+                if let Some(u) = write_units {
+                    ns.write_i32_field(WRITE_UNITS, *u);
+                }
+            }
+            Some(ReplicaOp::Drop { region }) => {
+                ns.write_string_field(REGION, region);
+            }
+            None if tags.is_none() => {
+                ns.write_string_field(STATEMENT, &self.statement);
+            }
+            None => {}
+        }
         ns.write_limits(&self.limits);
-        // TODO: freeform/defined tags
-        if let Some(etag) = &self.match_etag {
+        let etag = match tags {
+            Some((tags, etag)) => {
+                // tags are sent as a JSON object
+                let json = serde_json::to_string(tags).unwrap_or_default();
+                ns.write_string_field(FREE_FORM_TAGS, &json);
+                etag
+            }
+            None => &self.match_etag,
+        };
+        if let Some(etag) = etag {
             ns.write_string_field(ETAG, etag);
         }
         // TODO: these are currently only in http headers. Add to NSON?
@@ -253,6 +608,28 @@ impl TableRequest {
                 ETAG => {
                     res.match_etag = Some(walker.read_nson_string()?);
                 }
+                FREE_FORM_TAGS => {
+                    let json = walker.read_nson_string()?;
+                    res.freeform_tags = match serde_json::from_str(&json) {
+                        Ok(tags) => tags,
+                        Err(e) => {
+                            return Err(NoSQLError::new(
+                                NoSQLErrorCode::BadProtocolMessage,
+                                &format!("invalid free-form tags '{}': {}", json, e),
+                            ));
+                        }
+                    };
+                }
+                REPLICAS => {
+                    MapWalker::expect_type(walker.r, FieldType::Array)?;
+                    let _ = walker.r.read_i32()?; // skip array size in bytes
+                    let num_elements = walker.r.read_i32()?;
+                    res.replicas = Vec::with_capacity(num_elements as usize);
+                    for _n in 1..=num_elements {
+                        res.replicas
+                            .push(TableRequest::read_replica_info(walker.r)?);
+                    }
+                }
                 _ => {
                     //println!("   table_result: skipping field '{}'", name);
                     walker.skip_nson_field()?;
@@ -263,9 +640,81 @@ impl TableRequest {
     }
 }
 
+impl TableRequest {
+    fn read_replica_info(r: &mut Reader) -> Result<ReplicaInfo, NoSQLError> {
+        let mut walker = MapWalker::new(r)?;
+        let mut res: ReplicaInfo = Default::default();
+        while walker.has_next() {
+            walker.next()?;
+            let name = walker.current_name();
+            match name.as_str() {
+                REGION => {
+                    res.region = walker.read_nson_string()?;
+                }
+                TABLE_OCID => {
+                    res.table_ocid = walker.read_nson_string()?;
+                }
+                READ_UNITS => {
+                    res.read_units = walker.read_nson_i32()?;
+                }
+                WRITE_UNITS => {
+                    res.write_units = walker.read_nson_i32()?;
+                }
+                LIMITS_MODE => {
+                    if walker.read_nson_i32()? == CapacityMode::OnDemand as i32 {
+                        res.mode = CapacityMode::OnDemand;
+                    }
+                }
+                TABLE_STATE => {
+                    res.state = TableState::from_int(walker.read_nson_i32()?)?;
+                }
+                _ => {
+                    walker.skip_nson_field()?;
+                }
+            }
+        }
+        Ok(res)
+    }
+}
+
+// Get the name of the table of a DDL statement: the table created, altered
+// or dropped, or the table of an index. Returns None for other statements,
+// such as namespace statements.
+pub(crate) fn ddl_table_name(statement: &str) -> Option<&str> {
+    let mut words = statement
+        .split(|c: char| c.is_whitespace() || c == '(' || c == ';')
+        .filter(|w| !w.is_empty());
+    let is = |w: Option<&str>, kw: &str| w.is_some_and(|w| w.eq_ignore_ascii_case(kw));
+    let verb = words.next();
+    if !is(verb, "CREATE") && !is(verb, "ALTER") && !is(verb, "DROP") {
+        return None;
+    }
+    let mut word = words.next();
+    if is(word, "FULLTEXT") {
+        word = words.next();
+    }
+    let is_index = match word {
+        Some(w) if w.eq_ignore_ascii_case("TABLE") => false,
+        Some(w) if w.eq_ignore_ascii_case("INDEX") => true,
+        _ => return None,
+    };
+    let mut word = words.next();
+    if is(word, "IF") {
+        // IF [NOT] EXISTS
+        word = words.find(|w| w.eq_ignore_ascii_case("EXISTS"));
+        word = word.and_then(|_| words.next());
+    }
+    if is_index {
+        // skip the index name
+        words.find(|w| w.eq_ignore_ascii_case("ON"))?;
+        word = words.next();
+    }
+    word
+}
+
 impl NsonRequest for TableRequest {
     fn serialize(&self, w: &mut Writer, timeout: &Duration) {
-        self.nson_serialize(w, timeout);
+        self.nson_serialize(w, timeout, None);
     }
 }
 
@@ -277,6 +726,30 @@ impl GetTableRequest {
         }
     }
 
+    /// Create a `GetTableRequest` for the status of a table operation.
+    ///
+    /// `operation_id` is the value of [`TableResult::operation_id()`] returned by the
+    /// [`TableRequest`] that started the operation. It can be saved and used later, from
+    /// another process, to resume waiting for the operation to complete:
+    ///
+    /// ```no_run
+    /// # use oracle_nosql_rust_sdk::{Handle, GetTableRequest};
+    /// # use std::time::Duration;
+    /// # async fn run(handle: &Handle, saved_op_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut res = GetTableRequest::by_operation_id("users", saved_op_id)
+    ///     .execute(handle)
+    ///     .await?;
+    /// res.wait_for_completion(handle, Duration::from_secs(60), Duration::from_secs(1))
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// If the operation failed, `execute()` returns the error of the operation.
+    pub fn by_operation_id(table_name: &str, operation_id: &str) -> GetTableRequest {
+        GetTableRequest::new(table_name).operation_id(operation_id)
+    }
+
     /// Specify the timeout value for the request.
     ///
     /// This is optional.
@@ -301,6 +774,17 @@ impl GetTableRequest {
         self
     }
 
+    /// Apply [`RequestOptions`] to this request, using their timeout, compartment and namespace.
+    pub fn options(mut self, options: &RequestOptions) -> Self {
+        options.apply_timeout(&mut self.timeout);
+        options.apply_compartment_id(&mut self.compartment_id);
+        options.apply_namespace(&mut self.namespace);
+        self
+    }
+
+    /// Specify the id of a table operation to get the status of.
+    ///
+    /// See [`GetTableRequest::by_operation_id()`].
     pub fn operation_id(mut self, op_id: &str) -> GetTableRequest {
         self.operation_id = op_id.to_string();
         self
@@ -312,10 +796,21 @@ impl GetTableRequest {
         self
     }
 
+    /// Cloud only: also get the current storage used by the table.
+    ///
+    /// The service does not return storage usage with the table information, so this sends
+    /// a second request, a [`TableUsageRequest`](crate::TableUsageRequest) for the most
+    /// recent usage record of the table. The usage is then available from
+    /// [`TableResult::storage_used_gb()`] and [`TableResult::storage_used_percent()`]. This
+    /// has no effect on-premises, where table usage is not reported.
+    pub fn include_storage_usage(mut self, include: bool) -> GetTableRequest {
+        self.include_storage_usage = include;
+        self
+    }
+
     pub async fn execute(&self, h: &Handle) -> Result<TableResult, NoSQLError> {
         // TODO: validate
-        let mut w: Writer = Writer::new();
-        w.write_i16(h.inner.serial_version);
+        let mut w = h.request_writer();
         let timeout = h.get_timeout(&self.timeout);
         self.nson_serialize(&mut w, &timeout);
         let mut opts = SendOptions {
@@ -326,7 +821,30 @@ impl GetTableRequest {
             ..Default::default()
         };
         let mut r = h.send_and_receive(w, &mut opts).await?;
-        let resp = TableRequest::nson_deserialize(&mut r)?;
+        let mut resp = TableRequest::nson_deserialize(&mut r)?;
+        resp.table_name = h.logical_table_name(&resp.table_name);
+        // keep the request's settings, so the result can be used to wait for
+        // completion of the operation
+        if resp.table_name.is_empty() {
+            resp.table_name = self.table_name.clone();
+        }
+        if resp.compartment_id.is_empty() {
+            resp.compartment_id = self.compartment_id.clone();
+        }
+        if resp.namespace.is_empty() {
+            resp.namespace = self.namespace.clone();
+        }
+        if resp.operation_id.is_empty() && !resp.is_terminal() {
+            resp.operation_id = self.operation_id.clone();
+        }
+        if self.include_storage_usage && h.inner.builder.mode != HandleMode::Onprem {
+            let mut usage = TableUsageRequest::new(&self.table_name)
+                .compartment_id(&self.compartment_id)
+                .limit(1);
+            usage.timeout = self.timeout;
+            let records = usage.execute(h).await?.take_usage_records();
+            resp.storage_used_gb = records.last().map(|u| u.storage_gb as f64);
+        }
         Ok(resp)
     }
 
@@ -359,6 +877,9 @@ impl TableResult {
     ///
     /// This method will loop, polling the system for the status of the SystemRequest
     /// until it either succeeds, gets an error, or times out.
+    ///
+    /// If the operation fails, the error is returned and the reason for the failure
+    /// is also available from [`operation_error()`](TableResult::operation_error()).
     pub async fn wait_for_completion(
         &mut self,
         h: &Handle,
@@ -371,13 +892,76 @@ impl TableResult {
         if wait < delay {
             return ia_err!("wait duration must be greater than delay duration");
         }
+        self.wait_internal(h, wait, delay, delay).await
+    }
 
+    /// Wait for a TableRequest to complete, polling with an increasing interval.
+    ///
+    /// This is similar to [`wait_for_completion()`](TableResult::wait_for_completion()), but
+    /// the delay between polls starts at `initial_poll` and doubles after each poll, up to
+    /// `max_poll`. Fast operations are still detected quickly, while long-running operations
+    /// (such as creating a large table, or adding an index to one) use far fewer status
+    /// requests than with a small fixed interval.
+    ///
+    /// ```no_run
+    /// # use oracle_nosql_rust_sdk::{Handle, TableRequest};
+    /// # use std::time::Duration;
+    /// # async fn run(handle: &Handle) -> Result<(), Box<dyn std::error::Error>> {
+    /// TableRequest::new("events")
+    ///     .statement("create index ts_idx on events(ts)")
+    ///     .execute(handle)
+    ///     .await?
+    ///     // poll after 100ms, 200ms, 400ms, ... then every 10 seconds, for up to 30 minutes
+    ///     .wait_for_completion_backoff(
+    ///         handle,
+    ///         Duration::from_secs(1800),
+    ///         Duration::from_millis(100),
+    ///         Duration::from_secs(10),
+    ///     )
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn wait_for_completion_backoff(
+        &mut self,
+        h: &Handle,
+        wait: Duration,
+        initial_poll: Duration,
+        max_poll: Duration,
+    ) -> Result<(), NoSQLError> {
+        if self.is_terminal() {
+            return Ok(());
+        }
+        if initial_poll.is_zero() {
+            return ia_err!("initial poll interval must be greater than zero");
+        }
+        if max_poll < initial_poll {
+            return ia_err!("max poll interval must not be less than initial poll interval");
+        }
+        if wait < initial_poll {
+            return ia_err!("wait duration must be greater than initial poll interval");
+        }
+        self.wait_internal(h, wait, initial_poll, max_poll).await
+    }
+
+    // Poll until the operation is complete, sleeping between polls for a delay
+    // that starts at initial_poll and doubles up to max_poll.
+    async fn wait_internal(
+        &mut self,
+        h: &Handle,
+        wait: Duration,
+        initial_poll: Duration,
+        max_poll: Duration,
+    ) -> Result<(), NoSQLError> {
         let start_time = Instant::now();
         let mut first_loop = true;
+        let mut delay = initial_poll;
+        let was_creating = self.state == TableState::Creating;
 
-        while self.is_terminal() == false {
+        while !self.is_terminal() {
             //println!("  table-request: elapsed={:?}", start_time.elapsed());
-            if start_time.elapsed() > wait {
+            let elapsed = start_time.elapsed();
+            if elapsed > wait {
                 return Err(NoSQLError::new(
                     RequestTimeout,
                     "operation not completed in expected time",
@@ -391,21 +975,48 @@ impl TableResult {
             // TODO: namespace? Java sdk doesn't add it...?
 
             if !first_loop {
-                sleep(delay);
+                // don't sleep past the end of the wait time, so there is one
+                // last poll before timing out
+                sleep(std::cmp::min(delay, wait - elapsed)).await;
+                delay = std::cmp::min(delay * 2, max_poll);
             }
 
-            let res = get_request.execute(h).await?;
+            let res = match get_request.execute(h).await {
+                Ok(r) => r,
+                Err(e) => {
+                    // An error for the operation id (as opposed to a transient
+                    // error polling for it) means the operation itself failed.
+                    if !e.is_retryable() {
+                        self.operation_error = Some(e.message.clone());
+                    }
+                    return Err(e);
+                }
+            };
 
             // TODO: copy_most method?
             self.state = res.state;
             self.limits = res.limits;
             self.schema = res.schema;
             self.ddl = res.ddl;
-            // TODO: tags, MRT data
+            if res.match_etag.is_some() {
+                self.match_etag = res.match_etag;
+            }
+            self.replicas = res.replicas;
+            self.freeform_tags = res.freeform_tags;
+            // TODO: defined tags, MRT data
 
             first_loop = false;
         }
 
+        if was_creating && self.state == TableState::Dropped {
+            let msg = format!(
+                "creation of table '{}' failed: table was dropped before becoming active",
+                self.table_name
+            );
+            self.operation_error = Some(msg.clone());
+            return Err(NoSQLError::new(IllegalState, &msg));
+        }
+
         Ok(())
     }
 
@@ -440,11 +1051,14 @@ impl TableResult {
     pub fn table_name(&self) -> String {
         self.table_name.clone()
     }
-    /// Cloud only: get the compartment id of the table.
+    /// Cloud only: get the OCID of the compartment of the table.
     ///
-    /// This is only valid for [`GetTableRequest`].
-    pub fn compartment_id(&self) -> String {
-        self.compartment_id.clone()
+    /// This is `None` if the server did not return a compartment, as with on-premises stores.
+    pub fn compartment_id(&self) -> Option<String> {
+        if self.compartment_id.is_empty() {
+            return None;
+        }
+        Some(self.compartment_id.clone())
     }
     /// On-premises only: get the namespace of the table.
     ///
@@ -458,18 +1072,37 @@ impl TableResult {
     pub fn table_ocid(&self) -> String {
         self.table_ocid.clone()
     }
+    /// Cloud only: get the OCID of the table.
+    ///
+    /// This is the same as [`table_ocid()`](TableResult::table_ocid()), but is `None` if the
+    /// server did not return an OCID, as with on-premises stores. Together with
+    /// [`compartment_id()`](TableResult::compartment_id()), this identifies the table as an
+    /// OCI resource.
+    pub fn ocid(&self) -> Option<String> {
+        if self.table_ocid.is_empty() {
+            return None;
+        }
+        Some(self.table_ocid.clone())
+    }
     /// Get the DDL statement that was used to create the table.
     ///
     /// Note this will reflect any `ALTER TABLE` operations as well.
     pub fn ddl(&self) -> String {
         self.ddl.clone()
     }
-    /// Get the internal operation ID for an in-progress table request.
+    /// Get the operation id of an in-progress table operation.
     ///
-    /// This is typically not needed by applications; it is available for testing purposes only.
-    /// Internally, [`TableResult::wait_for_completion()`] uses this value when polling the system.
-    pub fn operation_id(&self) -> String {
-        self.operation_id.clone()
+    /// [`TableResult::wait_for_completion()`] uses this value when polling for the status of
+    /// the operation. It can also be saved, for example by a stateless function that starts a
+    /// long-running table creation, and used later with
+    /// [`GetTableRequest::by_operation_id()`] to resume waiting for completion. This is `None`
+    /// if the server did not return an operation id, such as for a [`GetTableRequest`] for a
+    /// table with no operation in progress.
+    pub fn operation_id(&self) -> Option<String> {
+        if self.operation_id.is_empty() {
+            return None;
+        }
+        Some(self.operation_id.clone())
     }
     /// Get the schema of the table.
     ///
@@ -477,6 +1110,84 @@ impl TableResult {
     pub fn schema(&self) -> String {
         self.schema.clone()
     }
+    /// Get the names of the primary key columns of the table, in key order.
+    ///
+    /// This is derived from the table [`schema()`](TableResult::schema()). If the schema
+    /// is not available (for example, if the table has been dropped), the returned vector is empty.
+    pub fn primary_key_fields(&self) -> Vec<String> {
+        self.schema_string_array("primaryKey")
+    }
+    /// Get the names of the shard key columns of the table, in key order.
+    ///
+    /// The shard key is always a prefix of the primary key. If the table was created
+    /// without an explicit `shard()` clause, this is the same as the primary key.
+    /// This is derived from the table [`schema()`](TableResult::schema()). If the schema
+    /// is not available, the returned vector is empty.
+    pub fn shard_key_fields(&self) -> Vec<String> {
+        let fields = self.schema_string_array("shardKey");
+        if fields.is_empty() {
+            return self.primary_key_fields();
+        }
+        fields
+    }
+
+    /// Get the names of the top-level columns of the table that have type `JSON`.
+    ///
+    /// The values of JSON columns are returned as plain [`FieldValue`](crate::types::FieldValue)s
+    /// (maps, arrays, strings, numbers, etc.), the same as values of typed columns, so this can
+    /// be used to tell them apart. Only JSON columns can hold values of different types in
+    /// different rows, and only they can contain JSON `null`s
+    /// ([`FieldValue::JsonNull`](crate::types::FieldValue::JsonNull)).
+    ///
+    /// This is derived from the table [`schema()`](TableResult::schema()). If the schema
+    /// is not available, the returned vector is empty.
+    pub fn json_fields(&self) -> Vec<String> {
+        let mut v: Vec<String> = Vec::new();
+        let schema = match self.schema_value() {
+            Some(s) => s,
+            None => {
+                return v;
+            }
+        };
+        if let Some(serde_json::Value::Array(fields)) = schema.get("fields") {
+            for f in fields {
+                let is_json = f
+                    .get("type")
+                    .and_then(|t| t.as_str())
+                    .is_some_and(|t| t.eq_ignore_ascii_case("json"));
+                if let (true, Some(name)) = (is_json, f.get("name").and_then(|n| n.as_str())) {
+                    v.push(name.to_string());
+                }
+            }
+        }
+        v
+    }
+
+    fn schema_value(&self) -> Option<serde_json::Value> {
+        if self.schema.is_empty() {
+            return None;
+        }
+        serde_json::from_str(&self.schema).ok()
+    }
+
+    // Get an array of strings from the top level of the JSON schema.
+    fn schema_string_array(&self, name: &str) -> Vec<String> {
+        let mut v: Vec<String> = Vec::new();
+        let schema = match self.schema_value() {
+            Some(s) => s,
+            None => {
+                return v;
+            }
+        };
+        if let Some(serde_json::Value::Array(arr)) = schema.get(name) {
+            for f in arr {
+                if let Some(s) = f.as_str() {
+                    v.push(s.to_string());
+                }
+            }
+        }
+        v
+    }
     /// Get the current state of the table.
     pub fn state(&self) -> TableState {
         self.state.clone()
@@ -488,13 +1199,67 @@ impl TableResult {
         }
         None
     }
+    /// Cloud only: get the storage currently used by the table, in gigabytes.
+    ///
+    /// This is only available if the result is from a [`GetTableRequest`] with
+    /// [`include_storage_usage()`](GetTableRequest::include_storage_usage()) set, and the
+    /// service reported usage for the table.
+    pub fn storage_used_gb(&self) -> Option<f64> {
+        self.storage_used_gb
+    }
+    /// Cloud only: get the storage currently used by the table, as a percentage of its
+    /// storage limit ([`TableLimits::storage_gb`]).
+    ///
+    /// This is `None` if the [`storage_used_gb()`](TableResult::storage_used_gb()) or the
+    /// storage limit of the table is not available.
+    pub fn storage_used_percent(&self) -> Option<f64> {
+        let used = self.storage_used_gb?;
+        match &self.limits {
+            Some(l) if l.storage_gb > 0 => Some(used * 100.0 / l.storage_gb as f64),
+            _ => None,
+        }
+    }
+    /// Cloud only: get the free-form tags of the table.
+    ///
+    /// This is empty if the table has no tags, or the server did not return them.
+    pub fn freeform_tags(&self) -> &HashMap<String, String> {
+        &self.freeform_tags
+    }
+    /// Cloud only: get the ETag for the table.
+    ///
+    /// The ETag identifies the current version of the table. It changes whenever the table
+    /// is modified, and is updated by [`wait_for_completion()`](TableResult::wait_for_completion())
+    /// once an operation completes. Pass it to [`TableRequest::match_etag()`] to only apply
+    /// a table operation if the table has not changed since.
+    pub fn etag(&self) -> Option<String> {
+        self.match_etag.clone()
+    }
     /// Cloud only: get the match ETag for the table.
     ///
-    /// see [`TableRequest::match_etag()`] for more details.
+    /// This is the same as [`etag()`](TableResult::etag()).
     pub fn match_etag(&self) -> Option<String> {
         if let Some(etag) = &self.match_etag {
             return Some(etag.clone());
         }
         None
     }
+    /// Cloud only: get the regional replicas of a Global Active Table.
+    ///
+    /// This is empty if the table is not replicated. Each [`ReplicaInfo`] reports the state,
+    /// capacity mode and throughput units of one replica, which may differ between regions.
+    pub fn replicas(&self) -> &Vec<ReplicaInfo> {
+        &self.replicas
+    }
+    /// Get the reason a table operation failed.
+    ///
+    /// This is set by [`wait_for_completion()`](TableResult::wait_for_completion()) when the
+    /// table operation fails after it was accepted by the system, for example a `CREATE TABLE`
+    /// that is rejected during execution. It is `None` if the operation succeeded or is still
+    /// in progress, or if waiting failed for an unrelated reason (such as a network error).
+    pub fn operation_error(&self) -> Option<String> {
+        if let Some(e) = &self.operation_error {
+            return Some(e.clone());
+        }
+        None
+    }
 }
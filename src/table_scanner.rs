@@ -0,0 +1,330 @@
+//
+// Copyright (c) 2024, 2025 Oracle and/or its affiliates. All rights reserved.
+//
+// Licensed under the Universal Permissive License v 1.0 as shown at
+//  https://oss.oracle.com/licenses/upl/
+//
+use crate::condition::validate_path;
+use crate::error::ia_err;
+use crate::error::NoSQLError;
+use crate::handle::Handle;
+use crate::prepared_statement::PreparedStatement;
+use crate::query_request::QueryRequest;
+use crate::reader::Reader;
+use crate::table_request::GetTableRequest;
+use crate::types::{FieldValue, MapValue};
+use crate::writer::Writer;
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+use std::collections::VecDeque;
+use std::result::Result;
+use std::time::Duration;
+use tracing::trace;
+
+// Default number of rows fetched at a time by next_row().
+const DEFAULT_BATCH_SIZE: usize = 100;
+
+// Field names used in checkpoints.
+const CHECKPOINT_TABLE: &str = "table";
+const CHECKPOINT_KEY: &str = "key";
+
+/// An incremental, resumable scan of all rows of a table, in primary key order.
+///
+/// A `TableScanner` reads a table in batches with [`next_batch()`](TableScanner::next_batch())
+/// or one row at a time with [`next_row()`](TableScanner::next_row()), without holding more
+/// than one batch of rows in memory. Each batch is a separate query for the rows that follow
+/// the last row read, by primary key, so no query state is kept on the server between batches.
+///
+/// The position of the scan can be saved at any time with [`checkpoint()`](TableScanner::checkpoint())
+/// and later passed to [`TableScanner::resume()`], possibly in another process, to continue
+/// the scan after the last row that was returned:
+/// ```no_run
+/// # use oracle_nosql_rust_sdk::{Handle, TableScanner};
+/// # #[tokio::main]
+/// # pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let handle = Handle::builder().build().await?;
+/// # let saved: Option<String> = None;
+/// let mut scanner = match saved {
+///     Some(cp) => TableScanner::resume(&handle, "users", &cp)?,
+///     None => TableScanner::new(&handle, "users"),
+/// };
+/// loop {
+///     let rows = scanner.next_batch(500).await?;
+///     if rows.is_empty() {
+///         break;
+///     }
+///     // ... process rows, then save scanner.checkpoint() ...
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The scan is not a snapshot of the table. Every row that exists for the whole duration of the
+/// scan is returned exactly once, including across resumptions. Rows inserted or deleted during
+/// the scan are returned only if their primary key is after the current position of the scan.
+/// Updated rows are returned with the values they have when their batch is read.
+///
+/// Sorting by primary key requires a query across all shards. Scanning a large table this way
+/// uses the same read units as a `SELECT *` query, but each batch is a separate round trip.
+#[derive(Debug)]
+pub struct TableScanner {
+    handle: Handle,
+    table_name: String,
+    // primary key fields of the table, in key order, read on first use
+    key_fields: Vec<String>,
+    // primary key of the last row read from the server
+    fetched_key: Option<MapValue>,
+    // primary key of the last row returned to the caller
+    returned_key: Option<MapValue>,
+    rows: VecDeque<MapValue>,
+    // true once the server has returned all rows
+    done: bool,
+    batch_size: usize,
+    timeout: Option<Duration>,
+    compartment_id: String,
+    // the last statement prepared, and its SQL text
+    prepared: Option<(String, PreparedStatement)>,
+}
+
+impl TableScanner {
+    /// Create a new scanner, positioned at the start of the given table.
+    pub fn new(handle: &Handle, table_name: &str) -> TableScanner {
+        TableScanner {
+            handle: handle.clone(),
+            table_name: table_name.to_string(),
+            key_fields: Vec::new(),
+            fetched_key: None,
+            returned_key: None,
+            rows: VecDeque::new(),
+            done: false,
+            batch_size: DEFAULT_BATCH_SIZE,
+            timeout: None,
+            compartment_id: String::new(),
+            prepared: None,
+        }
+    }
+
+    /// Create a scanner that continues a previous scan of the given table, from a
+    /// [`checkpoint()`](TableScanner::checkpoint()).
+    ///
+    /// Returns an `IllegalArgument` error if the checkpoint is not valid, or was saved
+    /// from a scan of a different table.
+    pub fn resume(
+        handle: &Handle,
+        table_name: &str,
+        checkpoint: &str,
+    ) -> Result<TableScanner, NoSQLError> {
+        let state = match decode_checkpoint(checkpoint) {
+            Some(s) => s,
+            None => {
+                return ia_err!("invalid table scanner checkpoint");
+            }
+        };
+        match state.get_string(CHECKPOINT_TABLE) {
+            Some(t) if t.eq_ignore_ascii_case(table_name) => {}
+            Some(t) => {
+                return ia_err!(
+                    "checkpoint is for table '{}', not table '{}'",
+                    t,
+                    table_name
+                );
+            }
+            None => {
+                return ia_err!("invalid table scanner checkpoint");
+            }
+        }
+        let mut scanner = TableScanner::new(handle, table_name);
+        if let Some(key) = state.get_map(CHECKPOINT_KEY) {
+            scanner.fetched_key = Some(key.deep_clone());
+            scanner.returned_key = Some(key.deep_clone());
+        }
+        Ok(scanner)
+    }
+
+    /// Specify the number of rows fetched at a time by [`next_row()`](TableScanner::next_row()).
+    ///
+    /// The default is 100. Values less than 1 are treated as 1.
+    pub fn batch_size(mut self, size: usize) -> Self {
+        self.batch_size = size.max(1);
+        self
+    }
+
+    /// Specify the timeout value for each batch of the scan.
+    ///
+    /// If not set, the default timeout value configured for the [`Handle`](crate::HandleBuilder::timeout()) is used.
+    pub fn timeout(mut self, t: &Duration) -> Self {
+        self.timeout = Some(*t);
+        self
+    }
+
+    /// Cloud Service only: set the name or id of a compartment to be used for the scan.
+    ///
+    /// See [`QueryRequest::compartment_id()`].
+    pub fn compartment_id(mut self, compartment_id: &str) -> Self {
+        self.compartment_id = compartment_id.to_string();
+        self
+    }
+
+    /// Get the next rows of the table, up to `size` rows.
+    ///
+    /// Returns an empty vector when all rows have been returned.
+    pub async fn next_batch(&mut self, size: usize) -> Result<Vec<MapValue>, NoSQLError> {
+        if size == 0 {
+            return ia_err!("batch size must be greater than zero");
+        }
+        while self.rows.len() < size && !self.done {
+            self.fetch(size - self.rows.len()).await?;
+        }
+        let n = size.min(self.rows.len());
+        let rows: Vec<MapValue> = self.rows.drain(..n).collect();
+        if let Some(last) = rows.last() {
+            self.returned_key = Some(self.row_key(last)?);
+        }
+        Ok(rows)
+    }
+
+    /// Get the next row of the table, or `None` when all rows have been returned.
+    ///
+    /// Rows are fetched from the server [`batch_size()`](TableScanner::batch_size()) rows at
+    /// a time.
+    pub async fn next_row(&mut self) -> Result<Option<MapValue>, NoSQLError> {
+        if self.rows.is_empty() && !self.done {
+            self.fetch(self.batch_size).await?;
+        }
+        match self.rows.pop_front() {
+            Some(row) => {
+                self.returned_key = Some(self.row_key(&row)?);
+                Ok(Some(row))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Return `true` if all rows of the table have been returned.
+    pub fn is_done(&self) -> bool {
+        self.done && self.rows.is_empty()
+    }
+
+    /// Save the position of the scan, after the last row returned.
+    ///
+    /// The returned string can be stored and passed to [`TableScanner::resume()`] to continue
+    /// the scan. It contains the table name and the primary key of the last row returned, and
+    /// should be treated as opaque.
+    pub fn checkpoint(&self) -> String {
+        let mut state = MapValue::new().str(CHECKPOINT_TABLE, &self.table_name);
+        if let Some(key) = &self.returned_key {
+            state.put_field_value(CHECKPOINT_KEY, FieldValue::Map(key.deep_clone()));
+        }
+        let mut w = Writer::new();
+        w.write_field_value(&FieldValue::Map(state));
+        BASE64_STANDARD.encode(&w.buf)
+    }
+
+    // Read the next rows after fetched_key from the server, up to limit rows.
+    async fn fetch(&mut self, limit: usize) -> Result<(), NoSQLError> {
+        if self.key_fields.is_empty() {
+            let mut treq =
+                GetTableRequest::new(&self.table_name).compartment_id(&self.compartment_id);
+            if let Some(t) = &self.timeout {
+                treq = treq.timeout(t);
+            }
+            self.key_fields = treq.execute(&self.handle).await?.primary_key_fields();
+            if self.key_fields.is_empty() {
+                return ia_err!(
+                    "could not get the primary key of table '{}'",
+                    self.table_name
+                );
+            }
+        }
+        let (sql, values) = self.statement(limit)?;
+        let ps = match &self.prepared {
+            Some((s, ps)) if *s == sql => ps.clone(),
+            _ => {
+                trace!("table scanner: preparing '{}'", sql);
+                let mut prep = QueryRequest::new(&sql)
+                    .prepare_only()
+                    .compartment_id(&self.compartment_id);
+                prep.timeout = self.timeout;
+                let ps = prep.execute(&self.handle).await?.prepared_statement();
+                self.prepared = Some((sql, ps.clone()));
+                ps
+            }
+        };
+        let mut qreq = QueryRequest::new_prepared(&ps).compartment_id(&self.compartment_id);
+        qreq.timeout = self.timeout;
+        for (i, v) in values.iter().enumerate() {
+            qreq.set_variable_by_id(i as i32 + 1, v)?;
+        }
+        let rows = qreq.execute(&self.handle).await?.take_rows();
+        trace!("table scanner: read {} rows", rows.len());
+        if rows.len() < limit {
+            self.done = true;
+        }
+        if let Some(last) = rows.last() {
+            self.fetched_key = Some(self.row_key(last)?);
+        }
+        self.rows.extend(rows);
+        Ok(())
+    }
+
+    // Get the query for the next rows and its positional bind variable values.
+    // Rows after a composite key (k1, k2) are those matching
+    //   t.k1 >= v1 AND ((t.k1 > v1) OR (t.k1 = v1 AND t.k2 > v2))
+    // The leading range on k1 lets the primary key index start the scan at the
+    // current position, instead of filtering all rows with the OR. The limit is
+    // also a variable, so that batches of any size use the same statement.
+    fn statement(&self, limit: usize) -> Result<(String, Vec<FieldValue>), NoSQLError> {
+        let table_name = self.handle.table_name(&self.table_name);
+        validate_path("table name", &table_name, ".:")?;
+        for k in &self.key_fields {
+            validate_path("key field", k, "")?;
+        }
+        let mut sql = format!("SELECT * FROM {} t", table_name);
+        let mut values: Vec<FieldValue> = Vec::new();
+        if let Some(key) = &self.fetched_key {
+            let mut terms: Vec<String> = Vec::new();
+            for (i, k) in self.key_fields.iter().enumerate() {
+                let mut parts: Vec<String> = Vec::new();
+                for prev in &self.key_fields[..i] {
+                    parts.push(format!("t.{} = ?", prev));
+                    values.push(key_value(key, prev)?);
+                }
+                parts.push(format!("t.{} > ?", k));
+                values.push(key_value(key, k)?);
+                terms.push(format!("({})", parts.join(" AND ")));
+            }
+            sql.push_str(" WHERE ");
+            if terms.len() > 1 {
+                let first = &self.key_fields[0];
+                sql.push_str(&format!("t.{} >= ? AND ({})", first, terms.join(" OR ")));
+                values.insert(0, key_value(key, first)?);
+            } else {
+                sql.push_str(&format!("t.{} > ?", self.key_fields[0]));
+            }
+        }
+        let order: Vec<String> = self.key_fields.iter().map(|k| format!("t.{}", k)).collect();
+        sql.push_str(&format!(" ORDER BY {} LIMIT ?", order.join(", ")));
+        values.push(FieldValue::Long(limit as i64));
+        Ok((sql, values))
+    }
+
+    fn row_key(&self, row: &MapValue) -> Result<MapValue, NoSQLError> {
+        let mut key = MapValue::new();
+        for k in &self.key_fields {
+            key.put_field_value(k, key_value(row, k)?);
+        }
+        Ok(key)
+    }
+}
+
+fn key_value(row: &MapValue, field: &str) -> Result<FieldValue, NoSQLError> {
+    match row.get_field_value(field) {
+        Some(v) => Ok(v.clone_internal()),
+        None => ia_err!("row is missing primary key field '{}'", field),
+    }
+}
+
+fn decode_checkpoint(checkpoint: &str) -> Option<MapValue> {
+    let bytes = BASE64_STANDARD.decode(checkpoint).ok()?;
+    let mut r = Reader::new().from_bytes(&bytes);
+    r.read_field_value().ok()?.get_map_value().ok()
+}
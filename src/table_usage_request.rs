@@ -9,6 +9,7 @@ use crate::handle::Handle;
 use crate::handle::SendOptions;
 use crate::nson::*;
 use crate::reader::Reader;
+use crate::request_options::RequestOptions;
 use crate::types::{FieldType, OpCode};
 use crate::writer::Writer;
 use chrono::{DateTime, FixedOffset};
@@ -114,6 +115,13 @@ impl TableUsageRequest {
         self
     }
 
+    /// Apply [`RequestOptions`] to this request, using their timeout and compartment.
+    pub fn options(mut self, options: &RequestOptions) -> Self {
+        options.apply_timeout(&mut self.timeout);
+        options.apply_compartment_id(&mut self.compartment_id);
+        self
+    }
+
     /// Specify the start time for TableUsage records.
     pub fn start_time(mut self, t: DateTime<FixedOffset>) -> TableUsageRequest {
         self.start_time = Some(t);
@@ -142,8 +150,7 @@ impl TableUsageRequest {
 
     /// Execute the request, returning a [`TableUsageResult`].
     pub async fn execute(&self, h: &Handle) -> Result<TableUsageResult, NoSQLError> {
-        let mut w: Writer = Writer::new();
-        w.write_i16(h.inner.serial_version);
+        let mut w = h.request_writer();
         let timeout = h.get_timeout(&self.timeout);
         self.nson_serialize(&mut w, &timeout);
         let mut opts = SendOptions {
@@ -7,11 +7,14 @@
 use base64::prelude::{Engine as _, BASE64_STANDARD};
 use bigdecimal::BigDecimal;
 use bigdecimal::Num;
+use bigdecimal::ToPrimitive;
 use chrono::{DateTime, FixedOffset};
 use std::cmp::Ordering;
-use std::collections::btree_map::Iter;
+use std::collections::btree_map::{Iter, Keys, Values};
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 use std::result::Result;
 use std::string::String;
 use std::vec::Vec;
@@ -136,6 +139,17 @@ pub struct NoSQLBinary {
 ///
 /// `FieldValue` instances are not thread-safe. On input, they should not be reused until the operation that uses
 /// them has returned.
+///
+/// An empty string is a value like any other: a `String("")` is written and read back as an empty
+/// string, and is never converted to or from a NULL value. Empty strings can be used as values of
+/// string columns and as string primary key components, so a row with an empty string key is
+/// distinct from a row with a missing or NULL key (which the server rejects).
+///
+/// `FieldValue` implements `Eq`, `Ord` and `Hash`, so values and rows can be used in standard collections such as
+/// a `HashSet`. Numeric values of different types are equal if they have the same value (`Integer(1)` equals
+/// `Long(1)` and `Double(1.0)`), and timestamps are equal if they are the same instant, in any timezone. SQL NULL,
+/// JSON null and empty values are not equal to each other. The hash only depends on the value, so it is the same
+/// across runs for a hasher with fixed keys.
 // Note: do not derive Clone. Use clone_internal() when needed.
 #[derive(Debug, Default)]
 pub enum FieldValue {
@@ -176,6 +190,65 @@ impl PartialOrd for FieldValue {
     }
 }
 
+// Values that compare equal must have the same hash. Integers, longs, doubles
+// and numbers with the same value compare equal, so all numeric values are
+// hashed by their f64 value. All special values (SQL NULL, JSON null, empty)
+// have the same hash.
+impl Hash for FieldValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            FieldValue::Integer(i) => hash_number(*i as f64, state),
+            FieldValue::Long(l) => hash_number(*l as f64, state),
+            FieldValue::Double(d) => hash_number(*d, state),
+            FieldValue::Number(n) => hash_number(n.to_f64().unwrap_or(f64::NAN), state),
+            FieldValue::Timestamp(t) => {
+                1u8.hash(state);
+                t.timestamp().hash(state);
+                t.timestamp_subsec_nanos().hash(state);
+            }
+            FieldValue::String(s) => {
+                2u8.hash(state);
+                s.hash(state);
+            }
+            FieldValue::Boolean(b) => {
+                3u8.hash(state);
+                b.hash(state);
+            }
+            FieldValue::Binary(b) => {
+                4u8.hash(state);
+                b.hash(state);
+            }
+            FieldValue::Array(a) => {
+                5u8.hash(state);
+                a.hash(state);
+            }
+            FieldValue::Map(m) => {
+                6u8.hash(state);
+                m.hash(state);
+            }
+            FieldValue::Null
+            | FieldValue::JsonNull
+            | FieldValue::Empty
+            | FieldValue::Uninitialized => {
+                7u8.hash(state);
+            }
+        }
+    }
+}
+
+fn hash_number<H: Hasher>(f: f64, state: &mut H) {
+    0u8.hash(state);
+    // -0.0 and 0.0 may compare equal (to a number zero), and NaN payloads are not significant
+    let f = if f == 0.0 {
+        0.0
+    } else if f.is_nan() {
+        f64::NAN
+    } else {
+        f
+    };
+    f.to_bits().hash(state);
+}
+
 impl FieldValue {
     pub fn new() -> Self {
         FieldValue::Uninitialized
@@ -258,6 +331,16 @@ impl FieldValue {
             _ => (),
         }
     }
+    /// Create a deep copy of this value.
+    ///
+    /// `FieldValue` does not implement `Clone`, so that values are never copied by accident.
+    /// This copies the value and, for arrays and maps, every nested value, which can be
+    /// expensive for large nested values such as JSON documents. Prefer moving values out of
+    /// results (for example with [`MapValue::take_field_value()`]) when a copy is not needed.
+    pub fn deep_clone(&self) -> FieldValue {
+        self.clone_internal()
+    }
+
     // This exists because we want to be sure that FieldValues
     // never get cloned without us doing it specifically.
     pub(crate) fn clone_internal(&self) -> FieldValue {
@@ -406,6 +489,15 @@ pub(crate) fn bd_try_from_str(val: &str) -> Result<BigDecimal, NoSQLError> {
 
 pub trait NoSQLColumnToFieldValue {
     fn to_field_value(&self) -> FieldValue;
+    /// Convert to a [`FieldValue`], returning an error if the conversion fails.
+    ///
+    /// The default implementation calls [`to_field_value()`](NoSQLColumnToFieldValue::to_field_value()),
+    /// which cannot fail. Types whose conversion can fail, such as structs that derive
+    /// [`NoSQLRow`](crate::NoSQLRow) and collections of them, override this to return the error
+    /// instead of a `Null` value. Writing rows from structs uses this method.
+    fn try_to_field_value(&self) -> Result<FieldValue, NoSQLError> {
+        Ok(self.to_field_value())
+    }
 }
 
 impl NoSQLColumnToFieldValue for FieldValue {
@@ -473,6 +565,22 @@ impl NoSQLColumnToFieldValue for DateTime<FixedOffset> {
         FieldValue::Timestamp(self.clone())
     }
 }
+#[cfg(feature = "uuid")]
+impl NoSQLColumnToFieldValue for uuid::Uuid {
+    fn to_field_value(&self) -> FieldValue {
+        // canonical lower-case hyphenated form, as generated by the server's random_uuid()
+        FieldValue::String(self.hyphenated().to_string())
+    }
+}
+
+// Round a timestamp to the given number of fractional second digits (0-9), the
+// same way the server rounds values stored in a TIMESTAMP(precision) column.
+pub(crate) fn round_timestamp(ts: &DateTime<FixedOffset>, precision: u8) -> DateTime<FixedOffset> {
+    let unit = 10i64.pow(9 - std::cmp::min(precision, 9) as u32);
+    let nanos = ts.timestamp_subsec_nanos() as i64;
+    let rounded = (nanos + unit / 2) / unit * unit;
+    *ts + chrono::Duration::nanoseconds(rounded - nanos)
+}
 
 impl NoSQLColumnToFieldValue for serde_json::value::Value {
     fn to_field_value(&self) -> FieldValue {
@@ -488,28 +596,9 @@ impl NoSQLColumnToFieldValue for serde_json::value::Value {
             }
             //serde_json::Value::Null => { return FieldValue::Null; },
             serde_json::Value::Number(n) => {
-                if n.is_f64() {
-                    return FieldValue::Double(n.as_f64().unwrap());
-                } else if n.is_i64() {
-                    let nv64 = n.as_i64().unwrap();
-                    if let Ok(nv32) = i32::try_from(nv64) {
-                        return FieldValue::Integer(nv32);
-                    } else {
-                        return FieldValue::Long(nv64);
-                    }
-                } else {
-                    // try converting the string representation to a BigDecimal number
-                    if let Ok(bd) = BigDecimal::from_str_radix(&n.to_string(), 10) {
-                        return FieldValue::Number(bd);
-                    }
-                    // For now, just set a String
-                    //println!(
-                    //"WARN: Number value '{:?}' is not an f64 or i64 or BigDecimal",
-                    //n
-                    //);
-                    return FieldValue::String(n.to_string());
-                    //return ia_err!("number value '{:?}' is not an f64 or i64", n);
-                }
+                // lenient conversion never fails
+                json_number_to_field_value(n, NumberPolicy::Lenient)
+                    .unwrap_or(FieldValue::Double(f64::NAN))
             }
             serde_json::Value::Array(a) => {
                 // a = Vec<Value>
@@ -526,6 +615,70 @@ impl NoSQLColumnToFieldValue for serde_json::value::Value {
             }
         }
     }
+    fn try_to_field_value(&self) -> Result<FieldValue, NoSQLError> {
+        match self {
+            serde_json::Value::Array(a) => {
+                let mut arr: Vec<FieldValue> = Vec::with_capacity(a.len());
+                for v in a {
+                    arr.push(v.try_to_field_value()?);
+                }
+                Ok(FieldValue::Array(arr))
+            }
+            serde_json::Value::Object(_) => Ok(FieldValue::Map(MapValue::from_json_object(self)?)),
+            _ => Ok(self.to_field_value()),
+        }
+    }
+}
+
+// Convert a JSON number to the FieldValue type that holds it.
+fn json_number_to_field_value(
+    n: &serde_json::Number,
+    policy: NumberPolicy,
+) -> Result<FieldValue, NoSQLError> {
+    if n.is_f64() {
+        let d = n.as_f64().unwrap();
+        if policy == NumberPolicy::StrictNumber {
+            // with arbitrary precision, the JSON text may have more digits than
+            // a double holds
+            let exact = match (
+                BigDecimal::from_str_radix(&n.to_string(), 10),
+                BigDecimal::from_str_radix(&d.to_string(), 10),
+            ) {
+                (Ok(a), Ok(b)) => a == b,
+                _ => false,
+            };
+            if !exact {
+                return ia_err!(
+                    "JSON number {} cannot be represented exactly as a double",
+                    n
+                );
+            }
+        }
+        Ok(FieldValue::Double(d))
+    } else if n.is_i64() {
+        let nv64 = n.as_i64().unwrap();
+        if let Ok(nv32) = i32::try_from(nv64) {
+            Ok(FieldValue::Integer(nv32))
+        } else {
+            Ok(FieldValue::Long(nv64))
+        }
+    } else if let Some(u) = n.as_u64() {
+        // larger than i64::MAX
+        Ok(FieldValue::Number(BigDecimal::from(u)))
+    } else {
+        // Any JSON number is a valid decimal, so this is only reached with
+        // arbitrary precision numbers beyond the range of a double. Never fall
+        // back to a String: that would change the type of the value.
+        match BigDecimal::from_str_radix(&n.to_string(), 10) {
+            Ok(bd) => Ok(FieldValue::Number(bd)),
+            Err(e) => {
+                if policy == NumberPolicy::StrictNumber {
+                    return ia_err!("JSON number {} is out of range: {}", n, e);
+                }
+                Ok(FieldValue::Double(n.as_f64().unwrap_or(f64::NAN)))
+            }
+        }
+    }
 }
 
 impl<T: NoSQLColumnToFieldValue> NoSQLColumnToFieldValue for Option<T> {
@@ -536,6 +689,29 @@ impl<T: NoSQLColumnToFieldValue> NoSQLColumnToFieldValue for Option<T> {
             FieldValue::Null
         }
     }
+    fn try_to_field_value(&self) -> Result<FieldValue, NoSQLError> {
+        match self {
+            Some(v) => v.try_to_field_value(),
+            None => Ok(FieldValue::Null),
+        }
+    }
+}
+
+impl<T: NoSQLColumnToFieldValue> NoSQLColumnToFieldValue for JsonNullable<T> {
+    fn to_field_value(&self) -> FieldValue {
+        match self {
+            JsonNullable::Absent => FieldValue::Uninitialized,
+            JsonNullable::Null => FieldValue::Null,
+            JsonNullable::JsonNull => FieldValue::JsonNull,
+            JsonNullable::Value(v) => v.to_field_value(),
+        }
+    }
+    fn try_to_field_value(&self) -> Result<FieldValue, NoSQLError> {
+        match self {
+            JsonNullable::Value(v) => v.try_to_field_value(),
+            _ => Ok(self.to_field_value()),
+        }
+    }
 }
 
 impl<T: NoSQLColumnToFieldValue> NoSQLColumnToFieldValue for &T {
@@ -543,6 +719,9 @@ impl<T: NoSQLColumnToFieldValue> NoSQLColumnToFieldValue for &T {
         let v = *self;
         v.to_field_value()
     }
+    fn try_to_field_value(&self) -> Result<FieldValue, NoSQLError> {
+        (*self).try_to_field_value()
+    }
 }
 
 impl<T: NoSQLColumnToFieldValue> NoSQLColumnToFieldValue for Vec<T> {
@@ -550,6 +729,11 @@ impl<T: NoSQLColumnToFieldValue> NoSQLColumnToFieldValue for Vec<T> {
         let v: Vec<FieldValue> = self.iter().map(|i| i.to_field_value()).collect();
         FieldValue::Array(v)
     }
+    fn try_to_field_value(&self) -> Result<FieldValue, NoSQLError> {
+        let v: Result<Vec<FieldValue>, NoSQLError> =
+            self.iter().map(|i| i.try_to_field_value()).collect();
+        Ok(FieldValue::Array(v?))
+    }
 }
 
 impl<T: NoSQLColumnToFieldValue> NoSQLColumnToFieldValue for HashMap<String, T> {
@@ -561,6 +745,13 @@ impl<T: NoSQLColumnToFieldValue> NoSQLColumnToFieldValue for HashMap<String, T>
         }
         FieldValue::Map(m)
     }
+    fn try_to_field_value(&self) -> Result<FieldValue, NoSQLError> {
+        let mut m = MapValue::new();
+        for (k, v) in self {
+            m.put_field_value(k, v.try_to_field_value()?);
+        }
+        Ok(FieldValue::Map(m))
+    }
 }
 impl<T: NoSQLColumnToFieldValue> NoSQLColumnToFieldValue for BTreeMap<String, T> {
     fn to_field_value(&self) -> FieldValue {
@@ -571,6 +762,74 @@ impl<T: NoSQLColumnToFieldValue> NoSQLColumnToFieldValue for BTreeMap<String, T>
         }
         FieldValue::Map(m)
     }
+    fn try_to_field_value(&self) -> Result<FieldValue, NoSQLError> {
+        let mut m = MapValue::new();
+        for (k, v) in self {
+            m.put_field_value(k, v.try_to_field_value()?);
+        }
+        Ok(FieldValue::Map(m))
+    }
+}
+
+/// An optional value that keeps the difference between a missing field, a SQL `NULL` and a
+/// JSON `null`.
+///
+/// Reading an `Option<T>` treats all three as `None`. Use `JsonNullable<T>` instead, for
+/// example as a field of a [`NoSQLRow`](crate::NoSQLRow) struct, when these must be told apart,
+/// such as when a document stored in a JSON column has `{"x": null}` rather than no `x` at all:
+/// ```
+/// use oracle_nosql_rust_sdk::types::{JsonNullable, MapValue, NoSQLColumnFromMapValue};
+/// use serde_json::json;
+///
+/// let doc = MapValue::from_json_object(&json!({"x": null, "y": 5}))?;
+/// let x: JsonNullable<i32> = JsonNullable::Absent.from_map("x", &doc)?;
+/// let y: JsonNullable<i32> = JsonNullable::Absent.from_map("y", &doc)?;
+/// let z: JsonNullable<i32> = JsonNullable::Absent.from_map("z", &doc)?;
+/// assert_eq!(x, JsonNullable::JsonNull);
+/// assert_eq!(y, JsonNullable::Value(5));
+/// assert_eq!(z, JsonNullable::Absent);
+/// # Ok::<(), oracle_nosql_rust_sdk::NoSQLError>(())
+/// ```
+///
+/// When written, `Absent` fields are left out of the row or JSON document entirely, `Null` is
+/// written as SQL `NULL` and `JsonNull` as JSON `null`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum JsonNullable<T> {
+    /// The field does not exist.
+    #[default]
+    Absent,
+    /// The field is SQL `NULL`.
+    Null,
+    /// The field is a JSON `null`.
+    JsonNull,
+    /// The field has a value.
+    Value(T),
+}
+
+impl<T> JsonNullable<T> {
+    /// Returns true if the field does not exist.
+    pub fn is_absent(&self) -> bool {
+        matches!(self, JsonNullable::Absent)
+    }
+    /// Returns true if the field is either SQL `NULL` or JSON `null`.
+    pub fn is_null(&self) -> bool {
+        matches!(self, JsonNullable::Null | JsonNullable::JsonNull)
+    }
+    /// Get a reference to the value, if there is one.
+    pub fn value(&self) -> Option<&T> {
+        match self {
+            JsonNullable::Value(v) => Some(v),
+            _ => None,
+        }
+    }
+    /// Convert to an `Option`, the same way `Option<T>` fields are read: missing and null
+    /// fields are all `None`.
+    pub fn into_option(self) -> Option<T> {
+        match self {
+            JsonNullable::Value(v) => Some(v),
+            _ => None,
+        }
+    }
 }
 
 pub trait NoSQLColumnFromFieldValue {
@@ -593,18 +852,32 @@ macro_rules! ffv {
     }};
 }
 
+// Numeric values in JSON fields are returned in the smallest type that holds them,
+// so a value written as a Long may be read back as an Integer (and vice versa). Allow
+// lossless conversions between numeric types, so structs round-trip through JSON.
 impl NoSQLColumnFromFieldValue for i32 {
     fn from_field(fv: &FieldValue) -> Result<Self, NoSQLError> {
+        if let FieldValue::Long(l) = fv {
+            if let Ok(i) = i32::try_from(*l) {
+                return Ok(i);
+            }
+        }
         ffv! {fv, FieldValue::Integer, "Integer"}
     }
 }
 impl NoSQLColumnFromFieldValue for i64 {
     fn from_field(fv: &FieldValue) -> Result<Self, NoSQLError> {
+        if let FieldValue::Integer(i) = fv {
+            return Ok(*i as i64);
+        }
         ffv! {fv, FieldValue::Long, "Long"}
     }
 }
 impl NoSQLColumnFromFieldValue for f64 {
     fn from_field(fv: &FieldValue) -> Result<Self, NoSQLError> {
+        if let FieldValue::Integer(i) = fv {
+            return Ok(*i as f64);
+        }
         ffv! {fv, FieldValue::Double, "Double"}
     }
 }
@@ -649,6 +922,21 @@ impl NoSQLColumnFromFieldValue for NoSQLBinary {
         )
     }
 }
+#[cfg(feature = "uuid")]
+impl NoSQLColumnFromFieldValue for uuid::Uuid {
+    fn from_field(fv: &FieldValue) -> Result<Self, NoSQLError> {
+        if let FieldValue::String(s) = fv {
+            return match uuid::Uuid::parse_str(s) {
+                Ok(u) => Ok(u),
+                Err(e) => ia_err!("NoSQL: invalid UUID '{}': {}", s, e),
+            };
+        }
+        ia_err!(
+            "NoSQL: wrong type for field: expected UUID String, actual: {:?}",
+            fv
+        )
+    }
+}
 impl<T: NoSQLColumnFromFieldValue> NoSQLColumnFromFieldValue for Option<T> {
     fn from_field(fv: &FieldValue) -> Result<Self, NoSQLError> {
         match fv {
@@ -660,8 +948,22 @@ impl<T: NoSQLColumnFromFieldValue> NoSQLColumnFromFieldValue for Option<T> {
         Ok(Some(T::from_field(fv)?))
     }
 }
+impl<T: NoSQLColumnFromFieldValue> NoSQLColumnFromFieldValue for JsonNullable<T> {
+    fn from_field(fv: &FieldValue) -> Result<Self, NoSQLError> {
+        match fv {
+            FieldValue::Uninitialized => Ok(JsonNullable::Absent),
+            FieldValue::Null => Ok(JsonNullable::Null),
+            FieldValue::JsonNull => Ok(JsonNullable::JsonNull),
+            _ => Ok(JsonNullable::Value(T::from_field(fv)?)),
+        }
+    }
+}
 impl<T: NoSQLColumnFromFieldValue> NoSQLColumnFromFieldValue for Vec<T> {
     fn from_field(fv: &FieldValue) -> Result<Self, NoSQLError> {
+        // a missing or NULL array/map field is read as an empty collection
+        if fv.is_null() {
+            return Ok(Self::new());
+        }
         if let FieldValue::Array(v) = fv {
             let mut v1: Vec<T> = Vec::new();
             for i in v {
@@ -677,6 +979,10 @@ impl<T: NoSQLColumnFromFieldValue> NoSQLColumnFromFieldValue for Vec<T> {
 }
 impl<T: NoSQLColumnFromFieldValue> NoSQLColumnFromFieldValue for HashMap<String, T> {
     fn from_field(fv: &FieldValue) -> Result<Self, NoSQLError> {
+        // a missing or NULL array/map field is read as an empty collection
+        if fv.is_null() {
+            return Ok(Self::new());
+        }
         if let FieldValue::Map(v) = fv {
             // TODO: map....collect()?
             let mut m1: HashMap<String, T> = HashMap::new();
@@ -693,6 +999,10 @@ impl<T: NoSQLColumnFromFieldValue> NoSQLColumnFromFieldValue for HashMap<String,
 }
 impl<T: NoSQLColumnFromFieldValue> NoSQLColumnFromFieldValue for BTreeMap<String, T> {
     fn from_field(fv: &FieldValue) -> Result<Self, NoSQLError> {
+        // a missing or NULL array/map field is read as an empty collection
+        if fv.is_null() {
+            return Ok(Self::new());
+        }
         if let FieldValue::Map(v) = fv {
             // TODO: map....collect()?
             let mut m1: BTreeMap<String, T> = BTreeMap::new();
@@ -730,6 +1040,10 @@ impl<T: NoSQLColumnFromFieldValue> NoSQLColumnFromMapValue for T {
 /// This struct is basically a Map of `String` to [`FieldValue`]. It is the primary
 /// struct for specifying the data in a single NoSQL table row.
 ///
+/// Two `MapValue`s are equal if they have the same field names and equal values, regardless of
+/// the order the fields were added in. `MapValue` implements `Hash` consistently with this, so
+/// rows can be deduplicated with a `HashSet`. See [`FieldValue`] for how values are compared.
+///
 // Note: do not derive Clone. Use clone_internal() when needed.
 #[derive(Default, Debug)]
 pub struct MapValue {
@@ -741,6 +1055,15 @@ impl MapValue {
         Default::default()
     }
 
+    /// Create a deep copy of this map.
+    ///
+    /// `MapValue` does not implement `Clone`, so that rows are never copied by accident. This
+    /// copies every field, including all nested arrays and maps, so it can be expensive for
+    /// large rows. See [`FieldValue::deep_clone()`].
+    pub fn deep_clone(&self) -> MapValue {
+        self.clone_internal()
+    }
+
     pub(crate) fn clone_internal(&self) -> MapValue {
         let mut m = BTreeMap::new();
         for i in &self.m {
@@ -770,6 +1093,21 @@ impl MapValue {
         ia_err!("from_json_object: json value is not an Object: {:#?}", json)
     }
 
+    /// Create a `MapValue` from a JSON object, converting numbers with the given policy.
+    ///
+    /// With [`NumberPolicy::Lenient`], this is the same as [`from_json_object()`](MapValue::from_json_object()).
+    /// With [`NumberPolicy::StrictNumber`], an `IllegalArgument` error is returned for any
+    /// number, at any depth, that cannot be converted exactly. See [`FieldValue::from_json_value()`].
+    pub fn from_json_object_with(
+        json: &serde_json::Value,
+        policy: NumberPolicy,
+    ) -> Result<Self, NoSQLError> {
+        match FieldValue::from_json_value(json, policy)? {
+            FieldValue::Map(m) => Ok(m),
+            _ => ia_err!("from_json_object: json value is not an Object: {:#?}", json),
+        }
+    }
+
     pub fn len(&self) -> usize {
         self.m.len()
     }
@@ -778,6 +1116,21 @@ impl MapValue {
         self.m.iter()
     }
 
+    /// Returns true if the map contains a field with the given name.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.m.contains_key(key)
+    }
+
+    /// Get an iterator over the field names of the map, in sorted order.
+    pub fn keys(&self) -> Keys<String, FieldValue> {
+        self.m.keys()
+    }
+
+    /// Get an iterator over the field values of the map, in the sorted order of their names.
+    pub fn values(&self) -> Values<String, FieldValue> {
+        self.m.values()
+    }
+
     pub fn put(&mut self, key: &str, val: impl NoSQLColumnToFieldValue) {
         self.m.insert(key.to_string(), val.to_field_value());
     }
@@ -899,6 +1252,67 @@ impl MapValue {
         }
     }
 
+    /// Get an array field as a typed vector.
+    ///
+    /// This is useful for consuming the results of `array_collect()` aggregates in group-by
+    /// queries, without unwrapping each [`FieldValue`] manually:
+    /// ```no_run
+    /// # use oracle_nosql_rust_sdk::{Handle, QueryRequest};
+    /// # async fn run(handle: &Handle) -> Result<(), Box<dyn std::error::Error>> {
+    /// let qres = QueryRequest::new(
+    ///     "select country, array_collect(name) as names from users group by country")
+    ///     .execute(handle)
+    ///     .await?;
+    /// for row in qres.rows() {
+    ///     let names: Vec<String> = row.get_vec("names")?;
+    ///     println!("{:?}: {:?}", row.get_string("country"), names);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    /// A `Null` value is returned as an empty vector. An error is returned if the field does
+    /// not exist, is not an array, or if any element cannot be converted to `T`.
+    pub fn get_vec<T: NoSQLColumnFromFieldValue>(&self, key: &str) -> Result<Vec<T>, NoSQLError> {
+        match self.m.get(key) {
+            Some(FieldValue::Null) | Some(FieldValue::JsonNull) => Ok(Vec::new()),
+            Some(fv) => Vec::<T>::from_field(fv),
+            None => ia_err!("field '{}' does not exist in map", key),
+        }
+    }
+    /// Get an array field as a typed vector with duplicate values removed.
+    ///
+    /// This is the counterpart of [`get_vec()`](MapValue::get_vec()) for the results of
+    /// `array_collect_distinct()` aggregates. The first occurrence of each value is kept, in
+    /// the order they appear in the array.
+    pub fn get_distinct_vec<T: NoSQLColumnFromFieldValue>(
+        &self,
+        key: &str,
+    ) -> Result<Vec<T>, NoSQLError> {
+        let arr = match self.m.get(key) {
+            Some(FieldValue::Null) | Some(FieldValue::JsonNull) => {
+                return Ok(Vec::new());
+            }
+            Some(FieldValue::Array(a)) => a,
+            Some(fv) => {
+                return ia_err!(
+                    "NoSQL: wrong type for field: expected Array, actual: {:?}",
+                    fv
+                );
+            }
+            None => {
+                return ia_err!("field '{}' does not exist in map", key);
+            }
+        };
+        let mut seen: BTreeSet<&FieldValue> = BTreeSet::new();
+        let mut v: Vec<T> = Vec::new();
+        for fv in arr {
+            if seen.insert(fv) {
+                v.push(T::from_field(fv)?);
+            }
+        }
+        Ok(v)
+    }
+
     pub fn put_field_value(&mut self, key: &str, val: FieldValue) {
         self.m.insert(key.to_string(), val);
     }
@@ -958,18 +1372,394 @@ impl PartialEq for MapValue {
 
 impl Eq for MapValue {}
 
+// Fields are hashed in field name order, as they are compared.
+impl Hash for MapValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.m.len().hash(state);
+        for (k, v) in &self.m {
+            k.hash(state);
+            v.hash(state);
+        }
+    }
+}
+
 impl PartialOrd for MapValue {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
+/// Access a field of a `MapValue` by name.
+///
+/// This panics if the field does not exist, like indexing a [`HashMap`]. Use
+/// [`get_field_value()`](MapValue::get_field_value()) or the typed getters for access
+/// that returns `None` instead:
+/// ```
+/// # use oracle_nosql_rust_sdk::types::*;
+/// let row = MapValue::new()
+///     .column("name", "jane")
+///     .column("scores", vec![12, 34])
+///     .column("info", MapValue::new().column("city", "Anytown"));
+/// assert_eq!(row["name"], FieldValue::String("jane".to_string()));
+/// assert_eq!(row["scores"][1], FieldValue::Integer(34));
+/// assert_eq!(row["info"]["city"], FieldValue::String("Anytown".to_string()));
+/// ```
+impl std::ops::Index<&str> for MapValue {
+    type Output = FieldValue;
+    fn index(&self, key: &str) -> &FieldValue {
+        match self.m.get(key) {
+            Some(v) => v,
+            None => panic!("field '{}' does not exist in map", key),
+        }
+    }
+}
+
+/// Access an element of a `FieldValue::Array` by position.
+///
+/// This panics if the value is not an array, or if the index is out of bounds. Use
+/// [`get_array_value_ref()`](FieldValue::get_array_value_ref()) for checked access.
+impl std::ops::Index<usize> for FieldValue {
+    type Output = FieldValue;
+    fn index(&self, idx: usize) -> &FieldValue {
+        match self {
+            FieldValue::Array(a) => &a[idx],
+            _ => panic!(
+                "cannot index {:?} by position: not an array",
+                self.get_type()
+            ),
+        }
+    }
+}
+
+/// Access a field of a `FieldValue::Map` by name.
+///
+/// This panics if the value is not a map, or if the field does not exist. Use
+/// [`get_map_value_ref()`](FieldValue::get_map_value_ref()) for checked access.
+impl std::ops::Index<&str> for FieldValue {
+    type Output = FieldValue;
+    fn index(&self, key: &str) -> &FieldValue {
+        match self {
+            FieldValue::Map(m) => &m[key],
+            _ => panic!("cannot index {:?} by name: not a map", self.get_type()),
+        }
+    }
+}
+
+/// Build a single-column key (or any one-field map) from a `(column, value)` pair.
+///
+/// ```
+/// # use oracle_nosql_rust_sdk::types::*;
+/// let key = MapValue::from(("id", 5));
+/// assert_eq!(key, MapValue::new().i32("id", 5));
+/// ```
+impl<V: NoSQLColumnToFieldValue> From<(&str, V)> for MapValue {
+    fn from(pair: (&str, V)) -> MapValue {
+        MapValue::new().column(pair.0, pair.1)
+    }
+}
+
+// Tuples of (column, value) pairs, for compound primary keys.
+macro_rules! map_from_pairs {
+    ($($v:ident $i:tt),+) => {
+        /// Build a compound key (or any map) from a tuple of `(column, value)` pairs.
+        ///
+        /// Values may be of any type that can be used with [`MapValue::column()`]. Tuples of
+        /// up to 6 pairs are supported; use the [`MapValue`] builder methods for more columns,
+        /// or when the columns are only known at runtime.
+        ///
+        /// ```
+        /// # use oracle_nosql_rust_sdk::types::*;
+        /// let key = MapValue::from((("region", "us"), ("id", 5)));
+        /// assert_eq!(key, MapValue::new().str("region", "us").i32("id", 5));
+        /// ```
+        impl<$($v: NoSQLColumnToFieldValue),+> From<($((&str, $v),)+)> for MapValue {
+            fn from(pairs: ($((&str, $v),)+)) -> MapValue {
+                MapValue::new()$(.column(pairs.$i.0, pairs.$i.1))+
+            }
+        }
+    };
+}
+
+map_from_pairs!(A 0, B 1);
+map_from_pairs!(A 0, B 1, C 2);
+map_from_pairs!(A 0, B 1, C 2, D 3);
+map_from_pairs!(A 0, B 1, C 2, D 3, E 4);
+map_from_pairs!(A 0, B 1, C 2, D 3, E 4, F 5);
+
 impl std::fmt::Display for MapValue {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "{:#?}", self)
     }
 }
 
+/// How [`FieldValue::Timestamp`] values are encoded in JSON output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum JsonTimestampFormat {
+    /// An RFC3339 string, for example `"2024-01-02T03:04:05.678+00:00"`. This is the default.
+    #[default]
+    Rfc3339,
+    /// A JSON number of milliseconds since the epoch (Jan 1 1970).
+    EpochMillis,
+}
+
+/// How [`FieldValue::Number`] values are encoded in JSON output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum JsonNumberFormat {
+    /// A JSON number with full precision. This is the default.
+    #[default]
+    Number,
+    /// A JSON string. This is useful for consumers that would lose precision
+    /// when parsing large or very precise numbers (JavaScript, for example).
+    String,
+}
+
+/// How JSON numbers are converted by [`FieldValue::from_json_value()`] and
+/// [`MapValue::from_json_object_with()`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NumberPolicy {
+    /// Convert every number, rounding numbers with more digits than a double holds.
+    /// This is the default.
+    #[default]
+    Lenient,
+    /// Return an error for a number that cannot be converted exactly.
+    StrictNumber,
+}
+
+/// How [`FieldValue::Binary`] values are encoded in JSON output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum JsonBinaryFormat {
+    /// A standard base64 string. This is the default.
+    #[default]
+    Base64,
+    /// A lowercase hexadecimal string.
+    Hex,
+}
+
+/// The order used to compare strings when query results are sorted by the SDK.
+///
+/// Queries that sort across shards or partitions merge sorted batches from the server,
+/// and the merged order is only correct if the SDK compares strings the same way the
+/// server does. The server orders strings by their UTF-16 code units, which differs from
+/// the code point order of Rust's `String::cmp()` only for strings that contain characters
+/// outside the Basic Multilingual Plane (such as emoji) and characters from U+E000 to U+FFFF.
+/// Neither order depends on the locale.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum StringCollation {
+    /// Compare strings by UTF-16 code units, the same way as the server. This is the default.
+    #[default]
+    Server,
+    /// Compare strings by Unicode code points, the same way as Rust's `String::cmp()`.
+    CodePoint,
+}
+
+impl StringCollation {
+    /// Compare two strings using this collation.
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        match self {
+            StringCollation::CodePoint => a.cmp(b),
+            StringCollation::Server => {
+                // Both orders agree up to the first differing character; only that
+                // character needs to be compared by code units.
+                let mut ac = a.chars();
+                let mut bc = b.chars();
+                loop {
+                    match (ac.next(), bc.next()) {
+                        (Some(x), Some(y)) if x == y => continue,
+                        (Some(x), Some(y)) => {
+                            let (mut xb, mut yb) = ([0u16; 2], [0u16; 2]);
+                            return x.encode_utf16(&mut xb).cmp(&y.encode_utf16(&mut yb));
+                        }
+                        (x, y) => return x.is_some().cmp(&y.is_some()),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Options used when converting a [`MapValue`] or [`FieldValue`] to JSON.
+///
+/// ```
+/// use oracle_nosql_rust_sdk::types::*;
+/// let mv = MapValue::new().i32("id", 1).column("data", NoSQLBinary{ data: vec![1, 2, 3] });
+/// let opts = JsonOptions::new().binary_format(JsonBinaryFormat::Hex);
+/// assert_eq!(mv.to_json_string_with(&opts), r#"{"data":"010203","id":1}"#);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct JsonOptions {
+    pub(crate) timestamp_format: JsonTimestampFormat,
+    pub(crate) number_format: JsonNumberFormat,
+    pub(crate) binary_format: JsonBinaryFormat,
+    pub(crate) pretty: bool,
+}
+
+impl JsonOptions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+    /// Specify how timestamps are encoded. The default is [`JsonTimestampFormat::Rfc3339`].
+    pub fn timestamp_format(mut self, f: JsonTimestampFormat) -> Self {
+        self.timestamp_format = f;
+        self
+    }
+    /// Specify how `Number` values are encoded. The default is [`JsonNumberFormat::Number`].
+    pub fn number_format(mut self, f: JsonNumberFormat) -> Self {
+        self.number_format = f;
+        self
+    }
+    /// Specify how binary values are encoded. The default is [`JsonBinaryFormat::Base64`].
+    pub fn binary_format(mut self, f: JsonBinaryFormat) -> Self {
+        self.binary_format = f;
+        self
+    }
+    /// Specify whether JSON strings are output with indentation and newlines.
+    pub fn pretty(mut self, pretty: bool) -> Self {
+        self.pretty = pretty;
+        self
+    }
+}
+
+impl FieldValue {
+    /// Convert this value to a [`serde_json::Value`], using the given options.
+    ///
+    /// `Null`, `JsonNull` and `Empty` values are converted to JSON `null`. Double values
+    /// that are not finite (NaN, infinity) have no JSON representation and are
+    /// converted to strings.
+    pub fn to_json_value_with(&self, opts: &JsonOptions) -> serde_json::Value {
+        match self {
+            FieldValue::Array(a) => {
+                let mut v: Vec<serde_json::Value> = Vec::with_capacity(a.len());
+                for i in a {
+                    v.push(i.to_json_value_with(opts));
+                }
+                serde_json::Value::Array(v)
+            }
+            FieldValue::Map(m) => m.to_json_value_with(opts),
+            FieldValue::Integer(i) => serde_json::Value::from(*i),
+            FieldValue::Long(l) => serde_json::Value::from(*l),
+            FieldValue::Double(d) => match serde_json::Number::from_f64(*d) {
+                Some(n) => serde_json::Value::Number(n),
+                None => serde_json::Value::String(d.to_string()),
+            },
+            FieldValue::Number(n) => {
+                let s = n.to_string();
+                if opts.number_format == JsonNumberFormat::String {
+                    return serde_json::Value::String(s);
+                }
+                match s.parse::<serde_json::Number>() {
+                    Ok(num) => serde_json::Value::Number(num),
+                    Err(_) => serde_json::Value::String(s),
+                }
+            }
+            FieldValue::String(s) => serde_json::Value::String(s.clone()),
+            FieldValue::Boolean(b) => serde_json::Value::Bool(*b),
+            FieldValue::Binary(b) => match opts.binary_format {
+                JsonBinaryFormat::Base64 => serde_json::Value::String(BASE64_STANDARD.encode(b)),
+                JsonBinaryFormat::Hex => {
+                    let mut s = String::with_capacity(b.len() * 2);
+                    for byte in b {
+                        s.push_str(&format!("{:02x}", byte));
+                    }
+                    serde_json::Value::String(s)
+                }
+            },
+            FieldValue::Timestamp(t) => match opts.timestamp_format {
+                JsonTimestampFormat::Rfc3339 => serde_json::Value::String(t.to_rfc3339()),
+                JsonTimestampFormat::EpochMillis => serde_json::Value::from(t.timestamp_millis()),
+            },
+            FieldValue::JsonNull
+            | FieldValue::Null
+            | FieldValue::Empty
+            | FieldValue::Uninitialized => serde_json::Value::Null,
+        }
+    }
+
+    /// Convert this value to a [`serde_json::Value`], using default [`JsonOptions`].
+    pub fn to_json_value(&self) -> serde_json::Value {
+        self.to_json_value_with(&JsonOptions::default())
+    }
+
+    /// Convert a [`serde_json::Value`] to a `FieldValue`, converting numbers with the given policy.
+    ///
+    /// Integers are converted to `Integer` or `Long` values, or to a `Number` if they are
+    /// larger than an `i64`. Other numbers are converted to `Double` values, or to a `Number`
+    /// if they are beyond the range of a double. JSON `null` is converted to `JsonNull`.
+    ///
+    /// With [`NumberPolicy::Lenient`], the default used by
+    /// [`NoSQLColumnToFieldValue`] for `serde_json::Value`, this never fails, but a number
+    /// with more digits than a double holds is rounded. With [`NumberPolicy::StrictNumber`],
+    /// an `IllegalArgument` error is returned instead for any number that cannot be converted
+    /// exactly, so that malformed input is not stored silently.
+    ///
+    /// ```
+    /// use oracle_nosql_rust_sdk::types::*;
+    /// let json: serde_json::Value = serde_json::from_str("[1, 0.5, 0.10000000000000000001]")?;
+    /// assert!(FieldValue::from_json_value(&json, NumberPolicy::Lenient).is_ok());
+    /// assert!(FieldValue::from_json_value(&json, NumberPolicy::StrictNumber).is_err());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_json_value(
+        json: &serde_json::Value,
+        policy: NumberPolicy,
+    ) -> Result<FieldValue, NoSQLError> {
+        match json {
+            serde_json::Value::Number(n) => json_number_to_field_value(n, policy),
+            serde_json::Value::Array(a) => {
+                let mut arr: Vec<FieldValue> = Vec::with_capacity(a.len());
+                for v in a {
+                    arr.push(FieldValue::from_json_value(v, policy)?);
+                }
+                Ok(FieldValue::Array(arr))
+            }
+            serde_json::Value::Object(o) => {
+                let mut mv = MapValue::new();
+                for (k, v) in o {
+                    mv.put_field_value(k, FieldValue::from_json_value(v, policy)?);
+                }
+                Ok(FieldValue::Map(mv))
+            }
+            _ => Ok(json.to_field_value()),
+        }
+    }
+}
+
+impl MapValue {
+    /// Convert this map to a [`serde_json::Value`] object, using the given options.
+    pub fn to_json_value_with(&self, opts: &JsonOptions) -> serde_json::Value {
+        let mut m = serde_json::Map::new();
+        for (k, v) in &self.m {
+            m.insert(k.clone(), v.to_json_value_with(opts));
+        }
+        serde_json::Value::Object(m)
+    }
+
+    /// Convert this map to a [`serde_json::Value`] object, using default [`JsonOptions`].
+    pub fn to_json_value(&self) -> serde_json::Value {
+        self.to_json_value_with(&JsonOptions::default())
+    }
+
+    /// Convert this map to a JSON string, using the given options.
+    ///
+    /// If [`JsonOptions::pretty()`] is set, the output is indented, otherwise it is compact.
+    pub fn to_json_string_with(&self, opts: &JsonOptions) -> String {
+        let v = self.to_json_value_with(opts);
+        if opts.pretty {
+            return serde_json::to_string_pretty(&v).unwrap_or_default();
+        }
+        v.to_string()
+    }
+
+    /// Convert this map to a compact JSON string, using default [`JsonOptions`].
+    pub fn to_json_string(&self) -> String {
+        self.to_json_string_with(&JsonOptions::default())
+    }
+
+    /// Convert this map to an indented JSON string, using default [`JsonOptions`].
+    pub fn to_json_string_pretty(&self) -> String {
+        self.to_json_string_with(&JsonOptions::default().pretty(true))
+    }
+}
+
 /// Trait that defines methods to convert to and from a NoSQL [`MapValue`].
 ///
 /// It is typically not necessary to implement this trait for structs. Instead,
@@ -982,6 +1772,15 @@ pub trait NoSQLRow {
     fn from_map_value(&mut self, value: &MapValue) -> Result<(), NoSQLError>;
 }
 
+// Convert a NoSQLRow to a MapValue for a request, returning an IllegalArgument
+// error if the conversion fails.
+pub(crate) fn row_to_map_value(row: &dyn NoSQLRow) -> Result<MapValue, NoSQLError> {
+    match row.to_map_value() {
+        Ok(value) => Ok(value),
+        Err(e) => ia_err!("could not convert struct to MapValue: {}", e),
+    }
+}
+
 /// Consistency is used to provide consistency guarantees for read operations.
 ///
 /// There are two consistency values available: Eventual and Absolute.
@@ -1006,92 +1805,181 @@ pub enum Consistency {
     Eventual = 2,
 }
 
+/// SyncPolicy defines the synchronization policy used when a write operation is
+/// committed, as part of a [`Durability`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum SyncPolicy {
+    /// Write and synchronously flush the log on commit.
+    Sync = 1,
+    /// Do not write or synchronously flush the log on commit.
+    NoSync = 2,
+    /// Write but do not synchronously flush the log on commit.
+    WriteNoSync = 3,
+}
+
+/// ReplicaAckPolicy defines the policy for how replicated commits are acknowledged,
+/// as part of a [`Durability`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum ReplicaAckPolicy {
+    /// All replicas must acknowledge that they have committed the transaction.
+    All = 1,
+    /// No transaction commit acknowledgments are required.
+    None = 2,
+    /// A simple majority of replicas must acknowledge that they have committed the transaction.
+    SimpleMajority = 3,
+}
+
+/// Durability defines the durability characteristics associated with a write operation.
+///
+/// On-premises only: this is ignored by the NoSQL Cloud Service. It consists of the
+/// [`SyncPolicy`] used on the master node, the [`SyncPolicy`] used on replica nodes,
+/// and the [`ReplicaAckPolicy`] used to acknowledge commits. If not set for a request,
+/// the server's default durability is used.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Durability {
+    pub(crate) master_sync: SyncPolicy,
+    pub(crate) replica_sync: SyncPolicy,
+    pub(crate) replica_ack: ReplicaAckPolicy,
+}
+
+impl Durability {
+    /// Create a new `Durability` from its master sync, replica sync and replica ack policies.
+    pub fn new(
+        master_sync: SyncPolicy,
+        replica_sync: SyncPolicy,
+        replica_ack: ReplicaAckPolicy,
+    ) -> Durability {
+        Durability {
+            master_sync,
+            replica_sync,
+            replica_ack,
+        }
+    }
+
+    /// Get the synchronization policy used on the master node.
+    pub fn master_sync(&self) -> SyncPolicy {
+        self.master_sync
+    }
+
+    /// Get the synchronization policy used on replica nodes.
+    pub fn replica_sync(&self) -> SyncPolicy {
+        self.replica_sync
+    }
+
+    /// Get the replica acknowledgement policy.
+    pub fn replica_ack(&self) -> ReplicaAckPolicy {
+        self.replica_ack
+    }
+
+    // The protocol encoding: master sync in bits 0-1, replica sync in bits 2-3
+    // and replica ack in bits 4-5.
+    pub(crate) fn encode(&self) -> i32 {
+        (self.master_sync as i32)
+            | ((self.replica_sync as i32) << 2)
+            | ((self.replica_ack as i32) << 4)
+    }
+}
+
+/// The operation of a request sent to the NoSQL service.
+///
+/// This is given to a [`RequestObserver`](crate::RequestObserver) for each request.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, TryFromPrimitive)]
 #[repr(u8)]
 #[allow(dead_code)]
-pub(crate) enum OpCode {
-    // Delete is used for the operation that deletes a row from table.
+pub enum OpCode {
+    /// Delete is used for the operation that deletes a row from table.
     Delete = 0,
 
-    // DeleteIfVersion is used for the operation that deletes a row from table
-    // if the row matches the specified version.
+    /// DeleteIfVersion is used for the operation that deletes a row from table
+    /// if the row matches the specified version.
     DeleteIfVersion, // 1
 
-    // Get is used for the operation that retrieves a row from table.
+    /// Get is used for the operation that retrieves a row from table.
     Get, // 2
 
-    // Put is used for the operation that unconditionally puts a row to table.
+    /// Put is used for the operation that unconditionally puts a row to table.
     Put, // 3
 
-    // PutIfAbsent is used for the operation that puts a row to table if the row
-    // is absent.
+    /// PutIfAbsent is used for the operation that puts a row to table if the row
+    /// is absent.
     PutIfAbsent, // 4
 
-    // PutIfPresent is used for the operation that puts a row to table if the row
-    // is present.
+    /// PutIfPresent is used for the operation that puts a row to table if the row
+    /// is present.
     PutIfPresent, // 5
 
-    // PutIfVersion is used for the operation that puts a row to table if the row
-    // matches the specified version.
+    /// PutIfVersion is used for the operation that puts a row to table if the row
+    /// matches the specified version.
     PutIfVersion, // 6
 
-    // Query is used for the query operation.
-    // A query operation can perform select, insert, update and delete operations
-    // over an SQL statement.
+    /// Query is used for the query operation.
+    /// A query operation can perform select, insert, update and delete operations
+    /// over an SQL statement.
     Query, // 7
 
-    // Prepare is used for the operation that compiles/prepares an SQL statement
-    // before execution.
+    /// Prepare is used for the operation that compiles/prepares an SQL statement
+    /// before execution.
     Prepare, // 8
 
-    // WriteMultiple is used to perform multiple write operations associated
-    // with a table in a single transaction.
+    /// WriteMultiple is used to perform multiple write operations associated
+    /// with a table in a single transaction.
     WriteMultiple, // 9
 
-    // MultiDelete is used for the operation that deletes multiple rows from a
-    // table in a single transaction.
+    /// MultiDelete is used for the operation that deletes multiple rows from a
+    /// table in a single transaction.
     MultiDelete, // 10
 
-    // GetTable is used for the operation that retrieves static information about a table.
+    /// GetTable is used for the operation that retrieves static information about a table.
     GetTable, // 11
 
-    // GetIndexes is used for the operation that retrieves information about an index.
+    /// GetIndexes is used for the operation that retrieves information about an index.
     GetIndexes, // 12
 
-    // GetTableUsage is used for the operation that retrieves usage information on a table.
+    /// GetTableUsage is used for the operation that retrieves usage information on a table.
     GetTableUsage, // 13
 
-    // ListTables is used for the operation that lists all available table names.
+    /// ListTables is used for the operation that lists all available table names.
     ListTables, // 14
 
-    // TableRequest is used for the operation that manages table schema or
-    // changes table limits.
+    /// TableRequest is used for the operation that manages table schema or
+    /// changes table limits.
     TableRequest, // 15
 
-    // Scan is reserved for internal use.
+    /// Scan is reserved for internal use.
     Scan, // 16
 
-    // IndexScan is reserved for internal use.
+    /// IndexScan is reserved for internal use.
     IndexScan, // 17
 
-    // CreateTable represents the operation that creates a table.
+    /// CreateTable represents the operation that creates a table.
     CreateTable, // 18
 
-    // AlterTable represents the operation that modifies the table schema.
+    /// AlterTable represents the operation that modifies the table schema.
     AlterTable, // 19
 
-    // DropTable represents the operation that drops a table.
+    /// DropTable represents the operation that drops a table.
     DropTable, // 20
 
-    // CreateIndex represents the operation that creates an index on a table.
+    /// CreateIndex represents the operation that creates an index on a table.
     CreateIndex, // 21
 
-    // SystemRequest is used to perform system operations such as
-    // administrative operations that do not affect a specific table.
+    /// DropIndex represents the operation that drops an index from a table.
+    DropIndex, // 22
+
+    /// SystemRequest is used to perform system operations such as
+    /// administrative operations that do not affect a specific table.
     SystemRequest, // 23
 
-    // SystemStatusRequest is used to retrieve the operation status of a SystemRequest.
+    /// SystemStatusRequest is used to retrieve the operation status of a SystemRequest.
     SystemStatusRequest, // 24
+
+    /// AddReplica is used to add a replica of a Global Active Table in another region.
+    AddReplica = 33,
+
+    /// DropReplica is used to remove a replica of a Global Active Table.
+    DropReplica, // 34
 }
 
 // Capacity represents the read/write throughput consumed by an operation.
@@ -1117,6 +2005,14 @@ impl Capacity {
         self.read_units += c.read_units;
         self.write_kb += c.write_kb;
     }
+
+    /// Get the number of write units consumed.
+    ///
+    /// A write unit represents 1 write per second of data up to 1 KB in size, and the
+    /// server reports writes in whole kilobytes, so this is the same as `write_kb`.
+    pub fn write_units(&self) -> i32 {
+        self.write_kb
+    }
 }
 
 #[derive(Clone, Copy, Default, Debug, Eq, PartialEq)]
@@ -1156,14 +2052,14 @@ impl TableState {
     }
 }
 
+/// The state of an on-premises system operation, from [`SystemResult::state()`](crate::SystemResult::state()).
 #[derive(Clone, Copy, Default, Debug, Eq, PartialEq)]
 #[repr(i32)]
 pub enum OperationState {
-    // The table is ready to be used. This is the steady state after
-    // creation or modification.
+    /// The operation has completed.
     #[default]
     Complete = 0,
-    // The table is being created and cannot yet be used
+    /// The operation is still in progress.
     Working = 1,
 }
 
@@ -1294,10 +2190,23 @@ pub(crate) fn sort_results(
 // used among values that are not normally comparable with each other:
 //
 // numerics < timestamps < strings < booleans < binaries < empty < json null < null
+//
+// Strings are compared by code point. A StringCollation is only applied to
+// sorted query results, through their SortSpecs.
 pub(crate) fn compare_atomics_total_order(
     v0: &FieldValue,
     v1: &FieldValue,
     nulls_equal: bool,
+) -> Ordering {
+    compare_atomics_collation(v0, v1, nulls_equal, StringCollation::CodePoint)
+}
+
+// Same as compare_atomics_total_order(), comparing strings with the given collation.
+fn compare_atomics_collation(
+    v0: &FieldValue,
+    v1: &FieldValue,
+    nulls_equal: bool,
+    collation: StringCollation,
 ) -> Ordering {
     let tc0 = v0.get_type();
     let tc1 = v1.get_type();
@@ -1485,7 +2394,7 @@ pub(crate) fn compare_atomics_total_order(
             match tc1 {
                 FieldType::String => {
                     let sv1 = String::from_field(v1).unwrap();
-                    return sv1.cmp(&sv0);
+                    return collation.compare(&sv1, &sv0);
                 }
                 FieldType::Integer => {
                     return Ordering::Greater;
@@ -1593,7 +2502,7 @@ fn compare_atomics_total_order_sortspec(
     ss: &SortSpec,
     nulls_equal: bool,
 ) -> Ordering {
-    let mut comp = compare_atomics_total_order(v1, v2, nulls_equal);
+    let mut comp = compare_atomics_collation(v1, v2, nulls_equal, ss.collation);
 
     if ss.is_desc {
         comp = comp.reverse();
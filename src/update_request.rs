@@ -0,0 +1,279 @@
+//
+// Copyright (c) 2024, 2025 Oracle and/or its affiliates. All rights reserved.
+//
+// Licensed under the Universal Permissive License v 1.0 as shown at
+//  https://oss.oracle.com/licenses/upl/
+//
+use crate::condition::{execute_statement, key_clauses, rows_affected, validate_path, Condition};
+use crate::error::ia_err;
+use crate::error::NoSQLError;
+use crate::handle::Handle;
+use crate::request_options::RequestOptions;
+use crate::types::{Capacity, Durability, FieldValue, MapValue, NoSQLColumnToFieldValue};
+use std::result::Result;
+use std::time::Duration;
+
+/// Struct used for updating some of the fields of a single row, leaving all other fields unchanged.
+///
+/// A [`PutRequest`](crate::PutRequest) always replaces the entire row: any column not given in
+/// its value is set to its default (or NULL). `UpdateRequest` instead modifies only the
+/// given fields of an existing row, without the need to read the row first:
+/// ```no_run
+/// # use oracle_nosql_rust_sdk::{Handle, UpdateRequest};
+/// # use oracle_nosql_rust_sdk::types::MapValue;
+/// # #[tokio::main]
+/// # pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let handle = Handle::builder().build().await?;
+/// let result = UpdateRequest::new("users", MapValue::new().i32("id", 10))
+///     .set("email", "jane@example.com")
+///     .set("address.city", "Springfield")
+///     .remove("preferences.theme")
+///     .execute(&handle)
+///     .await?;
+/// if result.num_updated() == 0 {
+///     println!("user 10 does not exist");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Internally, this generates and executes a SQL `UPDATE` statement for the given key, with all
+/// values passed as bind variables. Field names may be top-level column names or dot-separated
+/// paths into JSON, Record or Map columns. The row is not created if it does not exist.
+#[derive(Default, Debug)]
+pub struct UpdateRequest {
+    pub(crate) table_name: String,
+    pub(crate) key: MapValue,
+    pub(crate) set: Vec<(String, FieldValue)>,
+    pub(crate) remove: Vec<String>,
+    pub(crate) return_row: bool,
+    pub(crate) condition: Option<Condition>,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) compartment_id: String,
+    pub(crate) durability: Option<Durability>,
+    // the first error converting a value given to set(), returned by statement()
+    pub(crate) err: Option<NoSQLError>,
+}
+
+/// Struct representing the result of an [`UpdateRequest`] execution.
+#[derive(Default, Debug)]
+pub struct UpdateResult {
+    pub(crate) num_updated: i32,
+    pub(crate) row: Option<MapValue>,
+    pub(crate) consumed: Capacity,
+}
+
+impl UpdateResult {
+    /// Get the number of rows updated: 1 if the row existed (and matched the
+    /// [condition](UpdateRequest::if_condition()), if any) and was updated, 0 otherwise.
+    pub fn num_updated(&self) -> i32 {
+        self.num_updated
+    }
+    /// Get the updated row.
+    ///
+    /// This is only valid if [`return_row(true)`](UpdateRequest::return_row()) was called on
+    /// the [`UpdateRequest`] and the row exists.
+    pub fn row(&self) -> Option<&MapValue> {
+        if let Some(r) = &self.row {
+            return Some(r);
+        }
+        None
+    }
+    /// Get the total capacity consumed by the operation, including preparing the update statement.
+    /// This is only valid in the NoSQL Cloud Service.
+    pub fn consumed(&self) -> Capacity {
+        self.consumed
+    }
+}
+
+impl UpdateRequest {
+    /// Create a new `UpdateRequest`.
+    ///
+    /// `table_name` and `key` are required and must be non-empty.
+    ///
+    /// `key` must contain all fields required to construct the primary key for the table.
+    pub fn new(table_name: &str, key: MapValue) -> UpdateRequest {
+        UpdateRequest {
+            table_name: table_name.to_string(),
+            key,
+            ..Default::default()
+        }
+    }
+
+    /// Set a field to a new value.
+    ///
+    /// `field` is either a top-level column name, or a dot-separated path to a field within
+    /// a JSON, Record or Map column (for example, `"address.city"`).
+    ///
+    /// If `value` cannot be converted to a [`FieldValue`], such as a struct with a field that
+    /// fails to convert, the error is returned by [`execute()`](UpdateRequest::execute()) and
+    /// [`statement()`](UpdateRequest::statement()).
+    pub fn set(mut self, field: &str, value: impl NoSQLColumnToFieldValue) -> Self {
+        match value.try_to_field_value() {
+            Ok(v) => self.set.push((field.to_string(), v)),
+            Err(e) => {
+                self.err.get_or_insert(e);
+            }
+        }
+        self
+    }
+
+    /// Remove a field from a JSON or Map column.
+    ///
+    /// `field` must be a dot-separated path to a field within a JSON or Map column. Top-level
+    /// columns cannot be removed; set them to NULL instead.
+    pub fn remove(mut self, field: &str) -> Self {
+        self.remove.push(field.to_string());
+        self
+    }
+
+    /// Update the row only if its current field values match the given [`Condition`].
+    ///
+    /// If the row does not match, it is left unchanged and [`UpdateResult::num_updated()`]
+    /// returns 0.
+    pub fn if_condition(mut self, condition: Condition) -> Self {
+        self.condition = Some(condition);
+        self
+    }
+
+    /// Return the updated row in the result. See [`UpdateResult::row()`].
+    pub fn return_row(mut self, val: bool) -> Self {
+        self.return_row = val;
+        self
+    }
+
+    /// Specify the timeout value for the request.
+    ///
+    /// This is optional.
+    /// If set, it must be greater than or equal to 1 millisecond, otherwise an
+    /// IllegalArgument error will be returned.
+    /// If not set, the default timeout value configured for the [`Handle`](crate::HandleBuilder::timeout()) is used.
+    pub fn timeout(mut self, t: &Duration) -> Self {
+        self.timeout = Some(*t);
+        self
+    }
+
+    /// Cloud Service only: set the name or id of a compartment to be used for this operation.
+    ///
+    /// See [`PutRequest::compartment_id()`](crate::PutRequest::compartment_id()) for details.
+    pub fn compartment_id(mut self, compartment_id: &str) -> Self {
+        self.compartment_id = compartment_id.to_string();
+        self
+    }
+
+    /// On-premises only: specify the [`Durability`] to use for the operation.
+    ///
+    /// If not set, the server's default durability is used.
+    pub fn durability(mut self, d: Durability) -> Self {
+        self.durability = Some(d);
+        self
+    }
+
+    /// Apply [`RequestOptions`] to this request, using their timeout, compartment and durability.
+    pub fn options(mut self, options: &RequestOptions) -> Self {
+        options.apply_timeout(&mut self.timeout);
+        options.apply_compartment_id(&mut self.compartment_id);
+        options.apply_durability(&mut self.durability);
+        self
+    }
+
+    /// Get the SQL statement that will be executed for this request.
+    ///
+    /// All values are given as positional bind variables (`?`): first the values in the
+    /// order of the calls to [`set()`](UpdateRequest::set()), then the key fields, then the
+    /// values of the [condition](UpdateRequest::if_condition()), if any.
+    ///
+    /// The table prefix of the handle, if any (see
+    /// [`HandleBuilder::table_prefix()`](crate::HandleBuilder::table_prefix())), is not
+    /// included here, but is added to the table name when the request is executed.
+    pub fn statement(&self) -> Result<String, NoSQLError> {
+        self.statement_for_table(&self.table_name)
+    }
+
+    fn statement_for_table(&self, table_name: &str) -> Result<String, NoSQLError> {
+        if let Some(e) = &self.err {
+            return Err(e.clone());
+        }
+        validate_path("table name", table_name, ":.")?;
+        if self.key.len() == 0 {
+            return ia_err!("UpdateRequest: key must not be empty");
+        }
+        if self.set.is_empty() && self.remove.is_empty() {
+            return ia_err!("UpdateRequest: no fields to set or remove");
+        }
+        let mut clauses: Vec<String> = Vec::new();
+        for (i, (field, _)) in self.set.iter().enumerate() {
+            validate_path("field", field, ".")?;
+            if i == 0 {
+                clauses.push(format!("SET t.{} = ?", field));
+            } else {
+                clauses.push(format!("t.{} = ?", field));
+            }
+        }
+        for (i, field) in self.remove.iter().enumerate() {
+            validate_path("field", field, ".")?;
+            if !field.contains('.') {
+                return ia_err!(
+                    "UpdateRequest: cannot remove top-level column '{}', set it to NULL instead",
+                    field
+                );
+            }
+            if i == 0 {
+                clauses.push(format!("REMOVE t.{}", field));
+            } else {
+                clauses.push(format!("t.{}", field));
+            }
+        }
+        let mut keys = key_clauses(&self.key)?;
+        if let Some(c) = &self.condition {
+            c.add_clauses(&mut keys)?;
+        }
+        let mut stmt = format!(
+            "UPDATE {} t {} WHERE {}",
+            table_name,
+            clauses.join(", "),
+            keys.join(" AND ")
+        );
+        if self.return_row {
+            stmt.push_str(" RETURNING *");
+        }
+        Ok(stmt)
+    }
+
+    /// Execute the request.
+    ///
+    /// This prepares the generated `UPDATE` statement and then executes it, which takes two
+    /// round trips to the server.
+    pub async fn execute(&self, h: &Handle) -> Result<UpdateResult, NoSQLError> {
+        let stmt = self.statement_for_table(&h.table_name(&self.table_name))?;
+        let mut values: Vec<&FieldValue> = self.set.iter().map(|(_, v)| v).collect();
+        values.extend(self.key.iter().map(|(_, v)| v));
+        if let Some(c) = &self.condition {
+            values.extend(c.values());
+        }
+        let (rows, consumed) = execute_statement(
+            h,
+            &stmt,
+            &values,
+            &self.timeout,
+            &self.compartment_id,
+            self.durability,
+        )
+        .await?;
+
+        let mut res = UpdateResult {
+            consumed,
+            ..Default::default()
+        };
+        let mut rows = rows.into_iter();
+        if self.return_row {
+            res.row = rows.next();
+            if res.row.is_some() {
+                res.num_updated = 1;
+            }
+        } else if let Some(r) = rows.next() {
+            res.num_updated = rows_affected(&r, "NumRowsUpdated") as i32;
+        }
+        Ok(res)
+    }
+}
@@ -13,7 +13,10 @@ use crate::nson::NsonSubRequest;
 use crate::nson::*;
 use crate::put_request::PutRequest;
 use crate::reader::Reader;
-use crate::types::{Capacity, FieldType, FieldValue, MapValue, NoSQLRow, OpCode};
+use crate::request_options::RequestOptions;
+use crate::types::{
+    row_to_map_value, Capacity, Durability, FieldType, FieldValue, MapValue, NoSQLRow, OpCode,
+};
 use crate::writer::Writer;
 use crate::Version;
 use std::result::Result;
@@ -31,8 +34,7 @@ pub struct WriteMultipleRequest {
     // TODO: pub(crate) namespace: String,
     pub(crate) timeout: Option<Duration>,
     pub(crate) sub_requests: Vec<Box<dyn NsonSubRequest>>,
-    // TODO durability: Option<Vec<u8>>
-
+    pub(crate) durability: Option<Durability>,
     // TODO: limiters, retry stats, etc
 }
 
@@ -116,9 +118,31 @@ pub struct WriteMultipleResult {
 impl WriteMultipleResult {
     /// Get a vector of sub-operation results. This vector is ordered in the same order as
     /// put/delete items were added to the `WriteMultipleRequest`.
+    ///
+    /// If the operation was aborted because of a failed sub-operation (see
+    /// [`failed_operation_index()`](WriteMultipleResult::failed_operation_index())), this
+    /// only contains the result of the failed sub-operation.
     pub fn results(&self) -> &Vec<SubOperationResult> {
         &self.results
     }
+    /// Get the new [`Version`] of each row written, in the same order as put/delete items
+    /// were added to the `WriteMultipleRequest`.
+    ///
+    /// There is one entry for each sub-operation. It is `Some` for each put that succeeded,
+    /// and `None` for deletes and for puts that did not write a row, such as a
+    /// [`PutRequest::if_absent()`] put of an existing row. The versions can be used with
+    /// [`PutRequest::if_version()`] for later conditional writes of the same rows.
+    ///
+    /// If the operation was aborted, no rows were written and the vector is empty.
+    pub fn versions(&self) -> Vec<Option<&Version>> {
+        if self.failed_operation_index >= 0 {
+            return Vec::new();
+        }
+        self.results
+            .iter()
+            .map(|r| if r.success { r.version.as_ref() } else { None })
+            .collect()
+    }
     /// Get the offset of the first failed operation.
     /// If there are no failures, -1 is returned.
     pub fn failed_operation_index(&self) -> i32 {
@@ -165,6 +189,24 @@ impl WriteMultipleRequest {
         self
     }
 
+    /// On-premises only: specify the [`Durability`] to use for the operation.
+    ///
+    /// This applies to all operations in the request.
+    ///
+    /// If not set, the server's default durability is used.
+    pub fn durability(mut self, d: Durability) -> Self {
+        self.durability = Some(d);
+        self
+    }
+
+    /// Apply [`RequestOptions`] to this request, using their timeout, compartment and durability.
+    pub fn options(mut self, options: &RequestOptions) -> Self {
+        options.apply_timeout(&mut self.timeout);
+        options.apply_compartment_id(&mut self.compartment_id);
+        options.apply_durability(&mut self.durability);
+        self
+    }
+
     pub fn add(mut self, r: Box<dyn NsonSubRequest>) -> WriteMultipleRequest {
         self.sub_requests.push(r);
         self
@@ -199,27 +241,31 @@ impl WriteMultipleRequest {
     {
         for item in collection {
             // note: this implies collection.into_iter()
-            match item.to_map_value() {
-                Ok(value) => {
-                    self.sub_requests
-                        .push(Box::new(DeleteRequest::new("", value)));
-                }
-                Err(e) => {
-                    // TODO: save error as source
-                    return Err(NoSQLError::new(
-                        IllegalArgument,
-                        &format!("could not convert struct to MapValue: {}", e.to_string()),
-                    ));
-                }
-            }
+            let value = row_to_map_value(&item)?;
+            self.sub_requests
+                .push(Box::new(DeleteRequest::new("", value)));
         }
         Ok(self)
     }
 
+    /// Add a put of a native Rust struct to the request.
+    ///
+    /// `row` must implement the [`NoSQLRow`] trait, usually with the
+    /// [`derive@NoSQLRow`](crate::NoSQLRow) derive macro. Unlike [`put()`](WriteMultipleRequest::put()),
+    /// the struct is borrowed. If the struct cannot be converted to a row, an `IllegalArgument`
+    /// error is returned and nothing is added.
+    pub fn add_put_row<T: NoSQLRow>(mut self, row: &T) -> Result<WriteMultipleRequest, NoSQLError> {
+        self.sub_requests
+            .push(Box::new(PutRequest::new("").value_from_row(row)?));
+        Ok(self)
+    }
+
     pub async fn execute(&self, h: &Handle) -> Result<WriteMultipleResult, NoSQLError> {
         // TODO: validate: size > 0, etc
-        let mut w: Writer = Writer::new();
-        w.write_i16(h.inner.serial_version);
+        for r in &self.sub_requests {
+            r.validate()?;
+        }
+        let mut w = h.request_writer();
         let timeout = h.get_timeout(&self.timeout);
         self.serialize_internal(&mut w, &timeout);
         let mut opts = SendOptions {
@@ -254,7 +300,7 @@ impl WriteMultipleRequest {
 
         // TODO: compartment
         ns.start_payload();
-        ns.write_i32_field(DURABILITY, 0); // TODO
+        ns.write_i32_field(DURABILITY, self.durability.map_or(0, |d| d.encode()));
         ns.write_i32_field(NUM_OPERATIONS, self.sub_requests.len() as i32);
 
         // OPERATIONS: array of maps
@@ -4,7 +4,7 @@
 // Licensed under the Universal Permissive License v 1.0 as shown at
 //  https://oss.oracle.com/licenses/upl/
 //
-use chrono::{DateTime, FixedOffset};
+use chrono::{DateTime, FixedOffset, SecondsFormat, Timelike, Utc};
 use std::result::Result;
 use std::str;
 
@@ -22,12 +22,16 @@ use crate::types::MapValue;
 pub struct Writer {
     // The underlying byte buffer.
     pub buf: Vec<u8>,
+    // Prefix added to table names written by NsonSerializer, from
+    // HandleBuilder::table_prefix().
+    pub(crate) table_prefix: String,
 }
 
 impl Writer {
     pub fn new() -> Writer {
         Writer {
             buf: Vec::with_capacity(256),
+            table_prefix: String::new(),
         }
     }
 
@@ -110,8 +114,19 @@ impl Writer {
         self.buf.extend_from_slice(val.as_bytes());
     }
 
+    // Timestamps are always sent in UTC, as stored by the server, with only as many
+    // fractional digits (0, 3, 6 or 9) as needed to represent the value exactly.
+    // This is also the binary form of timestamps in primary keys (see ReceiveIter),
+    // so the same instant must always be written the same way. The server has no
+    // leap seconds, so a chrono leap second (23:59:60) is written as 23:59:59,
+    // as the server parses it.
     pub fn write_timestamp(&mut self, val: &DateTime<FixedOffset>) {
-        self.write_string(&val.to_rfc3339());
+        let mut utc = val.with_timezone(&Utc);
+        let nanos = utc.timestamp_subsec_nanos();
+        if nanos >= 1_000_000_000 {
+            utc = utc.with_nanosecond(nanos - 1_000_000_000).unwrap_or(utc);
+        }
+        self.write_string(&utc.to_rfc3339_opts(SecondsFormat::AutoSi, true));
     }
 
     pub fn write_binary(&mut self, val: &Vec<u8>) {
@@ -211,11 +226,15 @@ impl Writer {
         let off = self.buf.len();
         self.write_i32(0);
 
-        // next 4 bytes is the number of items in the map
-        self.write_i32(val.len() as i32);
+        // next 4 bytes is the number of items in the map. Uninitialized
+        // values (such as absent JsonNullable fields) are left out.
+        let items = val
+            .iter()
+            .filter(|(_, item)| !matches!(item, FieldValue::Uninitialized));
+        self.write_i32(items.clone().count() as i32);
 
         // Then all the items
-        for (key, item) in val.iter() {
+        for (key, item) in items {
             self.write_string(key);
             self.write_field_value(item);
         }
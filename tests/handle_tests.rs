@@ -588,3 +588,65 @@ async fn json_collection_test() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn timestamp_range_test() -> Result<(), Box<dyn Error>> {
+    let handle = get_builder()?.build().await?;
+
+    TableRequest::new("testevents")
+        .statement(
+            "create table if not exists testevents (id integer, ts timestamp(3),
+            primary key(id))",
+        )
+        .limits(&TableLimits::provisioned(10, 10, 10))
+        .execute(&handle)
+        .await?
+        .wait_for_completion_ms(&handle, 15000, 500)
+        .await?;
+
+    // the server rounds each value to milliseconds when it is stored
+    let values = [
+        (1, "2024-05-06T12:00:00.0004Z"),
+        (2, "2024-05-06T12:00:00.0006Z"),
+        (3, "2024-05-06T12:00:00.5Z"),
+        (4, "2024-05-06T12:00:00.9996Z"),
+    ];
+    for (id, ts) in values {
+        PutRequest::new("testevents")
+            .value(
+                MapValue::new()
+                    .i32("id", id)
+                    .timestamp("ts", &DateTime::parse_from_rfc3339(ts)?),
+            )
+            .execute(&handle)
+            .await?;
+    }
+
+    let prep = QueryRequest::new(
+        "declare $start timestamp; $end timestamp; \
+         select id from testevents where ts >= $start and ts < $end",
+    )
+    .prepare_only()
+    .execute(&handle)
+    .await?;
+    let mut qreq = QueryRequest::new_prepared(&prep.prepared_statement());
+    // the range [.0006, .9996) includes exactly the rows written with values in it
+    qreq.set_timestamp_range(
+        "$start",
+        "$end",
+        &DateTime::parse_from_rfc3339("2024-05-06T12:00:00.0006Z")?,
+        &DateTime::parse_from_rfc3339("2024-05-06T12:00:00.9996Z")?,
+        3,
+    )?;
+    let mut ids: Vec<i32> = qreq
+        .execute(&handle)
+        .await?
+        .rows()
+        .iter()
+        .filter_map(|r| r.get_i32("id"))
+        .collect();
+    ids.sort();
+    assert_eq!(ids, vec![2, 3]);
+
+    Ok(())
+}